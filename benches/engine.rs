@@ -0,0 +1,89 @@
+//! Engine throughput on repositories with large diffs.
+
+use std::{fs, hint::black_box, path::Path};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use if_changed::{Engine as _, GitEngine};
+
+/// The number of changed files of the repository.
+const FILES: usize = 2_000;
+/// The number of lines of the large changed file.
+const LINES: usize = 50_000;
+
+/// Create a repository whose working tree changes every file of its only
+/// commit, and every tenth line of a large file.
+fn repository(root: &Path) -> git2::Repository {
+    let repository = git2::Repository::init(root).unwrap();
+    let large = |changed: bool| {
+        (0..LINES)
+            .map(|line| match changed && line % 10 == 0 {
+                true => format!("changed line {line}\n"),
+                false => format!("line {line}\n"),
+            })
+            .collect::<String>()
+    };
+    fs::create_dir_all(root.join("src")).unwrap();
+    for file in 0..FILES {
+        fs::write(root.join(format!("src/{file}.ts")), "A,\nB,\n").unwrap();
+    }
+    fs::write(root.join("large.ts"), large(false)).unwrap();
+
+    let mut index = repository.index().unwrap();
+    index
+        .add_all(["."], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature =
+        git2::Signature::new("Example User", "test@example.com", &git2::Time::new(0, 0)).unwrap();
+    repository
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Initial commit",
+            &tree,
+            &[],
+        )
+        .unwrap();
+    drop(tree);
+
+    for file in 0..FILES {
+        fs::write(root.join(format!("src/{file}.ts")), "A,\nC,\n").unwrap();
+    }
+    fs::write(root.join("large.ts"), large(true)).unwrap();
+    repository
+}
+
+fn engine(c: &mut Criterion) {
+    let tempdir = tempfile::tempdir().unwrap();
+    let repository = repository(tempdir.path());
+
+    // Computing the diff is part of the first call to `matches`.
+    c.bench_function("git/diff_and_match_all", |b| {
+        b.iter(|| {
+            let engine = GitEngine::new(&repository, None, None).unwrap();
+            engine.matches(black_box(&[] as &[&str])).count()
+        })
+    });
+
+    let engine = GitEngine::new(&repository, None, None).unwrap();
+    c.bench_function("git/match_patterns", |b| {
+        b.iter(|| {
+            engine
+                .matches(black_box(["src/1*.ts", "large.ts", "missing.ts"]))
+                .count()
+        })
+    });
+    c.bench_function("git/is_range_modified", |b| {
+        b.iter(|| {
+            (1..LINES)
+                .step_by(100)
+                .filter(|start| engine.is_range_modified("large.ts", (*start, start + 5)))
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, engine);
+criterion_main!(benches);
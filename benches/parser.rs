@@ -0,0 +1,54 @@
+//! Parser throughput on representative corpora.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// A long source file with a block every `every` lines.
+fn long_file(lines: usize, every: usize) -> String {
+    let mut source = String::new();
+    for line in 0..lines {
+        if every > 0 && line % every == 0 {
+            source.push_str(&format!("// if-changed(block{line})\n"));
+            source.push_str("const value = 1;\n");
+            source.push_str(&format!("// then-change(other.ts:block{line})\n"));
+        } else {
+            source.push_str("    let value = compute(value, other_value) + 1; // a comment\n");
+        }
+    }
+    source
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_str");
+    for (name, source) in [
+        // Bundled or generated code, without blocks.
+        ("no_blocks", long_file(100_000, 0)),
+        // A large, sparsely annotated file.
+        ("sparse_blocks", long_file(100_000, 1_000)),
+        // A densely annotated file.
+        ("dense_blocks", long_file(10_000, 10)),
+    ] {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |b, source| {
+            b.iter(|| if_changed::parse_str("a.ts", black_box(source)).count())
+        });
+    }
+    group.finish();
+
+    // Many small files, as when checking a large change.
+    let files = (0..1_000)
+        .map(|file| (format!("src/{file}.ts"), long_file(50, 25)))
+        .collect::<Vec<_>>();
+    c.bench_function("parse_str/many_small_files", |b| {
+        b.iter(|| {
+            files
+                .iter()
+                .map(|(path, source)| if_changed::parse_str(path, black_box(source)).count())
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,280 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+    path::Path,
+};
+
+use anstyle::{AnsiColor, Style};
+use if_changed::{Code, Stats};
+
+use crate::{render::Renderer, run, scope::Scope, CheckArgs};
+
+const COMMIT: Style = AnsiColor::Yellow.on_default();
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Check every commit in the audited history on its own, printing the
+/// problems each commit introduced under it.
+///
+/// Returns the codes of the problems found.
+pub fn audit(
+    args: &CheckArgs,
+    repository: &git2::Repository,
+    scope: &Scope,
+    stats: &mut Stats,
+    mut out: impl Write,
+) -> io::Result<BTreeSet<Code>> {
+    let commits = commits(args, repository).map_err(io::Error::other)?;
+    let mut codes = BTreeSet::new();
+    let mut problems = 0;
+    let mut failed_commits = 0;
+    for commit in &commits {
+        let commit_args = CheckArgs {
+            from_ref: None,
+            to_ref: Some(commit.id().to_string()),
+            ..args.clone()
+        };
+        let diagnostics = run(&commit_args, repository, stats)?.collect::<Vec<_>>();
+        if diagnostics.is_empty() {
+            continue;
+        }
+        problems += diagnostics.len();
+        failed_commits += 1;
+
+        writeln!(
+            out,
+            "{COMMIT}commit {}{COMMIT:#} ({}) {}\n",
+            commit.id(),
+            format_date(commit.time().seconds()),
+            commit.summary().unwrap_or_default()
+        )?;
+        // Show the sources as they were in the commit.
+        let tree = commit.tree().map_err(io::Error::other)?;
+        let mut renderer = Renderer::with_reader(|path: &Path| {
+            let path = scope.pattern(&path.to_string_lossy());
+            let entry = tree.get_path(Path::new(&path)).ok()?;
+            let blob = entry.to_object(repository).ok()?.peel_to_blob().ok()?;
+            String::from_utf8(blob.content().to_owned()).ok()
+        });
+        for diagnostic in diagnostics {
+            codes.insert(diagnostic.code);
+            renderer.render(&scope.diagnostic(diagnostic), &mut out)?;
+        }
+        if args.fail_fast {
+            break;
+        }
+    }
+    writeln!(
+        out,
+        "Found {problems} problem(s) in {failed_commits} of {} audited commit(s).",
+        commits.len()
+    )?;
+    Ok(codes)
+}
+
+/// Get the commits to audit, oldest first. Merge commits are skipped since
+/// their changes were checked in the merged commits.
+fn commits<'repo>(
+    args: &CheckArgs,
+    repository: &'repo git2::Repository,
+) -> Result<Vec<git2::Commit<'repo>>, git2::Error> {
+    let mut revwalk = repository.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+    match &args.to_ref {
+        Some(to_ref) => revwalk.push(repository.revparse_single(to_ref)?.peel_to_commit()?.id())?,
+        None => revwalk.push_head()?,
+    }
+    if let Some(from_ref) = &args.from_ref {
+        revwalk.hide(repository.revparse_single(from_ref)?.peel_to_commit()?.id())?;
+    }
+    let mut commits = Vec::new();
+    for id in revwalk {
+        let commit = repository.find_commit(id?)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        if args
+            .since
+            .is_some_and(|since| commit.time().seconds() < since)
+        {
+            continue;
+        }
+        commits.push(commit);
+    }
+    Ok(commits)
+}
+
+/// Parse a UTC date, `YYYY-MM-DD` optionally followed by `THH:MM:SS` or
+/// ` HH:MM:SS`, into seconds since the Unix epoch.
+pub fn parse_date(value: &str) -> Result<i64, String> {
+    let invalid =
+        || format!("invalid date {value:?}; expected e.g. 2024-01-31 or 2024-01-31T12:00:00");
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let numbers = |value: &str, separator| {
+        value
+            .split(separator)
+            .map(|number| number.parse::<i64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let date = numbers(date, '-')?;
+    let time = match time {
+        Some(time) => numbers(time, ':')?,
+        None => vec![0, 0, 0],
+    };
+    let (&[year, month, day], &[hours, minutes, seconds]) = (&date[..], &time[..]) else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hours)
+        || !(0..60).contains(&minutes)
+        || !(0..60).contains(&seconds)
+    {
+        return Err(invalid());
+    }
+    Ok(days_from_civil(year, month, day) * SECONDS_PER_DAY + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Format seconds since the Unix epoch as a UTC `YYYY-MM-DD` date.
+pub fn format_date(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Count the days from 1970-01-01 to a date of the proleptic Gregorian
+/// calendar, following <https://howardhinnant.github.io/date_algorithms.html>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_date("2024-02-29"), Ok(1709164800));
+        assert_eq!(parse_date("2024-02-29T01:02:03"), Ok(1709168523));
+        assert_eq!(parse_date("2024-02-29 01:02:03"), Ok(1709168523));
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("2024-02").is_err());
+        assert!(parse_date("yesterday").is_err());
+        assert_eq!(format_date(1709168523), "2024-02-29");
+        assert_eq!(format_date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn test_audit() {
+        let (_tempdir, repo) = git_test! {
+            "add blocks": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts)
+                "},
+                "b.ts" => ""
+            ]
+            "change only a": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    AA,
+                    // then-change(b.ts)
+                "}
+            ]
+            "change both": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    AAA,
+                    // then-change(b.ts)
+                "},
+                "b.ts" => "B"
+            ]
+        };
+
+        let args = CheckArgs {
+            since: Some(0),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let codes = audit(
+            &args,
+            &repo,
+            &Scope::default(),
+            &mut Stats::default(),
+            anstream::StripStream::new(&mut out),
+        )
+        .unwrap();
+        assert_eq!(codes, BTreeSet::from([Code::UnmodifiedTarget]));
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        commit c95e1985c0b3c5cc131cd8482576a668a70dadc1 (1970-01-01) change only a
+
+        error[IC002]: Expected "b.ts" to be modified because of "then-change" in "a.ts" at line 3.
+         --> a.ts:3
+          |
+        1 | // if-changed
+        2 | AA,
+        3 | // then-change(b.ts)
+          | ^^^^^^^^^^^^^^^^^^^^
+          = target: b.ts
+          = changes:
+            -A,
+            +AA,
+
+        Found 1 problem(s) in 1 of 3 audited commit(s).
+
+        "###);
+
+        let args = CheckArgs {
+            since: Some(1),
+            ..Default::default()
+        };
+        assert!(commits(&args, &repo).unwrap().is_empty());
+
+        let args = CheckArgs {
+            from_ref: Some("HEAD~1".into()),
+            audit: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let codes = audit(
+            &args,
+            &repo,
+            &Scope::default(),
+            &mut Stats::default(),
+            anstream::StripStream::new(&mut out),
+        )
+        .unwrap();
+        assert!(codes.is_empty());
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        Found 0 problem(s) in 0 of 1 audited commit(s).
+
+        "###);
+    }
+}
@@ -0,0 +1,404 @@
+use std::io;
+
+use clap::{builder::BoolishValueParser, Args};
+use if_changed::{Diagnostic, Reporter, Summary};
+use serde_json::{json, Value};
+
+use crate::http::Api;
+
+/// The version of the Azure DevOps REST API requests are made against.
+const API_VERSION: &str = "7.1";
+
+/// The name of the status posted on pull requests.
+const STATUS: &str = "if-changed";
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct AzureArgs {
+    /// Check the latest iteration of an Azure Repos pull request instead of
+    /// `--from-ref` and `--to-ref`.
+    ///
+    /// The source commit of the iteration is compared with its merge base
+    /// with the target branch, as the diff of the pull request is, fetching
+    /// them from `--azure-remote` unless they exist locally.
+    #[arg(
+        long,
+        env = "IF_CHANGED_AZURE_PR",
+        value_name = "ID",
+        requires_all = ["azure_url", "azure_project", "azure_repository"],
+        conflicts_with_all = ["from_ref", "to_ref", "watch", "since", "audit"]
+    )]
+    pub azure_pr: Option<u64>,
+
+    /// Post the result as a status of the iteration of `--azure-pr`, e.g. for
+    /// a branch policy requiring it to succeed.
+    #[arg(
+        long,
+        env = "IF_CHANGED_AZURE_STATUS", value_parser = BoolishValueParser::new(),
+        requires_all = ["azure_pr", "azure_token"],
+        conflicts_with = "interactive"
+    )]
+    pub azure_status: bool,
+
+    /// The URL of the organization, e.g. `https://dev.azure.com/ORG/`.
+    #[arg(long, value_name = "URL", env = "SYSTEM_COLLECTIONURI")]
+    pub azure_url: Option<String>,
+
+    /// The project of the repository.
+    #[arg(long, value_name = "PROJECT", env = "SYSTEM_TEAMPROJECT")]
+    pub azure_project: Option<String>,
+
+    /// The name or id of the repository of the pull request.
+    #[arg(long, value_name = "REPOSITORY", env = "BUILD_REPOSITORY_NAME")]
+    pub azure_repository: Option<String>,
+
+    /// The token authenticating requests to Azure DevOps, e.g.
+    /// `$(System.AccessToken)`.
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "SYSTEM_ACCESSTOKEN",
+        hide_env_values = true
+    )]
+    pub azure_token: Option<String>,
+
+    /// The remote to fetch the commits of `--azure-pr` from.
+    #[arg(
+        long,
+        env = "IF_CHANGED_AZURE_REMOTE",
+        value_name = "REMOTE",
+        default_value = "origin"
+    )]
+    pub azure_remote: String,
+
+    /// The pull request of `--azure-pr`, once fetched.
+    #[arg(skip)]
+    pub pull_request: Option<PullRequest>,
+}
+
+/// The latest iteration of a pull request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    /// The id of the iteration.
+    pub iteration: u64,
+    /// The commit of the source branch.
+    pub source: String,
+    /// The ref of the source branch, e.g. `refs/heads/feature`.
+    pub source_ref: String,
+    /// The merge base of the source and target branches.
+    pub base: String,
+    /// The ref of the target branch.
+    pub target_ref: String,
+    /// The names of the active labels.
+    pub labels: Vec<String>,
+    /// The description, which may list files to ignore.
+    pub description: String,
+}
+
+impl AzureArgs {
+    fn client(&self) -> Client {
+        Client {
+            agent: ureq::agent(),
+            url: self
+                .azure_url
+                .as_deref()
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_owned(),
+            token: self.azure_token.clone(),
+        }
+    }
+
+    /// Get the path of the pull request of `--azure-pr`.
+    fn path(&self) -> String {
+        format!(
+            "/{}/_apis/git/repositories/{}/pullRequests/{}",
+            encode(self.azure_project.as_deref().unwrap_or_default()),
+            encode(self.azure_repository.as_deref().unwrap_or_default()),
+            self.azure_pr.unwrap_or_default()
+        )
+    }
+
+    /// Look up the latest iteration of `--azure-pr` and fetch the commits
+    /// missing from `repository`.
+    pub fn fetch(&self, repository: &git2::Repository) -> io::Result<PullRequest> {
+        let pull_request = pull_request(&mut self.client(), &self.path())?;
+        for (commit, reference) in [
+            (&pull_request.source, &pull_request.source_ref),
+            (&pull_request.base, &pull_request.target_ref),
+        ] {
+            let exists = git2::Oid::from_str(commit)
+                .and_then(|oid| repository.find_commit(oid))
+                .is_ok();
+            if !exists {
+                repository
+                    .find_remote(&self.azure_remote)
+                    .and_then(|mut remote| remote.fetch(&[reference], None, None))
+                    .map_err(|error| {
+                        io::Error::other(format!(
+                            "{error}; fetch it with `git fetch {} {reference}`",
+                            self.azure_remote
+                        ))
+                    })?;
+            }
+        }
+        Ok(pull_request)
+    }
+
+    /// Create the reporter posting the status, if `--azure-status` is given.
+    pub fn status(&self) -> Option<Status<Client>> {
+        if !self.azure_status {
+            return None;
+        }
+        let iteration = self.pull_request.as_ref()?.iteration;
+        Some(Status::new(self.client(), &self.path(), iteration))
+    }
+}
+
+/// A client of the Azure DevOps REST API of the organization at `url`,
+/// authenticated with `token`, if any.
+pub struct Client {
+    agent: ureq::Agent,
+    url: String,
+    token: Option<String>,
+}
+
+impl Api for Client {
+    fn send(&mut self, method: &str, path: &str, body: Option<Value>) -> io::Result<(u16, Value)> {
+        let mut request = self
+            .agent
+            .request(method, &format!("{}{path}", self.url))
+            .query("api-version", API_VERSION)
+            .set("Accept", "application/json")
+            .set(
+                "User-Agent",
+                concat!("if-changed/", env!("CARGO_PKG_VERSION")),
+            );
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+        let response = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        let response = match response {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(error) => return Err(io::Error::other(error)),
+        };
+        let status = response.status();
+        Ok((status, response.into_json().unwrap_or(Value::Null)))
+    }
+}
+
+/// Get the latest iteration of the pull request at `path`.
+fn pull_request(api: &mut impl Api, path: &str) -> io::Result<PullRequest> {
+    let response = send(api, "GET", path, None)?;
+    let iterations = format!("{path}/iterations");
+    let iteration = send(api, "GET", &iterations, None)?["value"]
+        .as_array()
+        .and_then(|iterations| iterations.last())
+        .cloned()
+        .ok_or_else(|| io::Error::other(format!("{iterations} has no iterations")))?;
+    let field = |value: &Value, pointer: &str| {
+        value
+            .pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| io::Error::other(format!("{path} has no {pointer}")))
+    };
+    let labels = response["labels"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|label| label["active"].as_bool().unwrap_or(true))
+        .filter_map(|label| label["name"].as_str().map(str::to_owned))
+        .collect();
+    Ok(PullRequest {
+        iteration: iteration["id"]
+            .as_u64()
+            .ok_or_else(|| io::Error::other(format!("{iterations} has no /id")))?,
+        source: field(&iteration, "/sourceRefCommit/commitId")?,
+        source_ref: field(&response, "/sourceRefName")?,
+        base: field(&iteration, "/commonRefCommit/commitId")?,
+        target_ref: field(&response, "/targetRefName")?,
+        labels,
+        description: response["description"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned(),
+    })
+}
+
+/// Send a request, failing unless Azure DevOps responds with a success.
+fn send(api: &mut impl Api, method: &str, path: &str, body: Option<Value>) -> io::Result<Value> {
+    let (status, response) = api.send(method, path, body)?;
+    if !(200..300).contains(&status) {
+        return Err(rejected(method, path, status, &response));
+    }
+    Ok(response)
+}
+
+/// Encode `name` as a segment of a path, e.g. a project with spaces.
+fn encode(name: &str) -> String {
+    name.replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('/', "%2F")
+        .replace('#', "%23")
+        .replace('?', "%3F")
+}
+
+/// Posts the result of the check as a status of an iteration of a pull
+/// request once the check is finished.
+pub struct Status<A: Api> {
+    api: A,
+    /// The path of the pull request.
+    path: String,
+    iteration: u64,
+    summary: Summary,
+}
+
+impl<A: Api> Status<A> {
+    fn new(api: A, path: &str, iteration: u64) -> Self {
+        Self {
+            api,
+            path: path.to_owned(),
+            iteration,
+            summary: Summary::default(),
+        }
+    }
+}
+
+impl<A: Api> Reporter for Status<A> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        self.summary.record(diagnostic);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<Summary> {
+        let status = json!({
+            "state": if self.summary.is_success() { "succeeded" } else { "failed" },
+            "description": format!("if-changed found {}.", self.summary),
+            "context": { "name": STATUS, "genre": STATUS },
+            "iterationId": self.iteration,
+        });
+        let path = format!("{}/statuses", self.path);
+        send(&mut self.api, "POST", &path, Some(status))?;
+        Ok(self.summary)
+    }
+}
+
+/// Describe a request Azure DevOps didn't accept.
+fn rejected(method: &str, path: &str, status: u16, response: &Value) -> io::Error {
+    let message = response["message"].as_str().unwrap_or("no message");
+    io::Error::other(format!(
+        "Azure DevOps responded to {method} {path} with {status}: {message}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{Code, Severity};
+
+    use super::*;
+
+    /// A pull request on Azure Repos, remembering the requests sent to it.
+    #[derive(Default)]
+    struct FakeAzure {
+        requests: Vec<String>,
+    }
+
+    impl Api for &mut FakeAzure {
+        fn send(
+            &mut self,
+            method: &str,
+            path: &str,
+            body: Option<Value>,
+        ) -> io::Result<(u16, Value)> {
+            self.requests.push(match &body {
+                Some(body) => format!("{method} {path} {body}"),
+                None => format!("{method} {path}"),
+            });
+            let Some(path) = path.strip_prefix("/My%20Project/_apis/git/repositories/r/") else {
+                return Ok((
+                    404,
+                    json!({ "message": "TF200016: The project does not exist." }),
+                ));
+            };
+            Ok(match (method, path) {
+                ("GET", "pullRequests/1") => (
+                    200,
+                    json!({
+                        "sourceRefName": "refs/heads/feature",
+                        "targetRefName": "refs/heads/main",
+                        "description": "Fix a bug.",
+                        "labels": [
+                            { "name": "skip-if-changed", "active": true },
+                            { "name": "old", "active": false },
+                        ],
+                    }),
+                ),
+                ("GET", "pullRequests/1/iterations") => (
+                    200,
+                    json!({
+                        "value": [
+                            { "id": 1, "sourceRefCommit": { "commitId": "old" }, "commonRefCommit": { "commitId": "base" } },
+                            { "id": 2, "sourceRefCommit": { "commitId": "abc" }, "commonRefCommit": { "commitId": "base" } },
+                        ],
+                    }),
+                ),
+                ("POST", "pullRequests/1/statuses") => (201, body.unwrap()),
+                _ => (404, json!({ "message": "Not found" })),
+            })
+        }
+    }
+
+    fn args() -> AzureArgs {
+        AzureArgs {
+            azure_pr: Some(1),
+            azure_project: Some("My Project".into()),
+            azure_repository: Some("r".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pull_request() {
+        let mut azure = FakeAzure::default();
+        assert_eq!(
+            pull_request(&mut &mut azure, &args().path()).unwrap(),
+            PullRequest {
+                iteration: 2,
+                source: "abc".into(),
+                source_ref: "refs/heads/feature".into(),
+                base: "base".into(),
+                target_ref: "refs/heads/main".into(),
+                labels: vec!["skip-if-changed".into()],
+                description: "Fix a bug.".into(),
+            }
+        );
+        let args = AzureArgs {
+            azure_project: Some("missing".into()),
+            ..args()
+        };
+        insta::assert_snapshot!(pull_request(&mut &mut azure, &args.path()).unwrap_err(), @"Azure DevOps responded to GET /missing/_apis/git/repositories/r/pullRequests/1 with 404: TF200016: The project does not exist.");
+    }
+
+    #[test]
+    fn test_status() {
+        let mut azure = FakeAzure::default();
+        let mut status = Status::new(&mut azure, &args().path(), 2);
+        let mut warning = Diagnostic::new(
+            Code::UnmodifiedTarget,
+            "a.ts",
+            Some(4),
+            "Expected \"b.ts\" to be modified.".into(),
+        );
+        warning.severity = Severity::Warning;
+        status.report(&warning).unwrap();
+        status.finish().unwrap();
+        insta::assert_debug_snapshot!(azure.requests, @r###"
+        [
+            "POST /My%20Project/_apis/git/repositories/r/pullRequests/1/statuses {\"context\":{\"genre\":\"if-changed\",\"name\":\"if-changed\"},\"description\":\"if-changed found 1 warning.\",\"iterationId\":2,\"state\":\"succeeded\"}",
+        ]
+        "###);
+    }
+}
@@ -0,0 +1,537 @@
+use std::io;
+
+use clap::{builder::BoolishValueParser, Args};
+use if_changed::{Diagnostic, Reporter, Severity, Summary};
+use serde_json::{json, Value};
+
+use crate::http::Api;
+
+/// The key of the Code Insights report, replaced on every run.
+const REPORT: &str = "if-changed";
+
+/// The URL of the API of Bitbucket Cloud. Other URLs are taken to be of
+/// Bitbucket Server or Data Center.
+const CLOUD_URL: &str = "https://api.bitbucket.org";
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct BitbucketArgs {
+    /// Check the changes of a Bitbucket pull request instead of `--from-ref`
+    /// and `--to-ref`.
+    ///
+    /// The source and destination commits are fetched from
+    /// `--bitbucket-remote` unless they exist locally, and compared from
+    /// their merge base, as the diff of the pull request is.
+    #[arg(
+        long,
+        env = "IF_CHANGED_BITBUCKET_PR",
+        value_name = "ID",
+        requires = "bitbucket_repository",
+        conflicts_with_all = ["from_ref", "to_ref", "watch", "since", "audit"]
+    )]
+    pub bitbucket_pr: Option<u64>,
+
+    /// Post problems as Code Insights annotations on the source commit of
+    /// `--bitbucket-pr`, in a report passing or failing with the check.
+    #[arg(
+        long,
+        env = "IF_CHANGED_BITBUCKET_INSIGHTS", value_parser = BoolishValueParser::new(),
+        requires_all = ["bitbucket_pr", "bitbucket_token"],
+        conflicts_with = "interactive"
+    )]
+    pub bitbucket_insights: bool,
+
+    /// The repository of the pull request, as `WORKSPACE/SLUG` on Bitbucket
+    /// Cloud or `PROJECT/SLUG` on Bitbucket Server.
+    #[arg(long, value_name = "OWNER/SLUG", env = "BITBUCKET_REPO_FULL_NAME")]
+    pub bitbucket_repository: Option<String>,
+
+    /// The access token authenticating requests to Bitbucket.
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "BITBUCKET_TOKEN",
+        hide_env_values = true
+    )]
+    pub bitbucket_token: Option<String>,
+
+    /// The URL of the Bitbucket API, e.g. of Bitbucket Server.
+    #[arg(long, env = "IF_CHANGED_BITBUCKET_URL", value_name = "URL", default_value = CLOUD_URL)]
+    pub bitbucket_url: String,
+
+    /// The remote to fetch the commits of `--bitbucket-pr` from.
+    #[arg(
+        long,
+        env = "IF_CHANGED_BITBUCKET_REMOTE",
+        value_name = "REMOTE",
+        default_value = "origin"
+    )]
+    pub bitbucket_remote: String,
+
+    /// The pull request of `--bitbucket-pr`, once fetched.
+    #[arg(skip)]
+    pub pull_request: Option<PullRequest>,
+}
+
+/// The commits of a pull request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    /// The commit of the source branch.
+    pub source: String,
+    /// The ref of the source branch, e.g. `refs/heads/feature`.
+    pub source_ref: String,
+    /// The commit of the destination branch.
+    pub destination: String,
+    /// The ref of the destination branch.
+    pub destination_ref: String,
+    /// The description, which may list files to ignore.
+    pub description: String,
+}
+
+/// The APIs of the flavors of Bitbucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flavor {
+    Cloud,
+    Server,
+}
+
+impl BitbucketArgs {
+    fn flavor(&self) -> Flavor {
+        if self.bitbucket_url.trim_end_matches('/') == CLOUD_URL {
+            Flavor::Cloud
+        } else {
+            Flavor::Server
+        }
+    }
+
+    fn client(&self) -> Client {
+        Client {
+            agent: ureq::agent(),
+            url: self.bitbucket_url.trim_end_matches('/').to_owned(),
+            token: self.bitbucket_token.clone(),
+        }
+    }
+
+    fn repository(&self) -> io::Result<(&str, &str)> {
+        let repository = self.bitbucket_repository.as_deref().unwrap_or_default();
+        repository.split_once('/').ok_or_else(|| {
+            io::Error::other(format!(
+                "invalid repository {repository:?}; expected OWNER/SLUG"
+            ))
+        })
+    }
+
+    /// Look up the commits of `--bitbucket-pr` and fetch the ones missing
+    /// from `repository`.
+    pub fn fetch(&self, repository: &git2::Repository) -> io::Result<PullRequest> {
+        let (owner, slug) = self.repository()?;
+        let id = self.bitbucket_pr.unwrap_or_default();
+        let mut pull_request = pull_request(&mut self.client(), self.flavor(), owner, slug, id)?;
+        for (commit, reference) in [
+            (&mut pull_request.source, &pull_request.source_ref),
+            (&mut pull_request.destination, &pull_request.destination_ref),
+        ] {
+            if repository.revparse_single(commit).is_err() {
+                repository
+                    .find_remote(&self.bitbucket_remote)
+                    .and_then(|mut remote| remote.fetch(&[reference], None, None))
+                    .map_err(|error| {
+                        io::Error::other(format!(
+                            "{error}; fetch it with `git fetch {} {reference}`",
+                            self.bitbucket_remote
+                        ))
+                    })?;
+            }
+            // Bitbucket Cloud abbreviates commits, but reports are attached
+            // to full ones.
+            let object = repository
+                .revparse_single(commit)
+                .and_then(|object| object.peel_to_commit())
+                .map_err(io::Error::other)?;
+            *commit = object.id().to_string();
+        }
+        Ok(pull_request)
+    }
+
+    /// Create the reporter posting annotations, if `--bitbucket-insights` is
+    /// given.
+    pub fn insights(&self) -> Option<Insights<Client>> {
+        if !self.bitbucket_insights {
+            return None;
+        }
+        let (owner, slug) = self.repository().ok()?;
+        let commit = &self.pull_request.as_ref()?.source;
+        Some(Insights::new(
+            self.client(),
+            self.flavor(),
+            &report_path(self.flavor(), owner, slug, commit),
+        ))
+    }
+}
+
+/// A client of the Bitbucket REST API at `url`, authenticated with `token`,
+/// if any.
+pub struct Client {
+    agent: ureq::Agent,
+    url: String,
+    token: Option<String>,
+}
+
+impl Api for Client {
+    fn send(&mut self, method: &str, path: &str, body: Option<Value>) -> io::Result<(u16, Value)> {
+        let mut request = self
+            .agent
+            .request(method, &format!("{}{path}", self.url))
+            .set("Accept", "application/json")
+            .set(
+                "User-Agent",
+                concat!("if-changed/", env!("CARGO_PKG_VERSION")),
+            );
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+        let response = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        let response = match response {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(error) => return Err(io::Error::other(error)),
+        };
+        let status = response.status();
+        Ok((status, response.into_json().unwrap_or(Value::Null)))
+    }
+}
+
+/// Get the commits and refs of the pull request `id` of `owner/slug`.
+fn pull_request(
+    api: &mut impl Api,
+    flavor: Flavor,
+    owner: &str,
+    slug: &str,
+    id: u64,
+) -> io::Result<PullRequest> {
+    let path = match flavor {
+        Flavor::Cloud => format!("/2.0/repositories/{owner}/{slug}/pullrequests/{id}"),
+        Flavor::Server => {
+            format!("/rest/api/1.0/projects/{owner}/repos/{slug}/pull-requests/{id}")
+        }
+    };
+    let response = send(api, "GET", &path, None)?;
+    let field = |pointer: &str| {
+        response
+            .pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| io::Error::other(format!("{path} has no {pointer}")))
+    };
+    let description = response["description"]
+        .as_str()
+        .unwrap_or_default()
+        .to_owned();
+    Ok(match flavor {
+        Flavor::Cloud => PullRequest {
+            source: field("/source/commit/hash")?,
+            source_ref: format!("refs/heads/{}", field("/source/branch/name")?),
+            destination: field("/destination/commit/hash")?,
+            destination_ref: format!("refs/heads/{}", field("/destination/branch/name")?),
+            description,
+        },
+        Flavor::Server => PullRequest {
+            source: field("/fromRef/latestCommit")?,
+            source_ref: field("/fromRef/id")?,
+            destination: field("/toRef/latestCommit")?,
+            destination_ref: field("/toRef/id")?,
+            description,
+        },
+    })
+}
+
+/// Get the path of the Code Insights report on `commit` of `owner/slug`.
+fn report_path(flavor: Flavor, owner: &str, slug: &str, commit: &str) -> String {
+    match flavor {
+        Flavor::Cloud => {
+            format!("/2.0/repositories/{owner}/{slug}/commit/{commit}/reports/{REPORT}")
+        }
+        Flavor::Server => format!(
+            "/rest/insights/1.0/projects/{owner}/repos/{slug}/commits/{commit}/reports/{REPORT}"
+        ),
+    }
+}
+
+/// Send a request, failing unless Bitbucket responds with a success.
+fn send(api: &mut impl Api, method: &str, path: &str, body: Option<Value>) -> io::Result<Value> {
+    let (status, response) = api.send(method, path, body)?;
+    if !(200..300).contains(&status) {
+        return Err(rejected(method, path, status, &response));
+    }
+    Ok(response)
+}
+
+/// Posts problems as Code Insights annotations on a commit once the check is
+/// finished, replacing the report of earlier runs.
+pub struct Insights<A: Api> {
+    api: A,
+    flavor: Flavor,
+    /// The path of the report.
+    path: String,
+    diagnostics: Vec<Diagnostic>,
+    summary: Summary,
+}
+
+impl<A: Api> Insights<A> {
+    fn new(api: A, flavor: Flavor, path: &str) -> Self {
+        Self {
+            api,
+            flavor,
+            path: path.to_owned(),
+            diagnostics: Vec::new(),
+            summary: Summary::default(),
+        }
+    }
+
+    /// Write the report, with the problems outside of files in its details.
+    fn report_body(&self) -> Value {
+        let mut details = format!("if-changed found {}.", self.summary);
+        for diagnostic in &self.diagnostics {
+            if diagnostic.path.as_os_str().is_empty() {
+                details += &format!(
+                    "\n{}[{}]: {}",
+                    diagnostic.severity, diagnostic.code, diagnostic.message
+                );
+            }
+        }
+        let passed = self.summary.is_success();
+        match self.flavor {
+            Flavor::Cloud => json!({
+                "title": REPORT,
+                "details": details,
+                "report_type": "BUG",
+                "reporter": REPORT,
+                "result": if passed { "PASSED" } else { "FAILED" },
+            }),
+            Flavor::Server => json!({
+                "title": REPORT,
+                "details": details,
+                "reporter": REPORT,
+                "result": if passed { "PASS" } else { "FAIL" },
+            }),
+        }
+    }
+
+    /// Write the annotations of the problems of files.
+    fn annotations(&self) -> Vec<Value> {
+        let mut annotations = Vec::new();
+        for diagnostic in &self.diagnostics {
+            if diagnostic.path.as_os_str().is_empty() {
+                continue;
+            }
+            let path = diagnostic.path.to_string_lossy().replace('\\', "/");
+            let message = format!(
+                "{}[{}]: {}",
+                diagnostic.severity, diagnostic.code, diagnostic.message
+            );
+            let severity = match diagnostic.severity {
+                Severity::Error => "HIGH",
+                _ => "MEDIUM",
+            };
+            let id = format!("{}-{}", diagnostic.code, annotations.len() + 1);
+            annotations.push(match self.flavor {
+                // Annotations without a line are on the whole file.
+                Flavor::Cloud => json!({
+                    "external_id": id,
+                    "annotation_type": "BUG",
+                    "path": path,
+                    "line": diagnostic.line,
+                    "summary": truncate(&message, 450),
+                    "severity": severity,
+                }),
+                Flavor::Server => json!({
+                    "externalId": id,
+                    "path": path,
+                    "line": diagnostic.line.unwrap_or(0),
+                    "message": truncate(&message, 2000),
+                    "severity": severity,
+                }),
+            });
+        }
+        annotations
+    }
+}
+
+impl<A: Api> Reporter for Insights<A> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        self.summary.record(diagnostic);
+        // Notes, e.g. of skipped files, aren't worth an annotation.
+        if diagnostic.severity != Severity::Note {
+            self.diagnostics.push(diagnostic.clone());
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<Summary> {
+        // Deleting the report of an earlier run deletes its annotations.
+        let path = self.path.clone();
+        let (status, response) = self.api.send("DELETE", &path, None)?;
+        if !(200..300).contains(&status) && status != 404 {
+            return Err(rejected("DELETE", &path, status, &response));
+        }
+        let report = self.report_body();
+        send(&mut self.api, "PUT", &path, Some(report))?;
+
+        let path = format!("{path}/annotations");
+        let annotations = self.annotations();
+        let chunk_size = match self.flavor {
+            Flavor::Cloud => 100,
+            Flavor::Server => 1000,
+        };
+        for chunk in annotations.chunks(chunk_size) {
+            let body = match self.flavor {
+                Flavor::Cloud => json!(chunk),
+                Flavor::Server => json!({ "annotations": chunk }),
+            };
+            send(&mut self.api, "POST", &path, Some(body))?;
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Cut `text` to at most `max` characters, the most Bitbucket accepts.
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max - 1) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text.to_owned(),
+    }
+}
+
+/// Describe a request Bitbucket didn't accept.
+fn rejected(method: &str, path: &str, status: u16, response: &Value) -> io::Error {
+    // Bitbucket Cloud and Server describe errors differently.
+    let message = response
+        .pointer("/error/message")
+        .or_else(|| response.pointer("/errors/0/message"))
+        .and_then(Value::as_str)
+        .unwrap_or("no message");
+    io::Error::other(format!(
+        "Bitbucket responded to {method} {path} with {status}: {message}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::Code;
+
+    use super::*;
+
+    /// Bitbucket Cloud and Server, remembering the requests sent to them.
+    #[derive(Default)]
+    struct FakeBitbucket {
+        requests: Vec<String>,
+    }
+
+    impl Api for &mut FakeBitbucket {
+        fn send(
+            &mut self,
+            method: &str,
+            path: &str,
+            body: Option<Value>,
+        ) -> io::Result<(u16, Value)> {
+            self.requests.push(match &body {
+                Some(body) => format!("{method} {path} {body}"),
+                None => format!("{method} {path}"),
+            });
+            Ok(match (method, path) {
+                ("GET", "/2.0/repositories/w/r/pullrequests/1") => (
+                    200,
+                    json!({
+                        "source": { "commit": { "hash": "abc" }, "branch": { "name": "feature" } },
+                        "destination": { "commit": { "hash": "def" }, "branch": { "name": "main" } },
+                        "description": "```ignore-if-changed\na.ts\n```",
+                    }),
+                ),
+                ("GET", "/rest/api/1.0/projects/P/repos/r/pull-requests/1") => (
+                    200,
+                    json!({
+                        "fromRef": { "id": "refs/heads/feature", "latestCommit": "abc" },
+                        "toRef": { "id": "refs/heads/main", "latestCommit": "def" },
+                        "description": "```ignore-if-changed\na.ts\n```",
+                    }),
+                ),
+                ("DELETE", _) => (404, Value::Null),
+                ("PUT" | "POST", _) => (200, json!({})),
+                _ => (
+                    404,
+                    json!({ "error": { "message": "Repository not found" } }),
+                ),
+            })
+        }
+    }
+
+    fn unmodified(path: &str, line: usize) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(
+            Code::UnmodifiedTarget,
+            path,
+            Some(line),
+            "Expected \"b.ts\" to be modified.".into(),
+        );
+        diagnostic.target = Some("b.ts".into());
+        diagnostic
+    }
+
+    #[test]
+    fn test_pull_request() {
+        let mut bitbucket = FakeBitbucket::default();
+        let expected = PullRequest {
+            source: "abc".into(),
+            source_ref: "refs/heads/feature".into(),
+            destination: "def".into(),
+            destination_ref: "refs/heads/main".into(),
+            description: "```ignore-if-changed\na.ts\n```".into(),
+        };
+        assert_eq!(
+            pull_request(&mut &mut bitbucket, Flavor::Cloud, "w", "r", 1).unwrap(),
+            expected
+        );
+        assert_eq!(
+            pull_request(&mut &mut bitbucket, Flavor::Server, "P", "r", 1).unwrap(),
+            expected
+        );
+        insta::assert_snapshot!(pull_request(&mut &mut bitbucket, Flavor::Cloud, "w", "missing", 1).unwrap_err(), @"Bitbucket responded to GET /2.0/repositories/w/missing/pullrequests/1 with 404: Repository not found");
+    }
+
+    fn insights(flavor: Flavor) -> Vec<String> {
+        let mut bitbucket = FakeBitbucket::default();
+        let path = report_path(flavor, "w", "r", "abc");
+        let mut insights = Insights::new(&mut bitbucket, flavor, &path);
+        let mut warning = unmodified("a.ts", 9);
+        warning.severity = Severity::Warning;
+        let trailer = Diagnostic::new(
+            Code::MissingIgnoreReason,
+            "",
+            None,
+            "Expected a reason for ignoring \"a.ts\".".into(),
+        );
+        for diagnostic in [unmodified("a.ts", 4), warning, trailer] {
+            insights.report(&diagnostic).unwrap();
+        }
+        insights.finish().unwrap();
+        bitbucket.requests
+    }
+
+    #[test]
+    fn test_insights() {
+        insta::assert_debug_snapshot!(insights(Flavor::Cloud), @r###"
+        [
+            "DELETE /2.0/repositories/w/r/commit/abc/reports/if-changed",
+            "PUT /2.0/repositories/w/r/commit/abc/reports/if-changed {\"details\":\"if-changed found 2 errors and 1 warning.\\nerror[IC301]: Expected a reason for ignoring \\\"a.ts\\\".\",\"report_type\":\"BUG\",\"reporter\":\"if-changed\",\"result\":\"FAILED\",\"title\":\"if-changed\"}",
+            "POST /2.0/repositories/w/r/commit/abc/reports/if-changed/annotations [{\"annotation_type\":\"BUG\",\"external_id\":\"IC002-1\",\"line\":4,\"path\":\"a.ts\",\"severity\":\"HIGH\",\"summary\":\"error[IC002]: Expected \\\"b.ts\\\" to be modified.\"},{\"annotation_type\":\"BUG\",\"external_id\":\"IC002-2\",\"line\":9,\"path\":\"a.ts\",\"severity\":\"MEDIUM\",\"summary\":\"warning[IC002]: Expected \\\"b.ts\\\" to be modified.\"}]",
+        ]
+        "###);
+        insta::assert_debug_snapshot!(insights(Flavor::Server), @r###"
+        [
+            "DELETE /rest/insights/1.0/projects/w/repos/r/commits/abc/reports/if-changed",
+            "PUT /rest/insights/1.0/projects/w/repos/r/commits/abc/reports/if-changed {\"details\":\"if-changed found 2 errors and 1 warning.\\nerror[IC301]: Expected a reason for ignoring \\\"a.ts\\\".\",\"reporter\":\"if-changed\",\"result\":\"FAIL\",\"title\":\"if-changed\"}",
+            "POST /rest/insights/1.0/projects/w/repos/r/commits/abc/reports/if-changed/annotations {\"annotations\":[{\"externalId\":\"IC002-1\",\"line\":4,\"message\":\"error[IC002]: Expected \\\"b.ts\\\" to be modified.\",\"path\":\"a.ts\",\"severity\":\"HIGH\"},{\"externalId\":\"IC002-2\",\"line\":9,\"message\":\"warning[IC002]: Expected \\\"b.ts\\\" to be modified.\",\"path\":\"a.ts\",\"severity\":\"MEDIUM\"}]}",
+        ]
+        "###);
+    }
+}
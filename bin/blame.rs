@@ -0,0 +1,206 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+use if_changed::{Blame, Diagnostic, Engine};
+
+use crate::{
+    completions,
+    scope::{PathMode, Scope},
+};
+
+#[derive(Args, Debug)]
+pub struct BlameArgs {
+    /// The lines to look up, as `FILE:START-END`, `FILE:LINE`, or `FILE` for
+    /// the whole file.
+    #[arg(required = true, value_name = "LOCATION", add = ArgValueCompleter::new(completions::complete_paths))]
+    pub locations: Vec<Location>,
+
+    /// How to interpret locations and print paths.
+    ///
+    /// With `cwd`, locations not starting with `/` are relative to the current
+    /// directory and printed paths are relative to it.
+    #[arg(long, value_enum, default_value_t)]
+    pub path_mode: PathMode,
+}
+
+/// A range of lines in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    /// The first and last lines, or `None` for the whole file.
+    pub range: Option<(usize, usize)>,
+}
+
+impl FromStr for Location {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let Some((path, lines)) = value.rsplit_once(':') else {
+            return Ok(Self {
+                path: value.into(),
+                range: None,
+            });
+        };
+        let parse = |line: &str| match line.parse::<usize>() {
+            Ok(line) if line > 0 => Ok(line),
+            _ => Err(format!("invalid line {line:?} in {value:?}")),
+        };
+        let range = match lines.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(lines)?, parse(lines)?),
+        };
+        if range.0 > range.1 {
+            return Err(format!("the range in {value:?} ends before it starts"));
+        }
+        Ok(Self {
+            path: path.into(),
+            range: Some(range),
+        })
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        match self.range {
+            Some((start, end)) if start == end => write!(f, ":{start}"),
+            Some((start, end)) => write!(f, ":{start}-{end}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Print the blocks covering each location and the targets they impose.
+///
+/// Returns the problems found parsing the files, if any.
+pub fn run(
+    args: &BlameArgs,
+    engine: &impl Engine,
+    scope: &Scope,
+    mut out: impl Write,
+) -> io::Result<Vec<Diagnostic>> {
+    let mut problems = Vec::new();
+    for location in &args.locations {
+        let path = PathBuf::from(scope.pattern(&location.path.to_string_lossy()));
+        let path = path.strip_prefix("/").unwrap_or(&path).to_owned();
+        let blames = match engine.blame(&path, location.range.unwrap_or((1, usize::MAX))) {
+            Ok(blames) => blames,
+            Err(diagnostics) => {
+                problems.extend(diagnostics);
+                continue;
+            }
+        };
+        if blames.is_empty() {
+            writeln!(out, "No blocks cover {location}.")?;
+        }
+        for blame in blames {
+            write_blame(&path, &blame, scope, &mut out)?;
+        }
+    }
+    Ok(problems)
+}
+
+fn write_blame(path: &Path, blame: &Blame, scope: &Scope, mut out: impl Write) -> io::Result<()> {
+    let location = Location {
+        path: scope.path(path),
+        range: Some(blame.range),
+    };
+    match &blame.name {
+        Some(name) => writeln!(out, "{location}: if-changed({name})")?,
+        None => writeln!(out, "{location}: if-changed")?,
+    }
+    if blame.targets.is_empty() {
+        writeln!(out, "    (no targets)")?;
+    }
+    for (target, name) in &blame.targets {
+        let target = scope.path(target);
+        match name {
+            Some(name) => writeln!(out, "    {}:{name}", target.display())?,
+            None => writeln!(out, "    {}", target.display())?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{testing::git_test, GitEngine};
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        assert_eq!(
+            "a.ts:3-5".parse(),
+            Ok(Location {
+                path: "a.ts".into(),
+                range: Some((3, 5))
+            })
+        );
+        assert_eq!(
+            "a.ts:3".parse::<Location>().map(|location| location.range),
+            Ok(Some((3, 3)))
+        );
+        assert_eq!(
+            "a.ts".parse::<Location>().map(|location| location.range),
+            Ok(None)
+        );
+        insta::assert_snapshot!("a.ts:5-3".parse::<Location>().unwrap_err(), @r###"the range in "a.ts:5-3" ends before it starts"###);
+        insta::assert_snapshot!("a.ts:0".parse::<Location>().unwrap_err(), @r###"invalid line "0" in "a.ts:0""###);
+    }
+
+    #[test]
+    fn test_run() {
+        let (tempdir, repo) = git_test! {
+            working: [
+                "src/a.ts" => indoc! {"
+                    // if-changed(a)
+                    A,
+                    // then-change(b.ts:b, /c.ts)
+                    B,
+                    // if-changed
+                    C,
+                    // then-change(a.ts)
+                "},
+                "src/b.ts" => "// if-changed\n",
+                "src/c.ts" => ""
+            ]
+        };
+
+        let args = BlameArgs {
+            locations: vec![
+                "a.ts:2".parse().unwrap(),
+                "a.ts:4".parse().unwrap(),
+                "a.ts".parse().unwrap(),
+                "b.ts".parse().unwrap(),
+                "/src/c.ts:1".parse().unwrap(),
+            ],
+            path_mode: PathMode::Cwd,
+        };
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let scope = Scope::new(PathMode::Cwd, tempdir.path(), &tempdir.path().join("src"));
+        let mut out = Vec::new();
+        let problems = run(&args, &engine, &scope, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        a.ts:1-3: if-changed(a)
+            b.ts:b
+            ../c.ts
+        No blocks cover a.ts:4.
+        a.ts:1-3: if-changed(a)
+            b.ts:b
+            ../c.ts
+        a.ts:5-7: if-changed
+            a.ts
+        No blocks cover /src/c.ts:1.
+
+        "###);
+        assert_eq!(problems.len(), 1);
+    }
+}
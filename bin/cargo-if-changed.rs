@@ -0,0 +1,140 @@
+#![cfg_attr(coverage_nightly, feature(coverage_attribute))]
+
+use std::{
+    env,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+};
+
+use serde_json::Value;
+
+/// The variable `if-changed` reads patterns from when none are given.
+const PATTERNS_VAR: &str = "IF_CHANGED_PATTERNS";
+
+/// Run `if-changed`, as `cargo if-changed`, on the files of the packages of
+/// the current workspace unless patterns are given.
+#[cfg_attr(coverage_nightly, coverage(off))]
+fn main() -> ExitCode {
+    let mut args = env::args_os().skip(1).peekable();
+    // Cargo passes the name of the subcommand first.
+    args.next_if(|arg| arg == "if-changed");
+    let args = args.collect::<Vec<_>>();
+
+    let mut command = Command::new(if_changed());
+    command.args(&args);
+    if env::var_os(PATTERNS_VAR).is_none() {
+        match workspace_patterns() {
+            Ok(patterns) if !patterns.is_empty() => {
+                command.env(PATTERNS_VAR, patterns.join("\n"));
+            }
+            Ok(_) => {}
+            Err(error) => {
+                eprintln!("Could not find the packages of the workspace: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    match command.status() {
+        Ok(status) if status.success() => ExitCode::SUCCESS,
+        Ok(status) => status
+            .code()
+            .and_then(|code| u8::try_from(code).ok())
+            .map_or(ExitCode::FAILURE, ExitCode::from),
+        Err(error) => {
+            eprintln!("Could not run if-changed: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Get the `if-changed` installed next to this binary, as by `cargo install`,
+/// or else the one on `PATH`.
+fn if_changed() -> PathBuf {
+    let name = format!("if-changed{}", env::consts::EXE_SUFFIX);
+    env::current_exe()
+        .map(|exe| exe.with_file_name(&name))
+        .ok()
+        .filter(|exe| exe.is_file())
+        .unwrap_or_else(|| name.into())
+}
+
+/// Get patterns matching the directories of the packages of the current
+/// workspace, relative to the repository root.
+fn workspace_patterns() -> io::Result<Vec<String>> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let output = Command::new(cargo)
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    let metadata = serde_json::from_slice::<Value>(&output.stdout)?;
+    let repository = git2::Repository::open_from_env().map_err(io::Error::other)?;
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| io::Error::other("bare repositories are not supported"))?;
+    Ok(member_patterns(&metadata, &workdir.canonicalize()?))
+}
+
+/// Get patterns matching the directories of the packages in `metadata`, as
+/// printed by `cargo metadata --no-deps`, relative to `root`. A package at the
+/// root matches all files, so no patterns are needed.
+fn member_patterns(metadata: &Value, root: &Path) -> Vec<String> {
+    let mut patterns = Vec::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        let Some(manifest) = package["manifest_path"].as_str() else {
+            continue;
+        };
+        let directory = Path::new(manifest).parent().unwrap_or(Path::new(""));
+        let directory = directory
+            .canonicalize()
+            .unwrap_or_else(|_| directory.to_owned());
+        let Ok(directory) = directory.strip_prefix(root) else {
+            continue;
+        };
+        if directory.as_os_str().is_empty() {
+            return Vec::new();
+        }
+        patterns.push(format!(
+            "/{}/",
+            directory.to_string_lossy().replace('\\', "/")
+        ));
+    }
+    patterns.sort();
+    patterns.dedup();
+    patterns
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_member_patterns() {
+        let metadata = json!({
+            "packages": [
+                { "manifest_path": "/repo/crates/b/Cargo.toml" },
+                { "manifest_path": "/repo/crates/a/Cargo.toml" },
+                { "manifest_path": "/elsewhere/c/Cargo.toml" },
+            ],
+        });
+        assert_eq!(
+            member_patterns(&metadata, Path::new("/repo")),
+            ["/crates/a/", "/crates/b/"]
+        );
+
+        let metadata = json!({
+            "packages": [
+                { "manifest_path": "/repo/crates/a/Cargo.toml" },
+                { "manifest_path": "/repo/Cargo.toml" },
+            ],
+        });
+        assert!(member_patterns(&metadata, Path::new("/repo")).is_empty());
+    }
+}
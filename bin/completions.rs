@@ -0,0 +1,134 @@
+use std::{collections::BTreeSet, ffi::OsStr, io};
+
+use clap::Args;
+use clap_complete::{engine::CompletionCandidate, env::Shells, Shell};
+
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    /// The shell to generate the completion script for.
+    pub shell: Shell,
+}
+
+/// Write the registration script for dynamic completions to `out`.
+///
+/// The script calls back into `if-changed` on every completion request, so
+/// tracked paths and revisions are always completed against the current
+/// repository.
+pub fn run(args: &CompletionsArgs, mut out: impl io::Write) -> io::Result<()> {
+    let shell = args.shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell)
+        .ok_or_else(|| io::Error::other(format!("unsupported shell `{shell}`")))?;
+    completer.write_registration(
+        "COMPLETE",
+        "if-changed",
+        "if-changed",
+        "if-changed",
+        &mut out,
+    )
+}
+
+/// Complete tracked paths in the repository discovered from the environment.
+pub fn complete_paths(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(repository) = git2::Repository::open_from_env() else {
+        return Vec::new();
+    };
+    tracked_paths(&repository, &current.to_string_lossy())
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Complete revisions in the repository discovered from the environment.
+pub fn complete_refs(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(repository) = git2::Repository::open_from_env() else {
+        return Vec::new();
+    };
+    refs(&repository, &current.to_string_lossy())
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Get the tracked paths starting with `prefix`, one path component at a time.
+///
+/// Directories are completed with a trailing `/` rather than listing every file
+/// under them, which keeps completion usable in large repositories.
+fn tracked_paths(repository: &git2::Repository, prefix: &str) -> BTreeSet<String> {
+    let Ok(index) = repository.index() else {
+        return BTreeSet::new();
+    };
+    let (prefix, anchor) = match prefix.strip_prefix('/') {
+        Some(prefix) => (prefix, "/"),
+        None => (prefix, ""),
+    };
+    index
+        .iter()
+        .filter_map(|entry| {
+            let path = String::from_utf8(entry.path).ok()?;
+            let rest = path.strip_prefix(prefix)?;
+            let end = rest
+                .find('/')
+                .map_or(path.len(), |index| prefix.len() + index + 1);
+            Some(format!("{anchor}{}", &path[..end]))
+        })
+        .collect()
+}
+
+/// Get the branch and tag names starting with `prefix`.
+fn refs(repository: &git2::Repository, prefix: &str) -> BTreeSet<String> {
+    let Ok(references) = repository.references() else {
+        return BTreeSet::new();
+    };
+    let mut refs = references
+        .flatten()
+        .filter(|reference| reference.is_branch() || reference.is_remote() || reference.is_tag())
+        .filter_map(|reference| reference.shorthand().map(str::to_owned))
+        .filter(|name| name.starts_with(prefix))
+        .collect::<BTreeSet<_>>();
+    if "HEAD".starts_with(prefix) {
+        refs.insert("HEAD".to_owned());
+    }
+    refs
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+
+    use super::*;
+
+    #[test]
+    fn test_tracked_paths() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a.ts" => "", "src/b.ts" => "", "src/c/d.ts" => ""]
+        };
+
+        insta::assert_compact_json_snapshot!(tracked_paths(&repo, ""), @r###"["a.ts", "src/"]"###);
+        insta::assert_compact_json_snapshot!(tracked_paths(&repo, "src/"), @r###"["src/b.ts", "src/c/"]"###);
+        insta::assert_compact_json_snapshot!(tracked_paths(&repo, "/src/c"), @r###"["/src/c/"]"###);
+        insta::assert_compact_json_snapshot!(tracked_paths(&repo, "b"), @"[]");
+    }
+
+    #[test]
+    fn test_refs() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a.ts" => ""]
+        };
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("feature", &head, false).unwrap();
+
+        insta::assert_compact_json_snapshot!(refs(&repo, "f"), @r###"["feature"]"###);
+        insta::assert_compact_json_snapshot!(refs(&repo, "H"), @r###"["HEAD"]"###);
+    }
+
+    #[test]
+    fn test_registration() {
+        let mut script = Vec::new();
+        run(&CompletionsArgs { shell: Shell::Bash }, &mut script).unwrap();
+        assert!(String::from_utf8(script)
+            .unwrap()
+            .contains("COMPLETE=\"bash\""));
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anstyle::Style;
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+use if_changed::Engine;
+
+use crate::{
+    completions,
+    scope::{PathMode, Scope},
+    stats::StatsFormat,
+};
+
+const EMPHASIS: Style = Style::new().bold();
+
+#[derive(Args, Debug)]
+pub struct CoverageArgs {
+    /// The revision to compare against. By default, HEAD is used.
+    #[arg(long, env = "PRE_COMMIT_FROM_REF", add = ArgValueCompleter::new(completions::complete_refs))]
+    pub from_ref: Option<String>,
+
+    /// The revision to compare with. By default, the current working tree is used.
+    #[arg(long, env = "PRE_COMMIT_TO_REF", add = ArgValueCompleter::new(completions::complete_refs))]
+    pub to_ref: Option<String>,
+
+    /// Git patterns defining the set of files to measure, as for `check`. By
+    /// default, this will be all changed files between revisions.
+    #[arg(add = ArgValueCompleter::new(completions::complete_paths))]
+    pub patterns: Vec<String>,
+
+    /// How to interpret patterns and print paths.
+    #[arg(long, value_enum, default_value_t)]
+    pub path_mode: PathMode,
+
+    /// The format of the report.
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: StatsFormat,
+}
+
+/// The changed lines of a file and how many of them are inside blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    /// The file, relative to the repository root.
+    pub path: PathBuf,
+    /// The number of lines added or removed inside blocks.
+    pub governed: usize,
+    /// The number of lines added or removed.
+    pub changed: usize,
+}
+
+/// Measure the changed lines of the files matching `patterns` that are inside
+/// blocks. Files without changed lines, e.g. renamed ones, are omitted.
+pub fn coverage(engine: &impl Engine, patterns: &[String]) -> Vec<FileCoverage> {
+    let mut files = Vec::new();
+    for path in engine.matches(patterns).flat_map(Result::ok) {
+        let changed = engine
+            .range_changes(&path, (1, usize::MAX))
+            .map_or(0, |changes| changes.len());
+        if changed == 0 {
+            continue;
+        }
+        // Files that can't be parsed, e.g. deleted ones, have no blocks.
+        let mut ranges = engine
+            .blame(&path, (1, usize::MAX))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|blame| blame.range)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+        // Merge nested and overlapping blocks so lines are counted once.
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        let governed = merged
+            .into_iter()
+            .map(|range| {
+                engine
+                    .range_changes(&path, range)
+                    .map_or(0, |changes| changes.len())
+            })
+            .sum();
+        files.push(FileCoverage {
+            path,
+            governed,
+            changed,
+        });
+    }
+    files
+}
+
+/// Write the coverage of each file and overall.
+pub fn write(
+    files: &[FileCoverage],
+    scope: &Scope,
+    format: StatsFormat,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let governed = files.iter().map(|file| file.governed).sum::<usize>();
+    let changed = files.iter().map(|file| file.changed).sum::<usize>();
+    match format {
+        StatsFormat::Text => {
+            if changed == 0 {
+                return writeln!(out, "No changed lines.");
+            }
+            let width = changed.to_string().len();
+            let line = |governed: usize, changed: usize| {
+                format!(
+                    "{governed:>width$}/{changed:<width$} {:>5.1}%",
+                    percent(governed, changed)
+                )
+            };
+            writeln!(out, "{EMPHASIS}Changed lines inside blocks:{EMPHASIS:#}")?;
+            for file in files {
+                writeln!(
+                    out,
+                    "  {}  {}",
+                    line(file.governed, file.changed),
+                    scope.path(&file.path).display()
+                )?;
+            }
+            writeln!(
+                out,
+                "{EMPHASIS}  {}  total{EMPHASIS:#}",
+                line(governed, changed)
+            )
+        }
+        StatsFormat::Json => {
+            let files = files
+                .iter()
+                .map(|file| {
+                    serde_json::json!({
+                        "path": file.path.to_string_lossy().replace('\\', "/"),
+                        "governed_lines": file.governed,
+                        "changed_lines": file.changed,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let json = serde_json::json!({
+                "files": files,
+                "governed_lines": governed,
+                "changed_lines": changed,
+            });
+            writeln!(out, "{json}")
+        }
+    }
+}
+
+fn percent(governed: usize, changed: usize) -> f64 {
+    governed as f64 * 100.0 / changed as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{testing::git_test, GitEngine};
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_coverage() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => indoc! {"
+                    A,
+                    // if-changed
+                    B,
+                    // if-changed
+                    C,
+                    // then-change(b.ts)
+                    D,
+                    // then-change(b.ts)
+                "},
+                "b.ts" => "B\n"
+            ]
+            working: [
+                "a.ts" => indoc! {"
+                    AA,
+                    // if-changed
+                    BB,
+                    // if-changed
+                    CC,
+                    // then-change(b.ts)
+                    D,
+                    // then-change(b.ts)
+                "},
+                "b.ts" => "BB\n"
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let files = coverage(&engine, &[]);
+        assert_eq!(
+            files,
+            [
+                FileCoverage {
+                    path: "a.ts".into(),
+                    governed: 4,
+                    changed: 6,
+                },
+                FileCoverage {
+                    path: "b.ts".into(),
+                    governed: 0,
+                    changed: 2,
+                },
+            ]
+        );
+
+        let mut out = Vec::new();
+        write(
+            &files,
+            &Scope::default(),
+            StatsFormat::Text,
+            anstream::StripStream::new(&mut out),
+        )
+        .unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        Changed lines inside blocks:
+          4/6  66.7%  a.ts
+          0/2   0.0%  b.ts
+          4/8  50.0%  total
+
+        "###);
+
+        let mut out = Vec::new();
+        write(&files, &Scope::default(), StatsFormat::Json, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        {"changed_lines":8,"files":[{"changed_lines":6,"governed_lines":4,"path":"a.ts"},{"changed_lines":2,"governed_lines":0,"path":"b.ts"}],"governed_lines":4}
+
+        "###);
+    }
+}
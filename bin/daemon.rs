@@ -0,0 +1,307 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use clap::Args;
+use if_changed::{GitEngine, ParseCache, Stats, Summary};
+use serde_json::{json, Value};
+
+use crate::{report, run_engine, sorted, CheckArgs};
+
+#[derive(Args, Debug)]
+pub struct DaemonArgs {
+    /// Serve clients connecting to a unix socket at PATH instead of requests
+    /// on stdin.
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+}
+
+/// A request, one JSON object per line.
+#[derive(serde::Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    /// Echoed in the response, e.g. to match responses to requests.
+    #[serde(default)]
+    id: Value,
+    /// `check` or `shutdown`.
+    method: String,
+    #[serde(default)]
+    params: CheckParams,
+}
+
+/// What a `check` request checks. Like the options of `if-changed`, patterns
+/// are relative to the repository root.
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CheckParams {
+    patterns: Vec<String>,
+    from_ref: Option<String>,
+    to_ref: Option<String>,
+}
+
+/// Answers check requests for a repository kept open between requests.
+pub struct Daemon<'repo> {
+    /// The options requests are checked with, as configured in
+    /// `.if-changed.toml`.
+    args: CheckArgs,
+    repository: &'repo git2::Repository,
+    /// The files parsed so far, reused until they change.
+    cache: Arc<ParseCache>,
+    /// Engines comparing two commits, keyed by their ids and the patterns
+    /// their diff is narrowed to. Their diff can't change, unlike diffs with
+    /// the working tree, which are computed for each request.
+    engines: HashMap<(Option<git2::Oid>, git2::Oid, Vec<String>), GitEngine<'repo>>,
+}
+
+impl<'repo> Daemon<'repo> {
+    pub fn new(args: CheckArgs, repository: &'repo git2::Repository) -> Self {
+        Self {
+            args,
+            repository,
+            cache: Arc::default(),
+            engines: HashMap::new(),
+        }
+    }
+
+    /// Answer the requests read from `input` until it ends or a `shutdown`
+    /// request, writing one JSON response per line to `out`. Returns whether
+    /// the daemon was shut down.
+    pub fn serve(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<bool> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, shutdown) = self.handle(&line);
+            writeln!(out, "{response}")?;
+            out.flush()?;
+            if shutdown {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Answer a request, returning the response and whether to shut down.
+    fn handle(&mut self, line: &str) -> (Value, bool) {
+        let request = match serde_json::from_str::<Request>(line) {
+            Ok(request) => request,
+            Err(error) => {
+                return (
+                    json!({ "id": null, "error": format!("Invalid request: {error}") }),
+                    false,
+                )
+            }
+        };
+        match request.method.as_str() {
+            "check" => match self.check(request.params) {
+                Ok(mut response) => {
+                    response["id"] = request.id;
+                    (response, false)
+                }
+                Err(error) => (json!({ "id": request.id, "error": error }), false),
+            },
+            "shutdown" => (json!({ "id": request.id }), true),
+            method => (
+                json!({ "id": request.id, "error": format!("Unknown method {method:?}") }),
+                false,
+            ),
+        }
+    }
+
+    /// Check the files of a request, in the same JSON schema as
+    /// `--format json` reports, with a summary.
+    fn check(&mut self, params: CheckParams) -> Result<Value, String> {
+        let args = CheckArgs {
+            patterns: params.patterns,
+            from_ref: params.from_ref,
+            to_ref: params.to_ref,
+            ..self.args.clone()
+        };
+        // Revisions are resolved up front to cache engines by commit.
+        let resolve = |revision: &Option<String>| {
+            revision
+                .as_deref()
+                .map(|revision| {
+                    self.repository
+                        .revparse_single(revision)
+                        .and_then(|object| object.peel_to_commit())
+                        .map(|commit| commit.id())
+                        .map_err(|error| format!("Invalid revision {revision:?}: {error}"))
+                })
+                .transpose()
+        };
+        let from = resolve(&args.from_ref)?;
+        let to = resolve(&args.to_ref)?;
+
+        let working_tree;
+        let engine = match to {
+            Some(to) => match self.engines.entry((from, to, args.patterns.clone())) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    args.engine(self.repository)
+                        .map_err(|error| error.to_string())?,
+                ),
+            },
+            None => {
+                working_tree = args
+                    .engine(self.repository)
+                    .map_err(|error| error.to_string())?;
+                &working_tree
+            }
+        };
+        let diagnostics = sorted(run_engine(
+            &args,
+            engine,
+            self.cache.clone(),
+            &mut Stats::default(),
+        ));
+
+        let mut summary = Summary::default();
+        for diagnostic in &diagnostics {
+            summary.record(diagnostic);
+        }
+        let mut response = report::json(&diagnostics);
+        response["summary"] = json!({
+            "errors": summary.errors,
+            "warnings": summary.warnings,
+        });
+        Ok(response)
+    }
+}
+
+/// Serve requests on stdin and stdout, or on the socket of `args`.
+pub fn run(args: &DaemonArgs, mut daemon: Daemon) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(path) = &args.socket {
+        let listener = std::os::unix::net::UnixListener::bind(path)?;
+        // Clients are served one at a time, in the order they connect.
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let input = io::BufReader::new(stream.try_clone()?);
+            match daemon.serve(input, &stream) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(error) => tracing::warn!(%error, "client disconnected"),
+            }
+        }
+        return std::fs::remove_file(path);
+    }
+    #[cfg(not(unix))]
+    let _ = args;
+    daemon.serve(io::stdin().lock(), io::stdout().lock())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_daemon() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts)
+                "}
+            ]
+            "second commit": [
+                "b.ts" => "B,\n"
+            ]
+        };
+
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let mut daemon = Daemon::new(CheckArgs::default(), &repository);
+        let input = indoc! {r#"
+            {"id": 1, "method": "check", "params": {"to_ref": "HEAD~"}}
+            {"id": 2, "method": "check", "params": {"to_ref": "HEAD~", "patterns": ["b.ts"]}}
+            {"id": 3, "method": "check", "params": {"to_ref": "unknown"}}
+            {"id": 4, "method": "restart"}
+            not json
+
+            {"id": 5, "method": "shutdown"}
+            {"id": 6, "method": "check"}
+        "#};
+        let mut out = Vec::new();
+        assert!(daemon.serve(input.as_bytes(), &mut out).unwrap());
+        // One engine per set of patterns.
+        assert_eq!(daemon.engines.len(), 2);
+        let responses = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut response = serde_json::from_str::<Value>(line).unwrap();
+                if let Some(error) = response.get_mut("error") {
+                    // Keep errors of serde and git short.
+                    *error = error.as_str().unwrap().split(':').next().unwrap().into();
+                }
+                response
+            })
+            .collect::<Vec<_>>();
+        insta::assert_json_snapshot!(responses, @r###"
+        [
+          {
+            "diagnostics": [
+              {
+                "block_name": null,
+                "changes": [
+                  "+// if-changed",
+                  "+A,",
+                  "+// then-change(b.ts)"
+                ],
+                "code": "IC002",
+                "kind": "unmodified-target",
+                "last_edit": null,
+                "message": "Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 3.",
+                "owners": [],
+                "severity": "error",
+                "source_file": "a.ts",
+                "source_line": 3,
+                "suggestion": null,
+                "target": "b.ts"
+              }
+            ],
+            "id": 1,
+            "summary": {
+              "errors": 1,
+              "warnings": 0
+            },
+            "version": 1
+          },
+          {
+            "diagnostics": [],
+            "id": 2,
+            "summary": {
+              "errors": 0,
+              "warnings": 0
+            },
+            "version": 1
+          },
+          {
+            "error": "Invalid revision \"unknown\"",
+            "id": 3
+          },
+          {
+            "error": "Unknown method \"restart\"",
+            "id": 4
+          },
+          {
+            "error": "Invalid request",
+            "id": null
+          },
+          {
+            "id": 5
+          }
+        ]
+        "###);
+    }
+}
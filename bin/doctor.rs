@@ -0,0 +1,442 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use anstyle::{AnsiColor, Style};
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+use if_changed::{Config, Engine as _, GitEngine, CONFIG_FILE_NAME};
+
+use crate::completions;
+
+const OK: Style = AnsiColor::Green.on_default().bold();
+const WARNING: Style = AnsiColor::Yellow.on_default().bold();
+const ERROR: Style = AnsiColor::Red.on_default().bold();
+const HELP: Style = AnsiColor::Cyan.on_default().bold();
+
+/// Number of files containing blocks to parse as a sample.
+const SAMPLE_SIZE: usize = 5;
+
+#[derive(Args, Debug, Default)]
+pub struct DoctorArgs {
+    /// The revision to compare against.
+    #[arg(long, env = "PRE_COMMIT_FROM_REF", add = ArgValueCompleter::new(completions::complete_refs))]
+    pub from_ref: Option<String>,
+
+    /// The revision to compare with.
+    #[arg(long, env = "PRE_COMMIT_TO_REF", add = ArgValueCompleter::new(completions::complete_refs))]
+    pub to_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The outcome of one check of the environment.
+#[derive(Debug)]
+struct Finding {
+    status: Status,
+    message: String,
+    /// How to fix the problem, if any.
+    help: Option<String>,
+}
+
+impl Finding {
+    fn ok(message: String) -> Self {
+        Self {
+            status: Status::Ok,
+            message,
+            help: None,
+        }
+    }
+
+    fn warning(message: String, help: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warning,
+            message,
+            help: Some(help.into()),
+        }
+    }
+
+    fn error(message: String, help: impl Into<String>) -> Self {
+        Self {
+            status: Status::Error,
+            message,
+            help: Some(help.into()),
+        }
+    }
+}
+
+/// Check the environment `if-changed` runs in, printing a line per check.
+///
+/// Returns whether all checks passed, possibly with warnings.
+pub fn run(
+    args: &DoctorArgs,
+    repository: Result<git2::Repository, git2::Error>,
+    mut out: impl Write,
+) -> io::Result<bool> {
+    let findings = match repository {
+        Ok(repository) => diagnose(args, &repository),
+        Err(error) => vec![Finding::error(
+            format!("Could not find a repository: {}", error.message()),
+            "Run `if-changed` inside a git repository, or set `GIT_DIR`.",
+        )],
+    };
+    for finding in &findings {
+        let (style, label) = match finding.status {
+            Status::Ok => (OK, "ok"),
+            Status::Warning => (WARNING, "warning"),
+            Status::Error => (ERROR, "error"),
+        };
+        writeln!(out, "{style}{label}{style:#}: {}", finding.message)?;
+        if let Some(help) = &finding.help {
+            writeln!(out, "  {HELP}help{HELP:#}: {help}")?;
+        }
+    }
+    Ok(findings
+        .iter()
+        .all(|finding| finding.status != Status::Error))
+}
+
+fn diagnose(args: &DoctorArgs, repository: &git2::Repository) -> Vec<Finding> {
+    let Some(workdir) = repository.workdir() else {
+        return vec![Finding::error(
+            format!("{} is a bare repository.", repository.path().display()),
+            "Run `if-changed` in a checkout with a working tree.",
+        )];
+    };
+    let mut findings = vec![Finding::ok(format!(
+        "Found the repository at {}.",
+        workdir.display()
+    ))];
+
+    let mut resolved = true;
+    for (option, revision) in [("--from-ref", &args.from_ref), ("--to-ref", &args.to_ref)] {
+        let Some(revision) = revision else {
+            continue;
+        };
+        match repository
+            .revparse_single(revision)
+            .and_then(|object| object.peel_to_commit())
+        {
+            Ok(commit) => findings.push(Finding::ok(format!(
+                "Resolved {option} {revision:?} to {}.",
+                short_id(&commit)
+            ))),
+            Err(error) => {
+                resolved = false;
+                findings.push(Finding::error(
+                    format!(
+                        "Could not resolve {option} {revision:?}: {}",
+                        error.message()
+                    ),
+                    "Check the revision exists locally, e.g. with `git fetch` for remote branches.",
+                ));
+            }
+        }
+    }
+    if resolved {
+        findings.push(history(args, repository));
+    }
+
+    let config = match Config::discover(workdir) {
+        Ok(config) => {
+            findings.push(Finding::ok(format!("Loaded {CONFIG_FILE_NAME}.")));
+            config
+        }
+        Err(error) => {
+            findings.push(Finding::error(
+                format!("Could not load {CONFIG_FILE_NAME}: {error}"),
+                format!(
+                    "Fix the file, or run `if-changed init --force` to replace it with a starter {CONFIG_FILE_NAME}."
+                ),
+            ));
+            Config::default()
+        }
+    };
+    let config = git_config(repository, config, &mut findings);
+    findings.extend(trailers(&config));
+
+    if resolved {
+        findings.push(sample(repository));
+    }
+    findings
+}
+
+/// Apply the settings of the git config of `repository` to `config`.
+fn git_config(
+    repository: &git2::Repository,
+    mut config: Config,
+    findings: &mut Vec<Finding>,
+) -> Config {
+    let merged = repository
+        .config()
+        .map_err(io::Error::other)
+        .and_then(|git_config| config.merge_git_config(&git_config));
+    if let Err(error) = merged {
+        findings.push(Finding::error(
+            format!("Could not load the git config: {error}"),
+            "Fix or remove the setting, e.g. with `git config --unset-all <key>`.",
+        ));
+    }
+    config
+}
+
+/// Check the history needed to compare the revisions is available.
+fn history(args: &DoctorArgs, repository: &git2::Repository) -> Finding {
+    if !repository.is_shallow() {
+        return Finding::ok("The repository has its full history.".to_owned());
+    }
+    let (None, Some(to_ref)) = (&args.from_ref, &args.to_ref) else {
+        return Finding::ok(
+            "The repository is shallow, but no parent commit is needed.".to_owned(),
+        );
+    };
+    let Ok(commit) = repository
+        .revparse_single(to_ref)
+        .and_then(|object| object.peel_to_commit())
+    else {
+        return Finding::ok(
+            "The repository is shallow, but no parent commit is needed.".to_owned(),
+        );
+    };
+    if commit.parent_count() > 0 && commit.parent(0).is_err() {
+        return Finding::error(
+            format!(
+                "The repository is a shallow clone missing the parent of {}.",
+                short_id(&commit)
+            ),
+            "Fetch more history with `git fetch --deepen=1`, or use `fetch-depth: 0` with actions/checkout.",
+        );
+    }
+    Finding::ok("The repository is shallow, but contains the parent commit.".to_owned())
+}
+
+/// Check the trailer settings can match commit trailers.
+fn trailers(config: &Config) -> Vec<Finding> {
+    let keys = &config.ignore_trailer.keys;
+    if keys.is_empty() {
+        return vec![Finding::warning(
+            "No ignore trailer keys are configured, so files can never be ignored.".to_owned(),
+            format!("Add a key to `keys` in the `[ignore-trailer]` section of {CONFIG_FILE_NAME}."),
+        )];
+    }
+
+    let mut findings = Vec::new();
+    let mut seen = HashSet::new();
+    for key in keys {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            findings.push(Finding::error(
+                format!("The ignore trailer key {key:?} can never match a commit trailer."),
+                "Trailer keys may only contain letters, digits, and `-`.",
+            ));
+        } else if !seen.insert(key.to_ascii_lowercase()) {
+            findings.push(Finding::warning(
+                format!("The ignore trailer key {key:?} is listed more than once."),
+                "Keys are compared case-insensitively; remove the duplicate.",
+            ));
+        }
+    }
+    let reason_pattern = config.ignore_trailer.reason_pattern.as_deref();
+    if let Some(Err(error)) = reason_pattern.map(regex::Regex::new) {
+        findings.push(Finding::error(
+            format!("The ignore reason pattern is invalid: {error}"),
+            format!(
+                "Fix `reason-pattern` in the `[ignore-trailer]` section of {CONFIG_FILE_NAME}."
+            ),
+        ));
+    }
+    if findings.is_empty() {
+        findings.push(Finding::ok(format!(
+            "Ignore trailers use {}{}.",
+            keys.iter()
+                .map(|key| format!("{key:?}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            match reason_pattern {
+                Some(pattern) => format!(" and require a reason matching `{pattern}`"),
+                None if config.ignore_trailer.require_reason => " and require a reason".to_owned(),
+                None => String::new(),
+            }
+        )));
+    }
+    findings
+}
+
+/// Parse a few tracked files containing blocks.
+fn sample(repository: &git2::Repository) -> Finding {
+    if repository.head().is_err() {
+        return Finding::warning(
+            "The repository has no commits, so no files were parsed.".to_owned(),
+            "Commit some files and run `if-changed doctor` again.",
+        );
+    }
+    let (Ok(index), Some(workdir)) = (repository.index(), repository.workdir()) else {
+        return Finding::warning(
+            "Could not read the index to find files to parse.".to_owned(),
+            "Check `git status` works in the repository.",
+        );
+    };
+    let paths = index
+        .iter()
+        .filter_map(|entry| String::from_utf8(entry.path).ok())
+        .filter(|path| {
+            std::fs::read(workdir.join(path))
+                .is_ok_and(|content| content.windows(10).any(|window| window == b"if-changed"))
+        })
+        .take(SAMPLE_SIZE)
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Finding::warning(
+            "No tracked file contains an \"if-changed\" block yet.".to_owned(),
+            "Run `if-changed init` for examples of blocks.",
+        );
+    }
+
+    // Comparing HEAD with itself only reports problems in the blocks themselves.
+    let engine = match GitEngine::new(repository, Some("HEAD"), Some("HEAD")) {
+        Ok(engine) => engine,
+        Err(error) => {
+            return Finding::error(
+                format!("Could not compare HEAD with itself: {error}"),
+                "Commit at least once before checking blocks.",
+            )
+        }
+    };
+    let errors = paths
+        .iter()
+        .filter_map(|path| engine.check(path).err())
+        .flatten()
+        .collect::<Vec<_>>();
+    match errors.first() {
+        None => Finding::ok(format!("Parsed {} sample file(s).", paths.len())),
+        Some(error) => Finding::error(
+            format!(
+                "Found {} problem(s) parsing {} sample file(s), e.g. {}",
+                errors.len(),
+                paths.len(),
+                error.message
+            ),
+            format!(
+                "Run `if-changed --explain {}` for how to fix it.",
+                error.code
+            ),
+        ),
+    }
+}
+
+fn short_id(commit: &git2::Commit<'_>) -> String {
+    commit
+        .as_object()
+        .short_id()
+        .ok()
+        .and_then(|id| id.as_str().map(str::to_owned))
+        .unwrap_or_else(|| commit.id().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+    use indoc::indoc;
+
+    use super::*;
+
+    fn doctor(args: &DoctorArgs, repository: git2::Repository) -> (bool, String) {
+        let workdir = repository.workdir().unwrap().display().to_string();
+        let mut out = Vec::new();
+        let passed = run(args, Ok(repository), anstream::StripStream::new(&mut out)).unwrap();
+        (
+            passed,
+            String::from_utf8(out).unwrap().replace(&workdir, "[repo]/"),
+        )
+    }
+
+    #[test]
+    fn test_doctor() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts)
+                "}
+            ]
+        };
+
+        let (passed, out) = doctor(
+            &DoctorArgs {
+                from_ref: None,
+                to_ref: Some("HEAD".into()),
+            },
+            repo,
+        );
+        assert!(passed);
+        insta::assert_snapshot!(out, @r###"
+        ok: Found the repository at [repo]/.
+        ok: Resolved --to-ref "HEAD" to 89abb7e.
+        ok: The repository has its full history.
+        ok: Loaded .if-changed.toml.
+        ok: Ignore trailers use "ignore-if-changed".
+        ok: Parsed 1 sample file(s).
+
+        "###);
+    }
+
+    #[test]
+    fn test_doctor_problems() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                "}
+            ]
+        };
+        std::fs::write(
+            tempdir.path().join(CONFIG_FILE_NAME),
+            "[ignore-trailer]\nkeys = [\"skip it\", \"Skip\", \"skip\"]",
+        )
+        .unwrap();
+
+        let (passed, out) = doctor(
+            &DoctorArgs {
+                from_ref: Some("missing".into()),
+                to_ref: None,
+            },
+            repo,
+        );
+        assert!(!passed);
+        insta::assert_snapshot!(out, @r###"
+        ok: Found the repository at [repo]/.
+        error: Could not resolve --from-ref "missing": revspec 'missing' not found
+          help: Check the revision exists locally, e.g. with `git fetch` for remote branches.
+        ok: Loaded .if-changed.toml.
+        error: The ignore trailer key "skip it" can never match a commit trailer.
+          help: Trailer keys may only contain letters, digits, and `-`.
+        warning: The ignore trailer key "skip" is listed more than once.
+          help: Keys are compared case-insensitively; remove the duplicate.
+
+        "###);
+    }
+
+    #[test]
+    fn test_doctor_without_repository() {
+        let mut out = Vec::new();
+        let passed = run(
+            &DoctorArgs::default(),
+            Err(git2::Error::from_str("not found")),
+            anstream::StripStream::new(&mut out),
+        )
+        .unwrap();
+        assert!(!passed);
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        error: Could not find a repository: not found
+          help: Run `if-changed` inside a git repository, or set `GIT_DIR`.
+
+        "###);
+    }
+}
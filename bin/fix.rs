@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+use if_changed::GitEngine;
+
+use crate::{completions, migrate::write_diff};
+
+#[derive(Args, Debug)]
+pub struct FixArgs {
+    /// Rewrite the targets of `then-change` naming files renamed between the
+    /// revisions to their new paths.
+    #[arg(long)]
+    pub renames: bool,
+
+    /// The revision to compare against. By default, HEAD is used.
+    #[arg(long, env = "PRE_COMMIT_FROM_REF", add = ArgValueCompleter::new(completions::complete_refs))]
+    pub from_ref: Option<String>,
+
+    /// The revision to compare with. By default, the current working tree is used.
+    #[arg(long, env = "PRE_COMMIT_TO_REF", add = ArgValueCompleter::new(completions::complete_refs))]
+    pub to_ref: Option<String>,
+
+    /// Print a diff of the changes instead of writing them.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Rewrite the targets of all tracked files left stale by the renames between
+/// the revisions of `args`.
+pub fn run(args: &FixArgs, repository: &git2::Repository, mut out: impl Write) -> io::Result<()> {
+    if !args.renames {
+        return Err(io::Error::other(
+            "nothing to fix; pass `--renames` to fix the targets of renamed files",
+        ));
+    }
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| io::Error::other("bare repos are not supported"))?;
+    let mut builder = GitEngine::builder(repository).detect_renames(true);
+    if let Some(from_ref) = &args.from_ref {
+        builder = builder.from_ref(from_ref);
+    }
+    if let Some(to_ref) = &args.to_ref {
+        builder = builder.to_ref(to_ref);
+    }
+    let renames = builder
+        .build()?
+        .renames()
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    tracing::debug!(?renames, "fixing renamed targets");
+
+    let mut fixed = 0;
+    for entry in repository.index().map_err(io::Error::other)?.iter() {
+        let Ok(path) = String::from_utf8(entry.path) else {
+            continue;
+        };
+        // Skip files that aren't text.
+        let Ok(source) = fs::read_to_string(workdir.join(&path)) else {
+            continue;
+        };
+        let Some(target) = fix_renames(Path::new(&path), &source, &renames) else {
+            continue;
+        };
+        fixed += 1;
+        if args.dry_run {
+            write_diff(&path, &source, &target, &mut out)?;
+        } else {
+            fs::write(workdir.join(&path), target)?;
+        }
+    }
+    writeln!(
+        out,
+        "{} {fixed} file(s).",
+        if args.dry_run { "Would fix" } else { "Fixed" }
+    )
+}
+
+/// Rewrite the targets of `path` naming files in `renames`, from old to new
+/// paths, or `None` if there are none.
+///
+/// If `path` was renamed itself, its relative targets are rewritten to be
+/// relative to its new directory.
+fn fix_renames(path: &Path, source: &str, renames: &HashMap<PathBuf, PathBuf>) -> Option<String> {
+    // Relative targets were written relative to the old directory.
+    let original = renames
+        .iter()
+        .find(|(_, new)| *new == path)
+        .map_or(path, |(old, _)| old.as_path());
+    rewrite_targets(path, original, source, |old| renames.get(old).cloned())
+}
+
+/// Rewrite the targets of `path`, previously at `original`, that `rename`
+/// gives a new path for, or `None` if there are none.
+///
+/// Targets are rewritten in place, so comment styles and line numbers are
+/// kept. Paths are relative to the repository root.
+pub fn rewrite_targets(
+    path: &Path,
+    original: &Path,
+    source: &str,
+    rename: impl Fn(&Path) -> Option<PathBuf>,
+) -> Option<String> {
+    let mut lines = source
+        .split_inclusive('\n')
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    for block in if_changed::parse_str(path, source).flatten() {
+        for pattern in block.targets {
+            let written = pattern.path.to_string_lossy();
+            // Patterns may match files other than the renamed ones.
+            if written.is_empty() || written.contains(['*', '?', '[']) {
+                continue;
+            }
+            let Some(old) = resolve(original, &written) else {
+                continue;
+            };
+            let new = rename(&old).unwrap_or(old);
+            let rewritten = match written.strip_prefix('/') {
+                Some(_) => format!("/{}", new.to_string_lossy()),
+                None => relative(path.parent().unwrap_or(Path::new("")), &new),
+            };
+            if rewritten == written {
+                continue;
+            }
+            let Some(line) = lines.get_mut(pattern.line - 1) else {
+                continue;
+            };
+            if let Some(replaced) = replace_path(line, &written, &rewritten) {
+                tracing::debug!(?path, %written, rewritten, "fixing a renamed target");
+                *line = replaced;
+            }
+        }
+    }
+    let target = lines.concat();
+    (target != source).then_some(target)
+}
+
+/// Get the path of the target `written` in `file`, relative to the repository
+/// root, or `None` if it points outside of it.
+pub fn resolve(file: &Path, written: &str) -> Option<PathBuf> {
+    let joined = match written.strip_prefix('/') {
+        Some(root) => PathBuf::from(root),
+        None => file.parent().unwrap_or(Path::new("")).join(written),
+    };
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir if resolved.pop() => {}
+            _ => return None,
+        }
+    }
+    Some(resolved)
+}
+
+/// Get the path of `to` relative to the directory `from`, both relative to
+/// the repository root.
+fn relative(from: &Path, to: &Path) -> String {
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec!["..".to_owned(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
+
+/// Replace the first occurrence of the target `written` in `line` with
+/// `rewritten`, or `None` if it isn't there.
+pub fn replace_path(line: &str, written: &str, rewritten: &str) -> Option<String> {
+    let is_boundary = |c: Option<char>| {
+        matches!(c, None | Some('(' | ')' | ',' | ':')) || c.is_some_and(char::is_whitespace)
+    };
+    line.match_indices(written).find_map(|(index, _)| {
+        let end = index + written.len();
+        (is_boundary(line[..index].chars().next_back()) && is_boundary(line[end..].chars().next()))
+            .then(|| format!("{}{rewritten}{}", &line[..index], &line[end..]))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_fix_renames() {
+        let renames = HashMap::from([
+            ("src/b.ts".into(), "lib/b.ts".into()),
+            ("src/a.ts".into(), "src/core/a.ts".into()),
+        ]);
+        let source = indoc! {"
+            // if-changed
+            A,
+            // then-change(b.ts:name, /src/b.ts, bb.ts, c.ts)
+            // if-changed
+            // then-change(
+            //   ../src/b.ts,
+            //   src/*.ts
+            // )
+        "};
+        insta::assert_snapshot!(fix_renames(Path::new("src/a.ts"), source, &renames).unwrap(), @r###"
+        // if-changed
+        A,
+        // then-change(../lib/b.ts:name, /lib/b.ts, bb.ts, c.ts)
+        // if-changed
+        // then-change(
+        //   ../lib/b.ts,
+        //   src/*.ts
+        // )
+        "###);
+        insta::assert_snapshot!(fix_renames(Path::new("src/core/a.ts"), source, &renames).unwrap(), @r###"
+        // if-changed
+        A,
+        // then-change(../../lib/b.ts:name, /lib/b.ts, ../bb.ts, ../c.ts)
+        // if-changed
+        // then-change(
+        //   ../../lib/b.ts,
+        //   src/*.ts
+        // )
+        "###);
+        assert_eq!(
+            fix_renames(Path::new("lib/c.ts"), "// then-change(b.ts)\n", &renames),
+            None
+        );
+    }
+
+    #[test]
+    fn test_relative() {
+        assert_eq!(relative(Path::new(""), Path::new("a/b.ts")), "a/b.ts");
+        assert_eq!(relative(Path::new("a"), Path::new("a/b.ts")), "b.ts");
+        assert_eq!(relative(Path::new("a/c"), Path::new("a/b.ts")), "../b.ts");
+        assert_eq!(relative(Path::new("c"), Path::new("b.ts")), "../b.ts");
+    }
+
+    #[test]
+    fn test_run() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => "// if-changed\n// then-change(b.ts)\n",
+                "b.ts" => "b\nb\nb\n"
+            ]
+        };
+        fs::create_dir(tempdir.path().join("lib")).unwrap();
+        fs::rename(tempdir.path().join("b.ts"), tempdir.path().join("lib/b.ts")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("b.ts")).unwrap();
+        index.add_path(Path::new("lib/b.ts")).unwrap();
+        index.write().unwrap();
+
+        let mut args = FixArgs {
+            renames: true,
+            from_ref: None,
+            to_ref: None,
+            dry_run: true,
+        };
+        let mut out = Vec::new();
+        run(&args, &repo, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        --- a/a.ts
+        +++ b/a.ts
+        @@ -2,1 +2,1 @@
+        -// then-change(b.ts)
+        +// then-change(lib/b.ts)
+        Would fix 1 file(s).
+        "###);
+
+        args.dry_run = false;
+        run(&args, &repo, io::sink()).unwrap();
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join("a.ts")).unwrap(),
+            "// if-changed\n// then-change(lib/b.ts)\n"
+        );
+
+        args.renames = false;
+        insta::assert_snapshot!(run(&args, &repo, io::sink()).unwrap_err(), @"nothing to fix; pass `--renames` to fix the targets of renamed files");
+    }
+}
@@ -0,0 +1,444 @@
+use std::{collections::BTreeMap, io};
+
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use clap::{builder::BoolishValueParser, Args};
+use if_changed::{Diagnostic, Reporter, Severity, Summary};
+use serde_json::{json, Value};
+
+use crate::http::Api;
+
+/// The prefix Gerrit writes before JSON responses to prevent XSSI.
+const XSSI_PREFIX: &str = ")]}'";
+
+/// The tag of the reviews posted, so Gerrit can hide all but the latest.
+const TAG: &str = "autogenerated:if-changed";
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct GerritArgs {
+    /// Check the current patchset of a Gerrit change instead of
+    /// `--from-ref` and `--to-ref`, e.g. `12345` or its Change-Id.
+    ///
+    /// The patchset is fetched from `--gerrit-remote` unless it exists
+    /// locally, and compared with its parent.
+    #[arg(
+        long = "gerrit", env = "IF_CHANGED_GERRIT",
+        value_name = "CHANGE",
+        requires = "gerrit_url",
+        conflicts_with_all = ["from_ref", "to_ref", "watch", "since", "audit"]
+    )]
+    pub gerrit_change: Option<String>,
+
+    /// The URL of the Gerrit server, e.g. `https://review.example.com`.
+    #[arg(long, value_name = "URL", env = "GERRIT_URL")]
+    pub gerrit_url: Option<String>,
+
+    /// The remote to fetch the patchset of `--gerrit` from.
+    #[arg(
+        long,
+        env = "IF_CHANGED_GERRIT_REMOTE",
+        value_name = "REMOTE",
+        default_value = "origin"
+    )]
+    pub gerrit_remote: String,
+
+    /// Post each problem as a comment on the patchset of `--gerrit`, anchored
+    /// to the line of the problem, e.g. the `then-change`.
+    #[arg(
+        long,
+        env = "IF_CHANGED_GERRIT_REVIEW", value_parser = BoolishValueParser::new(),
+        requires_all = ["gerrit_change", "gerrit_user", "gerrit_password"],
+        conflicts_with = "interactive"
+    )]
+    pub gerrit_review: bool,
+
+    /// The label `--gerrit-review` votes on, e.g. `Verified`: -1 if the check
+    /// fails and +1 otherwise.
+    #[arg(
+        long,
+        env = "IF_CHANGED_GERRIT_LABEL",
+        value_name = "LABEL",
+        requires = "gerrit_review"
+    )]
+    pub gerrit_label: Option<String>,
+
+    /// The user authenticating requests to Gerrit.
+    #[arg(long, value_name = "USER", env = "GERRIT_USER")]
+    pub gerrit_user: Option<String>,
+
+    /// The HTTP password of `--gerrit-user`, as generated in the settings of
+    /// Gerrit.
+    #[arg(
+        long,
+        value_name = "PASSWORD",
+        env = "GERRIT_HTTP_PASSWORD",
+        hide_env_values = true
+    )]
+    pub gerrit_password: Option<String>,
+
+    /// The patchset of `--gerrit`, once fetched.
+    #[arg(skip)]
+    pub patchset: Option<Patchset>,
+}
+
+/// The current patchset of a change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patchset {
+    /// The number of the change.
+    pub change: u64,
+    /// The commit of the patchset.
+    pub revision: String,
+    /// The ref of the patchset, e.g. `refs/changes/45/12345/3`.
+    pub reference: String,
+}
+
+impl GerritArgs {
+    fn client(&self) -> Client {
+        let credentials = match (&self.gerrit_user, &self.gerrit_password) {
+            (Some(user), Some(password)) => {
+                Some(BASE64_STANDARD.encode(format!("{user}:{password}")))
+            }
+            _ => None,
+        };
+        Client {
+            agent: ureq::agent(),
+            url: self
+                .gerrit_url
+                .as_deref()
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_owned(),
+            credentials,
+        }
+    }
+
+    /// Look up the current patchset of `--gerrit` and fetch it unless it
+    /// exists in `repository`.
+    pub fn fetch(&self, repository: &git2::Repository) -> io::Result<Patchset> {
+        let change = self.gerrit_change.as_deref().unwrap_or_default();
+        let patchset = current_patchset(&mut self.client(), change)?;
+        let exists = git2::Oid::from_str(&patchset.revision)
+            .and_then(|oid| repository.find_commit(oid))
+            .is_ok();
+        if !exists {
+            repository
+                .find_remote(&self.gerrit_remote)
+                .and_then(|mut remote| remote.fetch(&[&patchset.reference], None, None))
+                .map_err(|error| {
+                    io::Error::other(format!(
+                        "{error}; fetch it with `git fetch {} {}`",
+                        self.gerrit_remote, patchset.reference
+                    ))
+                })?;
+        }
+        Ok(patchset)
+    }
+
+    /// Create the reviewer posting comments, if `--gerrit-review` is given.
+    pub fn review(&self) -> Option<Review<Client>> {
+        if !self.gerrit_review {
+            return None;
+        }
+        let patchset = self.patchset.clone()?;
+        Some(Review::new(
+            self.client(),
+            patchset,
+            self.gerrit_label.clone(),
+        ))
+    }
+}
+
+/// A client of the Gerrit REST API at `url`, authenticated with the base64
+/// encoded `credentials`, if any.
+pub struct Client {
+    agent: ureq::Agent,
+    url: String,
+    credentials: Option<String>,
+}
+
+impl Api for Client {
+    fn send(&mut self, method: &str, path: &str, body: Option<Value>) -> io::Result<(u16, Value)> {
+        // Authenticated requests go to the endpoints under `/a`.
+        let prefix = if self.credentials.is_some() { "/a" } else { "" };
+        let mut request = self
+            .agent
+            .request(method, &format!("{}{prefix}{path}", self.url))
+            .set("Accept", "application/json")
+            .set(
+                "User-Agent",
+                concat!("if-changed/", env!("CARGO_PKG_VERSION")),
+            );
+        if let Some(credentials) = &self.credentials {
+            request = request.set("Authorization", &format!("Basic {credentials}"));
+        }
+        let response = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        let response = match response {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(error) => return Err(io::Error::other(error)),
+        };
+        let status = response.status();
+        let text = response.into_string()?;
+        let json = text.trim_start_matches(XSSI_PREFIX);
+        // Errors are plain text, which is kept as a string.
+        let body = serde_json::from_str(json).unwrap_or_else(|_| Value::String(text.trim().into()));
+        Ok((status, body))
+    }
+}
+
+/// Get the current patchset of `change`, e.g. `12345` or its Change-Id.
+fn current_patchset(api: &mut impl Api, change: &str) -> io::Result<Patchset> {
+    let path = format!("/changes/{}?o=CURRENT_REVISION", encode(change));
+    let response = send(api, "GET", &path, None)?;
+    let invalid = || io::Error::other(format!("{path} has no current patchset"));
+    let revision = response["current_revision"].as_str().ok_or_else(invalid)?;
+    Ok(Patchset {
+        change: response["_number"].as_u64().ok_or_else(invalid)?,
+        revision: revision.to_owned(),
+        reference: response["revisions"][revision]["ref"]
+            .as_str()
+            .ok_or_else(invalid)?
+            .to_owned(),
+    })
+}
+
+/// Send a request, failing unless Gerrit responds with a success.
+fn send(api: &mut impl Api, method: &str, path: &str, body: Option<Value>) -> io::Result<Value> {
+    let (status, response) = api.send(method, path, body)?;
+    if !(200..300).contains(&status) {
+        return Err(rejected(method, path, status, &response));
+    }
+    Ok(response)
+}
+
+/// Encode `change` as a segment of a path, e.g. `project~branch~I1234` with
+/// `/` in the project.
+fn encode(change: &str) -> String {
+    change.replace('%', "%25").replace('/', "%2F")
+}
+
+/// Posts problems as comments on a patchset once the check is finished,
+/// voting on a label if given.
+pub struct Review<A: Api> {
+    api: A,
+    patchset: Patchset,
+    label: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+    summary: Summary,
+}
+
+impl<A: Api> Review<A> {
+    pub fn new(api: A, patchset: Patchset, label: Option<String>) -> Self {
+        Self {
+            api,
+            patchset,
+            label,
+            diagnostics: Vec::new(),
+            summary: Summary::default(),
+        }
+    }
+
+    /// Write the review of the patchset, with the problems of files as
+    /// comments unless `inline` is false.
+    fn input(&self, inline: bool) -> Value {
+        let mut message = format!("if-changed found {}.", self.summary);
+        let mut comments = BTreeMap::<String, Vec<Value>>::new();
+        for diagnostic in &self.diagnostics {
+            let text = format!(
+                "{}[{}]: {}",
+                diagnostic.severity, diagnostic.code, diagnostic.message
+            );
+            if !inline || diagnostic.path.as_os_str().is_empty() {
+                message += &format!("\n\n{}", location(diagnostic, &text));
+                continue;
+            }
+            let mut comment = json!({
+                "message": format!("{text}\n\nRun `if-changed --explain {}` for details.", diagnostic.code),
+                "unresolved": diagnostic.severity == Severity::Error,
+            });
+            if let Some(line) = diagnostic.line {
+                comment["line"] = json!(line);
+            }
+            comments
+                .entry(diagnostic.path.to_string_lossy().replace('\\', "/"))
+                .or_default()
+                .push(comment);
+        }
+        let mut input = json!({ "message": message, "tag": TAG });
+        if !comments.is_empty() {
+            input["comments"] = json!(comments);
+        }
+        if let Some(label) = &self.label {
+            let vote = if self.summary.is_success() { 1 } else { -1 };
+            input["labels"] = json!({});
+            input["labels"][label] = json!(vote);
+        }
+        input
+    }
+}
+
+impl<A: Api> Reporter for Review<A> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        self.summary.record(diagnostic);
+        // Notes, e.g. of skipped files, aren't worth a comment.
+        if diagnostic.severity != Severity::Note {
+            self.diagnostics.push(diagnostic.clone());
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<Summary> {
+        let path = format!(
+            "/changes/{}/revisions/{}/review",
+            self.patchset.change, self.patchset.revision
+        );
+        let input = self.input(true);
+        let inline = input.get("comments").is_some();
+        let (status, response) = self.api.send("POST", &path, Some(input))?;
+        match status {
+            200..300 => {}
+            // Gerrit rejects comments on files outside of the patchset, e.g.
+            // of removed blocks, so they are retried as part of the message.
+            400 if inline => {
+                let input = self.input(false);
+                send(&mut self.api, "POST", &path, Some(input))?;
+            }
+            _ => return Err(rejected("POST", &path, status, &response)),
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Prefix `text` with the file and line of `diagnostic`, if any.
+fn location(diagnostic: &Diagnostic, text: &str) -> String {
+    if diagnostic.path.as_os_str().is_empty() {
+        return text.to_owned();
+    }
+    let path = diagnostic.path.to_string_lossy().replace('\\', "/");
+    match diagnostic.line {
+        Some(line) => format!("{path}:{line}: {text}"),
+        None => format!("{path}: {text}"),
+    }
+}
+
+/// Describe a request Gerrit didn't accept.
+fn rejected(method: &str, path: &str, status: u16, response: &Value) -> io::Error {
+    let message = response.as_str().unwrap_or("no message");
+    io::Error::other(format!(
+        "Gerrit responded to {method} {path} with {status}: {message}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::Code;
+
+    use super::*;
+
+    /// A change on Gerrit, remembering the requests sent to it.
+    #[derive(Default)]
+    struct FakeGerrit {
+        requests: Vec<String>,
+    }
+
+    impl Api for &mut FakeGerrit {
+        fn send(
+            &mut self,
+            method: &str,
+            path: &str,
+            body: Option<Value>,
+        ) -> io::Result<(u16, Value)> {
+            self.requests.push(match &body {
+                Some(body) => format!("{method} {path} {body}"),
+                None => format!("{method} {path}"),
+            });
+            Ok(match (method, path) {
+                (
+                    "GET",
+                    "/changes/12345?o=CURRENT_REVISION"
+                    | "/changes/p%2Fq~main~I1?o=CURRENT_REVISION",
+                ) => (
+                    200,
+                    json!({
+                        "_number": 12345,
+                        "current_revision": "abc",
+                        "revisions": { "abc": { "_number": 3, "ref": "refs/changes/45/12345/3" } },
+                    }),
+                ),
+                ("POST", "/changes/12345/revisions/abc/review") => {
+                    // Comments on deleted.ts are outside of the patchset.
+                    if body.unwrap()["comments"].get("deleted.ts").is_some() {
+                        (400, json!("file deleted.ts not found in revision"))
+                    } else {
+                        (200, json!({}))
+                    }
+                }
+                _ => (404, json!("Not found: 1")),
+            })
+        }
+    }
+
+    fn unmodified(path: &str, line: usize) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(
+            Code::UnmodifiedTarget,
+            path,
+            Some(line),
+            "Expected \"b.ts\" to be modified.".into(),
+        );
+        diagnostic.target = Some("b.ts".into());
+        diagnostic
+    }
+
+    fn review(gerrit: &mut FakeGerrit, diagnostics: &[Diagnostic]) -> Vec<String> {
+        let patchset = current_patchset(&mut &mut *gerrit, "12345").unwrap();
+        let mut review = Review::new(&mut *gerrit, patchset, Some("Verified".into()));
+        for diagnostic in diagnostics {
+            review.report(diagnostic).unwrap();
+        }
+        review.finish().unwrap();
+        std::mem::take(&mut gerrit.requests)
+    }
+
+    #[test]
+    fn test_current_patchset() {
+        let mut gerrit = FakeGerrit::default();
+        assert_eq!(
+            current_patchset(&mut &mut gerrit, "p/q~main~I1").unwrap(),
+            Patchset {
+                change: 12345,
+                revision: "abc".into(),
+                reference: "refs/changes/45/12345/3".into(),
+            }
+        );
+        insta::assert_snapshot!(current_patchset(&mut &mut gerrit, "404").unwrap_err(), @"Gerrit responded to GET /changes/404?o=CURRENT_REVISION with 404: Not found: 1");
+    }
+
+    #[test]
+    fn test_review() {
+        let mut gerrit = FakeGerrit::default();
+        insta::assert_debug_snapshot!(review(&mut gerrit, &[]), @r###"
+        [
+            "GET /changes/12345?o=CURRENT_REVISION",
+            "POST /changes/12345/revisions/abc/review {\"labels\":{\"Verified\":1},\"message\":\"if-changed found no problems.\",\"tag\":\"autogenerated:if-changed\"}",
+        ]
+        "###);
+
+        let mut warning = unmodified("a.ts", 9);
+        warning.severity = Severity::Warning;
+        insta::assert_debug_snapshot!(review(&mut gerrit, &[unmodified("a.ts", 4), warning]), @r###"
+        [
+            "GET /changes/12345?o=CURRENT_REVISION",
+            "POST /changes/12345/revisions/abc/review {\"comments\":{\"a.ts\":[{\"line\":4,\"message\":\"error[IC002]: Expected \\\"b.ts\\\" to be modified.\\n\\nRun `if-changed --explain IC002` for details.\",\"unresolved\":true},{\"line\":9,\"message\":\"warning[IC002]: Expected \\\"b.ts\\\" to be modified.\\n\\nRun `if-changed --explain IC002` for details.\",\"unresolved\":false}]},\"labels\":{\"Verified\":-1},\"message\":\"if-changed found 1 error and 1 warning.\",\"tag\":\"autogenerated:if-changed\"}",
+        ]
+        "###);
+
+        insta::assert_debug_snapshot!(review(&mut gerrit, &[unmodified("deleted.ts", 4)]), @r###"
+        [
+            "GET /changes/12345?o=CURRENT_REVISION",
+            "POST /changes/12345/revisions/abc/review {\"comments\":{\"deleted.ts\":[{\"line\":4,\"message\":\"error[IC002]: Expected \\\"b.ts\\\" to be modified.\\n\\nRun `if-changed --explain IC002` for details.\",\"unresolved\":true}]},\"labels\":{\"Verified\":-1},\"message\":\"if-changed found 1 error.\",\"tag\":\"autogenerated:if-changed\"}",
+            "POST /changes/12345/revisions/abc/review {\"labels\":{\"Verified\":-1},\"message\":\"if-changed found 1 error.\\n\\ndeleted.ts:4: error[IC002]: Expected \\\"b.ts\\\" to be modified.\",\"tag\":\"autogenerated:if-changed\"}",
+        ]
+        "###);
+    }
+}
@@ -0,0 +1,694 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+
+use clap::{builder::BoolishValueParser, Args};
+use if_changed::{Diagnostic, Reporter, Severity, Summary};
+use serde_json::{json, Value};
+
+use crate::http::Api;
+
+/// The prefix of the hidden marker starting each review comment, so comments
+/// of earlier runs can be found again.
+const MARKER: &str = "<!-- if-changed: ";
+
+/// The number of comments requested per page, the most GitHub allows.
+const PER_PAGE: usize = 100;
+
+/// The name of the check run created by `--github-checks`.
+const CHECK_NAME: &str = "if-changed";
+
+/// The number of annotations GitHub accepts per request to the Checks API.
+const ANNOTATIONS_PER_REQUEST: usize = 50;
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct ReviewArgs {
+    /// Post each problem as a review comment on a GitHub pull request,
+    /// anchored to the line of the problem, e.g. the `then-change`.
+    ///
+    /// Comments of earlier runs are updated instead of posted again, and
+    /// comments of problems no longer reported are marked as resolved.
+    #[arg(
+        long,
+        env = "IF_CHANGED_GITHUB_REVIEW", value_parser = BoolishValueParser::new(),
+        requires_all = ["github_token", "github_repository", "github_pr"],
+        conflicts_with_all = ["watch", "interactive", "since", "audit"]
+    )]
+    pub github_review: bool,
+
+    /// Create a check run with the result on the head commit of `--github-pr`
+    /// or else `--github-sha`, annotating each problem, so it shows in the
+    /// "Checks" tab of pull requests.
+    #[arg(
+        long,
+        env = "IF_CHANGED_GITHUB_CHECKS", value_parser = BoolishValueParser::new(),
+        requires_all = ["github_token", "github_repository"],
+        conflicts_with_all = ["watch", "interactive", "since", "audit"]
+    )]
+    pub github_checks: bool,
+
+    /// The token authenticating `--github-review` and `--github-checks`, e.g.
+    /// `${{ github.token }}`. It needs write access to pull requests or
+    /// checks, respectively.
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "GITHUB_TOKEN",
+        hide_env_values = true
+    )]
+    pub github_token: Option<String>,
+
+    /// The repository of the pull request, as `OWNER/NAME`.
+    #[arg(long, value_name = "OWNER/NAME", env = "GITHUB_REPOSITORY")]
+    pub github_repository: Option<String>,
+
+    /// The number of the pull request to review. Its labels and description
+    /// may ignore files, see `--ignore-label`.
+    #[arg(
+        long,
+        env = "IF_CHANGED_GITHUB_PR",
+        value_name = "NUMBER",
+        requires = "github_repository"
+    )]
+    pub github_pr: Option<u64>,
+
+    /// The commit to create the check run of `--github-checks` on, unless
+    /// `--github-pr` is given.
+    #[arg(long, value_name = "SHA", env = "GITHUB_SHA")]
+    pub github_sha: Option<String>,
+
+    /// The URL of the GitHub API, e.g. of GitHub Enterprise Server.
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "GITHUB_API_URL",
+        default_value = "https://api.github.com"
+    )]
+    pub github_api_url: String,
+}
+
+/// The labels and description of a pull request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    pub labels: Vec<String>,
+    pub description: String,
+}
+
+impl ReviewArgs {
+    fn client(&self) -> Client {
+        Client {
+            agent: ureq::agent(),
+            url: self.github_api_url.trim_end_matches('/').to_owned(),
+            token: self.github_token.clone(),
+        }
+    }
+
+    /// Look up the labels and description of `--github-pr`.
+    pub fn pull_request(&self) -> io::Result<PullRequest> {
+        pull_request(
+            &mut self.client(),
+            self.github_repository.as_deref().unwrap_or_default(),
+            self.github_pr.unwrap_or_default(),
+        )
+    }
+
+    /// Create the reviewer posting comments, if `--github-review` is given.
+    pub fn review(&self) -> Option<Review<Client>> {
+        if !self.github_review {
+            return None;
+        }
+        Some(Review::new(
+            self.client(),
+            self.github_repository.as_deref().unwrap_or_default(),
+            self.github_pr.unwrap_or_default(),
+        ))
+    }
+
+    /// Create the reporter creating a check run, if `--github-checks` is
+    /// given.
+    pub fn checks(&self) -> Option<Checks<Client>> {
+        if !self.github_checks {
+            return None;
+        }
+        Some(Checks::new(
+            self.client(),
+            self.github_repository.as_deref().unwrap_or_default(),
+            self.github_pr,
+            self.github_sha.as_deref(),
+        ))
+    }
+}
+
+/// A client of the GitHub REST API at `url`, authenticated with `token`, if
+/// any.
+pub struct Client {
+    agent: ureq::Agent,
+    url: String,
+    token: Option<String>,
+}
+
+impl Api for Client {
+    fn send(&mut self, method: &str, path: &str, body: Option<Value>) -> io::Result<(u16, Value)> {
+        let mut request = self
+            .agent
+            .request(method, &format!("{}{path}", self.url))
+            .set("Accept", "application/vnd.github+json")
+            .set("X-GitHub-Api-Version", "2022-11-28")
+            .set(
+                "User-Agent",
+                concat!("if-changed/", env!("CARGO_PKG_VERSION")),
+            );
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+        let response = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        let response = match response {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(error) => return Err(io::Error::other(error)),
+        };
+        let status = response.status();
+        Ok((status, response.into_json().unwrap_or(Value::Null)))
+    }
+}
+
+/// Get the labels and description of pull request `pr` of `repository`.
+fn pull_request(api: &mut impl Api, repository: &str, pr: u64) -> io::Result<PullRequest> {
+    let path = format!("/repos/{repository}/pulls/{pr}");
+    let response = send(api, "GET", &path, None)?;
+    let labels = response["labels"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|label| label["name"].as_str().map(str::to_owned))
+        .collect();
+    Ok(PullRequest {
+        labels,
+        // Pull requests without a description have a `null` body.
+        description: response["body"].as_str().unwrap_or_default().to_owned(),
+    })
+}
+
+/// Posts problems as review comments on a pull request once the check is
+/// finished, reconciling them with the comments of earlier runs.
+pub struct Review<A: Api> {
+    api: A,
+    /// The repository of the pull request, as `OWNER/NAME`.
+    repository: String,
+    pr: u64,
+    diagnostics: Vec<Diagnostic>,
+    summary: Summary,
+}
+
+impl<A: Api> Review<A> {
+    pub fn new(api: A, repository: &str, pr: u64) -> Self {
+        Self {
+            api,
+            repository: repository.to_owned(),
+            pr,
+            diagnostics: Vec::new(),
+            summary: Summary::default(),
+        }
+    }
+
+    /// Send a request, failing unless GitHub responds with a success.
+    fn send(&mut self, method: &str, path: &str, body: Option<Value>) -> io::Result<Value> {
+        send(&mut self.api, method, path, body)
+    }
+
+    /// Get the comments of earlier runs on the pull request by their markers,
+    /// with their ids and bodies.
+    fn comments(&mut self) -> io::Result<HashMap<String, (u64, String)>> {
+        let mut comments = HashMap::new();
+        for page in 1.. {
+            let path = format!(
+                "/repos/{}/pulls/{}/comments?per_page={PER_PAGE}&page={page}",
+                self.repository, self.pr
+            );
+            let response = self.send("GET", &path, None)?;
+            let page = response.as_array().cloned().unwrap_or_default();
+            for comment in &page {
+                let (Some(id), Some(body)) = (comment["id"].as_u64(), comment["body"].as_str())
+                else {
+                    continue;
+                };
+                if let Some(marker) = marker_of(body) {
+                    comments.insert(marker.to_owned(), (id, body.to_owned()));
+                }
+            }
+            if page.len() < PER_PAGE {
+                break;
+            }
+        }
+        Ok(comments)
+    }
+
+    /// Post a comment on `line` of `path`, or on the whole file if the line
+    /// is not part of the diff of the pull request.
+    fn post(&mut self, commit: &str, diagnostic: &Diagnostic, body: &str) -> io::Result<()> {
+        let path = format!("/repos/{}/pulls/{}/comments", self.repository, self.pr);
+        let file = diagnostic.path.to_string_lossy().replace('\\', "/");
+        if let Some(line) = diagnostic.line {
+            let comment = json!({
+                "body": body,
+                "commit_id": commit,
+                "path": file,
+                "line": line,
+                "side": "RIGHT",
+            });
+            // GitHub rejects lines outside of the diff as unprocessable.
+            let (status, response) = self.api.send("POST", &path, Some(comment))?;
+            match status {
+                200..300 => return Ok(()),
+                422 => {}
+                _ => return Err(rejected("POST", &path, status, &response)),
+            }
+        }
+        let comment = json!({
+            "body": body,
+            "commit_id": commit,
+            "path": file,
+            "subject_type": "file",
+        });
+        self.send("POST", &path, Some(comment)).map(drop)
+    }
+
+    /// Replace the body of the comment `id`.
+    fn edit(&mut self, id: u64, body: &str) -> io::Result<()> {
+        let path = format!("/repos/{}/pulls/comments/{id}", self.repository);
+        self.send("PATCH", &path, Some(json!({ "body": body })))
+            .map(drop)
+    }
+}
+
+impl<A: Api> Reporter for Review<A> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        self.summary.record(diagnostic);
+        // Notes, e.g. of skipped files, aren't worth a comment, and problems
+        // outside of files, e.g. in trailers, have nothing to anchor to.
+        if diagnostic.severity != Severity::Note && !diagnostic.path.as_os_str().is_empty() {
+            self.diagnostics.push(diagnostic.clone());
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<Summary> {
+        let commit = head(&mut self.api, &self.repository, self.pr)?;
+
+        let mut stale = self.comments()?;
+        let mut posted = HashSet::new();
+        for diagnostic in std::mem::take(&mut self.diagnostics) {
+            let marker = marker(&diagnostic);
+            if !posted.insert(marker.clone()) {
+                continue;
+            }
+            let body = body(&marker, &diagnostic);
+            match stale.remove(&marker) {
+                Some((id, old)) if old != body => self.edit(id, &body)?,
+                Some(_) => {}
+                None => self.post(&commit, &diagnostic, &body)?,
+            }
+        }
+        let mut stale = stale.into_iter().collect::<Vec<_>>();
+        stale.sort();
+        for (marker, (id, old)) in stale {
+            let body = resolved(&marker);
+            if old != body {
+                self.edit(id, &body)?;
+            }
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Creates a check run with the result once the check is finished, with an
+/// annotation for each problem in a file.
+pub struct Checks<A: Api> {
+    api: A,
+    /// The repository of the commit, as `OWNER/NAME`.
+    repository: String,
+    /// The pull request whose head commit is checked, if any.
+    pr: Option<u64>,
+    /// The commit checked, unless `pr` is given.
+    sha: Option<String>,
+    annotations: Vec<Value>,
+    /// Problems outside of files, e.g. in trailers, which are listed in the
+    /// summary instead.
+    problems: Vec<String>,
+    summary: Summary,
+}
+
+impl<A: Api> Checks<A> {
+    pub fn new(api: A, repository: &str, pr: Option<u64>, sha: Option<&str>) -> Self {
+        Self {
+            api,
+            repository: repository.to_owned(),
+            pr,
+            sha: sha.map(str::to_owned),
+            annotations: Vec::new(),
+            problems: Vec::new(),
+            summary: Summary::default(),
+        }
+    }
+}
+
+impl<A: Api> Reporter for Checks<A> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        self.summary.record(diagnostic);
+        if diagnostic.path.as_os_str().is_empty() {
+            self.problems.push(format!(
+                "- **{}[{}]**: {}",
+                diagnostic.severity, diagnostic.code, diagnostic.message
+            ));
+            return Ok(());
+        }
+        let line = diagnostic.line.unwrap_or(1);
+        self.annotations.push(json!({
+            "path": diagnostic.path.to_string_lossy().replace('\\', "/"),
+            "start_line": line,
+            "end_line": line,
+            "annotation_level": match diagnostic.severity {
+                Severity::Error => "failure",
+                Severity::Warning => "warning",
+                Severity::Note => "notice",
+            },
+            "title": format!("{}[{}]", diagnostic.severity, diagnostic.code),
+            "message": diagnostic.message,
+        }));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<Summary> {
+        let commit =
+            match (self.pr, &self.sha) {
+                (Some(pr), _) => head(&mut self.api, &self.repository, pr)?,
+                (None, Some(sha)) => sha.clone(),
+                (None, None) => return Err(io::Error::other(
+                    "no commit to create the check run on; pass `--github-pr` or `--github-sha`",
+                )),
+            };
+        let title = format!("if-changed found {}.", self.summary);
+        let mut summary = title.clone();
+        if !self.problems.is_empty() {
+            summary = format!("{summary}\n\n{}", self.problems.join("\n"));
+        }
+        let mut batches = self.annotations.chunks(ANNOTATIONS_PER_REQUEST);
+        let output = |annotations: Option<&[Value]>| {
+            json!({
+                "title": title,
+                "summary": summary,
+                "annotations": annotations.unwrap_or_default(),
+            })
+        };
+        let check_run = json!({
+            "name": CHECK_NAME,
+            "head_sha": commit,
+            "status": "completed",
+            "conclusion": if self.summary.is_success() { "success" } else { "failure" },
+            "output": output(batches.next()),
+        });
+        let path = format!("/repos/{}/check-runs", self.repository);
+        let response = send(&mut self.api, "POST", &path, Some(check_run))?;
+        // Further annotations are added by updating the check run.
+        let id = response["id"]
+            .as_u64()
+            .ok_or_else(|| io::Error::other(format!("{path} returned no id")))?;
+        let path = format!("{path}/{id}");
+        for batch in batches {
+            let update = json!({ "output": output(Some(batch)) });
+            send(&mut self.api, "PATCH", &path, Some(update))?;
+        }
+        Ok(self.summary)
+    }
+}
+
+/// Get the head commit of pull request `pr` of `repository`.
+fn head(api: &mut impl Api, repository: &str, pr: u64) -> io::Result<String> {
+    let path = format!("/repos/{repository}/pulls/{pr}");
+    let pull = send(api, "GET", &path, None)?;
+    pull["head"]["sha"]
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| io::Error::other(format!("{path} has no head commit")))
+}
+
+/// Send a request, failing unless GitHub responds with a success.
+fn send(api: &mut impl Api, method: &str, path: &str, body: Option<Value>) -> io::Result<Value> {
+    let (status, response) = api.send(method, path, body)?;
+    if !(200..300).contains(&status) {
+        return Err(rejected(method, path, status, &response));
+    }
+    Ok(response)
+}
+
+/// Identify the problem of `diagnostic` across runs, as long as its line
+/// doesn't move.
+fn marker(diagnostic: &Diagnostic) -> String {
+    let mut marker = format!(
+        "{} {}",
+        diagnostic.code,
+        diagnostic.path.to_string_lossy().replace('\\', "/")
+    );
+    if let Some(line) = diagnostic.line {
+        marker += &format!(":{line}");
+    }
+    if let Some(target) = &diagnostic.target {
+        marker += &format!(" -> {}", target.to_string_lossy().replace('\\', "/"));
+    }
+    if let Some(block) = &diagnostic.target_block {
+        marker += &format!(":{block}");
+    }
+    // The marker must not end the HTML comment hiding it.
+    marker.replace("--", "-\u{2010}")
+}
+
+/// Get the marker a comment body starts with, if it was posted by a review.
+fn marker_of(body: &str) -> Option<&str> {
+    body.lines()
+        .next()?
+        .strip_prefix(MARKER)?
+        .strip_suffix(" -->")
+}
+
+/// Write the body of the comment on `diagnostic`.
+fn body(marker: &str, diagnostic: &Diagnostic) -> String {
+    format!(
+        "{MARKER}{marker} -->\n**{}[{}]**: {}\n\nRun `if-changed --explain {}` for details.",
+        diagnostic.severity, diagnostic.code, diagnostic.message, diagnostic.code
+    )
+}
+
+/// Write the body replacing the comment of a problem that was fixed.
+fn resolved(marker: &str) -> String {
+    format!("{MARKER}{marker} -->\n**Resolved**: `if-changed` no longer reports this problem.")
+}
+
+/// Describe a request GitHub didn't accept.
+fn rejected(method: &str, path: &str, status: u16, response: &Value) -> io::Error {
+    let message = response["message"].as_str().unwrap_or("no message");
+    io::Error::other(format!(
+        "GitHub responded to {method} {path} with {status}: {message}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::Code;
+
+    use super::*;
+
+    /// A pull request on GitHub, remembering its comments and the requests
+    /// sent to it.
+    #[derive(Default)]
+    struct FakeGithub {
+        comments: Vec<Value>,
+        requests: Vec<String>,
+    }
+
+    impl Api for &mut FakeGithub {
+        fn send(
+            &mut self,
+            method: &str,
+            path: &str,
+            body: Option<Value>,
+        ) -> io::Result<(u16, Value)> {
+            self.requests.push(match &body {
+                Some(body) => format!("{method} {path} {body}"),
+                None => format!("{method} {path}"),
+            });
+            match (method, path) {
+                ("POST", "/repos/o/r/check-runs") => return Ok((201, json!({ "id": 7 }))),
+                ("PATCH", "/repos/o/r/check-runs/7") => return Ok((200, json!({ "id": 7 }))),
+                _ => {}
+            }
+            let Some(path) = path.strip_prefix("/repos/o/r/pulls/") else {
+                return Ok((404, json!({ "message": "Not Found" })));
+            };
+            Ok(match (method, path) {
+                ("GET", "1") => (
+                    200,
+                    json!({
+                        "head": { "sha": "abc" },
+                        "labels": [{ "name": "bug" }],
+                        "body": "Fix a bug.",
+                    }),
+                ),
+                ("GET", "2") => (200, json!({ "head": { "sha": "def" }, "body": null })),
+                ("GET", "1/comments?per_page=100&page=1") => (200, json!(self.comments)),
+                ("POST", "1/comments") => {
+                    let mut body = body.unwrap();
+                    // Line 9 is outside of the diff.
+                    if body["line"] == 9 {
+                        return Ok((422, json!({ "message": "Validation Failed" })));
+                    }
+                    body["id"] = json!(self.comments.len() + 1);
+                    self.comments.push(body.clone());
+                    (201, body)
+                }
+                ("PATCH", path) => {
+                    let id = path.strip_prefix("comments/").unwrap().parse::<usize>();
+                    let comment = &mut self.comments[id.unwrap() - 1];
+                    comment["body"] = body.unwrap()["body"].clone();
+                    (200, comment.clone())
+                }
+                _ => (404, json!({ "message": "Not Found" })),
+            })
+        }
+    }
+
+    #[test]
+    fn test_pull_request() {
+        let mut github = FakeGithub::default();
+        assert_eq!(
+            pull_request(&mut &mut github, "o/r", 1).unwrap(),
+            PullRequest {
+                labels: vec!["bug".into()],
+                description: "Fix a bug.".into(),
+            }
+        );
+        assert_eq!(
+            pull_request(&mut &mut github, "o/r", 2).unwrap(),
+            PullRequest {
+                labels: vec![],
+                description: String::new(),
+            }
+        );
+        insta::assert_snapshot!(pull_request(&mut &mut github, "o/missing", 1).unwrap_err(), @"GitHub responded to GET /repos/o/missing/pulls/1 with 404: Not Found");
+    }
+
+    fn review(github: &mut FakeGithub, diagnostics: &[Diagnostic]) -> Vec<String> {
+        let mut review = Review::new(&mut *github, "o/r", 1);
+        for diagnostic in diagnostics {
+            review.report(diagnostic).unwrap();
+        }
+        review.finish().unwrap();
+        std::mem::take(&mut github.requests)
+    }
+
+    #[test]
+    fn test_review() {
+        let unmodified = |line| {
+            let mut diagnostic = Diagnostic::new(
+                Code::UnmodifiedTarget,
+                "a.ts",
+                Some(line),
+                "Expected \"b.ts\" to be modified.".into(),
+            );
+            diagnostic.target = Some("b.ts".into());
+            diagnostic
+        };
+        let mut github = FakeGithub::default();
+
+        insta::assert_debug_snapshot!(review(&mut github, &[unmodified(4), unmodified(9)]), @r###"
+        [
+            "GET /repos/o/r/pulls/1",
+            "GET /repos/o/r/pulls/1/comments?per_page=100&page=1",
+            "POST /repos/o/r/pulls/1/comments {\"body\":\"<!-- if-changed: IC002 a.ts:4 -> b.ts -->\\n**error[IC002]**: Expected \\\"b.ts\\\" to be modified.\\n\\nRun `if-changed --explain IC002` for details.\",\"commit_id\":\"abc\",\"line\":4,\"path\":\"a.ts\",\"side\":\"RIGHT\"}",
+            "POST /repos/o/r/pulls/1/comments {\"body\":\"<!-- if-changed: IC002 a.ts:9 -> b.ts -->\\n**error[IC002]**: Expected \\\"b.ts\\\" to be modified.\\n\\nRun `if-changed --explain IC002` for details.\",\"commit_id\":\"abc\",\"line\":9,\"path\":\"a.ts\",\"side\":\"RIGHT\"}",
+            "POST /repos/o/r/pulls/1/comments {\"body\":\"<!-- if-changed: IC002 a.ts:9 -> b.ts -->\\n**error[IC002]**: Expected \\\"b.ts\\\" to be modified.\\n\\nRun `if-changed --explain IC002` for details.\",\"commit_id\":\"abc\",\"path\":\"a.ts\",\"subject_type\":\"file\"}",
+        ]
+        "###);
+
+        // Re-running with the same problems changes nothing.
+        assert_eq!(
+            review(&mut github, &[unmodified(4), unmodified(9)]).len(),
+            2
+        );
+
+        // Fixed problems are resolved, once.
+        insta::assert_debug_snapshot!(review(&mut github, &[unmodified(9)]), @r###"
+        [
+            "GET /repos/o/r/pulls/1",
+            "GET /repos/o/r/pulls/1/comments?per_page=100&page=1",
+            "PATCH /repos/o/r/pulls/comments/1 {\"body\":\"<!-- if-changed: IC002 a.ts:4 -> b.ts -->\\n**Resolved**: `if-changed` no longer reports this problem.\"}",
+        ]
+        "###);
+        assert_eq!(review(&mut github, &[unmodified(9)]).len(), 2);
+
+        // Problems coming back reopen their comments.
+        let mut changed = unmodified(4);
+        changed.severity = Severity::Warning;
+        assert_eq!(
+            review(&mut github, &[changed])[2..],
+            [
+                "PATCH /repos/o/r/pulls/comments/1 {\"body\":\"<!-- if-changed: IC002 a.ts:4 -> b.ts -->\\n**warning[IC002]**: Expected \\\"b.ts\\\" to be modified.\\n\\nRun `if-changed --explain IC002` for details.\"}",
+                "PATCH /repos/o/r/pulls/comments/2 {\"body\":\"<!-- if-changed: IC002 a.ts:9 -> b.ts -->\\n**Resolved**: `if-changed` no longer reports this problem.\"}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checks() {
+        let mut github = FakeGithub::default();
+        let mut checks = Checks::new(&mut github, "o/r", Some(1), Some("ignored"));
+        let mut warning = Diagnostic::new(
+            Code::UnmodifiedTarget,
+            "a.ts",
+            Some(4),
+            "Expected \"b.ts\" to be modified.".into(),
+        );
+        warning.severity = Severity::Warning;
+        let trailer = Diagnostic::new(
+            Code::MissingIgnoreReason,
+            "",
+            None,
+            "Expected a reason for ignoring \"a.ts\".".into(),
+        );
+        for diagnostic in [warning, trailer] {
+            checks.report(&diagnostic).unwrap();
+        }
+        checks.finish().unwrap();
+        insta::assert_debug_snapshot!(github.requests, @r###"
+        [
+            "GET /repos/o/r/pulls/1",
+            "POST /repos/o/r/check-runs {\"conclusion\":\"failure\",\"head_sha\":\"abc\",\"name\":\"if-changed\",\"output\":{\"annotations\":[{\"annotation_level\":\"warning\",\"end_line\":4,\"message\":\"Expected \\\"b.ts\\\" to be modified.\",\"path\":\"a.ts\",\"start_line\":4,\"title\":\"warning[IC002]\"}],\"summary\":\"if-changed found 1 error and 1 warning.\\n\\n- **error[IC301]**: Expected a reason for ignoring \\\"a.ts\\\".\",\"title\":\"if-changed found 1 error and 1 warning.\"},\"status\":\"completed\"}",
+        ]
+        "###);
+
+        // Annotations beyond the first batch are added by updates.
+        let mut github = FakeGithub::default();
+        let mut checks = Checks::new(&mut github, "o/r", None, Some("def"));
+        for line in 1..=ANNOTATIONS_PER_REQUEST + 1 {
+            let diagnostic = Diagnostic::new(Code::UnmodifiedTarget, "a.ts", Some(line), "".into());
+            checks.report(&diagnostic).unwrap();
+        }
+        checks.finish().unwrap();
+        assert_eq!(github.requests.len(), 2);
+        assert!(github.requests[0].contains("\"conclusion\":\"failure\""));
+        assert!(github.requests[1].starts_with("PATCH /repos/o/r/check-runs/7 "));
+        assert_eq!(github.requests[1].matches("\"start_line\"").count(), 1);
+
+        let mut checks = Checks::new(&mut github, "o/r", None, None);
+        insta::assert_snapshot!(checks.finish().unwrap_err(), @"no commit to create the check run on; pass `--github-pr` or `--github-sha`");
+    }
+
+    #[test]
+    fn test_review_rejected() {
+        let mut github = FakeGithub::default();
+        let mut review = Review::new(&mut github, "o/missing", 1);
+        insta::assert_snapshot!(review.finish().unwrap_err(), @"GitHub responded to GET /repos/o/missing/pulls/1 with 404: Not Found");
+    }
+}
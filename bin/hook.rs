@@ -0,0 +1,447 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, ValueEnum};
+
+/// The first line of the lines running `if-changed` in a hook script.
+const BEGIN: &str = "# >>> if-changed >>>";
+
+/// The last line of the lines running `if-changed` in a hook script.
+const END: &str = "# <<< if-changed <<<";
+
+/// A line of the scripts written by earlier versions of `if-changed init`,
+/// which ran nothing else.
+const LEGACY_MARKER: &str = "# Installed by `if-changed init`.";
+
+/// The configuration files of lefthook, which overwrites the hook scripts it
+/// manages.
+const LEFTHOOK_CONFIGS: [&str; 8] = [
+    "lefthook.yml",
+    ".lefthook.yml",
+    "lefthook.yaml",
+    ".lefthook.yaml",
+    "lefthook.toml",
+    ".lefthook.toml",
+    "lefthook.json",
+    ".lefthook.json",
+];
+
+#[derive(Args, Debug)]
+pub struct HookArgs {
+    /// The hook to run `if-changed` in.
+    #[arg(long, value_enum, default_value = "pre-commit")]
+    pub hook: Hook,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Check staged changes before every commit.
+    PreCommit,
+    /// Check the commits not yet pushed to the upstream branch before every
+    /// push.
+    PrePush,
+}
+
+/// Hook managers `init` prints the configuration of.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookManager {
+    /// Commands in `lefthook.yml`.
+    Lefthook,
+    /// Scripts in `.husky/`, as run by Husky 5 and later.
+    Husky,
+}
+
+impl Hook {
+    /// All hooks, in the order they run.
+    const ALL: [Hook; 2] = [Hook::PreCommit, Hook::PrePush];
+
+    fn name(self) -> &'static str {
+        match self {
+            Hook::PreCommit => "pre-commit",
+            Hook::PrePush => "pre-push",
+        }
+    }
+
+    /// Get the command checking the changes of the hook.
+    fn command(self) -> &'static str {
+        match self {
+            Hook::PreCommit => "if-changed",
+            Hook::PrePush => "if-changed --from-ref '@{u}' --to-ref HEAD",
+        }
+    }
+
+    /// Get a shell command running the command of the hook on a single line,
+    /// e.g. for the `run` of a lefthook command. Pushes of branches without an
+    /// upstream aren't checked.
+    fn run(self) -> String {
+        match self {
+            Hook::PreCommit => self.command().to_owned(),
+            Hook::PrePush => format!(
+                "if git rev-parse --quiet --verify '@{{u}}' >/dev/null; then {}; fi",
+                self.command()
+            ),
+        }
+    }
+
+    /// Get the lines running the command of the hook, failing the hook if it
+    /// fails. Pushes of branches without an upstream aren't checked.
+    fn lines(self) -> String {
+        let command = match self {
+            Hook::PreCommit => format!("{} || exit $?\n", self.command()),
+            Hook::PrePush => format!(
+                "if git rev-parse --quiet --verify '@{{u}}' >/dev/null; then\n    {} || exit $?\nfi\n",
+                self.command()
+            ),
+        };
+        format!("{BEGIN}\n{command}{END}\n")
+    }
+}
+
+/// What runs the hooks of a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Manager {
+    /// Git runs the scripts in the directory.
+    Git(PathBuf),
+    /// Husky runs the scripts in the directory from the hooks it generates in
+    /// its `_` subdirectory.
+    Husky(PathBuf),
+    /// Lefthook runs the commands of the configuration file.
+    Lefthook(PathBuf),
+}
+
+impl Manager {
+    fn detect(repository: &git2::Repository) -> Self {
+        let workdir = repository.workdir().unwrap_or(repository.path());
+        if let Some(config) = LEFTHOOK_CONFIGS
+            .iter()
+            .map(|name| workdir.join(name))
+            .find(|config| config.is_file())
+        {
+            return Manager::Lefthook(config);
+        }
+        let dir = hooks_dir(repository);
+        match dir.parent() {
+            Some(parent) if dir.ends_with("_") && parent.ends_with(".husky") => {
+                Manager::Husky(parent.to_owned())
+            }
+            _ => Manager::Git(dir),
+        }
+    }
+}
+
+/// Add the lines running `if-changed` to the script of `hook`, creating it if
+/// needed. Existing scripts keep running their other commands.
+pub fn install(repository: &git2::Repository, hook: Hook, mut out: impl Write) -> io::Result<()> {
+    let dir = match Manager::detect(repository) {
+        Manager::Lefthook(config) => {
+            writeln!(
+                out,
+                "Lefthook runs the hooks of this repository. Add this to {}:\n",
+                display(repository, &config).display()
+            )?;
+            return lefthook(&[hook], out);
+        }
+        Manager::Husky(dir) | Manager::Git(dir) => dir,
+    };
+    let path = dir.join(hook.name());
+    let existing = match fs::read_to_string(&path) {
+        Ok(script) if !script.contains(LEGACY_MARKER) => Some(script),
+        Ok(_) => None,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(error),
+    };
+    let (script, verb) = match &existing {
+        Some(script) if script.contains(BEGIN) => (
+            insert(&remove(script), &hook.lines()),
+            "Updated if-changed in",
+        ),
+        Some(script) => (insert(script, &hook.lines()), "Added if-changed to"),
+        None => (format!("#!/bin/sh\n{}", hook.lines()), "Installed"),
+    };
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    writeln!(out, "{verb} {}.", display(repository, &path).display())
+}
+
+/// Print the configuration running `if-changed` in all hooks with `manager`,
+/// to copy into the repository.
+pub fn configure(manager: HookManager, mut out: impl Write) -> io::Result<()> {
+    match manager {
+        HookManager::Lefthook => {
+            writeln!(out, "Add this to lefthook.yml:\n")?;
+            lefthook(&Hook::ALL, out)
+        }
+        HookManager::Husky => {
+            writeln!(out, "Add these lines to the scripts in .husky/:")?;
+            for hook in Hook::ALL {
+                writeln!(out, "\n.husky/{}:\n", hook.name())?;
+                for line in hook.lines().lines() {
+                    writeln!(out, "    {line}")?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Write the commands of lefthook running `if-changed` in `hooks`.
+fn lefthook(hooks: &[Hook], mut out: impl Write) -> io::Result<()> {
+    for hook in hooks {
+        writeln!(out, "{}:", hook.name())?;
+        writeln!(out, "  commands:")?;
+        writeln!(out, "    if-changed:")?;
+        writeln!(out, "      run: {}", hook.run())?;
+    }
+    Ok(())
+}
+
+/// Remove the lines running `if-changed` from the script of `hook`, deleting
+/// it if nothing else is left.
+pub fn uninstall(repository: &git2::Repository, hook: Hook, mut out: impl Write) -> io::Result<()> {
+    let dir = match Manager::detect(repository) {
+        Manager::Lefthook(config) => {
+            return writeln!(
+                out,
+                "Lefthook runs the hooks of this repository. Remove the if-changed command of {} from {}.",
+                hook.name(),
+                display(repository, &config).display()
+            );
+        }
+        Manager::Husky(dir) | Manager::Git(dir) => dir,
+    };
+    let path = dir.join(hook.name());
+    let shown = display(repository, &path);
+    let script = match fs::read_to_string(&path) {
+        Ok(script) => script,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return writeln!(out, "{} doesn't exist.", shown.display());
+        }
+        Err(error) => return Err(error),
+    };
+    if !script.contains(BEGIN) && !script.contains(LEGACY_MARKER) {
+        return writeln!(out, "{} doesn't run if-changed.", shown.display());
+    }
+    let rest = remove(&script);
+    let is_empty = rest
+        .lines()
+        .all(|line| line.trim().is_empty() || line.starts_with("#!"));
+    if script.contains(LEGACY_MARKER) || is_empty {
+        fs::remove_file(&path)?;
+        writeln!(out, "Removed {}.", shown.display())
+    } else {
+        fs::write(&path, rest)?;
+        writeln!(out, "Removed if-changed from {}.", shown.display())
+    }
+}
+
+/// Insert `lines` into `script` before the first top-level `exec` or `exit`,
+/// which would skip them, or else at the end.
+fn insert(script: &str, lines: &str) -> String {
+    let mut result = String::with_capacity(script.len() + lines.len() + 1);
+    let mut inserted = false;
+    for line in script.lines() {
+        if !inserted && (line.starts_with("exec ") || line.starts_with("exit")) {
+            result.push_str(lines);
+            inserted = true;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    if !inserted {
+        result.push_str(lines);
+    }
+    result
+}
+
+/// Remove the lines between [`BEGIN`] and [`END`], inclusive, from `script`.
+fn remove(script: &str) -> String {
+    let mut result = String::with_capacity(script.len());
+    let mut inside = false;
+    for line in script.lines() {
+        if line == BEGIN {
+            inside = true;
+        } else if line == END {
+            inside = false;
+        } else if !inside {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+    result
+}
+
+/// Get `path` relative to the working directory of `repository` if it is
+/// inside it.
+fn display<'a>(repository: &git2::Repository, path: &'a Path) -> &'a Path {
+    repository
+        .workdir()
+        .and_then(|workdir| path.strip_prefix(workdir).ok())
+        .unwrap_or(path)
+}
+
+/// Get the directory git runs hooks from, honoring `core.hooksPath`.
+fn hooks_dir(repository: &git2::Repository) -> PathBuf {
+    let configured = repository
+        .config()
+        .and_then(|config| config.get_path("core.hooksPath"))
+        .ok();
+    match configured {
+        Some(path) if path.is_absolute() => path,
+        Some(path) => repository.workdir().unwrap_or(repository.path()).join(path),
+        None => repository.path().join("hooks"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+
+    use super::*;
+
+    fn output(f: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        f(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_install() {
+        let (_tempdir, repo) = git_test! {};
+        let path = repo.path().join("hooks/pre-push");
+
+        insta::assert_snapshot!(output(|out| install(&repo, Hook::PrePush, out)), @"Installed .git/hooks/pre-push.");
+        insta::assert_snapshot!(fs::read_to_string(&path).unwrap(), @r###"
+        #!/bin/sh
+        # >>> if-changed >>>
+        if git rev-parse --quiet --verify '@{u}' >/dev/null; then
+            if-changed --from-ref '@{u}' --to-ref HEAD || exit $?
+        fi
+        # <<< if-changed <<<
+        "###);
+        insta::assert_snapshot!(output(|out| install(&repo, Hook::PrePush, out)), @"Updated if-changed in .git/hooks/pre-push.");
+        assert_eq!(fs::read_to_string(&path).unwrap().matches(BEGIN).count(), 1);
+
+        fs::write(&path, "#!/bin/sh\nmake lint\nexec make test\n").unwrap();
+        insta::assert_snapshot!(output(|out| install(&repo, Hook::PrePush, out)), @"Added if-changed to .git/hooks/pre-push.");
+        insta::assert_snapshot!(fs::read_to_string(&path).unwrap(), @r###"
+        #!/bin/sh
+        make lint
+        # >>> if-changed >>>
+        if git rev-parse --quiet --verify '@{u}' >/dev/null; then
+            if-changed --from-ref '@{u}' --to-ref HEAD || exit $?
+        fi
+        # <<< if-changed <<<
+        exec make test
+        "###);
+
+        insta::assert_snapshot!(output(|out| uninstall(&repo, Hook::PrePush, out)), @"Removed if-changed from .git/hooks/pre-push.");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "#!/bin/sh\nmake lint\nexec make test\n"
+        );
+        insta::assert_snapshot!(output(|out| uninstall(&repo, Hook::PrePush, out)), @r###"
+        .git/hooks/pre-push doesn't run if-changed.
+
+        "###);
+    }
+
+    #[test]
+    fn test_uninstall() {
+        let (_tempdir, repo) = git_test! {};
+        let path = repo.path().join("hooks/pre-commit");
+
+        insta::assert_snapshot!(output(|out| uninstall(&repo, Hook::PreCommit, out)), @".git/hooks/pre-commit doesn't exist.");
+        install(&repo, Hook::PreCommit, io::sink()).unwrap();
+        insta::assert_snapshot!(output(|out| uninstall(&repo, Hook::PreCommit, out)), @"Removed .git/hooks/pre-commit.");
+        assert!(!path.exists());
+
+        fs::write(
+            &path,
+            "#!/bin/sh\n# Installed by `if-changed init`.\nexec if-changed\n",
+        )
+        .unwrap();
+        insta::assert_snapshot!(output(|out| uninstall(&repo, Hook::PreCommit, out)), @"Removed .git/hooks/pre-commit.");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_managers() {
+        let (tempdir, repo) = git_test! {};
+
+        assert_eq!(
+            Manager::detect(&repo),
+            Manager::Git(repo.path().join("hooks"))
+        );
+        repo.config()
+            .unwrap()
+            .set_str("core.hooksPath", ".githooks")
+            .unwrap();
+        assert_eq!(
+            Manager::detect(&repo),
+            Manager::Git(tempdir.path().join(".githooks"))
+        );
+        repo.config()
+            .unwrap()
+            .set_str("core.hooksPath", ".husky/_")
+            .unwrap();
+        assert_eq!(
+            Manager::detect(&repo),
+            Manager::Husky(tempdir.path().join(".husky"))
+        );
+        insta::assert_snapshot!(output(|out| install(&repo, Hook::PreCommit, out)), @"Installed .husky/pre-commit.");
+
+        fs::write(tempdir.path().join("lefthook.yml"), "").unwrap();
+        insta::assert_snapshot!(output(|out| install(&repo, Hook::PrePush, out)), @r###"
+        Lefthook runs the hooks of this repository. Add this to lefthook.yml:
+
+        pre-push:
+          commands:
+            if-changed:
+              run: if git rev-parse --quiet --verify '@{u}' >/dev/null; then if-changed --from-ref '@{u}' --to-ref HEAD; fi
+
+        "###);
+    }
+
+    #[test]
+    fn test_configure() {
+        insta::assert_snapshot!(output(|out| configure(HookManager::Lefthook, out)), @r###"
+        Add this to lefthook.yml:
+
+        pre-commit:
+          commands:
+            if-changed:
+              run: if-changed
+        pre-push:
+          commands:
+            if-changed:
+              run: if git rev-parse --quiet --verify '@{u}' >/dev/null; then if-changed --from-ref '@{u}' --to-ref HEAD; fi
+
+        "###);
+        insta::assert_snapshot!(output(|out| configure(HookManager::Husky, out)), @r###"
+        Add these lines to the scripts in .husky/:
+
+        .husky/pre-commit:
+
+            # >>> if-changed >>>
+            if-changed || exit $?
+            # <<< if-changed <<<
+
+        .husky/pre-push:
+
+            # >>> if-changed >>>
+            if git rev-parse --quiet --verify '@{u}' >/dev/null; then
+                if-changed --from-ref '@{u}' --to-ref HEAD || exit $?
+            fi
+            # <<< if-changed <<<
+
+        "###);
+    }
+}
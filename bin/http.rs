@@ -0,0 +1,10 @@
+use std::io;
+
+use serde_json::Value;
+
+/// Sends requests to a REST API, e.g. of GitHub or Azure DevOps.
+pub trait Api {
+    /// Send `method` to `path`, e.g. `/repos/OWNER/NAME/pulls/1`, with `body`
+    /// as JSON, if any, and get the status and JSON body of the response.
+    fn send(&mut self, method: &str, path: &str, body: Option<Value>) -> io::Result<(u16, Value)>;
+}
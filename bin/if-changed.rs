@@ -1,20 +1,138 @@
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
-use std::process::ExitCode;
+mod audit;
+#[cfg(feature = "azure")]
+mod azure;
+#[cfg(feature = "bitbucket")]
+mod bitbucket;
+mod blame;
+mod completions;
+mod coverage;
+mod daemon;
+mod doctor;
+mod fix;
+#[cfg(feature = "gerrit")]
+mod gerrit;
+#[cfg(feature = "github")]
+mod github;
+mod hook;
+#[cfg(any(
+    feature = "azure",
+    feature = "bitbucket",
+    feature = "gerrit",
+    feature = "github"
+))]
+mod http;
+#[cfg(any(feature = "azure", feature = "bitbucket", feature = "github"))]
+mod ignore;
+mod init;
+mod list;
+mod lsp;
+mod migrate;
+mod rename;
+mod render;
+mod report;
+mod scope;
+mod stats;
+mod suggest;
+mod triage;
+mod validate;
+mod verify;
+mod watch;
 
-use clap::Parser as ClapParser;
-use genawaiter::{rc::gen, yield_};
-use if_changed::{Engine as _, GitEngine};
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::Instant,
+};
 
-#[derive(ClapParser, Debug)]
-#[command(author, version, about, long_about = None)]
+use clap::{
+    builder::{BoolishValueParser, PossibleValuesParser, TypedValueParser as _},
+    ArgAction, Args, ColorChoice, CommandFactory as _, Parser as ClapParser, Subcommand,
+};
+use clap_complete::{engine::ArgValueCompleter, CompleteEnv};
+use if_changed::{
+    Blame, CheckIter, Checker, Code, Config, ConfigTree, DeletedTargetPolicy, Diagnostic,
+    DiffFilter, Engine as _, GitEngine, Owners, ParseCache, Reporter, Severity, Stats, Summary,
+    TrailerConfig,
+};
+use render::Renderer;
+use report::{Report, ReportFormat, Tee};
+use scope::{PathMode, Scope};
+use tracing_subscriber::{filter::LevelFilter, fmt::format::FmtSpan, EnvFilter};
+
+#[derive(ClapParser, Debug, Default)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 pub struct Cli {
-    /// The revision to compare against. By default, HEAD is used.
-    #[arg(long, env = "PRE_COMMIT_FROM_REF")]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub check: CheckArgs,
+
+    /// When to use colors in diagnostics.
+    #[arg(
+        long,
+        env = "IF_CHANGED_COLOR",
+        value_enum,
+        default_value_t,
+        global = true
+    )]
+    pub color: ColorChoice,
+
+    /// Print a detailed explanation of an error code, e.g. `IC002`, and exit.
+    #[arg(long, value_name = "CODE")]
+    pub explain: Option<Code>,
+
+    /// Log what `if-changed` does to stderr. Repeat for more details, e.g.
+    /// `-vv`.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// The most detailed level of logs to print: `off`, `error`, `warn`,
+    /// `info`, `debug`, or `trace`.
+    ///
+    /// Overrides `-v`. Without either, logs are filtered by `RUST_LOG`, e.g.
+    /// `RUST_LOG=if_changed=trace`.
+    #[arg(
+        long,
+        env = "IF_CHANGED_LOG_LEVEL",
+        value_name = "LEVEL",
+        global = true
+    )]
+    pub log_level: Option<LevelFilter>,
+}
+
+impl Cli {
+    /// Get the filter of logs to print.
+    fn log_filter(&self) -> EnvFilter {
+        let level = match (self.log_level, self.verbose) {
+            (Some(level), _) => level,
+            (None, 0) => {
+                return EnvFilter::builder()
+                    .with_default_directive(LevelFilter::WARN.into())
+                    .from_env_lossy()
+            }
+            (None, 1) => LevelFilter::DEBUG,
+            (None, _) => LevelFilter::TRACE,
+        };
+        EnvFilter::default().add_directive(level.into())
+    }
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct CheckArgs {
+    /// The revision to compare against. By default, `if-changed.fromRef` in
+    /// git config is used, or else HEAD.
+    #[arg(long, env = "PRE_COMMIT_FROM_REF", add = ArgValueCompleter::new(completions::complete_refs))]
     pub from_ref: Option<String>,
 
-    /// The revision to compare with. By default, the current working tree is used.
-    #[arg(long, env = "PRE_COMMIT_TO_REF")]
+    /// The revision to compare with. By default, `if-changed.toRef` in git
+    /// config is used, or else the current working tree.
+    #[arg(long, env = "PRE_COMMIT_TO_REF", add = ArgValueCompleter::new(completions::complete_refs))]
     pub to_ref: Option<String>,
 
     /// Git patterns defining the set of files to check. By default, this will
@@ -26,33 +144,1154 @@ pub struct Cli {
     /// paths/patterns don't contain `/`. In particular, a leading `!` before a
     /// pattern will reinclude the pattern if it was excluded by a previous
     /// pattern.
-    #[arg()]
+    ///
+    /// Without patterns, the patterns in `IF_CHANGED_PATTERNS` are used, one
+    /// per line, e.g. as set by `cargo if-changed`.
+    #[arg(env = "IF_CHANGED_PATTERNS", value_delimiter = '\n', hide_env = true, add = ArgValueCompleter::new(completions::complete_paths))]
     pub patterns: Vec<String>,
+
+    /// Treat the positional arguments as exact paths instead of patterns,
+    /// e.g. the files pre-commit passes, which may contain `*`, `?` or `[`.
+    #[arg(long, env = "IF_CHANGED_LITERAL_PATHS", value_parser = BoolishValueParser::new())]
+    pub literal_paths: bool,
+
+    /// How to interpret patterns and print paths.
+    ///
+    /// With `cwd`, patterns not starting with `/` are relative to the current
+    /// directory (like `git` does) and paths in diagnostics are printed
+    /// relative to it.
+    #[arg(long, env = "IF_CHANGED_PATH_MODE", value_enum, default_value_t)]
+    pub path_mode: PathMode,
+
+    /// Keep running and re-check the working tree whenever it changes.
+    #[arg(long, env = "IF_CHANGED_WATCH", value_parser = BoolishValueParser::new(), conflicts_with = "to_ref")]
+    pub watch: bool,
+
+    /// Print statistics about the run when done.
+    #[arg(
+        long,
+        env = "IF_CHANGED_STATS",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        conflicts_with = "watch"
+    )]
+    pub stats: Option<stats::StatsFormat>,
+
+    /// A commit trailer key marking files to ignore, e.g. `Skip-If-Changed`.
+    ///
+    /// May be given several times to accept several keys. Overrides the keys
+    /// set in `.if-changed.toml`. By default, `ignore-if-changed` is used.
+    #[arg(
+        long = "ignore-trailer",
+        env = "IF_CHANGED_IGNORE_TRAILER",
+        value_delimiter = ',',
+        value_name = "KEY"
+    )]
+    pub ignore_trailers: Vec<String>,
+
+    /// Only honor ignore trailers giving a reason after `--`, e.g.
+    /// `ignore-if-changed: a.ts -- generated`.
+    #[arg(long, env = "IF_CHANGED_REQUIRE_IGNORE_REASON", value_parser = BoolishValueParser::new())]
+    pub require_ignore_reason: bool,
+
+    /// Only honor ignore trailers whose reason matches this regular
+    /// expression, e.g. `[A-Z]+-[0-9]+` to require a ticket. Implies
+    /// `--require-ignore-reason`.
+    #[arg(long, env = "IF_CHANGED_IGNORE_REASON_PATTERN", value_name = "REGEX")]
+    pub ignore_reason_pattern: Option<String>,
+
+    /// A label of pull requests ignoring all their files, e.g. when they only
+    /// move code around.
+    ///
+    /// May be given several times to accept several labels. Files may also be
+    /// ignored by listing them in an `ignore-if-changed` code block of the
+    /// description of a pull request.
+    #[cfg(any(feature = "azure", feature = "bitbucket", feature = "github"))]
+    #[arg(
+        long = "ignore-label",
+        env = "IF_CHANGED_IGNORE_LABEL",
+        value_delimiter = ',',
+        value_name = "LABEL",
+        default_value = "skip-if-changed"
+    )]
+    pub ignore_labels: Vec<String>,
+
+    /// Stop at the first problem instead of checking the remaining files.
+    #[arg(long, env = "IF_CHANGED_FAIL_FAST", value_parser = BoolishValueParser::new())]
+    pub fail_fast: bool,
+
+    /// Don't count untracked files of the working tree as changed.
+    #[arg(long, env = "IF_CHANGED_NO_UNTRACKED", value_parser = BoolishValueParser::new())]
+    pub no_untracked: bool,
+
+    /// Diff the whole tree even if paths are given. By default, only the
+    /// files matching them, the targets of their blocks before and after the
+    /// change, and the files generated from them are diffed.
+    #[arg(long, env = "IF_CHANGED_NO_NARROW", value_parser = BoolishValueParser::new())]
+    pub no_narrow: bool,
+
+    /// Whether deleting a target satisfies a modified block or fails the
+    /// check. Overrides `deleted` in the `targets` section of
+    /// `.if-changed.toml`. By default, deleting a target satisfies it.
+    #[arg(
+        long,
+        env = "IF_CHANGED_DELETED_TARGETS",
+        value_name = "POLICY",
+        value_parser = PossibleValuesParser::new(DeletedTargetPolicy::ALL.map(DeletedTargetPolicy::as_str))
+            .map(|policy| policy.parse::<DeletedTargetPolicy>().unwrap())
+    )]
+    pub deleted_targets: Option<DeletedTargetPolicy>,
+
+    /// Also check the blocks removed from files, e.g. with the files
+    /// deleted, as of `--from-ref`. Their targets must change as if the
+    /// blocks were modified.
+    #[arg(long, env = "IF_CHANGED_REMOVED_BLOCKS", value_parser = BoolishValueParser::new())]
+    pub removed_blocks: bool,
+
+    /// Report the targets removed from `then-change`, or with their blocks,
+    /// as of `--from-ref`, unless they changed too or a `remove-if-changed`
+    /// trailer acknowledges the removal.
+    #[arg(long, env = "IF_CHANGED_REMOVED_TARGETS", value_parser = BoolishValueParser::new())]
+    pub removed_targets: bool,
+
+    /// Count changes within N lines around blocks, e.g. right above their
+    /// `if-changed`, as changes of the blocks, both to trigger them and to
+    /// satisfy targets naming them. Unlike `--diff-context-lines`, this doesn't
+    /// change how git groups changes into hunks.
+    #[arg(
+        long,
+        env = "IF_CHANGED_BLOCK_CONTEXT_LINES",
+        value_name = "N",
+        default_value_t = 0
+    )]
+    pub block_context_lines: usize,
+
+    /// Don't count changes of the `if-changed` and `then-change` lines of
+    /// blocks, e.g. adding a target, as changes of the blocks, so maintaining
+    /// directives never requires changing targets.
+    #[arg(long, env = "IF_CHANGED_IGNORE_DIRECTIVE_CHANGES", value_parser = BoolishValueParser::new())]
+    pub ignore_directive_changes: bool,
+
+    /// Compare the content of changed blocks with the same blocks in
+    /// `--from-ref`, by name or else by position, and count blocks with the
+    /// same content as unmodified, e.g. blocks moved by lines inserted above
+    /// them.
+    #[arg(long, env = "IF_CHANGED_COMPARE_CONTENT", value_parser = BoolishValueParser::new())]
+    pub compare_content: bool,
+
+    /// Report who last edited the targets left unmodified, and when, with
+    /// `git blame`, e.g. to ping their owner. Slows down checks of large
+    /// files with long histories.
+    #[arg(long, env = "IF_CHANGED_BLAME_TARGETS", value_parser = BoolishValueParser::new())]
+    pub blame_targets: bool,
+
+    /// Skip files larger than SIZE bytes, e.g. generated or vendored files,
+    /// instead of parsing them. SIZE may end with `K`, `M` or `G`, e.g. `8M`,
+    /// and `0` disables the limit. Skipped files containing directives are
+    /// reported as notes.
+    #[arg(long, env = "IF_CHANGED_MAX_FILE_SIZE", value_name = "SIZE", default_value = "4M", value_parser = parse_size)]
+    pub max_file_size: Option<u64>,
+
+    /// Pair deleted and added files into renames, so renamed files are
+    /// checked by their new path only.
+    #[arg(long, env = "IF_CHANGED_DETECT_RENAMES", value_parser = BoolishValueParser::new())]
+    pub detect_renames: bool,
+
+    /// Compare against the merge base of `--from-ref` and `--to-ref`, or
+    /// HEAD, like `git diff FROM...TO` and pre-commit's `pre-push` stage, so
+    /// changes made on `--from-ref` since the branches diverged aren't checked.
+    #[arg(long, env = "IF_CHANGED_MERGE_BASE", value_parser = BoolishValueParser::new())]
+    pub merge_base: bool,
+
+    /// Count files whose mode changed, e.g. with `chmod +x`, as changed even
+    /// if their content didn't, so they trigger and satisfy blocks.
+    #[arg(long, env = "IF_CHANGED_INCLUDE_MODE_CHANGES", value_parser = BoolishValueParser::new())]
+    pub include_mode_changes: bool,
+
+    /// Only count files changed in these ways as changed, both to trigger and
+    /// to satisfy blocks, with the letters of `git diff --diff-filter`: `A`
+    /// (added), `M` (modified), `D` (deleted), `R` (renamed) and `T` (type
+    /// changed). Lower case excludes a kind instead, e.g. `a`. By default,
+    /// `if-changed.diffFilter` in git config is used, or else all files count.
+    #[arg(long, env = "IF_CHANGED_DIFF_FILTER", value_name = "FILTER")]
+    pub diff_filter: Option<DiffFilter>,
+
+    /// The number of unchanged lines around changes grouped into hunks. By
+    /// default, `if-changed.diffContextLines` in git config is used, or else 3
+    /// lines, as by `git diff`.
+    #[arg(long, env = "IF_CHANGED_DIFF_CONTEXT_LINES", value_name = "N")]
+    pub diff_context_lines: Option<u32>,
+
+    /// Audit the history: check each commit since DATE on its own and report
+    /// the problems with the commits introducing them.
+    ///
+    /// DATE is in UTC, e.g. `2024-01-31` or `2024-01-31T12:00:00`. Commits are
+    /// taken from `--to-ref`, or HEAD, and stop at `--from-ref`, if given.
+    #[arg(long, env = "IF_CHANGED_SINCE", value_name = "DATE", value_parser = audit::parse_date, conflicts_with_all = ["watch", "interactive"])]
+    pub since: Option<i64>,
+
+    /// Audit the history: check each commit between `--from-ref` and
+    /// `--to-ref`, or HEAD, on its own and report the problems with the
+    /// commits introducing them.
+    #[arg(long, env = "IF_CHANGED_AUDIT", value_parser = BoolishValueParser::new(), requires = "from_ref", conflicts_with_all = ["watch", "interactive"])]
+    pub audit: bool,
+
+    /// The format of the report written to `--output`. Reports in formats
+    /// other than `human` are written to stdout if `--output` is not given.
+    #[arg(long, env = "IF_CHANGED_FORMAT", value_enum, default_value_t)]
+    pub format: ReportFormat,
+
+    /// Also write the report to PATH, e.g. to archive it in CI. Diagnostics
+    /// are still printed to stderr.
+    #[arg(long, env = "IF_CHANGED_OUTPUT", value_name = "PATH", conflicts_with_all = ["watch", "interactive", "since", "audit"])]
+    pub output: Option<PathBuf>,
+
+    #[cfg(feature = "github")]
+    #[command(flatten)]
+    pub github: github::ReviewArgs,
+
+    #[cfg(feature = "gerrit")]
+    #[command(flatten)]
+    pub gerrit: gerrit::GerritArgs,
+
+    #[cfg(feature = "bitbucket")]
+    #[command(flatten)]
+    pub bitbucket: bitbucket::BitbucketArgs,
+
+    #[cfg(feature = "azure")]
+    #[command(flatten)]
+    pub azure: azure::AzureArgs,
+
+    /// Walk through problems one at a time, offering to fix or suppress each.
+    #[arg(long, env = "IF_CHANGED_INTERACTIVE", value_parser = BoolishValueParser::new(), conflicts_with_all = ["watch", "stats"])]
+    pub interactive: bool,
+
+    /// The file `--interactive` appends suggested commit trailers to. By
+    /// default, `IF_CHANGED_TRAILERS` in the git directory is used.
+    #[arg(
+        long,
+        env = "IF_CHANGED_TRAILERS_FILE",
+        value_name = "PATH",
+        requires = "interactive"
+    )]
+    pub trailers_file: Option<PathBuf>,
+
+    /// The severities and messages of problems, from `.if-changed.toml` and
+    /// those of subdirectories.
+    #[arg(skip)]
+    pub configs: ConfigTree,
+
+    /// Whether `\` in targets separates directories, from `.if-changed.toml`.
+    #[arg(skip = true)]
+    pub normalize_separators: bool,
+
+    /// Whether targets excluded by `.ifchangedignore` files must still
+    /// change, from `.if-changed.toml`.
+    #[arg(skip = true)]
+    pub require_excluded_targets: bool,
+
+    /// The owners of files, from `CODEOWNERS` or the file configured in
+    /// `.if-changed.toml`.
+    #[arg(skip)]
+    pub owners: Option<Owners>,
+
+    /// Patterns of the files to look for blocks in, from `.if-changed.toml`.
+    #[arg(skip)]
+    pub scan: Vec<String>,
+
+    /// Patterns of the files that must contain a block, from
+    /// `.if-changed.toml`.
+    #[arg(skip)]
+    pub require_blocks: Vec<String>,
+
+    /// Patterns of generated files and of the files they are generated from,
+    /// from `.if-changed.toml`.
+    #[arg(skip)]
+    pub generated: BTreeMap<String, String>,
+
+    /// The files ignored by the pull request being checked, from its labels
+    /// and description.
+    #[arg(skip)]
+    pub ignore: Vec<String>,
 }
 
-fn run(cli: Cli, repository: git2::Repository) -> impl Iterator<Item = String> {
-    gen!({
-        let engine = GitEngine::new(&repository, cli.from_ref.as_deref(), cli.to_ref.as_deref());
-        for result in engine.matches(cli.patterns) {
-            let Ok(path) = result else {
-                continue;
-            };
-            if engine.is_ignored(&path) {
-                continue;
+impl CheckArgs {
+    /// Apply settings from the repository configuration that were not given on
+    /// the command line.
+    fn configure(&mut self, workdir: &Path, config: Config) {
+        self.configs = ConfigTree::new(workdir, &config);
+        if self.ignore_trailers.is_empty() {
+            self.ignore_trailers = config.ignore_trailer.keys;
+        }
+        self.require_ignore_reason |= config.ignore_trailer.require_reason;
+        if self.ignore_reason_pattern.is_none() {
+            self.ignore_reason_pattern = config.ignore_trailer.reason_pattern;
+        }
+        self.deleted_targets.get_or_insert(config.targets.deleted);
+        self.normalize_separators = config.targets.normalize_separators;
+        self.require_excluded_targets = config.targets.require_excluded;
+        self.scan = config.scan;
+        self.require_blocks = config.require_blocks;
+        self.generated = config.generated;
+    }
+
+    /// Create the engine comparing the revisions to check.
+    fn engine<'repo>(&self, repository: &'repo git2::Repository) -> io::Result<GitEngine<'repo>> {
+        let mut builder = GitEngine::builder(repository)
+            .trailers(self.trailers())
+            .include_untracked(!self.no_untracked)
+            .detect_renames(self.detect_renames)
+            .include_mode_changes(self.include_mode_changes)
+            .merge_base(self.merge_base)
+            .ignore(&self.ignore);
+        if !self.no_narrow {
+            builder = builder
+                .narrow_to(&self.patterns)
+                .narrow_generated(&self.generated);
+        }
+        if let Some(from_ref) = &self.from_ref {
+            builder = builder.from_ref(from_ref);
+        }
+        if let Some(to_ref) = &self.to_ref {
+            builder = builder.to_ref(to_ref);
+        }
+        if let Some(context_lines) = self.diff_context_lines {
+            builder = builder.context_lines(context_lines);
+        }
+        if let Some(diff_filter) = self.diff_filter {
+            builder = builder.diff_filter(diff_filter);
+        }
+        builder.build()
+    }
+
+    /// Apply the defaults set in git config that were not given on the
+    /// command line, e.g. with `git config if-changed.fromRef origin/main`.
+    fn configure_git(&mut self, config: &git2::Config) -> io::Result<()> {
+        if self.from_ref.is_none() {
+            self.from_ref = git_config(config, "if-changed.fromRef")?;
+        }
+        if self.to_ref.is_none() {
+            self.to_ref = git_config(config, "if-changed.toRef")?;
+        }
+        if self.diff_context_lines.is_none() {
+            if let Some(value) = git_config(config, "if-changed.diffContextLines")? {
+                self.diff_context_lines = Some(value.parse().map_err(|error| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "invalid git config `if-changed.diffContextLines` {value:?}: {error}"
+                        ),
+                    )
+                })?);
             }
-            if let Err(errors) = engine.check(path) {
-                for error in errors {
-                    yield_!(error);
-                }
+        }
+        if self.diff_filter.is_none() {
+            if let Some(value) = git_config(config, "if-changed.diffFilter")? {
+                self.diff_filter = Some(value.parse().map_err(|error| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid git config `if-changed.diffFilter` {value:?}: {error}"),
+                    )
+                })?);
+            }
+        }
+        Ok(())
+    }
+
+    fn trailers(&self) -> TrailerConfig {
+        let mut trailers = TrailerConfig {
+            require_reason: self.require_ignore_reason,
+            reason_pattern: self.ignore_reason_pattern.clone(),
+            ..Default::default()
+        };
+        if !self.ignore_trailers.is_empty() {
+            trailers.keys.clone_from(&self.ignore_trailers);
+        }
+        trailers
+    }
+}
+
+/// Apply the settings of the git config of `repository` to `args` and
+/// `config`, below the command line and above `.if-changed.toml`.
+fn configure_git(
+    args: &mut CheckArgs,
+    mut config: Config,
+    repository: &git2::Repository,
+) -> io::Result<Config> {
+    let git_config = repository
+        .config()
+        .and_then(|mut config| config.snapshot())
+        .map_err(io::Error::other)?;
+    config.merge_git_config(&git_config)?;
+    args.configure_git(&git_config)?;
+    Ok(config)
+}
+
+/// Get the value of `key` in git config, if set.
+fn git_config(config: &git2::Config, key: &str) -> io::Result<Option<String>> {
+    match config.get_string(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(error) => Err(io::Error::other(error)),
+    }
+}
+
+/// Parse a number of bytes, optionally ending with `K`, `M` or `G`.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (number, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((index, _)) => value.split_at(index),
+        None => (value, ""),
+    };
+    let unit = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("invalid size {value:?}; expected e.g. 512K or 4M")),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(unit))
+        .ok_or_else(|| format!("invalid size {value:?}; expected e.g. 512K or 4M"))
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Check files for dependent changes. This is the default command.
+    Check(Box<CheckArgs>),
+
+    /// Print the blocks covering lines of a file and the targets they impose.
+    ///
+    /// Answers "if I touch these lines, what else must I change?" before
+    /// editing. Blocks are read from the working tree.
+    Blame(blame::BlameArgs),
+    /// Print a script enabling shell completions.
+    ///
+    /// For example, add `source <(if-changed completions bash)` to your
+    /// `.bashrc`. Completions include tracked paths and revisions of the
+    /// current repository.
+    Completions(completions::CompletionsArgs),
+
+    /// Measure how much of the diff is inside blocks.
+    ///
+    /// Prints the fraction of changed lines that fall inside `if-changed`
+    /// blocks for each changed file and overall, e.g. to track whether
+    /// critical files are annotated.
+    Coverage(coverage::CoverageArgs),
+
+    /// Keep the repository open and answer check requests.
+    ///
+    /// Requests are read as JSON objects, one per line, e.g. from editor
+    /// integrations, and answered in the schema of `--format json` reports.
+    /// Parsed files and diffs between commits are reused across requests.
+    Daemon(daemon::DaemonArgs),
+
+    /// Diagnose problems with the environment `if-changed` runs in.
+    ///
+    /// Checks the repository, revisions, history depth, configuration, and
+    /// the blocks of a few files, and prints how to fix each problem found.
+    Doctor(doctor::DoctorArgs),
+
+    /// Rewrite blocks to fix problems in place.
+    ///
+    /// With `--renames`, rewrites the targets of `then-change` naming files
+    /// renamed between the revisions to their new paths, in all tracked files,
+    /// keeping their comment style.
+    Fix(fix::FixArgs),
+
+    /// Set up `if-changed` in the current repository.
+    ///
+    /// Creates a starter `.if-changed.toml`, optionally installs a git hook,
+    /// and prints examples of blocks for the languages used in the repository.
+    Init(init::InitArgs),
+
+    /// Run `if-changed` in a git hook.
+    ///
+    /// Adds `if-changed` to the script of the hook in the hooks directory,
+    /// honoring `core.hooksPath` and Husky, and keeps the other commands of
+    /// existing scripts. For lefthook, prints the command to configure
+    /// instead.
+    InstallHook(hook::HookArgs),
+
+    /// Print the blocks of files and their targets.
+    ///
+    /// Targets are printed as written. With `--format json`, the blocks are
+    /// printed in the versioned schema described in the README, e.g. for
+    /// external tooling.
+    List(list::ListArgs),
+
+    /// Serve the Language Server Protocol over stdin and stdout.
+    ///
+    /// Editors get the problems of open files, go to the files and blocks
+    /// `then-change` targets refer to, and apply suggested fixes as code
+    /// actions.
+    Lsp,
+
+    /// Convert legacy annotations into `if-changed` blocks.
+    ///
+    /// Rewrites comments such as `LINT.IfChange` and `LINT.ThenChange` in
+    /// all tracked files in place, keeping their comment style. Other formats
+    /// can be configured under `[[migrate.formats]]` in `.if-changed.toml`.
+    Migrate(migrate::MigrateArgs),
+
+    /// Update the blocks referring to a renamed file, directory or block.
+    ///
+    /// Rewrites the targets of `then-change` naming OLD to name NEW in all
+    /// tracked files, keeping their comment style. With `--block`, renames the
+    /// blocks named OLD and the targets naming them instead. Files aren't
+    /// moved.
+    Rename(rename::RenameArgs),
+
+    /// Print a commit trailer ignoring the files with problems.
+    ///
+    /// Checks files like `check` and lists the files with errors in an
+    /// `ignore-if-changed` trailer, e.g. when a change intentionally leaves
+    /// targets unchanged. With `--amend`, prints the message of HEAD with the
+    /// trailer added, for `git commit --amend -F -`.
+    SuggestIgnore(Box<suggest::SuggestIgnoreArgs>),
+
+    /// Stop running `if-changed` in a git hook.
+    ///
+    /// Removes the lines added by `install-hook` from the script of the hook,
+    /// and the script if nothing else is left.
+    UninstallHook(hook::HookArgs),
+
+    /// Check the files listed in a manifest without reading git state.
+    ///
+    /// Blocks must be well-formed, their targets must be listed in the
+    /// manifest, and the blocks they name must exist. On success, an empty
+    /// output file is created, e.g. as the output of a Bazel or Buck2
+    /// validation action.
+    Validate(validate::ValidateArgs),
+
+    /// Check the blocks of all files, whether or not they changed.
+    ///
+    /// Reports malformed blocks, targets matching no tracked file, and named
+    /// blocks that don't exist. Blocks are cached in the git directory, so
+    /// only files changed since the last run are parsed, e.g. in pre-push
+    /// hooks.
+    Verify(verify::VerifyArgs),
+}
+
+/// Check the files of `cli` for dependent changes.
+fn run(
+    cli: &CheckArgs,
+    repository: &git2::Repository,
+    stats: &mut Stats,
+) -> io::Result<impl Iterator<Item = Diagnostic>> {
+    let engine = cli.engine(repository)?;
+    Ok(sorted(run_engine(cli, &engine, Arc::default(), stats)).into_iter())
+}
+
+/// Collect problems in a stable order, by file, line and code, so reports
+/// don't depend on the order files were diffed and targets checked in.
+fn sorted(diagnostics: impl Iterator<Item = Diagnostic>) -> Vec<Diagnostic> {
+    let mut diagnostics = diagnostics.collect::<Vec<_>>();
+    diagnostics.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    diagnostics
+}
+
+/// Check the files `engine` matches, sharing what is parsed through `cache`.
+fn run_engine<'a, 'repo>(
+    cli: &'a CheckArgs,
+    engine: &'a GitEngine<'repo>,
+    cache: Arc<ParseCache>,
+    stats: &'a mut Stats,
+) -> Run<'a, 'repo> {
+    let mut diagnostics = engine.diagnostics();
+    for diagnostic in &mut diagnostics {
+        cli.configs.apply(diagnostic);
+    }
+    Run {
+        cli,
+        engine,
+        cache,
+        stats,
+        configs: cli.configs.clone(),
+        pending: diagnostics.into_iter(),
+        checks: None,
+        done: false,
+    }
+}
+
+/// The problems found by [`run_engine`], checking files only as problems are
+/// consumed, so `--fail-fast` stops at the first one.
+struct Run<'a, 'repo> {
+    cli: &'a CheckArgs,
+    engine: &'a GitEngine<'repo>,
+    cache: Arc<ParseCache>,
+    stats: &'a mut Stats,
+    /// The configuration of the directories of the checked files, loaded
+    /// once they are known.
+    configs: ConfigTree,
+    /// Problems of the engine itself, e.g. rejected ignore trailers.
+    pending: std::vec::IntoIter<Diagnostic>,
+    /// The checks of the matched files, once started.
+    checks: Option<Checks<'a, 'repo>>,
+    done: bool,
+}
+
+/// The checks of the matched files: one file at a time with `--fail-fast`,
+/// so they stop at the first problem, or else all at once on rayon's thread
+/// pool.
+enum Checks<'a, 'repo> {
+    Lazy(Box<CheckIter<'a, GitEngine<'repo>>>),
+    Done(std::vec::IntoIter<Diagnostic>),
+}
+
+impl<'a, 'repo> Run<'a, 'repo> {
+    /// Start checking the matched files that are not ignored, if any.
+    fn start(&mut self) -> Option<Checks<'a, 'repo>> {
+        let start = Instant::now();
+        let mut paths = Vec::new();
+        for path in self.engine.matches(&self.cli.patterns).flat_map(Result::ok) {
+            if self.engine.is_ignored(&path) {
+                self.stats.ignored_files += 1;
+            } else if self.engine.is_binary(&path) {
+                self.stats.binary_files += 1;
+            } else {
+                paths.push(path);
+            }
+        }
+        self.stats.match_time += start.elapsed();
+        if paths.is_empty() {
+            return None;
+        }
+        let problems = paths
+            .iter()
+            .flat_map(|path| self.configs.load(path))
+            .collect::<Vec<_>>();
+        self.pending = problems.into_iter();
+
+        // A single checker parses each file at most once, even when several
+        // blocks target it.
+        let checker = paths.into_iter().fold(
+            Checker::builder(self.engine)
+                .cache(self.cache.clone())
+                .max_file_size(self.cli.max_file_size.filter(|size| *size > 0))
+                .deleted_targets(self.cli.deleted_targets.unwrap_or_default())
+                .removed_blocks(self.cli.removed_blocks)
+                .removed_targets(self.cli.removed_targets)
+                .block_context_lines(self.cli.block_context_lines)
+                .ignore_directive_changes(self.cli.ignore_directive_changes)
+                .compare_content(self.cli.compare_content)
+                .blame_targets(self.cli.blame_targets)
+                .normalize_separators(self.cli.normalize_separators)
+                .require_excluded_targets(self.cli.require_excluded_targets)
+                .scan(&self.cli.scan)
+                .require_blocks(&self.cli.require_blocks)
+                .generated(&self.cli.generated),
+            |checker, path| checker.path(path),
+        );
+        let checker = checker.build();
+        if self.cli.fail_fast {
+            return Some(Checks::Lazy(Box::new(checker.check_iter())));
+        }
+        let diagnostics = checker
+            .par_check_with_stats(self.stats)
+            .err()
+            .unwrap_or_default();
+        Some(Checks::Done(diagnostics.into_iter()))
+    }
+}
+
+impl Iterator for Run<'_, '_> {
+    type Item = Diagnostic;
+
+    fn next(&mut self) -> Option<Diagnostic> {
+        if self.done {
+            return None;
+        }
+        if let Some(diagnostic) = self.pending.next() {
+            self.done = self.cli.fail_fast && diagnostic.severity != Severity::Note;
+            return Some(diagnostic);
+        }
+        if self.checks.is_none() {
+            self.checks = self.start();
+            // Configuration files that can't be loaded are reported first.
+            if let Some(diagnostic) = self.pending.next() {
+                self.done = self.cli.fail_fast && diagnostic.severity != Severity::Note;
+                return Some(diagnostic);
+            }
+        }
+        let Some(checks) = &mut self.checks else {
+            self.done = true;
+            return None;
+        };
+        let mut diagnostic = match checks {
+            Checks::Lazy(checks) => checks.next(),
+            Checks::Done(diagnostics) => diagnostics.next(),
+        };
+        if let Some(diagnostic) = &mut diagnostic {
+            self.configs.apply(diagnostic);
+        }
+        if let (Some(diagnostic), Some(owners)) = (&mut diagnostic, &self.cli.owners) {
+            owners.annotate(diagnostic);
+        }
+        // Notes, e.g. of skipped files, aren't problems to stop at.
+        let stop = diagnostic
+            .as_ref()
+            .is_none_or(|diagnostic| self.cli.fail_fast && diagnostic.severity != Severity::Note);
+        if stop {
+            if let Checks::Lazy(checks) = checks {
+                *self.stats += checks.stats();
             }
+            self.done = true;
         }
-    })
-    .into_iter()
+        diagnostic
+    }
+}
+
+/// Report the problems `run` finds with `engine` to `reporter`, in the order
+/// of [`sorted`].
+fn check(
+    cli: &CheckArgs,
+    engine: &GitEngine,
+    stats: &mut Stats,
+    reporter: &mut dyn Reporter,
+) -> io::Result<Summary> {
+    for diagnostic in sorted(run_engine(cli, engine, Arc::default(), stats)) {
+        reporter.report(&diagnostic)?;
+    }
+    reporter.finish()
+}
+
+/// Get the modified blocks of the files `run` checks.
+fn modified_blocks(cli: &CheckArgs, engine: &GitEngine) -> Vec<(PathBuf, Blame)> {
+    let mut blocks = Vec::new();
+    for path in engine.matches(&cli.patterns).flat_map(Result::ok) {
+        if engine.is_ignored(&path) || engine.is_binary(&path) {
+            continue;
+        }
+        // Problems parsing the file are reported by `run`.
+        for blame in engine.blame(&path, (1, usize::MAX)).unwrap_or_default() {
+            if engine.is_range_modified(&path, blame.range) {
+                blocks.push((path.clone(), blame));
+            }
+        }
+    }
+    blocks
 }
 
 #[cfg_attr(coverage_nightly, coverage(off))]
 fn main() -> ExitCode {
-    let mut has_error = false;
+    CompleteEnv::with_factory(Cli::command).complete();
+
+    let cli = Cli::parse();
+    if let Some(code) = cli.explain {
+        print!("{}", code.explanation());
+        return ExitCode::SUCCESS;
+    }
+    let color = match cli.color {
+        ColorChoice::Auto => anstream::ColorChoice::Auto,
+        ColorChoice::Always => anstream::ColorChoice::Always,
+        ColorChoice::Never => anstream::ColorChoice::Never,
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(cli.log_filter())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(move || anstream::AutoStream::new(io::stderr(), color))
+        .init();
+    let mut suggestion = None;
+    let mut args = match cli.command {
+        Some(Command::Completions(args)) => {
+            return match completions::run(&args, io::stdout()) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not generate completions: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Coverage(args)) => {
+            let repository = match git2::Repository::open_from_env() {
+                Ok(repository) => repository,
+                Err(error) => {
+                    eprintln!("Could not open the repository: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let workdir = repository.workdir().unwrap_or(repository.path()).to_owned();
+            let scope = Scope::new(
+                args.path_mode,
+                &workdir,
+                &env::current_dir().unwrap_or_default(),
+            );
+            let patterns = args
+                .patterns
+                .iter()
+                .map(|pattern| scope.pattern(pattern))
+                .collect::<Vec<_>>();
+            let engine = match GitEngine::new(
+                &repository,
+                args.from_ref.as_deref(),
+                args.to_ref.as_deref(),
+            ) {
+                Ok(engine) => engine,
+                Err(error) => {
+                    eprintln!("Could not compare the revisions: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let files = coverage::coverage(&engine, &patterns);
+            let stdout = anstream::AutoStream::new(io::stdout(), color);
+            return match coverage::write(&files, &scope, args.format, stdout) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not write the coverage: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Daemon(args)) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| {
+                    let workdir = repository.workdir().unwrap_or(repository.path());
+                    let mut check = CheckArgs::default();
+                    let config =
+                        configure_git(&mut check, Config::discover(workdir)?, &repository)?;
+                    check.configure(workdir, config);
+                    daemon::run(&args, daemon::Daemon::new(check, &repository))
+                }) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not serve requests: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Lsp) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| {
+                    let workdir = repository.workdir().unwrap_or(repository.path());
+                    let mut check = CheckArgs::default();
+                    let config =
+                        configure_git(&mut check, Config::discover(workdir)?, &repository)?;
+                    check.configure(workdir, config);
+                    lsp::Server::new(check, &repository)?
+                        .serve(io::stdin().lock(), io::stdout().lock())
+                }) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not serve the language server: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Doctor(args)) => {
+            let stdout = anstream::AutoStream::new(io::stdout(), color);
+            return match doctor::run(&args, git2::Repository::open_from_env(), stdout) {
+                Ok(true) => ExitCode::SUCCESS,
+                Ok(false) => ExitCode::FAILURE,
+                Err(error) => {
+                    eprintln!("Could not write the diagnosis: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Init(args)) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| init::run(&args, &repository, io::stdout()))
+            {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not initialize the repository: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::InstallHook(args)) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| hook::install(&repository, args.hook, io::stdout()))
+            {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not install the hook: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::UninstallHook(args)) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| hook::uninstall(&repository, args.hook, io::stdout()))
+            {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not uninstall the hook: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Migrate(args)) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| {
+                    let workdir = repository.workdir().unwrap_or(repository.path());
+                    let config = Config::discover(workdir)?;
+                    migrate::run(&args, &repository, &config, io::stdout())
+                }) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not migrate the repository: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Fix(args)) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| fix::run(&args, &repository, io::stdout()))
+            {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not fix the repository: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Rename(args)) => {
+            return match git2::Repository::open_from_env()
+                .map_err(io::Error::other)
+                .and_then(|repository| {
+                    let workdir = repository.workdir().unwrap_or(repository.path());
+                    let scope = Scope::new(
+                        args.path_mode,
+                        workdir,
+                        &env::current_dir().unwrap_or_default(),
+                    );
+                    rename::run(&args, &repository, &scope, io::stdout())
+                }) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Could not rename: {error}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Blame(args)) => {
+            let repository = match git2::Repository::open_from_env() {
+                Ok(repository) => repository,
+                Err(error) => {
+                    eprintln!("Could not open the repository: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let workdir = repository.workdir().unwrap_or(repository.path()).to_owned();
+            let scope = Scope::new(
+                args.path_mode,
+                &workdir,
+                &env::current_dir().unwrap_or_default(),
+            );
+            let engine = match GitEngine::new(&repository, None, None) {
+                Ok(engine) => engine,
+                Err(error) => {
+                    eprintln!("Could not compare the revisions: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let problems = match blame::run(&args, &engine, &scope, io::stdout()) {
+                Ok(problems) => problems,
+                Err(error) => {
+                    eprintln!("Could not write blocks: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let mut stderr = anstream::AutoStream::new(io::stderr(), color);
+            let mut renderer = Renderer::new(scope.root(&workdir));
+            let failed = !problems.is_empty();
+            for diagnostic in problems {
+                if let Err(error) = renderer.render(&scope.diagnostic(diagnostic), &mut stderr) {
+                    eprintln!("Could not write diagnostics: {error}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            return if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+        Some(Command::List(args)) => {
+            let repository = match git2::Repository::open_from_env() {
+                Ok(repository) => repository,
+                Err(error) => {
+                    eprintln!("Could not open the repository: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let workdir = repository.workdir().unwrap_or(repository.path()).to_owned();
+            let scope = Scope::new(
+                args.path_mode,
+                &workdir,
+                &env::current_dir().unwrap_or_default(),
+            );
+            let engine = match GitEngine::new(&repository, None, None) {
+                Ok(engine) => engine,
+                Err(error) => {
+                    eprintln!("Could not compare the revisions: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let config = match Config::discover(&workdir) {
+                Ok(config) => config,
+                Err(error) => {
+                    eprintln!("Could not load {}: {error}", if_changed::CONFIG_FILE_NAME);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let skip_unreadable = args.paths.is_empty();
+            let paths = if skip_unreadable {
+                repository
+                    .index()
+                    .into_iter()
+                    .flat_map(|index| index.iter().collect::<Vec<_>>())
+                    .filter_map(|entry| String::from_utf8(entry.path).ok())
+                    .map(PathBuf::from)
+                    .filter(|path| config.scans(path))
+                    .collect::<Vec<_>>()
+            } else {
+                args.paths
+                    .iter()
+                    .map(|path| {
+                        let path = scope.pattern(path);
+                        PathBuf::from(path.strip_prefix('/').unwrap_or(&path))
+                    })
+                    .collect()
+            };
+            let stdout = anstream::AutoStream::new(io::stdout(), color);
+            let (written, problems) = if args.unreferenced {
+                let graph = if_changed::graph::build(&engine, paths);
+                let problems = graph
+                    .diagnostics()
+                    .iter()
+                    .filter(|problem| !skip_unreadable || problem.code != Code::Unreadable)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                (
+                    list::write_unreferenced(&graph, &scope, args.format, stdout),
+                    problems,
+                )
+            } else {
+                let (files, problems) = list::list(&engine, paths, skip_unreadable);
+                (
+                    list::write(&files, &scope, args.format, stdout).map(|()| false),
+                    problems,
+                )
+            };
+            let unreferenced = match written {
+                Ok(unreferenced) => unreferenced,
+                Err(error) => {
+                    eprintln!("Could not write blocks: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let mut stderr = anstream::AutoStream::new(io::stderr(), color);
+            let mut renderer = Renderer::new(scope.root(&workdir));
+            let failed = unreferenced || !problems.is_empty();
+            for diagnostic in problems {
+                if let Err(error) = renderer.render(&scope.diagnostic(diagnostic), &mut stderr) {
+                    eprintln!("Could not write diagnostics: {error}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            return if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+        Some(Command::Validate(args)) => {
+            let problems = match validate::run(&args) {
+                Ok(problems) => problems,
+                Err(error) => {
+                    eprintln!("Could not validate the manifest: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let mut stderr = anstream::AutoStream::new(io::stderr(), color);
+            let mut renderer = Renderer::new(&args.root);
+            let failed = problems
+                .iter()
+                .any(|problem| problem.severity == Severity::Error);
+            for diagnostic in problems {
+                if let Err(error) = renderer.render(&diagnostic, &mut stderr) {
+                    eprintln!("Could not write diagnostics: {error}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            return if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+        Some(Command::Verify(args)) => {
+            let repository = match git2::Repository::open_from_env() {
+                Ok(repository) => repository,
+                Err(error) => {
+                    eprintln!("Could not open the repository: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let workdir = repository.workdir().unwrap_or(repository.path()).to_owned();
+            let scope = Scope::new(
+                args.path_mode,
+                &workdir,
+                &env::current_dir().unwrap_or_default(),
+            );
+            let config = match Config::discover(&workdir) {
+                Ok(config) => config,
+                Err(error) => {
+                    eprintln!("Could not load {}: {error}", if_changed::CONFIG_FILE_NAME);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let problems = match verify::run(&args, &repository, &config, &scope) {
+                Ok(problems) => problems,
+                Err(error) => {
+                    eprintln!("Could not verify blocks: {error}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let mut stderr = anstream::AutoStream::new(io::stderr(), color);
+            let mut renderer = Renderer::new(scope.root(&workdir));
+            let failed = problems
+                .iter()
+                .any(|problem| problem.severity == Severity::Error);
+            for diagnostic in problems {
+                if let Err(error) = renderer.render(&scope.diagnostic(diagnostic), &mut stderr) {
+                    eprintln!("Could not write diagnostics: {error}");
+                    return ExitCode::FAILURE;
+                }
+            }
+            return if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+        Some(Command::Check(args)) => *args,
+        Some(Command::SuggestIgnore(args)) => {
+            let args = *args;
+            suggestion = Some(suggest::SuggestIgnoreArgs {
+                check: CheckArgs::default(),
+                ..args
+            });
+            args.check
+        }
+        None => cli.check,
+    };
+
+    let mut stderr = anstream::AutoStream::new(io::stderr(), color);
+
     let repository = match git2::Repository::open_from_env() {
         Ok(repository) => repository,
         Err(error) => {
@@ -60,11 +1299,269 @@ fn main() -> ExitCode {
             return ExitCode::FAILURE;
         }
     };
-    for error in run(Cli::parse(), repository) {
-        has_error = true;
-        eprintln!("{error}");
+    let workdir = repository.workdir().unwrap_or(repository.path()).to_owned();
+    let config = match Config::discover(&workdir) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Could not load {}: {error}", if_changed::CONFIG_FILE_NAME);
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = match configure_git(&mut args, config, &repository) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Could not load the git config: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match Owners::discover(&workdir, &config.owners) {
+        Ok(owners) => args.owners = owners,
+        Err(error) => {
+            eprintln!("Could not load the owners of files: {error}");
+            return ExitCode::FAILURE;
+        }
+    }
+    args.configure(&workdir, config);
+    #[cfg(feature = "gerrit")]
+    if let Some(change) = &args.gerrit.gerrit_change {
+        match args.gerrit.fetch(&repository) {
+            Ok(patchset) => {
+                args.from_ref = Some(format!("{}^", patchset.revision));
+                args.to_ref = Some(patchset.revision.clone());
+                args.gerrit.patchset = Some(patchset);
+            }
+            Err(error) => {
+                eprintln!("Could not fetch the Gerrit change {change}: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    #[cfg(feature = "bitbucket")]
+    if let Some(id) = args.bitbucket.bitbucket_pr {
+        match args.bitbucket.fetch(&repository) {
+            Ok(pull_request) => {
+                args.from_ref = Some(pull_request.destination.clone());
+                args.to_ref = Some(pull_request.source.clone());
+                args.merge_base = true;
+                args.ignore.extend(ignore::patterns(
+                    &[],
+                    &pull_request.description,
+                    &args.ignore_labels,
+                ));
+                args.bitbucket.pull_request = Some(pull_request);
+            }
+            Err(error) => {
+                eprintln!("Could not fetch the Bitbucket pull request {id}: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    #[cfg(feature = "azure")]
+    if let Some(id) = args.azure.azure_pr {
+        match args.azure.fetch(&repository) {
+            Ok(pull_request) => {
+                args.from_ref = Some(pull_request.base.clone());
+                args.to_ref = Some(pull_request.source.clone());
+                args.ignore.extend(ignore::patterns(
+                    &pull_request.labels,
+                    &pull_request.description,
+                    &args.ignore_labels,
+                ));
+                args.azure.pull_request = Some(pull_request);
+            }
+            Err(error) => {
+                eprintln!("Could not fetch the Azure Repos pull request {id}: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    #[cfg(feature = "github")]
+    if let Some(pr) = args.github.github_pr {
+        match args.github.pull_request() {
+            Ok(pull_request) => args.ignore.extend(ignore::patterns(
+                &pull_request.labels,
+                &pull_request.description,
+                &args.ignore_labels,
+            )),
+            Err(error) => {
+                eprintln!("Could not fetch the GitHub pull request {pr}: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let scope = Scope::new(
+        args.path_mode,
+        &workdir,
+        &env::current_dir().unwrap_or_default(),
+    );
+    args.patterns = args
+        .patterns
+        .iter()
+        .map(|pattern| {
+            if args.literal_paths {
+                scope.literal(pattern)
+            } else {
+                scope.pattern(pattern)
+            }
+        })
+        .collect();
+
+    if args.watch {
+        return match watch::watch(&args, &repository, &scope, &mut stderr) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("Could not watch the repository: {error}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if let Some(suggestion) = &suggestion {
+        let diagnostics = match run(&args, &repository, &mut Stats::default()) {
+            Ok(diagnostics) => diagnostics.collect::<Vec<_>>(),
+            Err(error) => {
+                eprintln!("Could not compare the revisions: {error}");
+                return ExitCode::FAILURE;
+            }
+        };
+        return match suggest::run(
+            suggestion,
+            &args.trailers(),
+            &repository,
+            &diagnostics,
+            io::stdout(),
+        ) {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => {
+                eprintln!("No files have problems to ignore.");
+                ExitCode::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("Could not suggest a trailer: {error}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if args.interactive {
+        let diagnostics = match run(&args, &repository, &mut Stats::default()) {
+            Ok(diagnostics) => diagnostics.collect::<Vec<_>>(),
+            Err(error) => {
+                eprintln!("Could not compare the revisions: {error}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let mut triage = triage::Triage::new(&args, &repository, &scope);
+        return match triage.run(diagnostics, io::stdin().lock(), &mut stderr) {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => ExitCode::FAILURE,
+            Err(error) => {
+                eprintln!("Could not triage problems: {error}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let start = Instant::now();
+    let mut stats = Stats::default();
+    // Only errors fail the check; warnings are reported only.
+    let failed;
+    if args.audit || args.since.is_some() {
+        let codes = match audit::audit(&args, &repository, &scope, &mut stats, &mut stderr) {
+            Ok(codes) => codes,
+            Err(error) => {
+                eprintln!("Could not audit the history: {error}");
+                return ExitCode::FAILURE;
+            }
+        };
+        if let Err(error) = render::explain_hint(&codes, &mut stderr) {
+            eprintln!("Could not write diagnostics: {error}");
+            return ExitCode::FAILURE;
+        }
+        failed = !codes.is_empty();
+    } else {
+        let engine = match args.engine(&repository) {
+            Ok(engine) => engine,
+            Err(error) => {
+                eprintln!("Could not compare the revisions: {error}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let out: Option<Box<dyn io::Write>> = match (&args.output, args.format) {
+            (Some(path), _) => match fs::File::create(path) {
+                Ok(file) => Some(Box::new(io::BufWriter::new(file))),
+                Err(error) => {
+                    eprintln!("Could not create {}: {error}", path.display());
+                    return ExitCode::FAILURE;
+                }
+            },
+            (None, ReportFormat::Human) => None,
+            (None, _) => Some(Box::new(io::stdout())),
+        };
+        let mut report = out.map(|out| {
+            Report::new(
+                args.format,
+                &workdir,
+                scope.clone(),
+                anstream::StripStream::new(out),
+            )
+            .with_trailer(&args.trailers().keys[0])
+        });
+        if let Some(report) = report.as_mut().filter(|_| args.format.lists_blocks()) {
+            for (path, blame) in modified_blocks(&args, &engine) {
+                report.block(&path, blame);
+            }
+        }
+        let mut terminal = Report::new(ReportFormat::Human, &workdir, scope.clone(), &mut stderr);
+        let mut reporters: Vec<&mut dyn Reporter> = vec![&mut terminal];
+        if let Some(report) = &mut report {
+            reporters.push(report);
+        }
+        #[cfg(feature = "github")]
+        let mut review = args.github.review();
+        #[cfg(feature = "github")]
+        if let Some(review) = &mut review {
+            reporters.push(review);
+        }
+        #[cfg(feature = "github")]
+        let mut checks = args.github.checks();
+        #[cfg(feature = "github")]
+        if let Some(checks) = &mut checks {
+            reporters.push(checks);
+        }
+        #[cfg(feature = "gerrit")]
+        let mut gerrit = args.gerrit.review();
+        #[cfg(feature = "gerrit")]
+        if let Some(gerrit) = &mut gerrit {
+            reporters.push(gerrit);
+        }
+        #[cfg(feature = "bitbucket")]
+        let mut insights = args.bitbucket.insights();
+        #[cfg(feature = "bitbucket")]
+        if let Some(insights) = &mut insights {
+            reporters.push(insights);
+        }
+        #[cfg(feature = "azure")]
+        let mut status = args.azure.status();
+        #[cfg(feature = "azure")]
+        if let Some(status) = &mut status {
+            reporters.push(status);
+        }
+        match check(&args, &engine, &mut stats, &mut Tee(reporters)) {
+            Ok(summary) => failed = !summary.is_success(),
+            Err(error) => {
+                eprintln!("Could not write diagnostics: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(format) = args.stats {
+        if let Err(error) = stats::write(&stats, start.elapsed(), format, &mut stderr) {
+            eprintln!("Could not write statistics: {error}");
+            return ExitCode::FAILURE;
+        }
     }
-    if has_error {
+    if failed {
         ExitCode::FAILURE
     } else {
         ExitCode::SUCCESS
@@ -100,11 +1597,12 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: None,
             to_ref: Some("HEAD".into()),
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @"[]");
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
     }
 
     #[test]
@@ -122,11 +1620,82 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: None,
             to_ref: Some("HEAD".into()),
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 4."]"###);
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 4."]"###);
+    }
+
+    #[test]
+    fn test_run_sorted() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": ["b.ts" => "", "c.ts" => ""]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(
+                    //   c.ts:c,
+                    //   b.ts,
+                    // )
+                "}
+            ]
+        };
+
+        // Unnamed targets are checked before named ones, but reported in
+        // the order they are listed.
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let diagnostics = run(&CheckArgs::default(), &repository, &mut Stats::default())
+            .unwrap()
+            .map(|diagnostic| (diagnostic.line, diagnostic.code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            [
+                (Some(4), Code::UnmodifiedTarget),
+                (Some(5), Code::UnmodifiedTarget)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_literal_paths() {
+        let block = indoc! {"
+            // if-changed
+            A,
+            // then-change(b.ts)
+        "};
+        let (tempdir, _repo) = git_test! {
+            "initial commit": ["b.ts" => ""]
+            working: ["a1.ts" => block, "a[1].ts" => block, "!a.ts" => block]
+        };
+
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let scope = Scope::default();
+        let paths = |patterns: Vec<String>| {
+            run(
+                &CheckArgs {
+                    patterns,
+                    ..Default::default()
+                },
+                &repository,
+                &mut Stats::default(),
+            )
+            .unwrap()
+            .map(|diagnostic| diagnostic.path)
+            .collect::<Vec<_>>()
+        };
+        // As a pattern, `[1]` matches `1` too.
+        assert_eq!(
+            paths(vec![scope.pattern("a[1].ts")]),
+            [PathBuf::from("a1.ts"), PathBuf::from("a[1].ts")]
+        );
+        assert_eq!(
+            paths(vec![scope.literal("a[1].ts"), scope.literal("!a.ts")]),
+            [PathBuf::from("!a.ts"), PathBuf::from("a[1].ts")]
+        );
     }
 
     #[test]
@@ -144,11 +1713,12 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: None,
             to_ref: Some("HEAD".into()),
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @"[]");
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
     }
 
     #[test]
@@ -166,11 +1736,302 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: None,
             to_ref: Some("HEAD".into()),
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @"[]");
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
+    }
+
+    #[test]
+    fn test_run_commit_footer_custom_key() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit\n\nSkip-If-Changed: a.ts": [
+                "a.ts" => indoc! {"
+                    const enum G {
+                        // if-changed
+                        A,
+                        // then-change(b.ts)
+                    }
+                "}
+            ]
+        };
+
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
+            from_ref: None,
+            to_ref: Some("HEAD".into()),
+            patterns: vec![],
+            ignore_trailers: vec!["skip-if-changed".into()],
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
+    }
+
+    #[test]
+    fn test_run_ignore_file() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": [
+                ".ifchangedignore" => "generated/\n",
+                "a.ts" => indoc! {"
+                    // if-changed
+                    const a = 1;
+                    // then-change(generated/b.ts)
+                "},
+                "generated/b.ts" => indoc! {"
+                    // if-changed
+                    const b = 1;
+                    // then-change(/c.ts)
+                "}
+            ]
+            "second commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    const a = 2;
+                    // then-change(generated/b.ts)
+                "},
+                "generated/b.ts" => indoc! {"
+                    // if-changed
+                    const b = 2;
+                    // then-change(/c.ts)
+                "}
+            ]
+            "third commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    const a = 3;
+                    // then-change(generated/b.ts)
+                "}
+            ]
+        };
+
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let args = CheckArgs {
+            from_ref: Some("HEAD~2".into()),
+            to_ref: Some("HEAD".into()),
+            require_excluded_targets: true,
+            ..Default::default()
+        };
+        let mut stats = Stats::default();
+        insta::assert_compact_json_snapshot!(run(&args, &repository, &mut stats).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
+        assert_eq!(stats.ignored_files, 1);
+
+        // Excluded files must still change as targets unless configured.
+        let args = CheckArgs {
+            from_ref: Some("HEAD~1".into()),
+            ..args
+        };
+        insta::assert_compact_json_snapshot!(run(&args, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @r###"["Expected \"generated/b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 3."]"###);
+
+        let args = CheckArgs {
+            require_excluded_targets: false,
+            ..args
+        };
+        insta::assert_compact_json_snapshot!(run(&args, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
+    }
+
+    #[test]
+    fn test_run_commit_footer_without_required_reason() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit\n\nignore-if-changed: a.ts": [
+                "a.ts" => indoc! {"
+                    const enum G {
+                        // if-changed
+                        A,
+                        // then-change(b.ts)
+                    }
+                "}
+            ]
+        };
+
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let diagnostics = run(
+            &CheckArgs {
+                from_ref: None,
+                to_ref: Some("HEAD".into()),
+                patterns: vec![],
+                require_ignore_reason: true,
+                ..Default::default()
+            },
+            &repository,
+            &mut Stats::default(),
+        )
+        .unwrap()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<_>>();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].starts_with("Ignoring \"ignore-if-changed: a.ts\" in commit "));
+        assert_eq!(
+            diagnostics[1],
+            "Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 4."
+        );
+    }
+
+    #[test]
+    fn test_run_fail_fast() {
+        let (tempdir, _repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(c.ts)
+                    // if-changed
+                    B,
+                    // then-change(d.ts)
+                "},
+                "b.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(c.ts)
+                "}
+            ]
+        };
+
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let mut stats = Stats::default();
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
+            fail_fast: true,
+            ..Default::default()
+        }, &repository, &mut stats).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @r###"["Expected \"c.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 3."]"###);
+        assert_eq!(stats.files, 1);
+    }
+
+    #[test]
+    fn test_configure() {
+        let config = Config::parse(indoc! {r#"
+            [ignore-trailer]
+            keys = ["skip"]
+            require-reason = true
+
+            [messages]
+            template = "{message} See https://wiki.example.com/if-changed."
+        "#})
+        .unwrap();
+
+        let mut cli = CheckArgs::default();
+        cli.configure(Path::new("."), config.clone());
+        assert_eq!(cli.trailers(), config.ignore_trailer);
+        assert_eq!(cli.configs, ConfigTree::new(".", &config));
+
+        let mut cli = CheckArgs {
+            ignore_trailers: vec!["other".into()],
+            ..Default::default()
+        };
+        cli.configure(Path::new("."), config);
+        assert_eq!(cli.trailers().keys, ["other"]);
+        assert!(cli.trailers().require_reason);
+
+        assert_eq!(CheckArgs::default().trailers(), TrailerConfig::default());
+    }
+
+    #[test]
+    fn test_env() {
+        let command = Cli::command();
+        let missing = command
+            .get_arguments()
+            .filter(|arg| {
+                arg.get_long()
+                    .is_some_and(|long| !["explain", "verbose", "help", "version"].contains(&long))
+                    && arg.get_env().is_none()
+            })
+            .map(|arg| arg.get_id().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(missing, [] as [&str; 0]);
+    }
+
+    #[test]
+    fn test_configure_git() {
+        let (_tempdir, repo) = git_test! {
+            "Add a": ["a.ts" => ""]
+        };
+        let mut git_config = repo.config().unwrap();
+        git_config
+            .set_str("if-changed.fromRef", "origin/main")
+            .unwrap();
+        git_config
+            .set_i32("if-changed.diffContextLines", 1)
+            .unwrap();
+        git_config.set_str("if-changed.diffFilter", "a").unwrap();
+        git_config
+            .set_str("if-changed.trailer", "Skip-If-Changed")
+            .unwrap();
+
+        let mut cli = CheckArgs {
+            diff_context_lines: Some(5),
+            ..Default::default()
+        };
+        let config = configure_git(&mut cli, Config::default(), &repo).unwrap();
+        assert_eq!(cli.from_ref.as_deref(), Some("origin/main"));
+        assert_eq!(cli.to_ref, None);
+        assert_eq!(cli.diff_context_lines, Some(5));
+        assert_eq!(
+            cli.diff_filter.map(|filter| filter.to_string()).as_deref(),
+            Some("MDRT")
+        );
+        assert_eq!(config.ignore_trailer.keys, ["Skip-If-Changed"]);
+
+        git_config
+            .set_str("if-changed.diffContextLines", "few")
+            .unwrap();
+        insta::assert_snapshot!(configure_git(&mut CheckArgs::default(), Config::default(), &repo).unwrap_err(), @r###"invalid git config `if-changed.diffContextLines` "few": invalid digit found in string"###);
+    }
+
+    #[test]
+    fn test_modified_blocks() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts)
+                    // if-changed(c)
+                    C,
+                    // then-change(c.ts)
+                "}
+            ]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts)
+                    // if-changed(c)
+                    CC,
+                    // then-change(c.ts)
+                "}
+            ]
+        };
+
+        let engine = CheckArgs::default().engine(&repo).unwrap();
+        let blocks = modified_blocks(&CheckArgs::default(), &engine);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].0, PathBuf::from("a.ts"));
+        assert_eq!(blocks[0].1.name.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let filter = |args: &[&str]| {
+            Cli::parse_from(["if-changed"].iter().chain(args))
+                .log_filter()
+                .to_string()
+        };
+        assert_eq!(filter(&["-v"]), "debug");
+        assert_eq!(filter(&["check", "-vv"]), "trace");
+        assert_eq!(filter(&["-vvv", "--log-level", "off"]), "off");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("8k"), Ok(8 << 10));
+        assert_eq!(parse_size("4M"), Ok(4 << 20));
+        assert_eq!(parse_size("1GB"), Ok(1 << 30));
+        assert!(parse_size("4T").is_err());
+        assert!(parse_size("M").is_err());
+        assert_eq!(
+            Cli::parse_from(["if-changed"]).check.max_file_size,
+            Some(4 << 20)
+        );
     }
 
     #[test]
@@ -188,11 +2049,203 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: None,
             to_ref: Some("HEAD".into()),
             patterns: vec!["c.js".to_string()],
-        }, repository).collect::<Vec<_>>(), @"[]");
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
+    }
+
+    #[test]
+    fn test_run_generated_paths() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": ["proto/a.proto" => "A\n", "gen/a.ts" => "A,\n"]
+            working: ["proto/a.proto" => "B\n", "gen/a.ts" => "B,\n"]
+        };
+
+        // Generated files are diffed even if only their generator is given,
+        // as pre-commit does.
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let args = CheckArgs {
+            patterns: vec!["proto/a.proto".to_string()],
+            generated: [("gen/*.ts".to_string(), "proto/*.proto".to_string())].into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            run(&args, &repository, &mut Stats::default())
+                .unwrap()
+                .collect::<Vec<_>>(),
+            []
+        );
+
+        fs::write(tempdir.path().join("gen/a.ts"), "A,\n").unwrap();
+        assert_eq!(
+            run(&args, &repository, &mut Stats::default())
+                .unwrap()
+                .map(|diagnostic| diagnostic.code)
+                .collect::<Vec<_>>(),
+            [Code::UnmodifiedTarget]
+        );
+    }
+
+    #[test]
+    fn test_run_removed_targets_paths() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": [
+                "a.js" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.js, c.js)
+                "},
+                "b.js" => "B,\n",
+                "c.js" => "C,\n"
+            ]
+            working: [
+                "a.js" => indoc! {"
+                    // if-changed
+                    B,
+                    // then-change(b.js)
+                "},
+                "b.js" => "C,\n",
+                "c.js" => "D,\n"
+            ]
+        };
+
+        // Targets removed from the given paths are diffed too.
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let args = CheckArgs {
+            patterns: vec!["a.js".to_string()],
+            removed_targets: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            run(&args, &repository, &mut Stats::default())
+                .unwrap()
+                .collect::<Vec<_>>(),
+            []
+        );
+
+        fs::write(tempdir.path().join("c.js"), "C,\n").unwrap();
+        assert_eq!(
+            run(&args, &repository, &mut Stats::default())
+                .unwrap()
+                .map(|diagnostic| diagnostic.code)
+                .collect::<Vec<_>>(),
+            [Code::RemovedTarget]
+        );
+    }
+
+    #[test]
+    fn test_run_removed_blocks_paths() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": [
+                "a.js" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.js)
+                "},
+                "b.js" => "B,\n"
+            ]
+            working: ["b.js" => "C,\n"]
+        };
+        fs::remove_file(tempdir.path().join("a.js")).unwrap();
+
+        // The targets of the blocks of deleted files are diffed too.
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let args = CheckArgs {
+            patterns: vec!["a.js".to_string()],
+            removed_blocks: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            run(&args, &repository, &mut Stats::default())
+                .unwrap()
+                .collect::<Vec<_>>(),
+            []
+        );
+
+        fs::write(tempdir.path().join("b.js"), "B,\n").unwrap();
+        assert_eq!(
+            run(&args, &repository, &mut Stats::default())
+                .unwrap()
+                .map(|diagnostic| diagnostic.code)
+                .collect::<Vec<_>>(),
+            [Code::UnmodifiedTarget]
+        );
+    }
+
+    #[test]
+    fn test_run_narrowed_paths() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": [
+                "a.js" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.js, c.js)
+                "},
+                "b.js" => "B,\n",
+                "c.js" => "C,\n",
+                "d.js" => indoc! {"
+                    // if-changed
+                    D,
+                    // then-change(e.js)
+                "},
+                "e.js" => "E,\n",
+                "proto/a.proto" => "A\n",
+                "gen/a.ts" => "A,\n"
+            ]
+            working: [
+                "a.js" => indoc! {"
+                    // if-changed
+                    B,
+                    // then-change(b.js)
+                "},
+                "b.js" => "C,\n",
+                "proto/a.proto" => "B\n"
+            ]
+        };
+        fs::remove_file(tempdir.path().join("d.js")).unwrap();
+
+        // Narrowing to the given paths reports the same problems as checking
+        // the whole tree with each option that looks beyond their targets.
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let generated = BTreeMap::from([("gen/*.ts".to_string(), "proto/*.proto".to_string())]);
+        let options = [
+            CheckArgs {
+                removed_targets: true,
+                ..Default::default()
+            },
+            CheckArgs {
+                removed_blocks: true,
+                ..Default::default()
+            },
+            CheckArgs {
+                generated,
+                ..Default::default()
+            },
+        ];
+        let codes = |args: &CheckArgs| {
+            run(args, &repository, &mut Stats::default())
+                .unwrap()
+                .map(|diagnostic| (diagnostic.path, diagnostic.code))
+                .collect::<Vec<_>>()
+        };
+        for args in options {
+            let args = CheckArgs {
+                patterns: vec!["a.js".into(), "d.js".into(), "proto/a.proto".into()],
+                ..args
+            };
+            let narrowed = codes(&args);
+            assert!(!narrowed.is_empty());
+            assert_eq!(
+                narrowed,
+                codes(&CheckArgs {
+                    no_narrow: true,
+                    ..args
+                })
+            );
+        }
     }
 
     #[test]
@@ -217,11 +2270,12 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: None,
             to_ref: None,
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @"[]");
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
     }
 
     #[test]
@@ -239,11 +2293,12 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: None,
             to_ref: None,
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 4."]"###);
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 4."]"###);
     }
 
     #[test]
@@ -286,11 +2341,12 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: Some("HEAD^".into()),
             to_ref: Some("HEAD".into()),
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @"[]");
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @"[]");
     }
 
     #[test]
@@ -325,11 +2381,12 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: Some("HEAD^".into()),
             to_ref: Some("HEAD".into()),
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 5."]"###);
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 5."]"###);
     }
 
     #[test]
@@ -371,10 +2428,11 @@ mod tests {
         };
 
         let repository = git2::Repository::open(tempdir.path()).unwrap();
-        insta::assert_compact_json_snapshot!(run(Cli {
+        insta::assert_compact_json_snapshot!(run(&CheckArgs {
             from_ref: Some("HEAD^".into()),
             to_ref: Some("HEAD".into()),
             patterns: vec![],
-        }, repository).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 5."]"###);
+            ..Default::default()
+        }, &repository, &mut Stats::default()).unwrap().map(|diagnostic| diagnostic.to_string()).collect::<Vec<_>>(), @r###"["Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 5."]"###);
     }
 }
@@ -0,0 +1,92 @@
+/// The info string of fenced code blocks in the description of a pull request
+/// listing files to ignore, one pathspec per line.
+const FENCE_INFO: &str = "ignore-if-changed";
+
+/// Get the patterns of the files a pull request ignores: all files if it has
+/// one of `ignore_labels`, or else those listed in its description.
+///
+/// Files are listed in fenced code blocks, e.g.
+///
+/// ````markdown
+/// ```ignore-if-changed
+/// src/generated/**
+/// # Reformatted only.
+/// docs/api.md
+/// ```
+/// ````
+pub fn patterns(labels: &[String], description: &str, ignore_labels: &[String]) -> Vec<String> {
+    let labeled = labels.iter().any(|label| {
+        ignore_labels
+            .iter()
+            .any(|ignore_label| ignore_label.eq_ignore_ascii_case(label.trim()))
+    });
+    if labeled {
+        tracing::debug!("ignoring all files from a label");
+        return vec!["*".to_owned()];
+    }
+
+    let mut patterns = Vec::new();
+    let mut fence = None;
+    for line in description.lines().map(str::trim) {
+        match fence {
+            None => {
+                let marker = line.len() - line.trim_start_matches(['`', '~']).len();
+                if marker >= 3 && line[marker..].trim() == FENCE_INFO {
+                    fence = Some(&line[..marker]);
+                }
+            }
+            Some(marker)
+                if line.starts_with(marker) && line.trim_matches(['`', '~']).is_empty() =>
+            {
+                fence = None;
+            }
+            Some(_) if line.is_empty() || line.starts_with('#') => {}
+            Some(_) => {
+                tracing::debug!(pattern = line, "ignoring files from a description");
+                patterns.push(line.to_owned());
+            }
+        }
+    }
+    patterns
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        let ignore_labels = ["skip-if-changed".to_owned()];
+        let description = indoc! {"
+            Regenerate the client.
+
+            ```ignore-if-changed
+            src/generated/**
+            # Reformatted only.
+
+            docs/api.md
+            ```
+
+            ```
+            not/ignored.md
+            ```
+
+            ~~~~ ignore-if-changed
+            README.md
+            ~~~~
+        "};
+        insta::assert_debug_snapshot!(patterns(&[], description, &ignore_labels), @r###"
+        [
+            "src/generated/**",
+            "docs/api.md",
+            "README.md",
+        ]
+        "###);
+
+        let labels = ["bug".to_owned(), "Skip-If-Changed".to_owned()];
+        assert_eq!(patterns(&labels, description, &ignore_labels), ["*"]);
+        assert_eq!(patterns(&labels, "", &[]), [] as [&str; 0]);
+    }
+}
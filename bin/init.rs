@@ -0,0 +1,249 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use clap::Args;
+use if_changed::CONFIG_FILE_NAME;
+
+use crate::hook::{self, Hook, HookManager};
+
+/// The configuration written by `init`. Every setting is commented out, so it
+/// is equivalent to the defaults.
+const STARTER_CONFIG: &str = r#"# Configuration for `if-changed`. See https://github.com/mathematic-inc/if-changed.
+
+[ignore-trailer]
+# Commit trailer keys marking files to ignore, compared case-insensitively.
+# keys = ["ignore-if-changed"]
+
+# Only honor trailers giving a reason after `--`, e.g.
+# `ignore-if-changed: lib.rs -- Only comments changed.`
+# require-reason = false
+"#;
+
+/// Number of detected languages to print examples for.
+const MAX_EXAMPLES: usize = 5;
+
+#[derive(Args, Debug, Default)]
+pub struct InitArgs {
+    /// Install a git hook running `if-changed`.
+    #[arg(long, value_enum)]
+    pub hook: Option<Hook>,
+
+    /// Print the configuration running `if-changed` before commits and
+    /// pushes with a hook manager, instead of installing a hook.
+    #[arg(long, value_enum, value_name = "MANAGER", conflicts_with = "hook")]
+    pub hook_manager: Option<HookManager>,
+
+    /// Overwrite an existing configuration file.
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Set up `if-changed` in a repository and print how to use it.
+pub fn run(args: &InitArgs, repository: &git2::Repository, mut out: impl Write) -> io::Result<()> {
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| io::Error::other("bare repos are not supported"))?;
+
+    let config = workdir.join(CONFIG_FILE_NAME);
+    if config.exists() && !args.force {
+        writeln!(out, "Keeping the existing {CONFIG_FILE_NAME}.")?;
+    } else {
+        fs::write(&config, STARTER_CONFIG)?;
+        writeln!(out, "Created {CONFIG_FILE_NAME}.")?;
+    }
+
+    if let Some(hook) = args.hook {
+        hook::install(repository, hook, &mut out)?;
+    }
+    if let Some(manager) = args.hook_manager {
+        writeln!(out)?;
+        hook::configure(manager, &mut out)?;
+    }
+
+    let languages = detect_languages(repository);
+    if !languages.is_empty() {
+        writeln!(
+            out,
+            "\nMark blocks depending on other files with comments, for example:"
+        )?;
+    }
+    for language in languages.into_iter().take(MAX_EXAMPLES) {
+        writeln!(out, "\n{} (.{}):", language.name, language.extension)?;
+        let (start, end) = language.comment;
+        for line in [
+            "if-changed(name)",
+            "...",
+            &format!("then-change(other.{}:name)", language.extension),
+        ] {
+            let line = if end.is_empty() {
+                format!("{start} {line}")
+            } else {
+                format!("{start} {line} {end}")
+            };
+            writeln!(out, "    {line}")?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Language {
+    name: &'static str,
+    extension: &'static str,
+    /// The start and end of a comment. The end is empty for line comments.
+    comment: (&'static str, &'static str),
+}
+
+/// Get the languages of the tracked files, most used first.
+fn detect_languages(repository: &git2::Repository) -> Vec<Language> {
+    let Ok(index) = repository.index() else {
+        return Vec::new();
+    };
+    let mut counts = BTreeMap::<&str, (usize, Language)>::new();
+    for entry in index.iter() {
+        let path = String::from_utf8_lossy(&entry.path).into_owned();
+        let Some(language) = Path::new(&path)
+            .extension()
+            .and_then(|extension| language(&extension.to_string_lossy()))
+        else {
+            continue;
+        };
+        counts.entry(language.name).or_insert((0, language)).0 += 1;
+    }
+    let mut languages = counts.into_values().collect::<Vec<_>>();
+    languages.sort_by(|(a, _), (b, _)| b.cmp(a));
+    languages
+        .into_iter()
+        .map(|(_, language)| language)
+        .collect()
+}
+
+fn language(extension: &str) -> Option<Language> {
+    let (name, extension, comment) = match extension {
+        "c" | "h" => ("C", "c", ("//", "")),
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" => ("C++", "cpp", ("//", "")),
+        "cs" => ("C#", "cs", ("//", "")),
+        "css" => ("CSS", "css", ("/*", "*/")),
+        "dart" => ("Dart", "dart", ("//", "")),
+        "go" => ("Go", "go", ("//", "")),
+        "hs" => ("Haskell", "hs", ("--", "")),
+        "html" | "htm" => ("HTML", "html", ("<!--", "-->")),
+        "java" => ("Java", "java", ("//", "")),
+        "js" | "jsx" | "mjs" | "cjs" => ("JavaScript", "js", ("//", "")),
+        "kt" | "kts" => ("Kotlin", "kt", ("//", "")),
+        "lua" => ("Lua", "lua", ("--", "")),
+        "md" => ("Markdown", "md", ("<!--", "-->")),
+        "php" => ("PHP", "php", ("//", "")),
+        "proto" => ("Protocol Buffers", "proto", ("//", "")),
+        "py" => ("Python", "py", ("#", "")),
+        "rb" => ("Ruby", "rb", ("#", "")),
+        "rs" => ("Rust", "rs", ("//", "")),
+        "scala" => ("Scala", "scala", ("//", "")),
+        "scss" => ("SCSS", "scss", ("//", "")),
+        "sh" | "bash" | "zsh" => ("Shell", "sh", ("#", "")),
+        "sql" => ("SQL", "sql", ("--", "")),
+        "swift" => ("Swift", "swift", ("//", "")),
+        "toml" => ("TOML", "toml", ("#", "")),
+        "ts" | "tsx" | "mts" | "cts" => ("TypeScript", "ts", ("//", "")),
+        "xml" => ("XML", "xml", ("<!--", "-->")),
+        "yaml" | "yml" => ("YAML", "yaml", ("#", "")),
+        _ => return None,
+    };
+    Some(Language {
+        name,
+        extension,
+        comment,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{testing::git_test, Config};
+
+    use super::*;
+
+    #[test]
+    fn test_starter_config() {
+        assert_eq!(Config::parse(STARTER_CONFIG).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_run() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.rs" => "",
+                "b.rs" => "",
+                "c.ts" => "",
+                "d.html" => "",
+                "e.unknown" => ""
+            ]
+        };
+
+        let mut out = Vec::new();
+        run(
+            &InitArgs {
+                hook: Some(Hook::PreCommit),
+                hook_manager: None,
+                force: false,
+            },
+            &repo,
+            &mut out,
+        )
+        .unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        Created .if-changed.toml.
+        Installed .git/hooks/pre-commit.
+
+        Mark blocks depending on other files with comments, for example:
+
+        Rust (.rs):
+            // if-changed(name)
+            // ...
+            // then-change(other.rs:name)
+
+        HTML (.html):
+            <!-- if-changed(name) -->
+            <!-- ... -->
+            <!-- then-change(other.html:name) -->
+
+        TypeScript (.ts):
+            // if-changed(name)
+            // ...
+            // then-change(other.ts:name)
+
+        "###);
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join(CONFIG_FILE_NAME)).unwrap(),
+            STARTER_CONFIG
+        );
+        assert!(repo.path().join("hooks/pre-commit").exists());
+
+        // Existing configuration files are kept unless forced.
+        fs::write(tempdir.path().join(CONFIG_FILE_NAME), "").unwrap();
+        let args = InitArgs {
+            hook: Some(Hook::PreCommit),
+            hook_manager: None,
+            force: false,
+        };
+        run(&args, &repo, io::sink()).unwrap();
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join(CONFIG_FILE_NAME)).unwrap(),
+            ""
+        );
+
+        let args = InitArgs {
+            hook: Some(Hook::PreCommit),
+            hook_manager: None,
+            force: true,
+        };
+        run(&args, &repo, io::sink()).unwrap();
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join(CONFIG_FILE_NAME)).unwrap(),
+            STARTER_CONFIG
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    io::{self, Read as _, Write},
+    path::PathBuf,
+};
+
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+use if_changed::{
+    graph::CouplingGraph, parse_str, Block, Code, Diagnostic, Engine, SCHEMA_VERSION,
+};
+
+use crate::{
+    completions,
+    scope::{PathMode, Scope},
+    stats::StatsFormat,
+};
+
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    /// The files to list the blocks of. By default, all tracked files
+    /// matching `scan` in `.if-changed.toml` are listed.
+    #[arg(add = ArgValueCompleter::new(completions::complete_paths))]
+    pub paths: Vec<String>,
+
+    /// How to interpret paths and print them.
+    #[arg(long, value_enum, default_value_t)]
+    pub path_mode: PathMode,
+
+    /// The format of the list.
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: StatsFormat,
+
+    /// List the named blocks no target refers to, and the files targets refer
+    /// to that have no blocks, instead of all blocks. Fails if there are any.
+    #[arg(long)]
+    pub unreferenced: bool,
+}
+
+/// Get the blocks of each file with any, in the working tree.
+///
+/// Files that can't be read are reported unless `skip_unreadable` is set,
+/// e.g. when listing binary files along with the rest.
+pub fn list(
+    engine: &impl Engine,
+    paths: impl IntoIterator<Item = PathBuf>,
+    skip_unreadable: bool,
+) -> (Vec<(PathBuf, Vec<Block>)>, Vec<Diagnostic>) {
+    let mut files = Vec::new();
+    let mut problems = Vec::new();
+    for path in paths {
+        let mut source = Vec::new();
+        if let Err(error) = engine
+            .read(&path)
+            .and_then(|mut reader| reader.read_to_end(&mut source))
+        {
+            if !skip_unreadable {
+                problems.push(Diagnostic::new(
+                    Code::Unreadable,
+                    &path,
+                    None,
+                    format!("Could not open {path:?}: {error}"),
+                ));
+            }
+            continue;
+        }
+        let mut blocks = Vec::new();
+        for block in parse_str(&path, &String::from_utf8_lossy(&source)) {
+            match block {
+                Ok(block) => blocks.push(block),
+                Err(diagnostics) => problems.extend(diagnostics),
+            }
+        }
+        if !blocks.is_empty() {
+            files.push((path, blocks));
+        }
+    }
+    (files, problems)
+}
+
+/// Write the blocks of each file.
+pub fn write(
+    files: &[(PathBuf, Vec<Block>)],
+    scope: &Scope,
+    format: StatsFormat,
+    mut out: impl Write,
+) -> io::Result<()> {
+    match format {
+        StatsFormat::Text => {
+            for (path, blocks) in files {
+                let path = scope.path(path);
+                for block in blocks {
+                    let (start, end) = block.lines();
+                    match &block.name {
+                        Some(name) => {
+                            writeln!(out, "{}:{start}-{end}: if-changed({name})", path.display())?
+                        }
+                        None => writeln!(out, "{}:{start}-{end}: if-changed", path.display())?,
+                    }
+                    for target in &block.targets {
+                        match &target.name {
+                            Some(name) => writeln!(out, "    {}:{name}", target.path.display())?,
+                            None => writeln!(out, "    {}", target.path.display())?,
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        StatsFormat::Json => {
+            let files = files
+                .iter()
+                .map(|(path, blocks)| {
+                    serde_json::json!({
+                        "path": path.to_string_lossy().replace('\\', "/"),
+                        "blocks": blocks,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let json = serde_json::json!({
+                "version": SCHEMA_VERSION,
+                "files": files,
+            });
+            writeln!(out, "{json}")
+        }
+    }
+}
+
+/// Write the named blocks of `graph` no target refers to, and the files
+/// targets refer to that have no blocks. Returns whether there were any.
+pub fn write_unreferenced(
+    graph: &CouplingGraph,
+    scope: &Scope,
+    format: StatsFormat,
+    mut out: impl Write,
+) -> io::Result<bool> {
+    let orphans = graph.orphans().collect::<Vec<_>>();
+    let blockless = graph.blockless_targets().collect::<Vec<_>>();
+    match format {
+        StatsFormat::Text => {
+            for orphan in &orphans {
+                writeln!(
+                    out,
+                    "{}: no target refers to if-changed({})",
+                    scope.path(&orphan.path).display(),
+                    orphan.name.as_deref().unwrap_or_default()
+                )?;
+            }
+            for path in &blockless {
+                writeln!(
+                    out,
+                    "{}: targets refer to it, but it has no blocks",
+                    scope.path(path).display()
+                )?;
+            }
+        }
+        StatsFormat::Json => {
+            let json = serde_json::json!({
+                "version": SCHEMA_VERSION,
+                "orphans": orphans
+                    .iter()
+                    .map(|orphan| serde_json::json!({
+                        "path": orphan.path.to_string_lossy().replace('\\', "/"),
+                        "name": orphan.name,
+                    }))
+                    .collect::<Vec<_>>(),
+                "blockless_targets": blockless
+                    .iter()
+                    .map(|path| path.to_string_lossy().replace('\\', "/"))
+                    .collect::<Vec<_>>(),
+            });
+            writeln!(out, "{json}")?;
+        }
+    }
+    Ok(!orphans.is_empty() || !blockless.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{testing::git_test, GitEngine};
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_list() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed(a)
+                    A,
+                    // then-change(b.ts:b, /c.ts)
+                    // if-changed
+                    // ignore-if-changed: c.ts
+                    // then-change(c.ts)
+                "},
+                "b.ts" => "// if-changed\n",
+                "c.ts" => ""
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let paths = ["a.ts", "b.ts", "c.ts", "d.ts"].map(PathBuf::from);
+        let (files, problems) = list(&engine, paths, false);
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| (problem.path.to_str().unwrap(), problem.code))
+                .collect::<Vec<_>>(),
+            [
+                ("b.ts", Code::UnterminatedBlock),
+                ("d.ts", Code::Unreadable)
+            ]
+        );
+
+        let mut out = Vec::new();
+        write(&files, &Scope::default(), StatsFormat::Text, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        a.ts:1-3: if-changed(a)
+            b.ts:b
+            /c.ts
+        a.ts:4-6: if-changed
+            c.ts
+
+        "###);
+
+        let mut out = Vec::new();
+        write(&files, &Scope::default(), StatsFormat::Json, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        {"files":[{"blocks":[{"name":"a","range":[1,3],"targets":[{"line":3,"name":"b","path":"b.ts"},{"line":3,"name":null,"path":"/c.ts"}]},{"ignored":["c.ts"],"name":null,"range":[4,6],"targets":[{"line":6,"name":null,"path":"c.ts"}]}],"path":"a.ts"}],"version":1}
+
+        "###);
+    }
+
+    #[test]
+    fn test_write_unreferenced() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => "// if-changed(a)\nA,\n// then-change(b.ts)\n",
+                "b.ts" => "B,\n",
+                "c.ts" => "// if-changed(c)\nC,\n// then-change(a.ts:a)\n"
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let graph = if_changed::graph::build(&engine, ["a.ts", "b.ts", "c.ts"]);
+        let mut out = Vec::new();
+        assert!(
+            write_unreferenced(&graph, &Scope::default(), StatsFormat::Text, &mut out).unwrap()
+        );
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        c.ts: no target refers to if-changed(c)
+        b.ts: targets refer to it, but it has no blocks
+
+        "###);
+
+        let mut out = Vec::new();
+        write_unreferenced(&graph, &Scope::default(), StatsFormat::Json, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        {"blockless_targets":["b.ts"],"orphans":[{"name":"c","path":"c.ts"}],"version":1}
+
+        "###);
+
+        let graph = if_changed::graph::build(&engine, ["b.ts"]);
+        assert!(
+            !write_unreferenced(&graph, &Scope::default(), StatsFormat::Text, io::sink()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,777 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use if_changed::{Diagnostic, ParseCache, Severity, Stats};
+use serde_json::{json, Value};
+
+use crate::{
+    run_engine,
+    scope::{self, PathMode, Scope},
+    CheckArgs,
+};
+
+/// The JSON-RPC error of requests for methods the server doesn't implement.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// An open document, as last sent by the editor.
+struct Document {
+    uri: String,
+    text: String,
+}
+
+/// Answers a language client, e.g. an editor, about the blocks of the files
+/// it opens.
+///
+/// Problems are checked against the working tree when documents are opened
+/// and saved, as the diff only changes then. While a document is edited,
+/// its malformed blocks are reported from the unsaved text.
+pub struct Server<'repo> {
+    /// The options documents are checked with, as configured in
+    /// `.if-changed.toml`.
+    args: CheckArgs,
+    repository: &'repo git2::Repository,
+    workdir: PathBuf,
+    /// The files parsed so far, reused until they change.
+    cache: Arc<ParseCache>,
+    /// The open documents, relative to the repository root.
+    documents: HashMap<PathBuf, Document>,
+    /// The problems of the open documents found by their last check.
+    checked: HashMap<PathBuf, Vec<Diagnostic>>,
+}
+
+impl<'repo> Server<'repo> {
+    pub fn new(args: CheckArgs, repository: &'repo git2::Repository) -> io::Result<Self> {
+        let workdir = repository
+            .workdir()
+            .ok_or_else(|| io::Error::other("bare repositories are not supported"))?
+            .to_owned();
+        Ok(Self {
+            args,
+            repository,
+            workdir,
+            cache: Arc::default(),
+            documents: HashMap::new(),
+            checked: HashMap::new(),
+        })
+    }
+
+    /// Answer the messages read from `input` until an `exit` notification or
+    /// the end of the input, writing responses and notifications to `out`.
+    pub fn serve(&mut self, mut input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        while let Some(message) = read_message(&mut input)? {
+            let method = message["method"].as_str().unwrap_or_default().to_owned();
+            if method == "exit" {
+                break;
+            }
+            let params = &message["params"];
+            let mut outgoing = Vec::new();
+            let result = match method.as_str() {
+                "initialize" => Ok(json!({
+                    "capabilities": {
+                        "textDocumentSync": { "openClose": true, "change": 1, "save": true },
+                        "definitionProvider": true,
+                        "codeActionProvider": { "codeActionKinds": ["quickfix"] },
+                    },
+                    "serverInfo": {
+                        "name": "if-changed",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                })),
+                "shutdown" => Ok(Value::Null),
+                "textDocument/definition" => Ok(self.definition(params)),
+                "textDocument/codeAction" => Ok(self.code_actions(params)),
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+                    if let Some(path) = self.path(&document["uri"]) {
+                        self.documents.insert(
+                            path,
+                            Document {
+                                uri: document["uri"].as_str().unwrap_or_default().to_owned(),
+                                text: document["text"].as_str().unwrap_or_default().to_owned(),
+                            },
+                        );
+                        self.check_all(&mut outgoing);
+                    }
+                    Ok(Value::Null)
+                }
+                "textDocument/didChange" => {
+                    let changes = params["contentChanges"].as_array();
+                    let text = changes.and_then(|changes| changes.last()?["text"].as_str());
+                    if let (Some(path), Some(text)) =
+                        (self.path(&params["textDocument"]["uri"]), text)
+                    {
+                        if let Some(document) = self.documents.get_mut(&path) {
+                            text.clone_into(&mut document.text);
+                            outgoing.push(self.publish(&path));
+                        }
+                    }
+                    Ok(Value::Null)
+                }
+                // Saving a file may satisfy the blocks of other files.
+                "textDocument/didSave" => {
+                    self.check_all(&mut outgoing);
+                    Ok(Value::Null)
+                }
+                "textDocument/didClose" => {
+                    if let Some(path) = self.path(&params["textDocument"]["uri"]) {
+                        if let Some(document) = self.documents.remove(&path) {
+                            self.checked.remove(&path);
+                            outgoing.push(notification(
+                                "textDocument/publishDiagnostics",
+                                json!({ "uri": document.uri, "diagnostics": [] }),
+                            ));
+                        }
+                    }
+                    Ok(Value::Null)
+                }
+                method => Err(format!("Unknown method {method:?}")),
+            };
+            // Notifications have no id and get no response.
+            if let Some(id) = message.get("id") {
+                outgoing.push(match result {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(message) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": METHOD_NOT_FOUND, "message": message },
+                    }),
+                });
+            }
+            for message in outgoing {
+                write_message(&mut out, &message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the path of a document, relative to the repository root, if it is
+    /// inside the repository.
+    fn path(&self, uri: &Value) -> Option<PathBuf> {
+        let path = uri_to_path(uri.as_str()?)?;
+        match path.strip_prefix(&self.workdir) {
+            Ok(path) => Some(path.to_owned()),
+            Err(_) => Some(
+                path.canonicalize()
+                    .ok()?
+                    .strip_prefix(self.workdir.canonicalize().ok()?)
+                    .ok()?
+                    .to_owned(),
+            ),
+        }
+    }
+
+    /// Check the open documents as saved in the working tree and publish
+    /// their problems. Failing checks are shown to the user, keeping the
+    /// server running.
+    fn check_all(&mut self, outgoing: &mut Vec<Value>) {
+        let scope = Scope::new(PathMode::Root, &self.workdir, &self.workdir);
+        let mut paths = self.documents.keys().cloned().collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            let args = CheckArgs {
+                patterns: vec![scope.literal(&path.to_string_lossy())],
+                ..self.args.clone()
+            };
+            let engine = match args.engine(self.repository) {
+                Ok(engine) => engine,
+                Err(error) => {
+                    outgoing.push(notification(
+                        "window/showMessage",
+                        json!({ "type": 1, "message": format!("Could not check {path:?}: {error}") }),
+                    ));
+                    return;
+                }
+            };
+            let diagnostics = run_engine(&args, &engine, self.cache.clone(), &mut Stats::default())
+                .filter(|diagnostic| diagnostic.path == path)
+                .collect();
+            self.checked.insert(path.clone(), diagnostics);
+            outgoing.push(self.publish(&path));
+        }
+    }
+
+    /// Get the problems of an open document: its malformed blocks as
+    /// currently edited, and the other problems of its last check.
+    fn diagnostics(&self, path: &Path) -> Vec<Diagnostic> {
+        let Some(document) = self.documents.get(path) else {
+            return Vec::new();
+        };
+        let mut diagnostics = if_changed::parse_str(path, &document.text)
+            .filter_map(Result::err)
+            .flatten()
+            .collect::<Vec<_>>();
+        diagnostics.extend(
+            self.checked
+                .get(path)
+                .into_iter()
+                .flatten()
+                .filter(|diagnostic| !is_malformed(diagnostic))
+                .cloned(),
+        );
+        diagnostics.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+        diagnostics
+    }
+
+    /// Build the notification publishing the problems of an open document.
+    fn publish(&self, path: &Path) -> Value {
+        let document = &self.documents[path];
+        let diagnostics = self
+            .diagnostics(path)
+            .iter()
+            .map(|diagnostic| {
+                let line = diagnostic.line.unwrap_or(1) - 1;
+                let end = document
+                    .text
+                    .lines()
+                    .nth(line)
+                    .map_or(0, |text| text.encode_utf16().count());
+                json!({
+                    "range": {
+                        "start": { "line": line, "character": 0 },
+                        "end": { "line": line, "character": end },
+                    },
+                    "severity": match diagnostic.severity {
+                        Severity::Error => 1,
+                        Severity::Warning => 2,
+                        Severity::Note => 3,
+                    },
+                    "code": diagnostic.code.as_str(),
+                    "source": "if-changed",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect::<Vec<_>>();
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": document.uri, "diagnostics": diagnostics }),
+        )
+    }
+
+    /// Find the file, or the named block, a `then-change` target under the
+    /// cursor refers to.
+    fn definition(&self, params: &Value) -> Value {
+        let Some(path) = self.path(&params["textDocument"]["uri"]) else {
+            return Value::Null;
+        };
+        let Some(document) = self.documents.get(&path) else {
+            return Value::Null;
+        };
+        let line = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+        let character = params["position"]["character"].as_u64().unwrap_or_default() as usize;
+        let Some(text) = document.text.lines().nth(line) else {
+            return Value::Null;
+        };
+        // Several targets may be listed on a line, so the one written around
+        // the cursor is preferred.
+        let targets = if_changed::parse_str(&path, &document.text)
+            .filter_map(Result::ok)
+            .flat_map(|block| block.targets)
+            .filter(|target| target.line == line + 1)
+            .collect::<Vec<_>>();
+        let under_cursor = targets.iter().find(|target| {
+            let mut written = target.path.to_string_lossy().into_owned();
+            if let Some(name) = &target.name {
+                written = format!("{written}:{name}");
+            }
+            text.match_indices(&written).any(|(start, _)| {
+                let start = text[..start].encode_utf16().count();
+                (start..=start + written.encode_utf16().count()).contains(&character)
+            })
+        });
+        let Some(target) = under_cursor.or(targets.first()) else {
+            return Value::Null;
+        };
+        let Some(file) = resolve(&path, &target.path) else {
+            return Value::Null;
+        };
+        if !self.workdir.join(&file).is_file() {
+            return Value::Null;
+        }
+        let line = match &target.name {
+            Some(name) => {
+                let blocks = match self.documents.get(&file) {
+                    Some(document) => if_changed::parse_str(&file, &document.text)
+                        .filter_map(Result::ok)
+                        .collect::<Vec<_>>(),
+                    None => if_changed::parse_file(self.workdir.join(&file))
+                        .map(|blocks| blocks.filter_map(Result::ok).collect())
+                        .unwrap_or_default(),
+                };
+                match blocks
+                    .iter()
+                    .find(|block| block.name.as_ref() == Some(name))
+                {
+                    Some(block) => block.range.0 - 1,
+                    None => return Value::Null,
+                }
+            }
+            None => 0,
+        };
+        let position = json!({ "line": line, "character": 0 });
+        json!({
+            "uri": path_to_uri(&self.workdir.join(file)),
+            "range": { "start": position, "end": position },
+        })
+    }
+
+    /// Offer the suggested fixes of the problems on the lines of a range.
+    fn code_actions(&self, params: &Value) -> Value {
+        let Some(path) = self.path(&params["textDocument"]["uri"]) else {
+            return json!([]);
+        };
+        let line = |position: &Value| position["line"].as_u64().unwrap_or_default() as usize + 1;
+        let range = &params["range"];
+        let lines = line(&range["start"])..=line(&range["end"]);
+        let actions = self
+            .diagnostics(&path)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.line.is_some_and(|line| lines.contains(&line)))
+            .filter_map(|diagnostic| {
+                let suggestion = diagnostic.suggestion?;
+                let position = json!({ "line": suggestion.line - 1, "character": 0 });
+                let uri = path_to_uri(&self.workdir.join(&suggestion.path));
+                Some(json!({
+                    "title": suggestion.message,
+                    "kind": "quickfix",
+                    "edit": {
+                        "changes": {
+                            uri: [{
+                                "range": { "start": position, "end": position },
+                                "newText": suggestion.text,
+                            }],
+                        },
+                    },
+                }))
+            })
+            .collect::<Vec<_>>();
+        json!(actions)
+    }
+}
+
+/// Whether a problem is a malformed block, found by parsing the file alone.
+fn is_malformed(diagnostic: &Diagnostic) -> bool {
+    diagnostic.code.as_str().starts_with("IC1")
+}
+
+/// Resolve a target written in `path` to a path relative to the repository
+/// root, as the check does, or `None` if it points outside of it.
+fn resolve(path: &Path, target: &Path) -> Option<PathBuf> {
+    let target = if target.as_os_str().is_empty() {
+        path.to_owned()
+    } else if let Ok(target) = target.strip_prefix("/") {
+        target.to_owned()
+    } else {
+        path.parent()?.join(target)
+    };
+    let target = scope::normalize(&target);
+    match target.components().next() {
+        Some(Component::Normal(_)) => Some(target),
+        _ => None,
+    }
+}
+
+/// Read a message framed by a `Content-Length` header, or `None` at the end
+/// of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Write a message framed by a `Content-Length` header.
+fn write_message(out: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    out.flush()
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Get the path of a `file:` URI.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut index = 0;
+    while index < path.len() {
+        let hex = path
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (path[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // Windows paths are written as `file:///C:/...`.
+    if cfg!(windows) {
+        return Some(PathBuf::from(path.strip_prefix('/').unwrap_or(&path)));
+    }
+    Some(PathBuf::from(path))
+}
+
+/// Get the `file:` URI of an absolute path.
+fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+    use indoc::indoc;
+
+    use super::*;
+
+    /// Frame messages as a client sends them.
+    fn input(messages: &[Value]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        input
+    }
+
+    #[test]
+    fn test_uri() {
+        let path = Path::new("/a b/c[1].ts");
+        assert_eq!(path_to_uri(path), "file:///a%20b/c%5B1%5D.ts");
+        assert_eq!(uri_to_path(&path_to_uri(path)).unwrap(), path);
+        assert_eq!(uri_to_path("untitled:1"), None);
+    }
+
+    #[test]
+    fn test_server() {
+        let (tempdir, _repo) = git_test! {
+            "initial commit": [
+                "a.ts" => "A,\n",
+                "b.ts" => indoc! {"
+                    B,
+                    // if-changed(b)
+                    B,
+                    // then-change(a.ts)
+                "}
+            ]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A, B,
+                    // then-change(b.ts:b, c.ts:c)
+                    // if-changed
+                "},
+                "c.ts" => "C,\n"
+            ]
+        };
+
+        let repository = git2::Repository::open(tempdir.path()).unwrap();
+        let mut server = Server::new(CheckArgs::default(), &repository).unwrap();
+        let root = path_to_uri(&server.workdir);
+        let uri = format!("{root}a.ts");
+        let text = std::fs::read_to_string(tempdir.path().join("a.ts")).unwrap();
+        let input = input(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": { "uri": uri, "languageId": "typescript", "version": 1, "text": text },
+            }}),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": 2, "character": 17 },
+            }}),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/definition", "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": 2, "character": 24 },
+            }}),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "textDocument/codeAction", "params": {
+                "textDocument": { "uri": uri },
+                "range": { "start": { "line": 2, "character": 0 }, "end": { "line": 3, "character": 0 } },
+                "context": { "diagnostics": [] },
+            }}),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": { "uri": uri, "version": 2 },
+                // The unterminated block is removed before saving.
+                "contentChanges": [{ "text": text.strip_suffix("// if-changed\n").unwrap() }],
+            }}),
+            json!({ "jsonrpc": "2.0", "id": 5, "method": "textDocument/hover", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didClose", "params": {
+                "textDocument": { "uri": uri },
+            }}),
+            json!({ "jsonrpc": "2.0", "id": 6, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+            json!({ "jsonrpc": "2.0", "id": 7, "method": "shutdown" }),
+        ]);
+        let mut out = Vec::new();
+        server.serve(input.as_slice(), &mut out).unwrap();
+
+        let mut out = out.as_slice();
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut out).unwrap() {
+            let message = message.to_string().replace(&root, "file:///root/");
+            messages.push(serde_json::from_str::<Value>(&message).unwrap());
+        }
+        messages[0]["result"]["serverInfo"]["version"] = json!("[version]");
+        insta::assert_json_snapshot!(messages, @r###"
+        [
+          {
+            "id": 1,
+            "jsonrpc": "2.0",
+            "result": {
+              "capabilities": {
+                "codeActionProvider": {
+                  "codeActionKinds": [
+                    "quickfix"
+                  ]
+                },
+                "definitionProvider": true,
+                "textDocumentSync": {
+                  "change": 1,
+                  "openClose": true,
+                  "save": true
+                }
+              },
+              "serverInfo": {
+                "name": "if-changed",
+                "version": "[version]"
+              }
+            }
+          },
+          {
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {
+              "diagnostics": [
+                {
+                  "code": "IC002",
+                  "message": "Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 3.",
+                  "range": {
+                    "end": {
+                      "character": 30,
+                      "line": 2
+                    },
+                    "start": {
+                      "character": 0,
+                      "line": 2
+                    }
+                  },
+                  "severity": 1,
+                  "source": "if-changed"
+                },
+                {
+                  "code": "IC003",
+                  "message": "Could not find \"if-changed\" with name \"c\" in \"c.ts\" for \"then-change\" in \"a.ts\" at line 3.",
+                  "range": {
+                    "end": {
+                      "character": 30,
+                      "line": 2
+                    },
+                    "start": {
+                      "character": 0,
+                      "line": 2
+                    }
+                  },
+                  "severity": 1,
+                  "source": "if-changed"
+                },
+                {
+                  "code": "IC101",
+                  "message": "Missing \"then-changed\" for \"if-changed\" at line 4 for \"a.ts\".",
+                  "range": {
+                    "end": {
+                      "character": 13,
+                      "line": 3
+                    },
+                    "start": {
+                      "character": 0,
+                      "line": 3
+                    }
+                  },
+                  "severity": 1,
+                  "source": "if-changed"
+                }
+              ],
+              "uri": "file:///root/a.ts"
+            }
+          },
+          {
+            "id": 2,
+            "jsonrpc": "2.0",
+            "result": {
+              "range": {
+                "end": {
+                  "character": 0,
+                  "line": 1
+                },
+                "start": {
+                  "character": 0,
+                  "line": 1
+                }
+              },
+              "uri": "file:///root/b.ts"
+            }
+          },
+          {
+            "id": 3,
+            "jsonrpc": "2.0",
+            "result": null
+          },
+          {
+            "id": 4,
+            "jsonrpc": "2.0",
+            "result": [
+              {
+                "edit": {
+                  "changes": {
+                    "file:///root/c.ts": [
+                      {
+                        "newText": "// if-changed(c)\n// then-change(/a.ts)\n",
+                        "range": {
+                          "end": {
+                            "character": 0,
+                            "line": 1
+                          },
+                          "start": {
+                            "character": 0,
+                            "line": 1
+                          }
+                        }
+                      }
+                    ]
+                  }
+                },
+                "kind": "quickfix",
+                "title": "add `// if-changed(c)` to \"c.ts\" around the lines to change with \"a.ts\""
+              },
+              {
+                "edit": {
+                  "changes": {
+                    "file:///root/a.ts": [
+                      {
+                        "newText": "// then-change()\n",
+                        "range": {
+                          "end": {
+                            "character": 0,
+                            "line": 4
+                          },
+                          "start": {
+                            "character": 0,
+                            "line": 4
+                          }
+                        }
+                      }
+                    ]
+                  }
+                },
+                "kind": "quickfix",
+                "title": "add `// then-change()` after the end of the block, listing the files to change with it"
+              }
+            ]
+          },
+          {
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {
+              "diagnostics": [
+                {
+                  "code": "IC002",
+                  "message": "Expected \"b.ts\" to be modified because of \"then-change\" in \"a.ts\" at line 3.",
+                  "range": {
+                    "end": {
+                      "character": 30,
+                      "line": 2
+                    },
+                    "start": {
+                      "character": 0,
+                      "line": 2
+                    }
+                  },
+                  "severity": 1,
+                  "source": "if-changed"
+                },
+                {
+                  "code": "IC003",
+                  "message": "Could not find \"if-changed\" with name \"c\" in \"c.ts\" for \"then-change\" in \"a.ts\" at line 3.",
+                  "range": {
+                    "end": {
+                      "character": 30,
+                      "line": 2
+                    },
+                    "start": {
+                      "character": 0,
+                      "line": 2
+                    }
+                  },
+                  "severity": 1,
+                  "source": "if-changed"
+                }
+              ],
+              "uri": "file:///root/a.ts"
+            }
+          },
+          {
+            "error": {
+              "code": -32601,
+              "message": "Unknown method \"textDocument/hover\""
+            },
+            "id": 5,
+            "jsonrpc": "2.0"
+          },
+          {
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {
+              "diagnostics": [],
+              "uri": "file:///root/a.ts"
+            }
+          },
+          {
+            "id": 6,
+            "jsonrpc": "2.0",
+            "result": null
+          }
+        ]
+        "###);
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+use clap::Args;
+use if_changed::{Config, LegacyFormat};
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    /// The legacy format to migrate from: `lint-ifchange`, or the name of a
+    /// format configured under `[[migrate.formats]]` in `.if-changed.toml`.
+    #[arg(long, value_name = "FORMAT")]
+    pub from: String,
+
+    /// Print a diff of the changes instead of writing them.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Get the formats that can be migrated from without configuration.
+fn builtin_formats() -> Vec<LegacyFormat> {
+    vec![LegacyFormat {
+        name: "lint-ifchange".to_owned(),
+        if_changed: "LINT.IfChange".to_owned(),
+        then_change: "LINT.ThenChange".to_owned(),
+        root_prefix: Some("//".to_owned()),
+    }]
+}
+
+/// Rewrite legacy annotations in all tracked files into `if-changed` blocks.
+pub fn run(
+    args: &MigrateArgs,
+    repository: &git2::Repository,
+    config: &Config,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| io::Error::other("bare repos are not supported"))?;
+    let formats = builtin_formats()
+        .into_iter()
+        .chain(config.migrate.formats.iter().cloned())
+        .collect::<Vec<_>>();
+    let format = formats
+        .iter()
+        .find(|format| format.name == args.from)
+        .ok_or_else(|| {
+            io::Error::other(format!(
+                "unknown format {:?}; expected one of {}",
+                args.from,
+                formats
+                    .iter()
+                    .map(|format| format!("{:?}", format.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })?;
+
+    let mut migrated = 0;
+    for entry in repository.index().map_err(io::Error::other)?.iter() {
+        let Ok(path) = String::from_utf8(entry.path) else {
+            continue;
+        };
+        // Skip files that aren't text.
+        let Ok(source) = fs::read_to_string(workdir.join(&path)) else {
+            continue;
+        };
+        let Some(target) = migrate(&source, format) else {
+            continue;
+        };
+        migrated += 1;
+        if args.dry_run {
+            write_diff(&path, &source, &target, &mut out)?;
+        } else {
+            fs::write(workdir.join(&path), target)?;
+        }
+    }
+    writeln!(
+        out,
+        "{} {migrated} file(s).",
+        if args.dry_run {
+            "Would migrate"
+        } else {
+            "Migrated"
+        }
+    )
+}
+
+/// Rewrite the legacy annotations of a file, or `None` if there are none.
+///
+/// Lines are rewritten in place, so comment styles and line numbers are kept.
+fn migrate(source: &str, format: &LegacyFormat) -> Option<String> {
+    if !source.contains(&format.if_changed) && !source.contains(&format.then_change) {
+        return None;
+    }
+    let mut list_prefix = None;
+    let target = source
+        .split_inclusive('\n')
+        .map(|line| {
+            let (content, newline) = split_newline(line);
+            format!(
+                "{}{newline}",
+                migrate_line(content, format, &mut list_prefix)
+            )
+        })
+        .collect::<String>();
+    (target != source).then_some(target)
+}
+
+/// Rewrite one line. `list_prefix` holds the comment prefix of a path list
+/// continuing on the next lines.
+fn migrate_line(line: &str, format: &LegacyFormat, list_prefix: &mut Option<String>) -> String {
+    let root_prefix = format.root_prefix.as_deref();
+    if let Some(prefix) = list_prefix.as_deref() {
+        let trimmed = line.trim_start();
+        let body = trimmed.strip_prefix(prefix).unwrap_or(trimmed);
+        let head = &line[..line.len() - body.len()];
+        let (list, tail) = match body.find(')') {
+            Some(index) => {
+                *list_prefix = None;
+                body.split_at(index)
+            }
+            None => (body, ""),
+        };
+        return format!("{head}{}{tail}", rewrite_paths(list, root_prefix));
+    }
+
+    let (before, after) = match line.find(&format.then_change) {
+        Some(index) => {
+            let args = &line[index + format.then_change.len()..];
+            let after = match args.strip_prefix('(') {
+                Some(args) => {
+                    let (list, tail) = match args.find(')') {
+                        Some(index) => args.split_at(index),
+                        None => {
+                            *list_prefix = Some(line[..index].trim().to_owned());
+                            (args, "")
+                        }
+                    };
+                    format!("then-change({}{tail}", rewrite_paths(list, root_prefix))
+                }
+                None => format!("then-change{args}"),
+            };
+            (&line[..index], after)
+        }
+        None => (line, String::new()),
+    };
+    format!(
+        "{}{after}",
+        before.replacen(&format.if_changed, "if-changed", 1)
+    )
+}
+
+/// Replace the root prefix of each comma-separated path with `/`.
+fn rewrite_paths(list: &str, root_prefix: Option<&str>) -> String {
+    list.split(',')
+        .map(|path| {
+            let trimmed = path.trim_start();
+            match root_prefix.and_then(|prefix| trimmed.strip_prefix(prefix)) {
+                Some(rest) => format!("{}/{rest}", &path[..path.len() - trimmed.len()]),
+                None => path.to_owned(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split_newline(line: &str) -> (&str, &str) {
+    if let Some(content) = line.strip_suffix("\r\n") {
+        (content, "\r\n")
+    } else if let Some(content) = line.strip_suffix('\n') {
+        (content, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+/// Write a unified diff without context between two versions of a file with
+/// the same number of lines.
+pub fn write_diff(path: &str, source: &str, target: &str, mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "--- a/{path}")?;
+    writeln!(out, "+++ b/{path}")?;
+    let lines = source.lines().zip(target.lines()).collect::<Vec<_>>();
+    let mut index = 0;
+    while index < lines.len() {
+        if lines[index].0 == lines[index].1 {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < lines.len() && lines[index].0 != lines[index].1 {
+            index += 1;
+        }
+        let count = index - start;
+        writeln!(out, "@@ -{0},{count} +{0},{count} @@", start + 1)?;
+        for (old, _) in &lines[start..index] {
+            writeln!(out, "-{old}")?;
+        }
+        for (_, new) in &lines[start..index] {
+            writeln!(out, "+{new}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lint() -> LegacyFormat {
+        builtin_formats().swap_remove(0)
+    }
+
+    #[test]
+    fn test_migrate() {
+        insta::assert_snapshot!(migrate(indoc! {"
+            // LINT.IfChange
+            enum A {}
+            // LINT.ThenChange(//src/a.ts, b.ts)
+
+            # LINT.IfChange(b)
+            B = 1
+            # LINT.ThenChange(
+            #     //src/b.rs:b,
+            #     :c,
+            # )
+
+            <!-- LINT.IfChange --><p></p><!-- LINT.ThenChange(//c.html) -->
+        "}, &lint()).unwrap(), @r###"
+        // if-changed
+        enum A {}
+        // then-change(/src/a.ts, b.ts)
+
+        # if-changed(b)
+        B = 1
+        # then-change(
+        #     /src/b.rs:b,
+        #     :c,
+        # )
+
+        <!-- if-changed --><p></p><!-- then-change(/c.html) -->
+
+        "###);
+        assert_eq!(
+            migrate("// if-changed\n// then-change(a.ts)\n", &lint()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_migrate_custom_format() {
+        let format = LegacyFormat {
+            name: "sync".into(),
+            if_changed: "SYNC".into(),
+            then_change: "SYNC.End".into(),
+            root_prefix: None,
+        };
+        assert_eq!(
+            migrate("-- SYNC(a)\r\nA\r\n-- SYNC.End(a.sql)\r\n", &format).as_deref(),
+            Some("-- if-changed(a)\r\nA\r\n-- then-change(a.sql)\r\n")
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.rs" => indoc! {"
+                    // LINT.IfChange
+                    enum A {}
+                    // LINT.ThenChange(//b.ts)
+                "},
+                "b.ts" => "const a = 1;\n"
+            ]
+        };
+
+        let mut out = Vec::new();
+        let args = MigrateArgs {
+            from: "lint-ifchange".into(),
+            dry_run: true,
+        };
+        run(&args, &repo, &Config::default(), &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        --- a/a.rs
+        +++ b/a.rs
+        @@ -1,1 +1,1 @@
+        -// LINT.IfChange
+        +// if-changed
+        @@ -3,1 +3,1 @@
+        -// LINT.ThenChange(//b.ts)
+        +// then-change(/b.ts)
+        Would migrate 1 file(s).
+
+        "###);
+        assert!(fs::read_to_string(tempdir.path().join("a.rs"))
+            .unwrap()
+            .contains("LINT.IfChange"));
+
+        let args = MigrateArgs {
+            from: "lint-ifchange".into(),
+            dry_run: false,
+        };
+        run(&args, &repo, &Config::default(), io::sink()).unwrap();
+        insta::assert_snapshot!(fs::read_to_string(tempdir.path().join("a.rs")).unwrap(), @r###"
+        // if-changed
+        enum A {}
+        // then-change(/b.ts)
+
+        "###);
+
+        let args = MigrateArgs {
+            from: "unknown".into(),
+            dry_run: false,
+        };
+        insta::assert_snapshot!(run(&args, &repo, &Config::default(), io::sink()).unwrap_err(), @r###"unknown format "unknown"; expected one of "lint-ifchange""###);
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+
+use crate::{
+    completions,
+    fix::{replace_path, resolve, rewrite_targets},
+    migrate::write_diff,
+    scope::{PathMode, Scope},
+};
+
+#[derive(Args, Debug)]
+pub struct RenameArgs {
+    /// The old path of a file or directory, or the old name of blocks with
+    /// `--block`.
+    #[arg(add = ArgValueCompleter::new(completions::complete_paths))]
+    pub old: String,
+
+    /// The new path of the file or directory, or the new name of the blocks.
+    pub new: String,
+
+    /// Rename the blocks named OLD and the targets naming them instead.
+    #[arg(long)]
+    pub block: bool,
+
+    /// Only rename the blocks of this file.
+    #[arg(long, requires = "block", value_name = "PATH", add = ArgValueCompleter::new(completions::complete_paths))]
+    pub file: Option<String>,
+
+    /// How to interpret paths.
+    #[arg(long, value_enum, default_value_t)]
+    pub path_mode: PathMode,
+
+    /// Print a diff of the changes instead of writing them.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Rewrite the blocks and targets of all tracked files referring to the old
+/// path or block name of `args`.
+///
+/// Files themselves aren't moved, e.g. to run before or after `git mv`.
+pub fn run(
+    args: &RenameArgs,
+    repository: &git2::Repository,
+    scope: &Scope,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| io::Error::other("bare repos are not supported"))?;
+    let path = |path: &str| {
+        let path = scope.pattern(path);
+        PathBuf::from(path.strip_prefix('/').unwrap_or(&path))
+    };
+    let file = args.file.as_deref().map(path);
+    let (old, new) = (path(&args.old), path(&args.new));
+    if args.block && !is_block_name(&args.new) {
+        return Err(io::Error::other(format!(
+            "invalid block name {:?}; names can't be empty or contain spaces, `(`, `)`, `,` or `:`",
+            args.new
+        )));
+    }
+
+    let mut renamed = 0;
+    for entry in repository.index().map_err(io::Error::other)?.iter() {
+        let Ok(entry_path) = String::from_utf8(entry.path) else {
+            continue;
+        };
+        // Skip files that aren't text.
+        let Ok(source) = fs::read_to_string(workdir.join(&entry_path)) else {
+            continue;
+        };
+        let entry_path = Path::new(&entry_path);
+        let target = if args.block {
+            rename_blocks(entry_path, &source, &args.old, &args.new, file.as_deref())
+        } else {
+            rewrite_targets(entry_path, entry_path, &source, |target| {
+                let rest = target.strip_prefix(&old).ok()?;
+                Some(if rest.as_os_str().is_empty() {
+                    new.clone()
+                } else {
+                    new.join(rest)
+                })
+            })
+        };
+        let Some(target) = target else {
+            continue;
+        };
+        renamed += 1;
+        let entry_path = entry_path.to_string_lossy();
+        if args.dry_run {
+            write_diff(&entry_path, &source, &target, &mut out)?;
+        } else {
+            fs::write(workdir.join(&*entry_path), target)?;
+        }
+    }
+    writeln!(
+        out,
+        "{} {renamed} file(s).",
+        if args.dry_run {
+            "Would update"
+        } else {
+            "Updated"
+        }
+    )
+}
+
+/// Rename the blocks of `path` named `old` to `new`, and the targets naming
+/// them, or `None` if there are none. With `file`, only the blocks of `file`
+/// and the targets resolving to it are renamed.
+fn rename_blocks(
+    path: &Path,
+    source: &str,
+    old: &str,
+    new: &str,
+    file: Option<&Path>,
+) -> Option<String> {
+    let mut lines = source
+        .split_inclusive('\n')
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    for block in if_changed::parse_str(path, source).flatten() {
+        if block.name.as_deref() == Some(old) && file.is_none_or(|file| file == path) {
+            if let Some(line) = lines.get_mut(block.range.0 - 1) {
+                if let Some(index) = line.find("if-changed") {
+                    let (head, rest) = line.split_at(index);
+                    if let Some(rest) = replace_path(rest, old, new) {
+                        *line = format!("{head}{rest}");
+                    }
+                }
+            }
+        }
+        for pattern in block.targets {
+            if pattern.name.as_deref() != Some(old) {
+                continue;
+            }
+            let written = pattern.path.to_string_lossy();
+            let target = if written.is_empty() {
+                Some(path.to_owned())
+            } else {
+                resolve(path, &written)
+            };
+            if file.is_some_and(|file| target.as_deref() != Some(file)) {
+                continue;
+            }
+            let Some(line) = lines.get_mut(pattern.line - 1) else {
+                continue;
+            };
+            if let Some(replaced) = replace_path(
+                line,
+                &format!("{written}:{old}"),
+                &format!("{written}:{new}"),
+            ) {
+                *line = replaced;
+            }
+        }
+    }
+    let target = lines.concat();
+    (target != source).then_some(target)
+}
+
+/// Check if `name` can be written as the name of a block.
+fn is_block_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | ',' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::testing::git_test;
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_rename_blocks() {
+        let source = indoc! {"
+            // if-changed(old)
+            A,
+            // then-change(b.ts:old, /c.ts:old, :other, d.ts:older)
+            // if-changed(other)
+            B,
+            // then-change(:old)
+        "};
+        insta::assert_snapshot!(rename_blocks(Path::new("a.ts"), source, "old", "new", None).unwrap(), @r###"
+        // if-changed(new)
+        A,
+        // then-change(b.ts:new, /c.ts:new, :other, d.ts:older)
+        // if-changed(other)
+        B,
+        // then-change(:new)
+        "###);
+        insta::assert_snapshot!(rename_blocks(Path::new("a.ts"), source, "old", "new", Some(Path::new("c.ts"))).unwrap(), @r###"
+        // if-changed(old)
+        A,
+        // then-change(b.ts:old, /c.ts:new, :other, d.ts:older)
+        // if-changed(other)
+        B,
+        // then-change(:old)
+        "###);
+        assert_eq!(
+            rename_blocks(
+                Path::new("a.ts"),
+                source,
+                "old",
+                "new",
+                Some(Path::new("e.ts"))
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_block_name() {
+        assert!(is_block_name("serializer"));
+        assert!(is_block_name("a-b.c"));
+        assert!(!is_block_name(""));
+        assert!(!is_block_name("a b"));
+        assert!(!is_block_name("a:b"));
+    }
+
+    #[test]
+    fn test_run() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => "// if-changed(a)\n// then-change(src/b.ts, /src/api/c.ts:c)\n",
+                "src/b.ts" => "// if-changed\n// then-change(api/c.ts, ../a.ts:a)\n",
+                "src/api/c.ts" => "// if-changed(c)\n// then-change(../b.ts)\n"
+            ]
+        };
+        let mut args = RenameArgs {
+            old: "src/api".into(),
+            new: "lib/api".into(),
+            block: false,
+            file: None,
+            path_mode: PathMode::Root,
+            dry_run: true,
+        };
+        let scope = Scope::new(PathMode::Root, tempdir.path(), tempdir.path());
+        let mut out = Vec::new();
+        run(&args, &repo, &scope, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        --- a/a.ts
+        +++ b/a.ts
+        @@ -2,1 +2,1 @@
+        -// then-change(src/b.ts, /src/api/c.ts:c)
+        +// then-change(src/b.ts, /lib/api/c.ts:c)
+        --- a/src/b.ts
+        +++ b/src/b.ts
+        @@ -2,1 +2,1 @@
+        -// then-change(api/c.ts, ../a.ts:a)
+        +// then-change(../lib/api/c.ts, ../a.ts:a)
+        Would update 2 file(s).
+        "###);
+
+        args = RenameArgs {
+            old: "c".into(),
+            new: "core".into(),
+            block: true,
+            file: Some("src/api/c.ts".into()),
+            dry_run: false,
+            ..args
+        };
+        let mut out = Vec::new();
+        run(&args, &repo, &scope, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @"Updated 2 file(s).");
+        assert_eq!(
+            fs::read_to_string(tempdir.path().join("src/api/c.ts")).unwrap(),
+            "// if-changed(core)\n// then-change(../b.ts)\n"
+        );
+
+        args.new = "a b".into();
+        insta::assert_snapshot!(run(&args, &repo, &scope, io::sink()).unwrap_err(), @r###"invalid block name "a b"; names can't be empty or contain spaces, `(`, `)`, `,` or `:`"###);
+    }
+}
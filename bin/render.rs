@@ -0,0 +1,465 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anstyle::{AnsiColor, Style};
+use if_changed::{Code, Diagnostic, Severity};
+
+const ERROR: Style = AnsiColor::Red.on_default().bold();
+const WARNING: Style = AnsiColor::Yellow.on_default().bold();
+const NOTE: Style = AnsiColor::Cyan.on_default().bold();
+const EMPHASIS: Style = Style::new().bold();
+const GUTTER: Style = AnsiColor::Blue.on_default().bold();
+const ADDED: Style = AnsiColor::Green.on_default();
+const REMOVED: Style = AnsiColor::Red.on_default();
+
+/// Number of lines kept at each end of a block when eliding its middle.
+const FRAME_CONTEXT: usize = 2;
+
+/// Number of changed lines shown before eliding the rest.
+const MAX_CHANGES: usize = 6;
+
+/// Reads a source given its path as printed.
+type Reader<'a> = Box<dyn FnMut(&Path) -> Option<String> + 'a>;
+
+/// Renders diagnostics with frames of the source they refer to.
+pub struct Renderer<'a> {
+    read: Reader<'a>,
+    sources: HashMap<PathBuf, Option<Vec<String>>>,
+}
+
+impl Renderer<'static> {
+    /// Create a renderer reading sources relative to `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self::with_reader(move |path| fs::read_to_string(root.join(path)).ok())
+    }
+}
+
+impl<'a> Renderer<'a> {
+    /// Create a renderer reading sources with `read`, e.g. from a commit.
+    pub fn with_reader(read: impl FnMut(&Path) -> Option<String> + 'a) -> Self {
+        Self {
+            read: Box::new(read),
+            sources: HashMap::new(),
+        }
+    }
+
+    pub fn render(&mut self, diagnostic: &Diagnostic, mut out: impl Write) -> io::Result<()> {
+        let style = match diagnostic.severity {
+            Severity::Error => ERROR,
+            Severity::Warning => WARNING,
+            Severity::Note => NOTE,
+        };
+        writeln!(
+            out,
+            "{style}{}[{}]{style:#}{EMPHASIS}: {}{EMPHASIS:#}",
+            diagnostic.severity, diagnostic.code, diagnostic.message
+        )?;
+
+        // Diagnostics about e.g. commit trailers don't refer to any file.
+        if diagnostic.path.as_os_str().is_empty() {
+            return writeln!(out);
+        }
+
+        let path = diagnostic.path.display();
+        let Some(line) = diagnostic.line else {
+            writeln!(out, "{GUTTER}-->{GUTTER:#} {path}")?;
+            return writeln!(out);
+        };
+
+        let (start, end) = diagnostic.range.unwrap_or((line, line));
+        let (start, end) = (start.min(line).max(1), end.max(line));
+        let width = end.to_string().len();
+        writeln!(out, "{:width$}{GUTTER}-->{GUTTER:#} {path}:{line}", "")?;
+
+        // Blocks removed entirely, e.g. checked with `--removed-blocks`, refer
+        // to lines of the original content, which can't be shown.
+        let removed = diagnostic.range.is_some_and(|(start, end)| {
+            diagnostic.changes.len() == end - start + 1
+                && diagnostic
+                    .changes
+                    .iter()
+                    .all(|change| change.starts_with('-'))
+        });
+        if let Some(source) = self
+            .source(&diagnostic.path)
+            .filter(|source| !removed && end <= source.len())
+        {
+            writeln!(out, "{:width$} {GUTTER}|{GUTTER:#}", "")?;
+            let mut elided = false;
+            for number in start..=end {
+                let shown = number < start + FRAME_CONTEXT
+                    || number + FRAME_CONTEXT > end
+                    || number.abs_diff(line) <= 1;
+                if !shown {
+                    if !elided {
+                        writeln!(out, "{GUTTER}...{GUTTER:#}")?;
+                        elided = true;
+                    }
+                    continue;
+                }
+                elided = false;
+
+                let text = &source[number - 1];
+                writeln!(out, "{GUTTER}{number:>width$} |{GUTTER:#} {text}")?;
+                if number == line {
+                    let trimmed = text.trim_start();
+                    let indent = &text[..text.len() - trimmed.len()];
+                    let carets = "^".repeat(trimmed.trim_end().chars().count().max(1));
+                    writeln!(
+                        out,
+                        "{:width$} {GUTTER}|{GUTTER:#} {indent}{ERROR}{carets}{ERROR:#}",
+                        ""
+                    )?;
+                }
+            }
+        }
+
+        if let Some(target) = &diagnostic.target {
+            writeln!(
+                out,
+                "{:width$} {GUTTER}={GUTTER:#} {EMPHASIS}target{EMPHASIS:#}: {}",
+                "",
+                target.display()
+            )?;
+        }
+        if let Some(last_edit) = &diagnostic.last_edit {
+            writeln!(
+                out,
+                "{:width$} {GUTTER}={GUTTER:#} {EMPHASIS}last edited{EMPHASIS:#}: {} <{}> on {} ({:.7})",
+                "",
+                last_edit.author,
+                last_edit.email,
+                crate::audit::format_date(last_edit.time),
+                last_edit.commit
+            )?;
+        }
+        if !diagnostic.owners.is_empty() {
+            writeln!(
+                out,
+                "{:width$} {GUTTER}={GUTTER:#} {EMPHASIS}owners{EMPHASIS:#}: {}",
+                "",
+                diagnostic.owners.join(", ")
+            )?;
+        }
+        if !diagnostic.changes.is_empty() {
+            writeln!(
+                out,
+                "{:width$} {GUTTER}={GUTTER:#} {EMPHASIS}changes{EMPHASIS:#}:",
+                ""
+            )?;
+            for change in diagnostic.changes.iter().take(MAX_CHANGES) {
+                let style = if change.starts_with('+') {
+                    ADDED
+                } else {
+                    REMOVED
+                };
+                writeln!(out, "{:width$}   {style}{change}{style:#}", "")?;
+            }
+            if let Some(elided) = diagnostic.changes.len().checked_sub(MAX_CHANGES + 1) {
+                writeln!(out, "{:width$}   ... and {} more line(s)", "", elided + 1)?;
+            }
+        }
+        if let Some(suggestion) = &diagnostic.suggestion {
+            writeln!(
+                out,
+                "{:width$} {GUTTER}={GUTTER:#} {EMPHASIS}help{EMPHASIS:#}: {}",
+                "", suggestion.message
+            )?;
+            for line in suggestion.text.lines() {
+                writeln!(out, "{:width$}   {ADDED}+{line}{ADDED:#}", "")?;
+            }
+        }
+        writeln!(out)
+    }
+
+    /// Get a line of a file, starting at 1.
+    pub fn line(&mut self, path: &Path, number: usize) -> Option<&str> {
+        self.source(path)?
+            .get(number.checked_sub(1)?)
+            .map(String::as_str)
+    }
+
+    /// Get the lines of a file, reading it at most once.
+    fn source(&mut self, path: &Path) -> Option<&[String]> {
+        let read = &mut self.read;
+        self.sources
+            .entry(path.to_owned())
+            .or_insert_with(|| read(path).map(|source| source.lines().map(str::to_owned).collect()))
+            .as_deref()
+    }
+}
+
+/// Point to `--explain` for the codes of the rendered diagnostics, if any.
+pub fn explain_hint(codes: &BTreeSet<Code>, mut out: impl Write) -> io::Result<()> {
+    let Some(first) = codes.first() else {
+        return Ok(());
+    };
+    if codes.len() == 1 {
+        return writeln!(
+            out,
+            "{EMPHASIS}For more information about this error, try `if-changed --explain {first}`.{EMPHASIS:#}"
+        );
+    }
+    let codes = codes
+        .iter()
+        .map(|code| code.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(
+        out,
+        "{EMPHASIS}Some errors have detailed explanations: {codes}.{EMPHASIS:#}"
+    )?;
+    writeln!(
+        out,
+        "{EMPHASIS}For more information about an error, try `if-changed --explain {first}`.{EMPHASIS:#}"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{testing::git_test, Checker, Engine as _, GitEngine};
+    use indoc::indoc;
+
+    use super::*;
+
+    fn render(root: &Path, diagnostics: impl IntoIterator<Item = Diagnostic>) -> String {
+        let mut renderer = Renderer::new(root);
+        let mut out = Vec::new();
+        for diagnostic in diagnostics {
+            renderer
+                .render(&diagnostic, anstream::StripStream::new(&mut out))
+                .unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let (tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    const enum G {
+                        // if-changed
+                        A,
+                        // then-change(b.ts)
+                    }
+                "}
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        insta::assert_snapshot!(render(tempdir.path(), engine.check("a.ts").unwrap_err()), @r###"
+        error[IC002]: Expected "b.ts" to be modified because of "then-change" in "a.ts" at line 4.
+         --> a.ts:4
+          |
+        2 |     // if-changed
+        3 |     A,
+        4 |     // then-change(b.ts)
+          |     ^^^^^^^^^^^^^^^^^^^^
+          = target: b.ts
+          = changes:
+            +    // if-changed
+            +    A,
+            +    // then-change(b.ts)
+
+
+        "###);
+    }
+
+    #[test]
+    fn test_render_last_edit() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "b.ts" => indoc! {"
+                    // if-changed(b)
+                    B,
+                    // then-change(a.ts)
+                "}
+            ]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts:b)
+                "}
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let checker = Checker::builder(&engine)
+            .path("a.ts")
+            .blame_targets(true)
+            .build();
+        insta::assert_snapshot!(render(tempdir.path(), checker.check().unwrap_err()), @r###"
+        error[IC002]: Expected "b.ts" to be modified because of "then-change" in "a.ts" at line 3.
+         --> a.ts:3
+          |
+        1 | // if-changed
+        2 | A,
+        3 | // then-change(b.ts:b)
+          | ^^^^^^^^^^^^^^^^^^^^^^
+          = target: b.ts
+          = last edited: Example User <test@example.com> on 1970-01-01 (e1912e9)
+          = changes:
+            +// if-changed
+            +A,
+            +// then-change(b.ts:b)
+
+
+        "###);
+    }
+
+    #[test]
+    fn test_render_long_block() {
+        let (tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    B,
+                    C,
+                    D,
+                    E,
+                    F,
+                    G,
+                    H,
+                    // then-change(b.ts)
+                "}
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        insta::assert_snapshot!(render(tempdir.path(), engine.check("a.ts").unwrap_err()), @r###"
+        error[IC002]: Expected "b.ts" to be modified because of "then-change" in "a.ts" at line 10.
+          --> a.ts:10
+           |
+         1 | // if-changed
+         2 | A,
+        ...
+         9 | H,
+        10 | // then-change(b.ts)
+           | ^^^^^^^^^^^^^^^^^^^^
+           = target: b.ts
+           = changes:
+             +// if-changed
+             +A,
+             +B,
+             +C,
+             +D,
+             +E,
+             ... and 4 more line(s)
+
+
+        "###);
+    }
+
+    #[test]
+    fn test_render_suggestion() {
+        let (tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed(a)
+                    A,
+                    // then-change(b.ts:b)
+                "},
+                "b.ts" => "B,\n"
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        insta::assert_snapshot!(render(tempdir.path(), engine.check("a.ts").unwrap_err()), @r###"
+        error[IC003]: Could not find "if-changed" with name "b" in "b.ts" for "then-change" in "a.ts" at line 3.
+         --> a.ts:3
+          |
+        1 | // if-changed(a)
+        2 | A,
+        3 | // then-change(b.ts:b)
+          | ^^^^^^^^^^^^^^^^^^^^^^
+          = target: b.ts
+          = changes:
+            +// if-changed(a)
+            +A,
+            +// then-change(b.ts:b)
+          = help: add `// if-changed(b)` to "b.ts" around the lines to change with "a.ts"
+            +// if-changed(b)
+            +// then-change(/a.ts:a)
+
+        "###);
+    }
+
+    #[test]
+    fn test_render_without_line() {
+        let tempdir = tempfile::tempdir().unwrap();
+        insta::assert_snapshot!(render(tempdir.path(), [Diagnostic {
+            code: Code::Unreadable,
+            severity: Severity::Error,
+            path: "a.ts".into(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Could not open \"a.ts\".".into(),
+        }]), @r###"
+        error[IC201]: Could not open "a.ts".
+        --> a.ts
+
+        "###);
+    }
+
+    #[test]
+    fn test_render_without_path() {
+        let tempdir = tempfile::tempdir().unwrap();
+        insta::assert_snapshot!(render(tempdir.path(), [Diagnostic {
+            code: Code::MissingIgnoreReason,
+            severity: Severity::Error,
+            path: "".into(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Ignoring \"ignore-if-changed: a.ts\" in commit 1234567 because it gives no reason after \"--\".".into(),
+        }]), @r###"
+        error[IC301]: Ignoring "ignore-if-changed: a.ts" in commit 1234567 because it gives no reason after "--".
+
+        "###);
+    }
+
+    #[test]
+    fn test_explain_hint() {
+        let hint = |codes: &[Code]| {
+            let mut out = Vec::new();
+            explain_hint(
+                &codes.iter().copied().collect(),
+                anstream::StripStream::new(&mut out),
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(hint(&[]), "");
+        insta::assert_snapshot!(hint(&[Code::UnmodifiedTarget]), @r###"
+        For more information about this error, try `if-changed --explain IC002`.
+
+        "###);
+        insta::assert_snapshot!(hint(&[Code::UnterminatedBlock, Code::UnmodifiedTarget]), @r###"
+        Some errors have detailed explanations: IC002, IC101.
+        For more information about an error, try `if-changed --explain IC002`.
+
+        "###);
+    }
+}
@@ -0,0 +1,1020 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use if_changed::{Blame, Code, Diagnostic, Reporter, Severity, Summary, DEFAULT_IGNORE_TRAILER};
+use serde_json::{json, Value};
+
+use crate::{
+    render::{self, Renderer},
+    scope::Scope,
+};
+
+/// The formats reports can be written in.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Diagnostics with frames of the source, as printed to the terminal.
+    #[default]
+    Human,
+    /// SARIF 2.1.0, e.g. for GitHub code scanning.
+    Sarif,
+    /// GitHub Actions workflow commands, showing annotations on pull requests.
+    Github,
+    /// Azure Pipelines `##vso[task.logissue]` logging commands, showing
+    /// problems in the summary of builds.
+    Azure,
+    /// JUnit XML with a test case per modified block, e.g. for CI test
+    /// summaries.
+    Junit,
+    /// A JSON object with a versioned schema, documented in the README.
+    Json,
+    /// Test Anything Protocol version 14 with a test per modified block, e.g.
+    /// for `prove`.
+    Tap,
+    /// A Markdown table of problems, e.g. for pull request comments or
+    /// `$GITHUB_STEP_SUMMARY`.
+    Markdown,
+    /// `file:line:column: error: message` lines, e.g. for Vim's `:make` or
+    /// Emacs' `M-x compile`.
+    Quickfix,
+    /// Problems grouped by the owners of their targets in `CODEOWNERS`, e.g.
+    /// to delegate the changes a large pull request requires.
+    Owners,
+}
+
+impl ReportFormat {
+    /// Whether the report lists the modified blocks, not only problems.
+    pub fn lists_blocks(self) -> bool {
+        matches!(self, ReportFormat::Junit | ReportFormat::Tap)
+    }
+}
+
+/// Writes diagnostics to a report in the selected format.
+pub struct Report<W: Write> {
+    format: ReportFormat,
+    scope: Scope,
+    renderer: Renderer<'static>,
+    codes: BTreeSet<Code>,
+    /// Diagnostics of formats written all at once when finished.
+    diagnostics: Vec<Diagnostic>,
+    /// Modified blocks, for formats listing them.
+    blocks: Vec<(PathBuf, Blame)>,
+    /// The trailer key suggested for ignoring files.
+    trailer: String,
+    summary: Summary,
+    out: W,
+}
+
+/// Reports to several reporters at once, e.g. to the terminal and a file.
+pub struct Tee<'a>(pub Vec<&'a mut dyn Reporter>);
+
+impl Reporter for Tee<'_> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        for reporter in &mut self.0 {
+            reporter.report(diagnostic)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<Summary> {
+        let mut summary = Summary::default();
+        for reporter in &mut self.0 {
+            summary = reporter.finish()?;
+        }
+        Ok(summary)
+    }
+}
+
+impl<W: Write> Report<W> {
+    /// Create a report of the repository at `workdir`, printing paths as
+    /// `scope` does where the format allows it.
+    pub fn new(format: ReportFormat, workdir: &Path, scope: Scope, out: W) -> Self {
+        Self {
+            format,
+            renderer: Renderer::new(scope.root(workdir)),
+            scope,
+            codes: BTreeSet::new(),
+            diagnostics: Vec::new(),
+            blocks: Vec::new(),
+            trailer: DEFAULT_IGNORE_TRAILER.to_owned(),
+            summary: Summary::default(),
+            out,
+        }
+    }
+
+    /// Suggest the trailer `key` for ignoring files, where the format does.
+    pub fn with_trailer(mut self, key: &str) -> Self {
+        key.clone_into(&mut self.trailer);
+        self
+    }
+
+    /// Record a modified block of a file, relative to the repository root.
+    pub fn block(&mut self, path: &Path, blame: Blame) {
+        self.blocks.push((path.to_owned(), blame));
+    }
+}
+
+impl<W: Write> Reporter for Report<W> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        self.codes.insert(diagnostic.code);
+        self.summary.record(diagnostic);
+        match self.format {
+            ReportFormat::Human => self
+                .renderer
+                .render(&self.scope.diagnostic(diagnostic.clone()), &mut self.out),
+            ReportFormat::Sarif
+            | ReportFormat::Junit
+            | ReportFormat::Json
+            | ReportFormat::Tap
+            | ReportFormat::Markdown
+            | ReportFormat::Owners => {
+                self.diagnostics.push(diagnostic.clone());
+                Ok(())
+            }
+            ReportFormat::Github => writeln!(self.out, "{}", github(diagnostic)),
+            ReportFormat::Azure => writeln!(self.out, "{}", azure(diagnostic)),
+            ReportFormat::Quickfix => {
+                let diagnostic = self.scope.diagnostic(diagnostic.clone());
+                let column = diagnostic
+                    .line
+                    .and_then(|line| self.renderer.line(&diagnostic.path, line))
+                    .map_or(1, |text| text.len() - text.trim_start().len() + 1);
+                writeln!(self.out, "{}", quickfix(&diagnostic, column))
+            }
+        }
+    }
+
+    /// Write the end of the report, if any, and flush it.
+    fn finish(&mut self) -> io::Result<Summary> {
+        match self.format {
+            ReportFormat::Human => render::explain_hint(&self.codes, &mut self.out)?,
+            ReportFormat::Sarif => {
+                serde_json::to_writer_pretty(&mut self.out, &sarif(&self.diagnostics))?;
+                writeln!(self.out)?;
+            }
+            ReportFormat::Github | ReportFormat::Azure | ReportFormat::Quickfix => {}
+            ReportFormat::Junit => junit(&self.blocks, &self.diagnostics, &mut self.out)?,
+            ReportFormat::Tap => tap(&self.blocks, &self.diagnostics, &mut self.out)?,
+            ReportFormat::Markdown => markdown(&self.diagnostics, &self.trailer, &mut self.out)?,
+            ReportFormat::Owners => owners(&self.diagnostics, &mut self.out)?,
+            ReportFormat::Json => {
+                serde_json::to_writer_pretty(&mut self.out, &json(&self.diagnostics))?;
+                writeln!(self.out)?;
+            }
+        }
+        self.out.flush()?;
+        Ok(self.summary)
+    }
+}
+
+/// Build a SARIF 2.1.0 log of one run producing `diagnostics`.
+fn sarif(diagnostics: &[Diagnostic]) -> Value {
+    let rules = Code::ALL
+        .iter()
+        .map(|code| {
+            let explanation = code.explanation();
+            json!({
+                "id": code.as_str(),
+                "name": format!("{code:?}"),
+                "shortDescription": {
+                    "text": explanation.lines().next().unwrap_or_default(),
+                },
+                "help": {
+                    "text": explanation,
+                    "markdown": explanation,
+                },
+                "defaultConfiguration": { "level": "error" },
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut result = json!({
+                "ruleId": diagnostic.code.as_str(),
+                "ruleIndex": Code::ALL.iter().position(|code| *code == diagnostic.code),
+                "level": diagnostic.severity.as_str(),
+                "message": { "text": diagnostic.message },
+                "locations": [],
+            });
+            if !diagnostic.path.as_os_str().is_empty() {
+                let mut location = json!({
+                    "physicalLocation": {
+                        "artifactLocation": artifact(&diagnostic.path),
+                    },
+                });
+                if let Some(line) = diagnostic.line {
+                    location["physicalLocation"]["region"] = json!({ "startLine": line });
+                }
+                if let Some((start, end)) = diagnostic.range {
+                    location["physicalLocation"]["contextRegion"] =
+                        json!({ "startLine": start, "endLine": end });
+                }
+                result["locations"] = json!([location]);
+            }
+            if let Some(target) = &diagnostic.target {
+                result["relatedLocations"] = json!([{
+                    "id": 0,
+                    "physicalLocation": { "artifactLocation": artifact(target) },
+                    "message": { "text": "The target of the block." },
+                }]);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "if-changed",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+/// Build a JSON report of `diagnostics`.
+pub fn json(diagnostics: &[Diagnostic]) -> Value {
+    let path = |path: &Path| path.to_string_lossy().replace('\\', "/");
+    let diagnostics = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "code": diagnostic.code.as_str(),
+                "kind": diagnostic.code.name(),
+                "severity": diagnostic.severity.as_str(),
+                "source_file": Some(&diagnostic.path)
+                    .filter(|source| !source.as_os_str().is_empty())
+                    .map(|source| path(source)),
+                "source_line": diagnostic.line,
+                "target": diagnostic.target.as_deref().map(path),
+                "block_name": diagnostic.block,
+                "changes": diagnostic.changes,
+                "suggestion": diagnostic.suggestion.as_ref().map(|suggestion| json!({
+                    "message": suggestion.message,
+                    "file": path(&suggestion.path),
+                    "line": suggestion.line,
+                    "text": suggestion.text,
+                })),
+                "owners": diagnostic.owners,
+                "last_edit": diagnostic.last_edit.as_ref().map(|last_edit| json!({
+                    "commit": last_edit.commit,
+                    "author": last_edit.author,
+                    "email": last_edit.email,
+                    "time": last_edit.time,
+                })),
+                "message": diagnostic.message,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "version": if_changed::SCHEMA_VERSION,
+        "diagnostics": diagnostics,
+    })
+}
+
+/// Format a diagnostic as an `::error`, `::warning` or `::notice` workflow
+/// command of GitHub Actions.
+fn github(diagnostic: &Diagnostic) -> String {
+    let escape_data = |value: &str| {
+        value
+            .replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |value: &str| escape_data(value).replace(':', "%3A").replace(',', "%2C");
+    let mut properties = Vec::new();
+    if !diagnostic.path.as_os_str().is_empty() {
+        properties.push(format!(
+            "file={}",
+            escape_property(&diagnostic.path.to_string_lossy().replace('\\', "/"))
+        ));
+    }
+    if let Some(line) = diagnostic.line {
+        properties.push(format!("line={line}"));
+    }
+    properties.push(format!("title={}", diagnostic.code));
+    let command = match diagnostic.severity {
+        Severity::Note => "notice",
+        severity => severity.as_str(),
+    };
+    format!(
+        "::{command} {}::{}",
+        properties.join(","),
+        escape_data(&diagnostic.message)
+    )
+}
+
+/// Format a diagnostic as a `##vso[task.logissue]` logging command of Azure
+/// Pipelines, which has no notes, so they are logged as warnings.
+fn azure(diagnostic: &Diagnostic) -> String {
+    let escape_data = |value: &str| {
+        value
+            .replace('%', "%AZP25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |value: &str| escape_data(value).replace(';', "%3B").replace(']', "%5D");
+    let kind = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning | Severity::Note => "warning",
+    };
+    let mut properties = vec![format!("type={kind}")];
+    if !diagnostic.path.as_os_str().is_empty() {
+        properties.push(format!(
+            "sourcepath={}",
+            escape_property(&diagnostic.path.to_string_lossy().replace('\\', "/"))
+        ));
+    }
+    if let Some(line) = diagnostic.line {
+        properties.push(format!("linenumber={line}"));
+    }
+    properties.push(format!("code={}", diagnostic.code));
+    format!(
+        "##vso[task.logissue {};]{}",
+        properties.join(";"),
+        escape_data(&diagnostic.message)
+    )
+}
+
+/// A test of a block, or of a problem outside of any block: the name of the
+/// file, the name of the test, and the diagnostics failing it.
+type TestCase<'a> = (String, String, Vec<&'a Diagnostic>);
+
+/// Get a test case per block, failed by the diagnostics of its range. Other
+/// diagnostics get test cases of their own.
+fn test_cases<'a>(blocks: &[(PathBuf, Blame)], diagnostics: &'a [Diagnostic]) -> Vec<TestCase<'a>> {
+    let mut cases = blocks
+        .iter()
+        .map(|(path, blame)| {
+            let (start, end) = blame.range;
+            let name = match &blame.name {
+                Some(name) => format!("if-changed({name}) at lines {start}-{end}"),
+                None => format!("if-changed at lines {start}-{end}"),
+            };
+            let failures = diagnostics
+                .iter()
+                .filter(|diagnostic| {
+                    diagnostic.path == *path && diagnostic.range == Some(blame.range)
+                })
+                .collect::<Vec<_>>();
+            (path.to_string_lossy().into_owned(), name, failures)
+        })
+        .collect::<Vec<_>>();
+    for diagnostic in diagnostics {
+        let in_block = blocks
+            .iter()
+            .any(|(path, blame)| diagnostic.path == *path && diagnostic.range == Some(blame.range));
+        if in_block {
+            continue;
+        }
+        let classname = if diagnostic.path.as_os_str().is_empty() {
+            "if-changed".to_owned()
+        } else {
+            diagnostic.path.to_string_lossy().into_owned()
+        };
+        let name = match diagnostic.line {
+            Some(line) => format!("{} at line {line}", diagnostic.code),
+            None => diagnostic.code.to_string(),
+        };
+        cases.push((classname, name, vec![diagnostic]));
+    }
+    cases
+}
+
+/// Format a diagnostic as a line of a compiler's output, jumping to `column`
+/// of its line.
+fn quickfix(diagnostic: &Diagnostic, column: usize) -> String {
+    let message = format!(
+        "{}: {} [{}]",
+        diagnostic.severity,
+        diagnostic.message.replace('\n', " "),
+        diagnostic.code
+    );
+    if diagnostic.path.as_os_str().is_empty() {
+        return format!("if-changed: {message}");
+    }
+    format!(
+        "{}:{}:{column}: {message}",
+        diagnostic.path.display(),
+        diagnostic.line.unwrap_or(1)
+    )
+}
+
+/// Write the problems grouped by the owners of their targets, followed by
+/// those without any owner.
+fn owners(diagnostics: &[Diagnostic], mut out: impl Write) -> io::Result<()> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for diagnostic in diagnostics {
+        if diagnostic.owners.is_empty() {
+            groups
+                .entry((true, "Unowned"))
+                .or_default()
+                .push(diagnostic);
+        }
+        for owner in &diagnostic.owners {
+            groups
+                .entry((false, owner.as_str()))
+                .or_default()
+                .push(diagnostic);
+        }
+    }
+    for (index, ((_, owner), diagnostics)) in groups.into_iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        let mut summary = Summary::default();
+        for diagnostic in &diagnostics {
+            summary.record(diagnostic);
+        }
+        writeln!(out, "{owner} ({summary})")?;
+        for diagnostic in diagnostics {
+            let problem = format!(
+                "{}[{}]: {}",
+                diagnostic.severity,
+                diagnostic.code,
+                diagnostic.message.replace('\n', " ")
+            );
+            match diagnostic.line {
+                _ if diagnostic.path.as_os_str().is_empty() => writeln!(out, "  {problem}")?,
+                Some(line) => writeln!(out, "  {}:{line}: {problem}", diagnostic.path.display())?,
+                None => writeln!(out, "  {}: {problem}", diagnostic.path.display())?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write a JUnit XML report with a test case per block, failed by the
+/// diagnostics of its range. Other diagnostics get test cases of their own.
+fn junit(
+    blocks: &[(PathBuf, Blame)],
+    diagnostics: &[Diagnostic],
+    mut out: impl Write,
+) -> io::Result<()> {
+    let cases = test_cases(blocks, diagnostics);
+    let failures = cases
+        .iter()
+        .filter(|(_, _, failures)| !failures.is_empty())
+        .count();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="if-changed" tests="{0}" failures="{failures}" errors="0">"#,
+        cases.len()
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="if-changed" tests="{0}" failures="{failures}" errors="0" skipped="0">"#,
+        cases.len()
+    )?;
+    for (classname, name, failures) in &cases {
+        let (classname, name) = (escape_xml(classname), escape_xml(name));
+        let Some(first) = failures.first() else {
+            writeln!(
+                out,
+                r#"    <testcase classname="{classname}" name="{name}"/>"#
+            )?;
+            continue;
+        };
+        writeln!(
+            out,
+            r#"    <testcase classname="{classname}" name="{name}">"#
+        )?;
+        let body = failures
+            .iter()
+            .map(|diagnostic| format!("error[{}]: {}", diagnostic.code, diagnostic.message))
+            .collect::<Vec<_>>()
+            .join("\n");
+        writeln!(
+            out,
+            r#"      <failure type="{}" message="{}">{}</failure>"#,
+            first.code,
+            escape_xml(&first.message),
+            escape_xml(&body)
+        )?;
+        writeln!(out, "    </testcase>")?;
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")
+}
+
+/// Write a TAP report with a test per block, as [`junit`] does. Failures are
+/// described in YAML blocks.
+fn tap(
+    blocks: &[(PathBuf, Blame)],
+    diagnostics: &[Diagnostic],
+    mut out: impl Write,
+) -> io::Result<()> {
+    let cases = test_cases(blocks, diagnostics);
+    writeln!(out, "TAP version 14")?;
+    writeln!(out, "1..{}", cases.len())?;
+    // JSON strings are valid YAML scalars.
+    let quote = |value: &str| Value::from(value).to_string();
+    for (number, (file, name, failures)) in (1..).zip(&cases) {
+        // `#` would start a directive.
+        let description = format!("{file} {name}").replace('#', "\\#");
+        if failures.is_empty() {
+            writeln!(out, "ok {number} - {description}")?;
+            continue;
+        }
+        writeln!(out, "not ok {number} - {description}")?;
+        writeln!(out, "  ---")?;
+        writeln!(out, "  failures:")?;
+        for diagnostic in failures {
+            writeln!(out, "    - code: {}", diagnostic.code)?;
+            writeln!(out, "      message: {}", quote(&diagnostic.message))?;
+            if let Some(line) = diagnostic.line {
+                writeln!(out, "      line: {line}")?;
+            }
+            if let Some(target) = &diagnostic.target {
+                writeln!(out, "      target: {}", quote(&target.to_string_lossy()))?;
+            }
+        }
+        writeln!(out, "  ...")?;
+    }
+    Ok(())
+}
+
+/// Write a Markdown summary of `diagnostics`, with a footer on ignoring files
+/// with the `trailer` key.
+fn markdown(diagnostics: &[Diagnostic], trailer: &str, mut out: impl Write) -> io::Result<()> {
+    if diagnostics.is_empty() {
+        return writeln!(out, "**if-changed** found no problems.");
+    }
+    let cell = |value: &str| value.replace('|', "\\|").replace('\n', " ");
+    let code = |value: &Path| format!("`{}`", value.to_string_lossy().replace('\\', "/"));
+    writeln!(
+        out,
+        "**if-changed** found {} problem(s).\n",
+        diagnostics.len()
+    )?;
+    writeln!(out, "| Source | Line | Missing target | Reason |")?;
+    writeln!(out, "| --- | --: | --- | --- |")?;
+    for diagnostic in diagnostics {
+        let source = Some(&diagnostic.path)
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| code(path))
+            .unwrap_or_default();
+        let line = diagnostic
+            .line
+            .map(|line| line.to_string())
+            .unwrap_or_default();
+        let target = diagnostic.target.as_deref().map(code).unwrap_or_default();
+        writeln!(
+            out,
+            "| {source} | {line} | {target} | {} {} |",
+            diagnostic.code,
+            cell(&diagnostic.message)
+        )?;
+    }
+    writeln!(
+        out,
+        "\nIf a change intentionally leaves a target unchanged, add a trailer such as \
+         `{trailer}: <source> -- <reason>` to the commit message to skip checking the source."
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Refer to a file relative to the root of the repository.
+fn artifact(path: &Path) -> Value {
+    json!({
+        "uri": path.to_string_lossy().replace('\\', "/"),
+        "uriBaseId": "%SRCROOT%",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::Severity;
+
+    use super::*;
+
+    fn diagnostic() -> Diagnostic {
+        Diagnostic {
+            code: Code::UnmodifiedTarget,
+            severity: Severity::Error,
+            path: "src/a.ts".into(),
+            line: Some(3),
+            range: Some((1, 3)),
+            block: None,
+            target: Some("src/b.ts".into()),
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Expected \"src/b.ts\" to be modified.".into(),
+        }
+    }
+
+    #[test]
+    fn test_human() {
+        let mut out = Vec::new();
+        let mut report = Report::new(
+            ReportFormat::Human,
+            Path::new(""),
+            Scope::default(),
+            anstream::StripStream::new(&mut out),
+        );
+        report.report(&diagnostic()).unwrap();
+        assert_eq!(
+            report.finish().unwrap(),
+            Summary {
+                errors: 1,
+                warnings: 0
+            }
+        );
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        error[IC002]: Expected "src/b.ts" to be modified.
+         --> src/a.ts:3
+          = target: src/b.ts
+
+        For more information about this error, try `if-changed --explain IC002`.
+
+        "###);
+    }
+
+    #[test]
+    fn test_github() {
+        insta::assert_snapshot!(github(&diagnostic()), @r###"::error file=src/a.ts,line=3,title=IC002::Expected "src/b.ts" to be modified."###);
+        insta::assert_snapshot!(github(&Diagnostic {
+            code: Code::Unreadable,
+            severity: Severity::Error,
+            path: "a,b:c.ts".into(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Could not open \"a,b:c.ts\": 100% broken\nreally".into(),
+        }), @r###"::error file=a%2Cb%3Ac.ts,title=IC201::Could not open "a,b:c.ts": 100%25 broken%0Areally"###);
+    }
+
+    #[test]
+    fn test_azure() {
+        insta::assert_snapshot!(azure(&diagnostic()), @r###"##vso[task.logissue type=error;sourcepath=src/a.ts;linenumber=3;code=IC002;]Expected "src/b.ts" to be modified."###);
+        insta::assert_snapshot!(azure(&Diagnostic {
+            code: Code::Unreadable,
+            severity: Severity::Warning,
+            path: "a;b].ts".into(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Could not open \"a;b].ts\": 100% broken\nreally".into(),
+        }), @r###"##vso[task.logissue type=warning;sourcepath=a%3Bb%5D.ts;code=IC201;]Could not open "a;b].ts": 100%AZP25 broken%0Areally"###);
+    }
+
+    #[test]
+    fn test_junit() {
+        let block = |start, end| Blame {
+            name: None,
+            range: (start, end),
+            targets: Vec::new(),
+        };
+        let blocks = [
+            ("src/a.ts".into(), block(1, 3)),
+            ("src/a.ts".into(), block(5, 7)),
+            (
+                "src/b.ts".into(),
+                Blame {
+                    name: Some("<b>".into()),
+                    ..block(1, 3)
+                },
+            ),
+        ];
+        let unreadable = Diagnostic {
+            code: Code::Unreadable,
+            severity: Severity::Error,
+            path: "src/c.ts".into(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Could not open \"src/c.ts\".".into(),
+        };
+        let mut out = Vec::new();
+        junit(&blocks, &[diagnostic(), unreadable], &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <testsuites name="if-changed" tests="4" failures="2" errors="0">
+          <testsuite name="if-changed" tests="4" failures="2" errors="0" skipped="0">
+            <testcase classname="src/a.ts" name="if-changed at lines 1-3">
+              <failure type="IC002" message="Expected &quot;src/b.ts&quot; to be modified.">error[IC002]: Expected &quot;src/b.ts&quot; to be modified.</failure>
+            </testcase>
+            <testcase classname="src/a.ts" name="if-changed at lines 5-7"/>
+            <testcase classname="src/b.ts" name="if-changed(&lt;b&gt;) at lines 1-3"/>
+            <testcase classname="src/c.ts" name="IC201">
+              <failure type="IC201" message="Could not open &quot;src/c.ts&quot;.">error[IC201]: Could not open &quot;src/c.ts&quot;.</failure>
+            </testcase>
+          </testsuite>
+        </testsuites>
+
+        "###);
+    }
+
+    #[test]
+    fn test_tap() {
+        let block = |name: Option<&str>, start, end| Blame {
+            name: name.map(str::to_owned),
+            range: (start, end),
+            targets: Vec::new(),
+        };
+        let blocks = [
+            ("src/a.ts".into(), block(None, 1, 3)),
+            ("src/b.ts".into(), block(Some("#b"), 1, 3)),
+        ];
+        let trailer = Diagnostic {
+            code: Code::MissingIgnoreReason,
+            severity: Severity::Error,
+            path: PathBuf::new(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e.".into(),
+        };
+        let mut out = Vec::new();
+        tap(&blocks, &[diagnostic(), trailer], &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        TAP version 14
+        1..3
+        not ok 1 - src/a.ts if-changed at lines 1-3
+          ---
+          failures:
+            - code: IC002
+              message: "Expected \"src/b.ts\" to be modified."
+              line: 3
+              target: "src/b.ts"
+          ...
+        ok 2 - src/b.ts if-changed(\#b) at lines 1-3
+        not ok 3 - if-changed IC301
+          ---
+          failures:
+            - code: IC301
+              message: "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e."
+          ...
+
+        "###);
+    }
+
+    #[test]
+    fn test_quickfix() {
+        let trailer = Diagnostic {
+            code: Code::MissingIgnoreReason,
+            severity: Severity::Error,
+            path: PathBuf::new(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e.".into(),
+        };
+        insta::assert_snapshot!(quickfix(&diagnostic(), 5), @r###"src/a.ts:3:5: error: Expected "src/b.ts" to be modified. [IC002]"###);
+        insta::assert_snapshot!(quickfix(&trailer, 1), @r###"if-changed: error: Ignoring "ignore-if-changed: a.ts" in commit 89abb7e. [IC301]"###);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let piped = Diagnostic {
+            code: Code::MissingNamedBlock,
+            message: "Could not find \"a|b\".".into(),
+            ..diagnostic()
+        };
+        let mut out = Vec::new();
+        markdown(&[diagnostic(), piped], "Skip-If-Changed", &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        **if-changed** found 2 problem(s).
+
+        | Source | Line | Missing target | Reason |
+        | --- | --: | --- | --- |
+        | `src/a.ts` | 3 | `src/b.ts` | IC002 Expected "src/b.ts" to be modified. |
+        | `src/a.ts` | 3 | `src/b.ts` | IC003 Could not find "a\|b". |
+
+        If a change intentionally leaves a target unchanged, add a trailer such as `Skip-If-Changed: <source> -- <reason>` to the commit message to skip checking the source.
+
+        "###);
+
+        let mut out = Vec::new();
+        markdown(&[], DEFAULT_IGNORE_TRAILER, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        **if-changed** found no problems.
+
+        "###);
+    }
+
+    #[test]
+    fn test_owners() {
+        let shared = Diagnostic {
+            line: Some(7),
+            owners: vec!["@org/web".into(), "@org/api".into()],
+            ..diagnostic()
+        };
+        let warning = Diagnostic {
+            severity: Severity::Warning,
+            owners: vec!["@org/web".into()],
+            ..diagnostic()
+        };
+        let unowned = Diagnostic {
+            code: Code::MissingIgnoreReason,
+            path: PathBuf::new(),
+            line: None,
+            target: None,
+            message: "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e.".into(),
+            ..diagnostic()
+        };
+        let mut out = Vec::new();
+        owners(&[diagnostic(), shared, warning, unowned], &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        @org/api (1 error)
+          src/a.ts:7: error[IC002]: Expected "src/b.ts" to be modified.
+
+        @org/web (1 error and 1 warning)
+          src/a.ts:7: error[IC002]: Expected "src/b.ts" to be modified.
+          src/a.ts:3: warning[IC002]: Expected "src/b.ts" to be modified.
+
+        Unowned (2 errors)
+          src/a.ts:3: error[IC002]: Expected "src/b.ts" to be modified.
+          error[IC301]: Ignoring "ignore-if-changed: a.ts" in commit 89abb7e.
+
+        "###);
+    }
+
+    #[test]
+    fn test_json() {
+        let named = Diagnostic {
+            code: Code::MissingNamedBlock,
+            block: Some("colors".into()),
+            ..diagnostic()
+        };
+        let trailer = Diagnostic {
+            code: Code::MissingIgnoreReason,
+            severity: Severity::Error,
+            path: PathBuf::new(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e.".into(),
+        };
+        insta::assert_json_snapshot!(json(&[named, trailer]), @r###"
+        {
+          "diagnostics": [
+            {
+              "block_name": "colors",
+              "changes": [],
+              "code": "IC003",
+              "kind": "missing-named-block",
+              "last_edit": null,
+              "message": "Expected \"src/b.ts\" to be modified.",
+              "owners": [],
+              "severity": "error",
+              "source_file": "src/a.ts",
+              "source_line": 3,
+              "suggestion": null,
+              "target": "src/b.ts"
+            },
+            {
+              "block_name": null,
+              "changes": [],
+              "code": "IC301",
+              "kind": "missing-ignore-reason",
+              "last_edit": null,
+              "message": "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e.",
+              "owners": [],
+              "severity": "error",
+              "source_file": null,
+              "source_line": null,
+              "suggestion": null,
+              "target": null
+            }
+          ],
+          "version": 1
+        }
+        "###);
+    }
+
+    #[test]
+    fn test_sarif() {
+        let trailer = Diagnostic {
+            code: Code::MissingIgnoreReason,
+            severity: Severity::Error,
+            path: PathBuf::new(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e because it gives no reason after \"--\".".into(),
+        };
+        let sarif = sarif(&[diagnostic(), trailer]);
+        assert_eq!(
+            sarif["runs"][0]["tool"]["driver"]["rules"][1]["shortDescription"]["text"],
+            "A block was modified, but a target of its `then-change` was not."
+        );
+        insta::assert_json_snapshot!(sarif["runs"][0]["results"], @r###"
+        [
+          {
+            "level": "error",
+            "locations": [
+              {
+                "physicalLocation": {
+                  "artifactLocation": {
+                    "uri": "src/a.ts",
+                    "uriBaseId": "%SRCROOT%"
+                  },
+                  "contextRegion": {
+                    "endLine": 3,
+                    "startLine": 1
+                  },
+                  "region": {
+                    "startLine": 3
+                  }
+                }
+              }
+            ],
+            "message": {
+              "text": "Expected \"src/b.ts\" to be modified."
+            },
+            "relatedLocations": [
+              {
+                "id": 0,
+                "message": {
+                  "text": "The target of the block."
+                },
+                "physicalLocation": {
+                  "artifactLocation": {
+                    "uri": "src/b.ts",
+                    "uriBaseId": "%SRCROOT%"
+                  }
+                }
+              }
+            ],
+            "ruleId": "IC002",
+            "ruleIndex": 1
+          },
+          {
+            "level": "error",
+            "locations": [],
+            "message": {
+              "text": "Ignoring \"ignore-if-changed: a.ts\" in commit 89abb7e because it gives no reason after \"--\"."
+            },
+            "ruleId": "IC301",
+            "ruleIndex": 18
+          }
+        ]
+        "###);
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::{Component, Path, PathBuf};
+
+use clap::ValueEnum;
+use if_changed::Diagnostic;
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathMode {
+    /// Patterns and printed paths are relative to the repository root.
+    #[default]
+    Root,
+    /// Patterns and printed paths are relative to the current directory.
+    Cwd,
+}
+
+/// Translates between paths relative to the repository root and paths as the
+/// user sees them.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    /// The current directory relative to the repository root.
+    prefix: PathBuf,
+}
+
+impl Scope {
+    /// Create a scope for a process running in `cwd` inside `workdir`.
+    ///
+    /// If `cwd` is not inside `workdir`, paths are left relative to the root.
+    pub fn new(mode: PathMode, workdir: &Path, cwd: &Path) -> Self {
+        let prefix = match mode {
+            PathMode::Root => PathBuf::new(),
+            PathMode::Cwd => match (workdir.canonicalize(), cwd.canonicalize()) {
+                (Ok(workdir), Ok(cwd)) => cwd
+                    .strip_prefix(workdir)
+                    .map(Path::to_owned)
+                    .unwrap_or_default(),
+                _ => PathBuf::new(),
+            },
+        };
+        Self { prefix }
+    }
+
+    /// Get the directory printed paths are relative to.
+    pub fn root(&self, workdir: &Path) -> PathBuf {
+        workdir.join(&self.prefix)
+    }
+
+    /// Translate a user-provided pattern into one relative to the repository root.
+    ///
+    /// Patterns starting with `/` are always relative to the repository root.
+    pub fn pattern(&self, pattern: &str) -> String {
+        let (negation, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => ("!", pattern),
+            None => ("", pattern),
+        };
+        if pattern.starts_with('/') || self.prefix.as_os_str().is_empty() {
+            return format!("{negation}{pattern}");
+        }
+        let pattern = normalize(&self.prefix.join(pattern));
+        format!("{negation}{}", pattern.to_string_lossy())
+    }
+
+    /// Translate a user-provided path into a pattern matching exactly that
+    /// path, even if it contains characters special in patterns, e.g. `[`.
+    pub fn literal(&self, path: &str) -> String {
+        let mut pattern = String::with_capacity(path.len());
+        for (index, char) in path.char_indices() {
+            if matches!(char, '*' | '?' | '[' | '\\') || (index == 0 && char == '!') {
+                pattern.push('\\');
+            }
+            pattern.push(char);
+        }
+        self.pattern(&pattern)
+    }
+
+    /// Translate a path relative to the repository root into one relative to
+    /// the current directory.
+    pub fn path(&self, path: &Path) -> PathBuf {
+        let mut base = self.prefix.components().peekable();
+        let mut path = path.components().peekable();
+        while let (Some(a), Some(b)) = (base.peek(), path.peek()) {
+            if a != b {
+                break;
+            }
+            base.next();
+            path.next();
+        }
+        base.map(|_| Component::ParentDir).chain(path).collect()
+    }
+
+    /// Translate the paths of a diagnostic.
+    pub fn diagnostic(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        diagnostic.path = self.path(&diagnostic.path);
+        diagnostic.target = diagnostic.target.map(|target| self.path(&target));
+        if let Some(suggestion) = &mut diagnostic.suggestion {
+            suggestion.path = self.path(&suggestion.path);
+        }
+        diagnostic
+    }
+}
+
+/// Lexically resolve `.` and `..` components of a relative path.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.last(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(prefix: &str) -> Scope {
+        Scope {
+            prefix: prefix.into(),
+        }
+    }
+
+    #[test]
+    fn test_pattern() {
+        assert_eq!(scope("").pattern("a.ts"), "a.ts");
+        assert_eq!(scope("").pattern("!a.ts"), "!a.ts");
+        assert_eq!(scope("pkg/foo").pattern("a.ts"), "pkg/foo/a.ts");
+        assert_eq!(scope("pkg/foo").pattern("*.ts"), "pkg/foo/*.ts");
+        assert_eq!(scope("pkg/foo").pattern("!a.ts"), "!pkg/foo/a.ts");
+        assert_eq!(scope("pkg/foo").pattern("/a.ts"), "/a.ts");
+        assert_eq!(scope("pkg/foo").pattern("../bar/a.ts"), "pkg/bar/a.ts");
+        assert_eq!(scope("pkg/foo").pattern("./a.ts"), "pkg/foo/a.ts");
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(scope("").literal("a.ts"), "a.ts");
+        assert_eq!(scope("").literal("!a[1]*.ts"), "\\!a\\[1]\\*.ts");
+        assert_eq!(scope("pkg/foo").literal("a?.ts"), "pkg/foo/a\\?.ts");
+    }
+
+    #[test]
+    fn test_path() {
+        assert_eq!(scope("").path(Path::new("a.ts")), Path::new("a.ts"));
+        assert_eq!(
+            scope("pkg/foo").path(Path::new("pkg/foo/a.ts")),
+            Path::new("a.ts")
+        );
+        assert_eq!(
+            scope("pkg/foo").path(Path::new("pkg/bar/a.ts")),
+            Path::new("../bar/a.ts")
+        );
+        assert_eq!(
+            scope("pkg/foo").path(Path::new("a.ts")),
+            Path::new("../../a.ts")
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cwd = tempdir.path().join("pkg/foo");
+        std::fs::create_dir_all(&cwd).unwrap();
+
+        assert_eq!(
+            Scope::new(PathMode::Cwd, tempdir.path(), &cwd).prefix,
+            Path::new("pkg/foo")
+        );
+        assert_eq!(
+            Scope::new(PathMode::Root, tempdir.path(), &cwd).prefix,
+            Path::new("")
+        );
+        assert_eq!(
+            Scope::new(PathMode::Cwd, &cwd, tempdir.path()).prefix,
+            Path::new("")
+        );
+    }
+}
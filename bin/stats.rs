@@ -0,0 +1,104 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use anstyle::Style;
+use clap::ValueEnum;
+use if_changed::Stats;
+
+const EMPHASIS: Style = Style::new().bold();
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// A human-readable summary.
+    Text,
+    /// A single JSON object.
+    Json,
+}
+
+/// Write the statistics of a run that took `elapsed` in total.
+pub fn write(
+    stats: &Stats,
+    elapsed: Duration,
+    format: StatsFormat,
+    mut out: impl Write,
+) -> io::Result<()> {
+    match format {
+        StatsFormat::Text => {
+            writeln!(
+                out,
+                "{EMPHASIS}Checked {} file(s) ({} ignored, {} binary) in {elapsed:.2?}{EMPHASIS:#}",
+                stats.files, stats.ignored_files, stats.binary_files
+            )?;
+            writeln!(
+                out,
+                "  blocks:   {} found, {} unmodified",
+                stats.blocks, stats.unmodified_blocks
+            )?;
+            writeln!(out, "  matching: {:.2?}", stats.match_time)?;
+            writeln!(out, "  parsing:  {:.2?}", stats.parse_time)?;
+            writeln!(out, "  diffing:  {:.2?}", stats.diff_time)?;
+            writeln!(out, "  targets:  {:.2?}", stats.target_time)
+        }
+        StatsFormat::Json => {
+            let json = serde_json::json!({
+                "files": stats.files,
+                "ignored_files": stats.ignored_files,
+                "binary_files": stats.binary_files,
+                "blocks": stats.blocks,
+                "unmodified_blocks": stats.unmodified_blocks,
+                "timings": {
+                    "total": elapsed.as_secs_f64(),
+                    "matching": stats.match_time.as_secs_f64(),
+                    "parsing": stats.parse_time.as_secs_f64(),
+                    "diffing": stats.diff_time.as_secs_f64(),
+                    "targets": stats.target_time.as_secs_f64(),
+                },
+            });
+            writeln!(out, "{json}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> Stats {
+        Stats {
+            files: 3,
+            ignored_files: 1,
+            binary_files: 1,
+            blocks: 4,
+            unmodified_blocks: 2,
+            match_time: Duration::from_millis(1),
+            parse_time: Duration::from_millis(2),
+            diff_time: Duration::from_millis(3),
+            target_time: Duration::from_millis(4),
+        }
+    }
+
+    fn write_to_string(format: StatsFormat) -> String {
+        let mut out = Vec::new();
+        write(&stats(), Duration::from_millis(10), format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_text() {
+        insta::assert_snapshot!(anstream::adapter::strip_str(&write_to_string(StatsFormat::Text)), @r###"
+        Checked 3 file(s) (1 ignored, 1 binary) in 10.00ms
+          blocks:   4 found, 2 unmodified
+          matching: 1.00ms
+          parsing:  2.00ms
+          diffing:  3.00ms
+          targets:  4.00ms
+        "###);
+    }
+
+    #[test]
+    fn test_write_json() {
+        insta::assert_snapshot!(write_to_string(StatsFormat::Json), @r###"{"binary_files":1,"blocks":4,"files":3,"ignored_files":1,"timings":{"diffing":0.003,"matching":0.001,"parsing":0.002,"targets":0.004,"total":0.01},"unmodified_blocks":2}"###);
+    }
+}
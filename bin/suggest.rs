@@ -0,0 +1,196 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+};
+
+use clap::Args;
+use if_changed::{Diagnostic, Severity, TrailerConfig};
+
+use crate::CheckArgs;
+
+#[derive(Args, Debug)]
+pub struct SuggestIgnoreArgs {
+    /// The reason to give after `--`, e.g. `Only comments changed.`.
+    #[arg(long)]
+    pub reason: Option<String>,
+
+    /// Print the message of HEAD with the trailer added, to amend it with
+    /// `git commit --amend -F -`.
+    #[arg(long)]
+    pub amend: bool,
+
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+/// Print the trailer ignoring the files with errors in `diagnostics`, or the
+/// message of HEAD with it if `--amend` is given.
+///
+/// Returns whether there were any files to ignore.
+pub fn run(
+    args: &SuggestIgnoreArgs,
+    trailers: &TrailerConfig,
+    repository: &git2::Repository,
+    diagnostics: &[Diagnostic],
+    mut out: impl Write,
+) -> io::Result<bool> {
+    let reason = args.reason.as_deref().map(str::trim).unwrap_or_default();
+    if (trailers.require_reason || trailers.reason_pattern.is_some()) && reason.is_empty() {
+        return Err(io::Error::other(
+            "ignore trailers must give a reason; pass it with `--reason`",
+        ));
+    }
+    if let Some(pattern) = &trailers.reason_pattern {
+        let regex = regex::Regex::new(pattern).map_err(io::Error::other)?;
+        if !regex.is_match(reason) {
+            return Err(io::Error::other(format!(
+                "the reason must match `{pattern}`"
+            )));
+        }
+    }
+    let Some(trailer) = trailer(&trailers.keys[0], diagnostics, reason) else {
+        return Ok(false);
+    };
+    if args.amend {
+        let head = repository
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(io::Error::other)?;
+        let message = String::from_utf8_lossy(head.message_bytes());
+        write!(out, "{}", amend(&message, &trailer))?;
+    } else {
+        writeln!(out, "{trailer}")?;
+    }
+    Ok(true)
+}
+
+/// Get the trailer with `key` ignoring the files with errors in
+/// `diagnostics`, if any.
+fn trailer(key: &str, diagnostics: &[Diagnostic], reason: &str) -> Option<String> {
+    // Warnings and notes don't fail the check, and problems of trailers
+    // themselves aren't in any file.
+    let paths = diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            diagnostic.severity == Severity::Error && !diagnostic.path.as_os_str().is_empty()
+        })
+        .map(|diagnostic| diagnostic.path.to_string_lossy().replace('\\', "/"))
+        .collect::<BTreeSet<_>>();
+    if paths.is_empty() {
+        return None;
+    }
+    let mut trailer = format!("{key}: {}", Vec::from_iter(paths).join(", "));
+    if !reason.is_empty() {
+        trailer = format!("{trailer} -- {reason}");
+    }
+    Some(trailer)
+}
+
+/// Add `trailer` to the trailers of a commit message, starting a paragraph of
+/// trailers if it has none.
+fn amend(message: &str, trailer: &str) -> String {
+    let message = message.trim_end();
+    let has_trailers = message.contains('\n')
+        && git2::message_trailers_strs(message).is_ok_and(|trailers| trailers.len() > 0);
+    let separator = if has_trailers { "\n" } else { "\n\n" };
+    format!("{message}{separator}{trailer}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{testing::git_test, Code};
+
+    use super::*;
+
+    fn diagnostic(path: &str, severity: Severity) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(Code::UnmodifiedTarget, path, Some(3), "".into());
+        diagnostic.severity = severity;
+        diagnostic
+    }
+
+    #[test]
+    fn test_trailer() {
+        let diagnostics = [
+            diagnostic("src/b.ts", Severity::Error),
+            diagnostic("src/a.ts", Severity::Error),
+            diagnostic("src/a.ts", Severity::Error),
+            diagnostic("src/c.ts", Severity::Warning),
+            diagnostic("", Severity::Error),
+        ];
+        insta::assert_snapshot!(trailer("ignore-if-changed", &diagnostics, "").unwrap(), @"ignore-if-changed: src/a.ts, src/b.ts");
+        insta::assert_snapshot!(trailer("Skip-If-Changed", &diagnostics, "Generated.").unwrap(), @"Skip-If-Changed: src/a.ts, src/b.ts -- Generated.");
+        assert_eq!(trailer("ignore-if-changed", &diagnostics[3..], ""), None);
+    }
+
+    #[test]
+    fn test_amend() {
+        let trailer = "ignore-if-changed: a.ts";
+        insta::assert_snapshot!(amend("Change a\n", trailer), @r###"
+        Change a
+
+        ignore-if-changed: a.ts
+        "###);
+        insta::assert_snapshot!(amend("Change a\n\nBecause.\n\nSigned-off-by: A <a@example.com>\n", trailer), @r###"
+        Change a
+
+        Because.
+
+        Signed-off-by: A <a@example.com>
+        ignore-if-changed: a.ts
+        "###);
+    }
+
+    #[test]
+    fn test_run() {
+        let (_tempdir, repo) = git_test! {
+            "Change a": ["a.ts" => ""]
+        };
+        let args = SuggestIgnoreArgs {
+            reason: Some(" Generated. ".into()),
+            amend: true,
+            check: CheckArgs::default(),
+        };
+        let diagnostics = [diagnostic("a.ts", Severity::Error)];
+        let mut out = Vec::new();
+        assert!(run(
+            &args,
+            &TrailerConfig::default(),
+            &repo,
+            &diagnostics,
+            &mut out
+        )
+        .unwrap());
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r###"
+        Change a
+
+        ignore-if-changed: a.ts -- Generated.
+
+        "###);
+        assert!(!run(&args, &TrailerConfig::default(), &repo, &[], io::sink()).unwrap());
+
+        let trailers = TrailerConfig {
+            require_reason: true,
+            ..Default::default()
+        };
+        let args = SuggestIgnoreArgs {
+            reason: None,
+            ..args
+        };
+        insta::assert_snapshot!(run(&args, &trailers, &repo, &diagnostics, io::sink()).unwrap_err(), @"ignore trailers must give a reason; pass it with `--reason`");
+
+        let trailers = TrailerConfig {
+            reason_pattern: Some("[A-Z]+-[0-9]+".into()),
+            ..Default::default()
+        };
+        let args = SuggestIgnoreArgs {
+            reason: Some("Generated.".into()),
+            ..args
+        };
+        insta::assert_snapshot!(run(&args, &trailers, &repo, &diagnostics, io::sink()).unwrap_err(), @"the reason must match `[A-Z]+-[0-9]+`");
+        let args = SuggestIgnoreArgs {
+            reason: Some("PROJ-1: Generated.".into()),
+            ..args
+        };
+        assert!(run(&args, &trailers, &repo, &diagnostics, io::sink()).unwrap());
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use anstyle::{AnsiColor, Style};
+use if_changed::{Code, Diagnostic};
+
+use crate::{render::Renderer, scope::Scope, CheckArgs};
+
+const PROMPT: Style = AnsiColor::Cyan.on_default().bold();
+const STATUS: Style = Style::new().dimmed();
+
+/// The file suggested trailers are appended to, relative to the git directory.
+const TRAILERS_FILE_NAME: &str = "IF_CHANGED_TRAILERS";
+
+/// Walks through problems one at a time, offering actions to resolve each.
+pub struct Triage<'a> {
+    workdir: PathBuf,
+    scope: &'a Scope,
+    trailer_key: String,
+    trailers_file: PathBuf,
+    /// Lines inserted into each file so far, in the order they were inserted.
+    inserted: HashMap<PathBuf, Vec<usize>>,
+}
+
+impl<'a> Triage<'a> {
+    pub fn new(args: &CheckArgs, repository: &git2::Repository, scope: &'a Scope) -> Self {
+        Self {
+            workdir: repository.workdir().unwrap_or(repository.path()).to_owned(),
+            scope,
+            trailer_key: args.trailers().keys.swap_remove(0),
+            trailers_file: args
+                .trailers_file
+                .clone()
+                .unwrap_or_else(|| repository.path().join(TRAILERS_FILE_NAME)),
+            inserted: HashMap::new(),
+        }
+    }
+
+    /// Triage `diagnostics`, reading actions from `input`.
+    ///
+    /// Returns whether every problem was acted upon, i.e. none were skipped.
+    pub fn run(
+        &mut self,
+        diagnostics: Vec<Diagnostic>,
+        mut input: impl BufRead,
+        mut out: impl Write,
+    ) -> io::Result<bool> {
+        let total = diagnostics.len();
+        let mut skipped = 0;
+        let mut appended = false;
+        'diagnostics: for (index, diagnostic) in diagnostics.into_iter().enumerate() {
+            let diagnostic = self.shift(diagnostic);
+            Renderer::new(self.scope.root(&self.workdir))
+                .render(&self.scope.diagnostic(diagnostic.clone()), &mut out)?;
+            loop {
+                let Some(action) = prompt(
+                    &mut input,
+                    &mut out,
+                    &format!(
+                        "[{}/{total}] (e)dit, (s)uppress inline, (t)railer, (n)ext, (q)uit?",
+                        index + 1
+                    ),
+                )?
+                else {
+                    return Ok(false);
+                };
+                match action.to_lowercase().as_str() {
+                    "e" | "edit" => self.edit(&diagnostic)?,
+                    "s" | "suppress" => {
+                        let Some(reason) = prompt(&mut input, &mut out, "Reason:")? else {
+                            return Ok(false);
+                        };
+                        match self.suppress(&diagnostic, &reason) {
+                            Ok(()) => writeln!(out, "{STATUS}Added a suppression.{STATUS:#}")?,
+                            Err(error) => {
+                                writeln!(out, "{STATUS}Could not suppress: {error}{STATUS:#}")?;
+                                continue;
+                            }
+                        }
+                    }
+                    "t" | "trailer" => {
+                        let Some(reason) = prompt(&mut input, &mut out, "Reason:")? else {
+                            return Ok(false);
+                        };
+                        self.append_trailer(&diagnostic, &reason)?;
+                        appended = true;
+                    }
+                    "" | "n" | "next" => skipped += 1,
+                    "q" | "quit" => {
+                        skipped += total - index;
+                        break 'diagnostics;
+                    }
+                    _ => continue,
+                }
+                break;
+            }
+        }
+        if appended {
+            writeln!(
+                out,
+                "Suggested trailers were appended to {}. Add them to your commit message.",
+                self.trailers_file.display()
+            )?;
+        }
+        Ok(skipped == 0)
+    }
+
+    /// Adjust the lines of a diagnostic for the suppressions inserted so far.
+    fn shift(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        let Some(inserted) = self.inserted.get(&diagnostic.path) else {
+            return diagnostic;
+        };
+        let shift = |mut line: usize| {
+            for &position in inserted {
+                if position <= line {
+                    line += 1;
+                }
+            }
+            line
+        };
+        diagnostic.line = diagnostic.line.map(shift);
+        diagnostic.range = diagnostic
+            .range
+            .map(|(start, end)| (shift(start), shift(end)));
+        diagnostic
+    }
+
+    /// Open the target of a diagnostic, or its file if it has none, in the
+    /// user's editor.
+    fn edit(&self, diagnostic: &Diagnostic) -> io::Result<()> {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_owned());
+        let mut words = editor.split_whitespace();
+        let mut command = process::Command::new(words.next().unwrap_or("vi"));
+        command.args(words);
+        match (&diagnostic.target, diagnostic.line) {
+            (Some(target), _) => command.arg(self.workdir.join(target)),
+            (None, Some(line)) => command
+                .arg(format!("+{line}"))
+                .arg(self.workdir.join(&diagnostic.path)),
+            (None, None) => command.arg(self.workdir.join(&diagnostic.path)),
+        };
+        command.status()?;
+        Ok(())
+    }
+
+    /// Suppress the target of a diagnostic with an inline `ignore-if-changed`
+    /// just before the block's `then-change`.
+    fn suppress(&mut self, diagnostic: &Diagnostic, reason: &str) -> io::Result<()> {
+        let (Code::UnmodifiedTarget, Some(target), Some((start, end))) =
+            (diagnostic.code, &diagnostic.target, diagnostic.range)
+        else {
+            return Err(io::Error::other(
+                "only unmodified targets can be suppressed",
+            ));
+        };
+        if start == end {
+            return Err(io::Error::other("single-line blocks cannot be suppressed"));
+        }
+
+        let path = self.workdir.join(&diagnostic.path);
+        let source = fs::read_to_string(&path)?;
+        let mut lines = source.split_inclusive('\n').collect::<Vec<_>>();
+        let then_change = lines
+            .get(end - 1)
+            .ok_or_else(|| io::Error::other("the block is out of date"))?;
+        let suppression = suppression(then_change, target, reason)
+            .ok_or_else(|| io::Error::other("the block is out of date"))?;
+        lines.insert(end - 1, &suppression);
+        fs::write(&path, lines.concat())?;
+
+        self.inserted
+            .entry(diagnostic.path.clone())
+            .or_default()
+            .push(end);
+        Ok(())
+    }
+
+    /// Append a trailer ignoring the file of a diagnostic to the trailers file.
+    fn append_trailer(&self, diagnostic: &Diagnostic, reason: &str) -> io::Result<()> {
+        let mut trailer = format!("{}: {}", self.trailer_key, diagnostic.path.display());
+        if !reason.is_empty() {
+            trailer = format!("{trailer} -- {reason}");
+        }
+        let existing = match fs::read_to_string(&self.trailers_file) {
+            Ok(existing) => existing,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error),
+        };
+        if existing.lines().any(|line| line == trailer) {
+            return Ok(());
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.trailers_file)?;
+        writeln!(file, "{trailer}")
+    }
+}
+
+/// Build an inline suppression of `target` matching the comment style of the
+/// `then-change` line it is inserted before.
+fn suppression(then_change: &str, target: &Path, reason: &str) -> Option<String> {
+    let (content, newline) = match then_change.strip_suffix("\r\n") {
+        Some(content) => (content, "\r\n"),
+        None => (then_change.trim_end_matches('\n'), "\n"),
+    };
+    let start = content.find("then-change")?;
+    // Keep block comment terminators such as `*/` or `-->`.
+    let end = content[start..]
+        .rfind(')')
+        .map(|index| content[start + index + 1..].trim())
+        .filter(|end| !end.is_empty())
+        .map(|end| format!(" {end}"))
+        .unwrap_or_default();
+    Some(format!(
+        "{}ignore-if-changed: /{} -- {reason}{end}{newline}",
+        &content[..start],
+        target.display(),
+    ))
+}
+
+/// Print `text` and read a trimmed line of input, or `None` at the end of input.
+fn prompt(
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+    text: &str,
+) -> io::Result<Option<String>> {
+    write!(out, "{PROMPT}{text}{PROMPT:#} ")?;
+    out.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        writeln!(out)?;
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use if_changed::{testing::git_test, Engine as _, GitEngine};
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_suppression() {
+        let target = Path::new("src/b.ts");
+        assert_eq!(
+            suppression("    // then-change(b.ts)\n", target, "Generated."),
+            Some("    // ignore-if-changed: /src/b.ts -- Generated.\n".to_owned())
+        );
+        assert_eq!(
+            suppression("/* then-change(b.ts) */\r\n", target, ""),
+            Some("/* ignore-if-changed: /src/b.ts --  */\r\n".to_owned())
+        );
+        assert_eq!(
+            suppression("<!-- then-change(", target, "x"),
+            Some("<!-- ignore-if-changed: /src/b.ts -- x\n".to_owned())
+        );
+        assert_eq!(suppression("// if-changed", target, "x"), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let (tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts, c.ts)
+                    // if-changed
+                    B,
+                    // then-change(d.ts)
+                "}
+            ]
+        };
+
+        let trailers_file = tempdir.path().join("trailers");
+        let args = CheckArgs {
+            interactive: true,
+            trailers_file: Some(trailers_file.clone()),
+            ..Default::default()
+        };
+        let scope = Scope::default();
+        let diagnostics = GitEngine::new(&repo, None, None)
+            .unwrap()
+            .check("a.ts")
+            .unwrap_err();
+        assert_eq!(diagnostics.len(), 3);
+
+        let mut out = Vec::new();
+        let mut triage = Triage::new(&args, &repo, &scope);
+        let input = "s\nGenerated.\nS\nAlso generated.\nt\nNothing to sync.\n";
+        assert!(triage
+            .run(
+                diagnostics,
+                input.as_bytes(),
+                anstream::StripStream::new(&mut out)
+            )
+            .unwrap());
+
+        insta::assert_snapshot!(fs::read_to_string(tempdir.path().join("a.ts")).unwrap(), @r###"
+        // if-changed
+        A,
+        // ignore-if-changed: /c.ts -- Generated.
+        // ignore-if-changed: /b.ts -- Also generated.
+        // then-change(b.ts, c.ts)
+        // if-changed
+        B,
+        // then-change(d.ts)
+
+        "###);
+        insta::assert_snapshot!(fs::read_to_string(&trailers_file).unwrap(), @r###"
+        ignore-if-changed: a.ts -- Nothing to sync.
+
+        "###);
+        assert!(GitEngine::new(&repo, None, None)
+            .unwrap()
+            .check("a.ts")
+            .unwrap_err()
+            .iter()
+            .all(|diagnostic| diagnostic.target.as_deref() == Some(Path::new("d.ts"))));
+    }
+
+    #[test]
+    fn test_run_skip_and_quit() {
+        let (tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts, c.ts)
+                "}
+            ]
+        };
+
+        let args = CheckArgs {
+            interactive: true,
+            trailers_file: Some(tempdir.path().join("trailers")),
+            ..Default::default()
+        };
+        let scope = Scope::default();
+        let diagnostics = GitEngine::new(&repo, None, None)
+            .unwrap()
+            .check("a.ts")
+            .unwrap_err();
+
+        let mut triage = Triage::new(&args, &repo, &scope);
+        assert!(!triage
+            .run(diagnostics.clone(), "n\nq\n".as_bytes(), io::sink())
+            .unwrap());
+        assert!(!triage
+            .run(diagnostics, "unknown\n".as_bytes(), io::sink())
+            .unwrap());
+        assert!(!tempdir.path().join("trailers").exists());
+    }
+}
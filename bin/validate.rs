@@ -0,0 +1,87 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use if_changed::{Diagnostic, Manifest, Severity};
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// A JSON object listing the files to check as `srcs` and the files
+    /// their targets may refer to as `deps`, relative to `--root`.
+    #[arg(long, value_name = "PATH")]
+    pub manifest: PathBuf,
+
+    /// Create an empty file at PATH if no errors are found, e.g. the output
+    /// of a validation action.
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// The directory paths of the manifest are relative to.
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub root: PathBuf,
+}
+
+/// Check the files of the manifest of `args`, creating the output if no
+/// errors are found. Returns the problems found.
+pub fn run(args: &ValidateArgs) -> io::Result<Vec<Diagnostic>> {
+    let manifest = read_manifest(&args.manifest)?;
+    let problems = manifest.validate(&args.root);
+    let failed = problems
+        .iter()
+        .any(|problem| problem.severity == Severity::Error);
+    if let Some(output) = args.output.as_ref().filter(|_| !failed) {
+        fs::write(output, "")?;
+    }
+    Ok(problems)
+}
+
+fn read_manifest(path: &Path) -> io::Result<Manifest> {
+    let manifest = fs::read(path)?;
+    serde_json::from_slice(&manifest).map_err(|error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid manifest {path:?}: {error}"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::write(
+            root.join("a.ts"),
+            "// if-changed\nA,\n// then-change(b.ts)\n",
+        )
+        .unwrap();
+        fs::write(root.join("b.ts"), "B,\n").unwrap();
+        let args = |manifest: &str| {
+            fs::write(root.join("manifest.json"), manifest).unwrap();
+            ValidateArgs {
+                manifest: root.join("manifest.json"),
+                output: Some(root.join("out")),
+                root: root.to_owned(),
+            }
+        };
+
+        let problems = run(&args(r#"{"srcs": ["a.ts"]}"#)).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(!root.join("out").exists());
+
+        assert!(run(&args(r#"{"srcs": ["a.ts"], "deps": ["b.ts"]}"#))
+            .unwrap()
+            .is_empty());
+        assert_eq!(fs::read(root.join("out")).unwrap(), b"");
+
+        insta::assert_snapshot!(
+            run(&args(r#"{"files": []}"#)).unwrap_err().to_string().replace(&*root.to_string_lossy(), "[root]"),
+            @r###"invalid manifest "[root]/manifest.json": unknown field `files`, expected `srcs` or `deps` at line 1 column 8"###
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+use if_changed::{BlockIndex, Config, Diagnostic};
+
+use crate::{
+    completions,
+    scope::{PathMode, Scope},
+};
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// The files to verify. By default, all tracked files matching `scan` in
+    /// `.if-changed.toml` and present in the working tree are verified.
+    #[arg(add = ArgValueCompleter::new(completions::complete_paths))]
+    pub paths: Vec<String>,
+
+    /// How to interpret paths and print them.
+    #[arg(long, value_enum, default_value_t)]
+    pub path_mode: PathMode,
+
+    /// Parse every file instead of reusing the blocks cached in the git
+    /// directory, and don't update the cache.
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+/// Verify the blocks of the files of `args`, or of all tracked files scanned
+/// according to `config`, against the working tree, and that the files
+/// matching `require-blocks` have blocks.
+pub fn run(
+    args: &VerifyArgs,
+    repository: &git2::Repository,
+    config: &Config,
+    scope: &Scope,
+) -> std::io::Result<Vec<Diagnostic>> {
+    let paths = if args.paths.is_empty() {
+        let workdir = repository.workdir().unwrap_or(repository.path());
+        repository
+            .index()
+            .map_err(std::io::Error::other)?
+            .iter()
+            .filter_map(|entry| String::from_utf8(entry.path).ok())
+            .map(PathBuf::from)
+            .filter(|path| config.scans(path))
+            // Deleted files are checked as targets only.
+            .filter(|path| workdir.join(path).exists())
+            .collect::<Vec<_>>()
+    } else {
+        args.paths
+            .iter()
+            .map(|path| {
+                let path = scope.pattern(path);
+                PathBuf::from(path.strip_prefix('/').unwrap_or(&path))
+            })
+            .collect()
+    };
+    let mut index = if args.no_cache {
+        BlockIndex::new(repository)
+    } else {
+        BlockIndex::open(repository)
+    };
+    let mut problems = index.verify(&paths);
+    problems.extend(index.verify_required(&paths, &config.require_blocks));
+    if !args.no_cache {
+        index.save()?;
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use if_changed::{testing::git_test, Code, INDEX_FILE_NAME};
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts:b)
+                "},
+                "b.ts" => "// if-changed\n"
+            ]
+        };
+
+        let scope = Scope::new(PathMode::Root, tempdir.path(), tempdir.path());
+        let args = VerifyArgs {
+            paths: vec![],
+            path_mode: PathMode::Root,
+            no_cache: true,
+        };
+        let problems = run(&args, &repo, &Config::default(), &scope).unwrap();
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| problem.code)
+                .collect::<Vec<_>>(),
+            [Code::UnterminatedBlock, Code::UnterminatedBlock]
+        );
+        assert!(!repo.path().join(INDEX_FILE_NAME).exists());
+
+        let config = Config {
+            scan: vec!["b.*".into()],
+            ..Default::default()
+        };
+        let problems = run(&args, &repo, &config, &scope).unwrap();
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| problem.path.to_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["b.ts"]
+        );
+
+        let args = VerifyArgs {
+            paths: vec!["a.ts".into()],
+            no_cache: false,
+            ..args
+        };
+        assert_eq!(
+            run(&args, &repo, &Config::default(), &scope).unwrap().len(),
+            1
+        );
+        assert!(repo.path().join(INDEX_FILE_NAME).exists());
+
+        let config = Config {
+            require_blocks: vec!["*.ts".into()],
+            ..Default::default()
+        };
+        fs::write(tempdir.path().join("b.ts"), "B,\n").unwrap();
+        assert_eq!(
+            run(&args, &repo, &config, &scope)
+                .unwrap()
+                .iter()
+                .map(|problem| problem.code)
+                .collect::<Vec<_>>(),
+            [Code::MissingNamedBlock]
+        );
+        let args = VerifyArgs {
+            paths: vec!["b.ts".into()],
+            ..args
+        };
+        assert_eq!(
+            run(&args, &repo, &config, &scope)
+                .unwrap()
+                .iter()
+                .map(|problem| (problem.code, problem.path.to_str().unwrap()))
+                .collect::<Vec<_>>(),
+            [(Code::UnannotatedFile, "b.ts")]
+        );
+
+        // Deleted files are targets that no longer exist, but not unreadable.
+        fs::remove_file(tempdir.path().join("b.ts")).unwrap();
+        let args = VerifyArgs {
+            paths: vec![],
+            ..args
+        };
+        assert_eq!(
+            run(&args, &repo, &Config::default(), &scope)
+                .unwrap()
+                .iter()
+                .map(|problem| (problem.code, problem.path.to_str().unwrap()))
+                .collect::<Vec<_>>(),
+            [(Code::MissingTarget, "a.ts")]
+        );
+    }
+}
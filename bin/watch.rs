@@ -0,0 +1,188 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::mpsc,
+    time::Duration,
+};
+
+use anstyle::{AnsiColor, Style};
+use if_changed::{Diagnostic, Stats};
+use notify::{EventKind, RecursiveMode, Watcher as _};
+
+use crate::{render::Renderer, run, scope::Scope, CheckArgs};
+
+const RESOLVED: Style = AnsiColor::Green.on_default().bold();
+const STATUS: Style = Style::new().dimmed();
+
+/// How long the working tree must stay quiet before re-running the check.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Re-run the check every time the working tree changes.
+///
+/// Only diagnostics that appeared since the last run are rendered. Diagnostics
+/// that disappeared are reported as resolved.
+pub fn watch(
+    cli: &CheckArgs,
+    repository: &git2::Repository,
+    scope: &Scope,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| io::Error::other("bare repos are not supported"))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+    watcher
+        .watch(workdir, RecursiveMode::Recursive)
+        .map_err(io::Error::other)?;
+
+    let mut session = Session::default();
+    loop {
+        let mut renderer = Renderer::new(scope.root(workdir));
+        let (new, resolved) = session.update(
+            run(cli, repository, &mut Stats::default())?
+                .map(|diagnostic| scope.diagnostic(diagnostic))
+                .collect(),
+        );
+        for diagnostic in &new {
+            renderer.render(diagnostic, &mut out)?;
+        }
+        for diagnostic in &resolved {
+            writeln!(
+                out,
+                "{RESOLVED}resolved{RESOLVED:#}: {}",
+                diagnostic.message
+            )?;
+        }
+        writeln!(
+            out,
+            "{STATUS}[watch] {} problem(s). Waiting for changes...{STATUS:#}",
+            session.previous.len()
+        )?;
+
+        // Block until something relevant changes, then wait for the burst of
+        // events from a save to settle.
+        loop {
+            let event = receiver
+                .recv()
+                .map_err(io::Error::other)?
+                .map_err(io::Error::other)?;
+            if is_relevant(&event, repository.path()) {
+                break;
+            }
+        }
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+    }
+}
+
+/// Check whether an event may change the result of the check.
+///
+/// Edits inside the git directory are ignored except for the index and `HEAD`,
+/// which change what is considered modified.
+fn is_relevant(event: &notify::Event, git_dir: &Path) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    event
+        .paths
+        .iter()
+        .any(|path| match path.strip_prefix(git_dir) {
+            Ok(relpath) => relpath == Path::new("index") || relpath == Path::new("HEAD"),
+            Err(_) => true,
+        })
+}
+
+#[derive(Default)]
+struct Session {
+    previous: Vec<Diagnostic>,
+}
+
+impl Session {
+    /// Record the diagnostics of a run, returning the new and resolved ones.
+    fn update(&mut self, current: Vec<Diagnostic>) -> (Vec<Diagnostic>, Vec<Diagnostic>) {
+        let new = current
+            .iter()
+            .filter(|diagnostic| !self.previous.contains(diagnostic))
+            .cloned()
+            .collect();
+        let previous = std::mem::replace(&mut self.previous, current);
+        let resolved = previous
+            .into_iter()
+            .filter(|diagnostic| !self.previous.contains(diagnostic))
+            .collect();
+        (new, resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+    use if_changed::{Code, Severity};
+
+    use super::*;
+
+    fn event(kind: EventKind, path: &str) -> notify::Event {
+        notify::Event::new(kind).add_path(PathBuf::from(path))
+    }
+
+    fn diagnostic(message: &str) -> Diagnostic {
+        Diagnostic {
+            code: Code::UnmodifiedTarget,
+            severity: Severity::Error,
+            path: "a.ts".into(),
+            line: None,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: vec![],
+            suggestion: None,
+            last_edit: None,
+            owners: vec![],
+            message: message.into(),
+        }
+    }
+
+    #[test]
+    fn test_is_relevant() {
+        let git_dir = Path::new("/repo/.git");
+        let modify = EventKind::Modify(ModifyKind::Any);
+
+        assert!(is_relevant(&event(modify, "/repo/a.ts"), git_dir));
+        assert!(is_relevant(
+            &event(EventKind::Create(CreateKind::File), "/repo/b/c.ts"),
+            git_dir
+        ));
+        assert!(is_relevant(&event(modify, "/repo/.git/index"), git_dir));
+        assert!(is_relevant(&event(modify, "/repo/.git/HEAD"), git_dir));
+        assert!(!is_relevant(
+            &event(modify, "/repo/.git/objects/ab/cdef"),
+            git_dir
+        ));
+        assert!(!is_relevant(
+            &event(EventKind::Access(AccessKind::Any), "/repo/a.ts"),
+            git_dir
+        ));
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::default();
+
+        let (new, resolved) = session.update(vec![diagnostic("a"), diagnostic("b")]);
+        assert_eq!(new, vec![diagnostic("a"), diagnostic("b")]);
+        assert_eq!(resolved, vec![]);
+
+        let (new, resolved) = session.update(vec![diagnostic("b"), diagnostic("c")]);
+        assert_eq!(new, vec![diagnostic("c")]);
+        assert_eq!(resolved, vec![diagnostic("a")]);
+
+        let (new, resolved) = session.update(vec![]);
+        assert_eq!(new, vec![]);
+        assert_eq!(resolved, vec![diagnostic("b"), diagnostic("c")]);
+    }
+}
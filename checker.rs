@@ -0,0 +1,2998 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
+    vec,
+};
+
+#[cfg(feature = "tokio")]
+use crate::AsyncEngine;
+use crate::{
+    config::{scans, unannotated_file},
+    engine::{is_outside_root, resolve_patterns, root_relative},
+    owners::match_directory,
+    parser::{has_directives, Comment, Parser},
+    Block, Code, DeletedTargetPolicy, Diagnostic, Engine, LastEdit, MessageConfig, Pattern,
+    Reporter, Severity, Stats, Suggestion, Summary, REMOVAL_TRAILER,
+};
+
+/// Checks files for dependent changes with a policy, e.g. the severity of
+/// each kind of problem.
+///
+/// ```
+/// use if_changed::{Checker, Code, MemoryEngine, Severity};
+///
+/// let engine = MemoryEngine::default();
+/// let result = Checker::builder(&engine)
+///     .path("src/a.ts")
+///     .severity_overrides([(Code::MissingNamedBlock, Severity::Warning)])
+///     .build()
+///     .check();
+/// ```
+pub struct Checker<'a, E: ?Sized> {
+    engine: &'a E,
+    paths: Vec<PathBuf>,
+    severities: BTreeMap<Code, Severity>,
+    messages: MessageConfig,
+    cache: Arc<ParseCache>,
+    /// Patterns of the files to look for blocks in, or all if empty.
+    scan: Vec<String>,
+    /// Patterns of the files that must contain a block.
+    require_blocks: Vec<String>,
+    /// Patterns of generated files and of the files they are generated from.
+    generated: Vec<(String, String)>,
+    policy: Policy,
+}
+
+/// How a [`Checker`] reads files and checks the targets of blocks.
+#[derive(Debug, Clone, Copy)]
+struct Policy {
+    max_file_size: Option<u64>,
+    deleted_targets: DeletedTargetPolicy,
+    removed_blocks: bool,
+    removed_targets: bool,
+    /// The number of lines around blocks whose changes count as changes of
+    /// the blocks.
+    block_context_lines: usize,
+    /// Whether changes of the `if-changed` and `then-change` lines of blocks
+    /// don't trigger them.
+    ignore_directive_changes: bool,
+    /// Whether blocks with the same content as a block of the original
+    /// content of their file, e.g. moved by lines inserted above them, are
+    /// unmodified.
+    compare_content: bool,
+    normalize_separators: bool,
+    blame_targets: bool,
+    require_excluded_targets: bool,
+}
+
+impl Policy {
+    /// Widen `range` by the block context lines, within the bounds of a file.
+    fn widen(self, range: (usize, usize)) -> (usize, usize) {
+        (
+            range.0.saturating_sub(self.block_context_lines).max(1),
+            range.1 + self.block_context_lines,
+        )
+    }
+
+    /// Get the ranges of lines whose changes trigger `block`: the lines of
+    /// the block widened by the context lines, without its directives if
+    /// their changes are ignored.
+    ///
+    /// Blocks end at the line of their `then-change`, so the targets listed
+    /// on later lines are skipped too when they fall within the context lines
+    /// or the scope of the block.
+    fn trigger_ranges(self, block: &Block) -> Vec<(usize, usize)> {
+        let (start, end) = self.widen(block.lines());
+        if !self.ignore_directive_changes {
+            return vec![(start, end)];
+        }
+        let last_target = block
+            .targets
+            .iter()
+            .map(|pattern| pattern.line)
+            .fold(block.range.1, usize::max);
+        let mut ranges = Vec::new();
+        let mut next = start;
+        for (first, last) in [(block.range.0, block.range.0), (block.range.1, last_target)] {
+            if next < first {
+                ranges.push((next, end.min(first - 1)));
+            }
+            next = next.max(last + 1);
+        }
+        ranges.push((next, end));
+        ranges.retain(|(start, end)| start <= end);
+        ranges
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_file_size: None,
+            deleted_targets: DeletedTargetPolicy::default(),
+            removed_blocks: false,
+            removed_targets: false,
+            block_context_lines: 0,
+            ignore_directive_changes: false,
+            compare_content: false,
+            normalize_separators: true,
+            blame_targets: false,
+            require_excluded_targets: true,
+        }
+    }
+}
+
+/// Builds a [`Checker`].
+pub struct CheckerBuilder<'a, E: ?Sized> {
+    engine: &'a E,
+    paths: Vec<PathBuf>,
+    severities: BTreeMap<Code, Severity>,
+    messages: MessageConfig,
+    cache: Arc<ParseCache>,
+    /// Patterns of the files to look for blocks in, or all if empty.
+    scan: Vec<String>,
+    /// Patterns of the files that must contain a block.
+    require_blocks: Vec<String>,
+    /// Patterns of generated files and of the files they are generated from.
+    generated: Vec<(String, String)>,
+    policy: Policy,
+}
+
+impl<'a, E: ?Sized> Checker<'a, E> {
+    /// Start building a checker of the files of `engine`, an [`Engine`] or
+    /// an [`AsyncEngine`](crate::AsyncEngine).
+    pub fn builder(engine: &'a E) -> CheckerBuilder<'a, E> {
+        CheckerBuilder {
+            engine,
+            paths: Vec::new(),
+            severities: BTreeMap::new(),
+            messages: MessageConfig::default(),
+            cache: Arc::default(),
+            scan: Vec::new(),
+            require_blocks: Vec::new(),
+            generated: Vec::new(),
+            policy: Policy::default(),
+        }
+    }
+}
+
+impl<'a, E: Engine + ?Sized> Checker<'a, E> {
+    /// Check the files for dependent changes.
+    pub fn check(&self) -> Result<(), Vec<Diagnostic>> {
+        self.check_with_stats(&mut Stats::default())
+    }
+
+    /// Check the files for dependent changes, reporting each problem to
+    /// `reporter`.
+    pub fn run(&self, reporter: &mut dyn Reporter) -> io::Result<Summary> {
+        for diagnostic in self.check_iter() {
+            reporter.report(&diagnostic)?;
+        }
+        reporter.finish()
+    }
+
+    /// Check the files for dependent changes, recording statistics into
+    /// `stats`.
+    pub fn check_with_stats(&self, stats: &mut Stats) -> Result<(), Vec<Diagnostic>> {
+        let mut iter = self.check_iter();
+        let errors = iter.by_ref().collect::<Vec<_>>();
+        *stats += iter.stats();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check the files for dependent changes lazily, yielding problems as
+    /// they are found, e.g. to show progress or stop at the first error.
+    pub fn check_iter(&self) -> CheckIter<'a, E> {
+        let mut stats = Stats::default();
+        let mut pending = Vec::new();
+        let (paths, filter) = if self.paths.is_empty() {
+            pending.extend(self.engine.diagnostics());
+            let start = Instant::now();
+            let paths = self
+                .engine
+                .matches(Vec::<PathBuf>::new())
+                .flat_map(Result::ok)
+                .collect::<Vec<_>>();
+            stats.match_time += start.elapsed();
+            (paths, true)
+        } else {
+            (self.paths.clone(), false)
+        };
+        CheckIter {
+            engine: self.engine,
+            severities: self.severities.clone(),
+            messages: self.messages.clone(),
+            paths: paths.into_iter(),
+            filter,
+            file: None,
+            pending: pending.into_iter(),
+            cache: self.cache.clone(),
+            scan: self.scan.clone(),
+            require_blocks: self.require_blocks.clone(),
+            generated: self.generated.clone(),
+            policy: self.policy,
+            removed: Vec::new().into_iter(),
+            couplings: Couplings::default(),
+            stats,
+        }
+    }
+
+    /// Check the targets of a block of `path`, or of a block removed from its
+    /// original content if `removed`.
+    fn check_block(
+        engine: &E,
+        cache: &ParseCache,
+        policy: Policy,
+        path: &Path,
+        block: Block,
+        removed: bool,
+        stats: &mut Stats,
+    ) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        let start = Instant::now();
+        let changes = if removed {
+            engine.removed_lines(path, block.lines())
+        } else {
+            policy
+                .trigger_ranges(&block)
+                .into_iter()
+                .filter_map(|range| engine.range_changes(path, range))
+                .reduce(|mut changes, more| {
+                    changes.extend(more);
+                    changes
+                })
+        };
+        let changes = changes.filter(|_| {
+            removed
+                || !policy.compare_content
+                || !is_block_moved(engine, cache, policy, path, &block)
+        });
+        stats.diff_time += start.elapsed();
+        let Some(changes) = changes else {
+            tracing::trace!(range = ?block.range, "skipping unmodified block");
+            stats.unmodified_blocks += 1;
+            return errors;
+        };
+        if let Some(name) = &block.name {
+            if engine.is_block_ignored(path, name) {
+                tracing::debug!(name, "skipping ignored block");
+                return errors;
+            }
+        }
+
+        let _span = tracing::debug_span!("targets", range = ?block.range).entered();
+        let start = Instant::now();
+
+        let (mut block, unnamed_patterns, named_patterns) =
+            ModifiedBlock::new(path, block, changes, policy.normalize_separators);
+        block.removed = removed;
+        // Only unmodified targets can be excused by being excluded.
+        let excluded =
+            |target: &Path| !policy.require_excluded_targets && engine.is_excluded(target);
+        let mut targets = Vec::new();
+        for pattern in engine
+            .matches(unnamed_patterns.keys())
+            .flat_map(Result::err)
+        {
+            targets.push(TargetFacts {
+                line: unnamed_patterns[&pattern],
+                name: None,
+                excluded: excluded(&pattern),
+                state: TargetState::Unmatched {
+                    pattern,
+                    last_edit: None,
+                },
+            });
+        }
+        if policy.deleted_targets == DeletedTargetPolicy::Fail {
+            for (pattern, line) in &unnamed_patterns {
+                for dependent in engine.matches([pattern]).flat_map(Result::ok) {
+                    if engine.is_deleted(&dependent) {
+                        targets.push(TargetFacts {
+                            line: *line,
+                            name: None,
+                            excluded: false,
+                            state: TargetState::Deleted(dependent),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (pattern, (name, line)) in &named_patterns {
+            for result in engine.matches([pattern]) {
+                let mut target = TargetFacts {
+                    line: *line,
+                    name: Some(name.clone()),
+                    excluded: false,
+                    state: TargetState::Binary,
+                };
+                let dependent = match result {
+                    Ok(path) => path,
+                    Err(pattern) => {
+                        target.excluded = excluded(&pattern);
+                        target.state = TargetState::Unmatched {
+                            last_edit: policy
+                                .blame_targets
+                                .then(|| last_edit(engine, cache, policy, &pattern, name))
+                                .flatten(),
+                            pattern,
+                        };
+                        targets.push(target);
+                        continue;
+                    }
+                };
+                target.state = if engine.is_deleted(&dependent) {
+                    TargetState::Deleted(dependent)
+                } else if engine.is_binary(&dependent) {
+                    tracing::debug!(path = %dependent.display(), "skipping binary target");
+                    stats.binary_files += 1;
+                    TargetState::Binary
+                } else {
+                    match cache.parse(engine, &dependent, policy.max_file_size) {
+                        Ok(parsed) => {
+                            let (found, comment) =
+                                find_named_block(parsed.blocks.iter().cloned(), name);
+                            match found {
+                                Some(Ok(found)) => {
+                                    let modified = is_block_modified(
+                                        engine, cache, policy, &dependent, &found,
+                                    );
+                                    target.excluded = !modified && excluded(&dependent);
+                                    TargetState::Found {
+                                        last_edit: (!modified && policy.blame_targets)
+                                            .then(|| engine.last_edit(&dependent, found.lines()))
+                                            .flatten(),
+                                        dependent,
+                                        modified,
+                                    }
+                                }
+                                Some(Err(problems)) => TargetState::Invalid(problems),
+                                None => {
+                                    let original_removed = removed
+                                        && is_original_block_removed(engine, &dependent, name);
+                                    target.excluded = removed && excluded(&dependent);
+                                    TargetState::Missing {
+                                        dependent,
+                                        comment,
+                                        end: parsed.lines + 1,
+                                        original_removed,
+                                    }
+                                }
+                            }
+                        }
+                        Err(error) => TargetState::Unreadable(dependent, error),
+                    }
+                };
+                targets.push(target);
+            }
+        }
+        errors.extend(block.problems(policy, targets));
+        stats.target_time += start.elapsed();
+        errors
+    }
+}
+
+#[cfg(any(test, feature = "rayon"))]
+impl<E: Engine + Sync + ?Sized> Checker<'_, E> {
+    /// Check the files for dependent changes on rayon's thread pool, one task
+    /// per file, recording statistics into `stats`.
+    ///
+    /// Source files and the targets of their blocks are read and parsed in
+    /// parallel. Problems are returned in the same order as by
+    /// [`Checker::check`].
+    pub fn par_check_with_stats(&self, stats: &mut Stats) -> Result<(), Vec<Diagnostic>> {
+        use rayon::prelude::*;
+
+        let mut iter = self.check_iter();
+        let paths = std::mem::take(&mut iter.paths);
+        let mut errors = iter.by_ref().collect::<Vec<_>>();
+        *stats += iter.stats();
+
+        let files = paths
+            .as_slice()
+            .par_iter()
+            .map(|path| {
+                let mut file = CheckIter {
+                    engine: self.engine,
+                    severities: self.severities.clone(),
+                    messages: self.messages.clone(),
+                    paths: vec![path.clone()].into_iter(),
+                    filter: iter.filter,
+                    file: None,
+                    pending: Vec::new().into_iter(),
+                    cache: self.cache.clone(),
+                    scan: self.scan.clone(),
+                    require_blocks: self.require_blocks.clone(),
+                    generated: self.generated.clone(),
+                    policy: self.policy,
+                    removed: Vec::new().into_iter(),
+                    couplings: Couplings::default(),
+                    stats: Stats::default(),
+                };
+                let errors = file.by_ref().collect::<Vec<_>>();
+                (errors, file.stats)
+            })
+            .collect::<Vec<_>>();
+        for (file_errors, file_stats) in files {
+            errors.extend(file_errors);
+            *stats += &file_stats;
+        }
+        // Each file was checked on its own, so couplings between files are
+        // only known now.
+        let mut couplings = Couplings::default();
+        errors.retain(|error| !couplings.is_reported(error));
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Problems found by [`Checker::check_iter`], yielded as blocks are checked.
+pub struct CheckIter<'a, E: Engine + ?Sized> {
+    engine: &'a E,
+    severities: BTreeMap<Code, Severity>,
+    messages: MessageConfig,
+    paths: vec::IntoIter<PathBuf>,
+    /// Whether to skip ignored paths.
+    filter: bool,
+    /// The file being checked, with the index of its next block and its span.
+    file: Option<(PathBuf, Arc<ParsedFile>, usize, tracing::Span)>,
+    pending: vec::IntoIter<Diagnostic>,
+    cache: Arc<ParseCache>,
+    scan: Vec<String>,
+    require_blocks: Vec<String>,
+    generated: Vec<(String, String)>,
+    policy: Policy,
+    /// The blocks removed from the file being checked, checked after its
+    /// other blocks.
+    removed: vec::IntoIter<(PathBuf, Block)>,
+    couplings: Couplings,
+    stats: Stats,
+}
+
+impl<E: Engine + ?Sized> CheckIter<'_, E> {
+    /// Get the statistics of the files checked so far.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+}
+
+impl<E: Engine + ?Sized> Iterator for CheckIter<'_, E> {
+    type Item = Diagnostic;
+
+    fn next(&mut self) -> Option<Diagnostic> {
+        loop {
+            if let Some(diagnostic) = self.pending.next() {
+                match configure(
+                    diagnostic,
+                    &self.severities,
+                    &self.messages,
+                    &mut self.couplings,
+                ) {
+                    Some(diagnostic) => return Some(diagnostic),
+                    None => continue,
+                }
+            }
+
+            if let Some((path, parsed, next, span)) = &mut self.file {
+                let entered = span.enter();
+                let block = parsed.blocks.get(*next).cloned();
+                *next += 1;
+                self.pending = match block {
+                    Some(Ok(block)) => {
+                        self.stats.blocks += 1;
+                        Checker::check_block(
+                            self.engine,
+                            &self.cache,
+                            self.policy,
+                            path,
+                            block,
+                            false,
+                            &mut self.stats,
+                        )
+                    }
+                    Some(Err(error)) => error,
+                    None => {
+                        drop(entered);
+                        self.file = None;
+                        continue;
+                    }
+                }
+                .into_iter();
+                continue;
+            }
+
+            if let Some((path, block)) = self.removed.next() {
+                self.stats.blocks += 1;
+                self.pending = Checker::check_block(
+                    self.engine,
+                    &self.cache,
+                    self.policy,
+                    &path,
+                    block,
+                    true,
+                    &mut self.stats,
+                )
+                .into_iter();
+                continue;
+            }
+
+            let path = self.paths.next()?;
+            // Paths given to check may point anywhere, unlike changed files.
+            if is_outside_root(&path) {
+                self.pending = vec![escaping_path(&path)].into_iter();
+                continue;
+            }
+            if self.filter && self.engine.is_ignored(&path) {
+                self.stats.ignored_files += 1;
+                continue;
+            }
+            // Generators need no blocks, so binary and unscanned ones count
+            // too.
+            let mut problems = generated_targets(self.engine, &self.generated, &path);
+            if self.engine.is_binary(&path) {
+                tracing::debug!(path = %path.display(), "skipping binary file");
+                self.stats.binary_files += 1;
+                self.pending = problems.into_iter();
+                continue;
+            }
+            if !scans(&self.scan, &path) {
+                tracing::debug!(path = %path.display(), "skipping unscanned file");
+                self.stats.ignored_files += 1;
+                let has_directives = self.engine.read(&path).and_then(has_directives);
+                if has_directives.unwrap_or_default() {
+                    problems.push(unscanned_file(&path));
+                }
+                self.pending = problems.into_iter();
+                continue;
+            }
+            // Deleted files have no blocks left, only removed ones.
+            let deleted = self.engine.is_deleted(&path);
+            if deleted && !self.policy.removed_blocks && !self.policy.removed_targets {
+                tracing::debug!(path = %path.display(), "skipping deleted file");
+                self.pending = problems.into_iter();
+                continue;
+            }
+            let span = tracing::debug_span!("check", path = %path.display());
+            self.stats.files += 1;
+            let start = Instant::now();
+            let parsed = span.in_scope(|| {
+                tracing::trace_span!("parse").in_scope(|| {
+                    if deleted {
+                        Ok(Arc::new(ParsedFile {
+                            blocks: Vec::new(),
+                            lines: 0,
+                        }))
+                    } else {
+                        self.cache
+                            .parse(self.engine, &path, self.policy.max_file_size)
+                    }
+                })
+            });
+            self.stats.parse_time += start.elapsed();
+            match parsed {
+                Ok(parsed) => {
+                    if !deleted {
+                        problems.extend(unannotated_file(
+                            &self.require_blocks,
+                            &path,
+                            &parsed.blocks,
+                        ));
+                    }
+                    if self.policy.removed_blocks {
+                        self.removed = span
+                            .in_scope(|| removed_blocks(self.engine, &path, &parsed.blocks))
+                            .into_iter()
+                            .map(|block| (path.clone(), block))
+                            .collect::<Vec<_>>()
+                            .into_iter();
+                    }
+                    if self.policy.removed_targets {
+                        problems.extend(span.in_scope(|| {
+                            removed_targets(
+                                self.engine,
+                                &self.cache,
+                                self.policy,
+                                &path,
+                                &parsed.blocks,
+                                self.removed.as_slice(),
+                            )
+                        }));
+                    }
+                    self.pending = problems.into_iter();
+                    self.file = Some((path, parsed, 0, span));
+                }
+                Err(error) => {
+                    problems.push(unreadable(&path, &error));
+                    self.pending = problems.into_iter();
+                }
+            }
+        }
+    }
+}
+
+/// Report that `path`, given to check, is outside of the repository.
+fn escaping_path(path: &Path) -> Diagnostic {
+    Diagnostic::new(
+        Code::EscapingPath,
+        path,
+        None,
+        format!("Could not check {path:?} because it is outside of the repository."),
+    )
+}
+
+/// Report that `path` is outside of `scan` but contains directives.
+fn unscanned_file(path: &Path) -> Diagnostic {
+    Diagnostic::new(
+        Code::UnscannedFile,
+        path,
+        None,
+        format!("Skipped {path:?} since it is outside the directories in `scan`, but it contains directives. Add it to `scan` to check it."),
+    )
+    .with_severity(Severity::Warning)
+}
+
+/// Report that `path` could not be read.
+fn unreadable(path: &Path, error: &io::Error) -> Diagnostic {
+    Diagnostic::new(
+        Code::Unreadable,
+        path,
+        None,
+        format!("Could not open {path:?}: {error}"),
+    )
+}
+
+/// Give `diagnostic` the severity and message configured for its code, or
+/// drop it if its coupling was already reported from the other end.
+fn configure(
+    mut diagnostic: Diagnostic,
+    severities: &BTreeMap<Code, Severity>,
+    messages: &MessageConfig,
+    couplings: &mut Couplings,
+) -> Option<Diagnostic> {
+    if couplings.is_reported(&diagnostic) {
+        tracing::debug!(%diagnostic, "skipping problem reported from the other end");
+        return None;
+    }
+    if let Some(severity) = severities.get(&diagnostic.code) {
+        diagnostic.severity = *severity;
+    }
+    messages.apply(&mut diagnostic);
+    Some(diagnostic)
+}
+
+/// Get the patterns of the files generated from `path` according to
+/// `generated`, pairs of patterns of generated files and of their generators.
+/// Patterns of generated files are relative to the directory their generator
+/// matched from, e.g. `web/package-lock.json` for `package-lock.json` if
+/// `package.json` matched `web/package.json`.
+pub(crate) fn generated_patterns(generated: &[(String, String)], path: &Path) -> Vec<PathBuf> {
+    let written = path.to_string_lossy().replace('\\', "/");
+    generated
+        .iter()
+        .filter_map(|(files, generator)| {
+            let directory = match_directory(generator, &written)?;
+            Some(PathBuf::from(directory).join(root_relative(Path::new(files))))
+        })
+        .collect()
+}
+
+/// Report the patterns of files generated from `path` according to
+/// `generated` that match no modified file, as if `path` had a block
+/// spanning it with them as targets.
+fn generated_targets<E: Engine + ?Sized>(
+    engine: &E,
+    generated: &[(String, String)],
+    path: &Path,
+) -> Vec<Diagnostic> {
+    generated_patterns(generated, path)
+        .into_iter()
+        .filter(|files| !engine.matches([files]).any(|result| result.is_ok()))
+        .map(|files| unmodified_generated(path, &files))
+        .collect()
+}
+
+/// Report that no file matching `files`, generated from `path`, was
+/// modified.
+fn unmodified_generated(path: &Path, files: &Path) -> Diagnostic {
+    Diagnostic::new(
+        Code::UnmodifiedTarget,
+        path,
+        None,
+        format!(
+            "Expected files matching `{}` to be modified because they are generated from {path:?}.",
+            files.display()
+        ),
+    )
+    .with_target(files)
+}
+
+/// Look up the last edit of the block named `name` in `path`, a target left
+/// unchanged, if it has one.
+fn last_edit<E: Engine + ?Sized>(
+    engine: &E,
+    cache: &ParseCache,
+    policy: Policy,
+    path: &Path,
+    name: &str,
+) -> Option<LastEdit> {
+    let parsed = cache.parse(engine, path, policy.max_file_size).ok()?;
+    let (found, _) = find_named_block(parsed.blocks.iter().cloned(), name);
+    engine.last_edit(path, found?.ok()?.lines())
+}
+
+/// Get the blocks of the original content of `path` that were removed
+/// entirely. Named blocks still found in `blocks`, the current blocks of the
+/// file, were moved instead.
+fn removed_blocks<E: Engine + ?Sized>(
+    engine: &E,
+    path: &Path,
+    blocks: &[Result<Block, Vec<Diagnostic>>],
+) -> Vec<Block> {
+    let Ok(reader) = engine.read_original(path) else {
+        return Vec::new();
+    };
+    // Problems of the original content were reported when it was current.
+    removal_candidates(Parser::scan(path, reader).flatten(), blocks)
+        .into_iter()
+        .filter(|block| is_wholly_removed(block, engine.removed_lines(path, block.lines())))
+        .collect()
+}
+
+/// Get the blocks of `original` that may have been removed, i.e. unnamed
+/// blocks and named blocks not found in `blocks` anymore.
+fn removal_candidates(
+    original: impl IntoIterator<Item = Block>,
+    blocks: &[Result<Block, Vec<Diagnostic>>],
+) -> Vec<Block> {
+    original
+        .into_iter()
+        .filter(|block| {
+            block.name.is_none()
+                || !blocks
+                    .iter()
+                    .flatten()
+                    .any(|current| current.name == block.name)
+        })
+        .collect()
+}
+
+/// Check if all lines of `block` are among `removed`, the lines removed from
+/// them.
+fn is_wholly_removed(block: &Block, removed: Option<Vec<String>>) -> bool {
+    let (start, end) = block.lines();
+    removed.is_some_and(|removed| removed.len() == end - start + 1)
+}
+
+/// Report the targets of the original content of `path` that no block of
+/// `blocks`, its current blocks, has anymore, unless they changed too or the
+/// removal was acknowledged.
+fn removed_targets<E: Engine + ?Sized>(
+    engine: &E,
+    cache: &ParseCache,
+    policy: Policy,
+    path: &Path,
+    blocks: &[Result<Block, Vec<Diagnostic>>],
+    removed: &[(PathBuf, Block)],
+) -> Vec<Diagnostic> {
+    let Ok(reader) = engine.read_original(path) else {
+        return Vec::new();
+    };
+    // Problems of the original content were reported when it was current.
+    let original = Parser::scan(path, reader).flatten();
+    RemovedTarget::find(policy, path, original, blocks, removed)
+        .into_iter()
+        .filter(|removal| {
+            !engine.is_removal_acknowledged(path, removal.block.as_deref())
+                && !is_target_changed(
+                    engine,
+                    cache,
+                    policy,
+                    &removal.target,
+                    removal.name.as_deref(),
+                )
+        })
+        .map(|removal| removal.diagnostic(path))
+        .collect()
+}
+
+/// A target of the original content of a file that no block has anymore.
+struct RemovedTarget {
+    /// The name of the block the target was removed from.
+    block: Option<String>,
+    target: PathBuf,
+    /// The name of the block of the target, if any.
+    name: Option<String>,
+    /// The line of the target in the original content.
+    line: usize,
+}
+
+impl RemovedTarget {
+    /// Find the targets of `original`, the blocks of the original content of
+    /// `path`, that no block of `blocks`, its current blocks, has anymore.
+    ///
+    /// Targets are compared by the name of their block, so moving a block
+    /// keeps them. The targets of `removed`, the blocks checked as removed,
+    /// are left to that check.
+    fn find(
+        policy: Policy,
+        path: &Path,
+        original: impl IntoIterator<Item = Block>,
+        blocks: &[Result<Block, Vec<Diagnostic>>],
+        removed: &[(PathBuf, Block)],
+    ) -> Vec<Self> {
+        let targets = |block: &Block| {
+            resolve_patterns(
+                path,
+                block.targets.clone(),
+                &[],
+                policy.normalize_separators,
+            )
+            .into_iter()
+            .flatten()
+            .map(|pattern| (root_relative(&pattern.path), pattern.name, pattern.line))
+        };
+        let current = blocks
+            .iter()
+            .flatten()
+            .flat_map(|block| {
+                targets(block).map(|(target, name, _)| (block.name.clone(), target, name))
+            })
+            .collect::<HashSet<_>>();
+        let mut removals = Vec::new();
+        for block in original {
+            if removed
+                .iter()
+                .any(|(_, removed)| removed.range == block.range)
+            {
+                continue;
+            }
+            for (target, name, line) in targets(&block) {
+                if !current.contains(&(block.name.clone(), target.clone(), name.clone())) {
+                    removals.push(RemovedTarget {
+                        block: block.name.clone(),
+                        target,
+                        name,
+                        line,
+                    });
+                }
+            }
+        }
+        removals
+    }
+
+    /// Report the removal of the target from `path`.
+    fn diagnostic(self, path: &Path) -> Diagnostic {
+        let RemovedTarget {
+            block,
+            target,
+            name,
+            line,
+        } = self;
+        let written = match &name {
+            Some(name) => format!("{}:{name}", target.display()),
+            None => target.display().to_string(),
+        };
+        let acknowledgment = match &block {
+            Some(block_name) => format!("{}#{block_name}", path.display()),
+            None => path.display().to_string(),
+        };
+        let mut diagnostic = Diagnostic::new(
+            Code::RemovedTarget,
+            path,
+            None,
+            format!(
+                "Removed `{written}` from \"then-change\" in {path:?}, originally at line {line}, but it was not modified. Modify it too, or acknowledge the removal with a \"{REMOVAL_TRAILER}: {acknowledgment}\" trailer."
+            ),
+        )
+        .with_block(block)
+        .with_target(&target);
+        if let Some(name) = &name {
+            diagnostic = diagnostic.with_target_block(name);
+        }
+        diagnostic
+    }
+}
+
+/// Check if `target`, or its block named `name`, changed, e.g. by being
+/// deleted or removed.
+fn is_target_changed<E: Engine + ?Sized>(
+    engine: &E,
+    cache: &ParseCache,
+    policy: Policy,
+    target: &Path,
+    name: Option<&str>,
+) -> bool {
+    engine.matches([target]).flatten().any(|dependent| {
+        let Some(name) = name else {
+            return true;
+        };
+        if engine.is_deleted(&dependent) || engine.is_binary(&dependent) {
+            return true;
+        }
+        let Ok(parsed) = cache.parse(engine, &dependent, policy.max_file_size) else {
+            return false;
+        };
+        match find_named_block(parsed.blocks.iter().cloned(), name).0 {
+            Some(Ok(found)) => is_block_modified(engine, cache, policy, &dependent, &found),
+            Some(Err(_)) => false,
+            None => is_original_block_removed(engine, &dependent, name),
+        }
+    })
+}
+
+/// Check if `block` of `path` was modified, with the context lines of `policy`.
+fn is_block_modified<E: Engine + ?Sized>(
+    engine: &E,
+    cache: &ParseCache,
+    policy: Policy,
+    path: &Path,
+    block: &Block,
+) -> bool {
+    engine.is_range_modified(path, policy.widen(block.lines()))
+        && !(policy.compare_content && is_block_moved(engine, cache, policy, path, block))
+}
+
+/// Check if the lines of `block` of `path`, with the context lines of `policy`,
+/// have the same content as the lines of the same block in the original
+/// content of `path`, i.e. it only moved. `false` if the original content is
+/// unknown.
+///
+/// Named blocks are found by their name, and unnamed blocks by their position
+/// among the unnamed blocks of the file.
+fn is_block_moved<E: Engine + ?Sized>(
+    engine: &E,
+    cache: &ParseCache,
+    policy: Policy,
+    path: &Path,
+    block: &Block,
+) -> bool {
+    let (Ok(current), Ok(original)) = (cache.read(engine, path), cache.original(engine, path))
+    else {
+        return false;
+    };
+    is_moved(&current, &original, policy, block)
+}
+
+/// Check if the lines of `block` in `current`, with the context lines of
+/// `policy`, are the lines of the same block in `original`.
+fn is_moved(current: &FileContent, original: &FileContent, policy: Policy, block: &Block) -> bool {
+    let moved = match &block.name {
+        Some(name) => original
+            .blocks
+            .iter()
+            .find(|moved| moved.name.as_ref() == Some(name)),
+        None => {
+            let Some(position) = current
+                .blocks
+                .iter()
+                .filter(|other| other.name.is_none())
+                .position(|other| other.range == block.range)
+            else {
+                return false;
+            };
+            original
+                .blocks
+                .iter()
+                .filter(|moved| moved.name.is_none())
+                .nth(position)
+        }
+    };
+    moved.is_some_and(|moved| {
+        current.lines(policy.widen(block.lines())) == original.lines(policy.widen(moved.lines()))
+    })
+}
+
+/// Check if the block named `name` of the original content of `dependent`
+/// was removed from it, at least in part.
+fn is_original_block_removed<E: Engine + ?Sized>(engine: &E, dependent: &Path, name: &str) -> bool {
+    let Ok(reader) = engine.read_original(dependent) else {
+        return false;
+    };
+    match find_named_block(Parser::scan(dependent, reader), name).0 {
+        Some(Ok(block)) => engine.removed_lines(dependent, block.lines()).is_some(),
+        _ => false,
+    }
+}
+
+/// The blocks of a file, parsed once.
+struct ParsedFile {
+    blocks: Vec<Result<Block, Vec<Diagnostic>>>,
+    /// The number of lines of the file.
+    lines: usize,
+}
+
+impl ParsedFile {
+    fn new(path: &Path, reader: impl io::BufRead + 'static) -> Self {
+        let mut parser = Parser::scan(path, reader);
+        let blocks = parser.by_ref().collect();
+        ParsedFile {
+            blocks,
+            lines: parser.lines_read(),
+        }
+    }
+
+    /// Skip an oversized file, with a note if it contains directives so the
+    /// limit can be raised for it.
+    fn skipped(path: &Path, size: u64, limit: u64, has_directives: bool) -> Self {
+        tracing::debug!(size, limit, "skipping oversized file");
+        let blocks = if has_directives {
+            let note = Diagnostic::new(
+                Code::OversizedFile,
+                path,
+                None,
+                format!(
+                    "Skipped {path:?} since it is larger than {limit} bytes ({size} bytes), but it contains directives. Raise `--max-file-size` to check it."
+                ),
+            )
+            .with_severity(Severity::Note);
+            vec![Err(vec![note])]
+        } else {
+            Vec::new()
+        };
+        ParsedFile { blocks, lines: 0 }
+    }
+}
+
+/// The content of a file and its blocks, read once to compare the content of
+/// blocks with `compare_content`.
+struct FileContent {
+    lines: Vec<String>,
+    blocks: Vec<Block>,
+}
+
+impl FileContent {
+    fn new(path: &Path, reader: io::Result<Box<dyn io::BufRead>>) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader?.read_to_end(&mut bytes)?;
+        let text = String::from_utf8_lossy(&bytes);
+        Ok(FileContent {
+            lines: text.lines().map(str::to_owned).collect(),
+            blocks: crate::parse_str(path, &text).flatten().collect(),
+        })
+    }
+
+    /// Get the lines from `start` to `end`, starting at 1.
+    fn lines(&self, (start, end): (usize, usize)) -> &[String] {
+        let end = end.min(self.lines.len());
+        self.lines.get(start - 1..end).unwrap_or_default()
+    }
+}
+
+/// The files parsed by checkers, keyed by path and the fingerprint of their
+/// content, so files targeted by several blocks are parsed only once.
+///
+/// Shared between checkers with [`CheckerBuilder::cache`], e.g. by a
+/// long-running process checking the same files repeatedly. Files are parsed
+/// again once their fingerprint changes.
+#[derive(Default)]
+pub struct ParseCache {
+    files: Mutex<HashMap<CacheKey, Arc<ParsedFile>>>,
+    /// The content of files, read when comparing the content of blocks.
+    contents: Mutex<HashMap<CacheKey, Arc<FileContent>>>,
+    /// The original content of files, keyed by its own fingerprint.
+    originals: Mutex<HashMap<CacheKey, Arc<FileContent>>>,
+}
+
+/// A path and the fingerprint of its content.
+type CacheKey = (PathBuf, Option<String>);
+
+impl ParseCache {
+    /// Get the blocks of `path`, parsing it unless it was parsed already or
+    /// is larger than `max_file_size` bytes.
+    fn parse<E: Engine + ?Sized>(
+        &self,
+        engine: &E,
+        path: &Path,
+        max_file_size: Option<u64>,
+    ) -> io::Result<Arc<ParsedFile>> {
+        if let Some((size, limit)) = engine.size(path).zip(max_file_size) {
+            if size > limit {
+                return Self::skip(engine, path, size, limit);
+            }
+        }
+        let key = (path.to_owned(), engine.fingerprint(path));
+        if let Some(parsed) = self.files.lock().unwrap().get(&key) {
+            return Ok(parsed.clone());
+        }
+        let parsed = Arc::new(ParsedFile::new(path, engine.read(path)?));
+        self.files.lock().unwrap().insert(key, parsed.clone());
+        Ok(parsed)
+    }
+
+    /// Get the content of `path`, reading it unless it was read already.
+    fn read<E: Engine + ?Sized>(&self, engine: &E, path: &Path) -> io::Result<Arc<FileContent>> {
+        let key = (path.to_owned(), engine.fingerprint(path));
+        Self::get_or_read(&self.contents, key, || {
+            FileContent::new(path, engine.read(path))
+        })
+    }
+
+    /// Get the original content of `path`, reading it unless it was read
+    /// already.
+    fn original<E: Engine + ?Sized>(
+        &self,
+        engine: &E,
+        path: &Path,
+    ) -> io::Result<Arc<FileContent>> {
+        let key = (path.to_owned(), engine.original_fingerprint(path));
+        Self::get_or_read(&self.originals, key, || {
+            FileContent::new(path, engine.read_original(path))
+        })
+    }
+
+    fn get_or_read(
+        contents: &Mutex<HashMap<CacheKey, Arc<FileContent>>>,
+        key: CacheKey,
+        read: impl FnOnce() -> io::Result<FileContent>,
+    ) -> io::Result<Arc<FileContent>> {
+        if let Some(content) = contents.lock().unwrap().get(&key) {
+            return Ok(content.clone());
+        }
+        let content = Arc::new(read()?);
+        contents.lock().unwrap().insert(key, content.clone());
+        Ok(content)
+    }
+
+    /// Skip an oversized file.
+    fn skip<E: Engine + ?Sized>(
+        engine: &E,
+        path: &Path,
+        size: u64,
+        limit: u64,
+    ) -> io::Result<Arc<ParsedFile>> {
+        let has_directives = has_directives(engine.read(path)?)?;
+        Ok(Arc::new(ParsedFile::skipped(
+            path,
+            size,
+            limit,
+            has_directives,
+        )))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<E: AsyncEngine + ?Sized> Checker<'_, E> {
+    /// Check the files for dependent changes with an [`AsyncEngine`], as
+    /// [`Checker::check`] does with an [`Engine`]. Files are read again for
+    /// each check, and no statistics are recorded.
+    pub async fn check_async(&self) -> Result<(), Vec<Diagnostic>> {
+        let mut problems = Vec::new();
+        let (paths, filter) = if self.paths.is_empty() {
+            problems.extend(self.engine.diagnostics().await);
+            let paths = self.engine.matches(Vec::new()).await;
+            (paths.into_iter().flatten().collect(), true)
+        } else {
+            (self.paths.clone(), false)
+        };
+        for path in paths {
+            problems.extend(self.check_file_async(&path, filter).await);
+        }
+
+        let mut couplings = Couplings::default();
+        let errors = problems
+            .into_iter()
+            .filter_map(|problem| {
+                configure(problem, &self.severities, &self.messages, &mut couplings)
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check `path` as [`CheckIter`] does, skipping it if ignored and
+    /// `filter`.
+    async fn check_file_async(&self, path: &Path, filter: bool) -> Vec<Diagnostic> {
+        let (engine, policy) = (self.engine, self.policy);
+        if is_outside_root(path) {
+            return vec![escaping_path(path)];
+        }
+        if filter && engine.is_ignored(path).await {
+            return Vec::new();
+        }
+        let mut problems = Vec::new();
+        for files in generated_patterns(&self.generated, path) {
+            let matches = engine.matches(vec![files.clone()]).await;
+            if !matches.iter().any(Result::is_ok) {
+                problems.push(unmodified_generated(path, &files));
+            }
+        }
+        if engine.is_binary(path).await {
+            return problems;
+        }
+        if !scans(&self.scan, path) {
+            let source = engine.read(path).await;
+            if source.is_ok_and(|source| has_directives(source.as_bytes()).unwrap_or_default()) {
+                problems.push(unscanned_file(path));
+            }
+            return problems;
+        }
+        let deleted = engine.is_deleted(path).await;
+        if deleted && !policy.removed_blocks && !policy.removed_targets {
+            return problems;
+        }
+        let parsed = if deleted {
+            ParsedFile {
+                blocks: Vec::new(),
+                lines: 0,
+            }
+        } else {
+            match parse_async(engine, path, policy.max_file_size).await {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    problems.push(unreadable(path, &error));
+                    return problems;
+                }
+            }
+        };
+        if !deleted {
+            problems.extend(unannotated_file(&self.require_blocks, path, &parsed.blocks));
+        }
+
+        let mut removed = Vec::new();
+        if policy.removed_blocks || policy.removed_targets {
+            // Problems of the original content were reported when it was
+            // current.
+            let original = match engine.read_original(path).await {
+                Ok(source) => Some(ParsedFile::new(path, io::Cursor::new(source.into_bytes()))),
+                Err(_) => None,
+            };
+            let original = original.map(|original| original.blocks.into_iter().flatten());
+            let original = original.into_iter().flatten().collect::<Vec<_>>();
+            if policy.removed_blocks {
+                for block in removal_candidates(original.iter().cloned(), &parsed.blocks) {
+                    let lines = engine.removed_lines(path, block.lines()).await;
+                    if is_wholly_removed(&block, lines) {
+                        removed.push((path.to_owned(), block));
+                    }
+                }
+            }
+            if policy.removed_targets {
+                for removal in RemovedTarget::find(policy, path, original, &parsed.blocks, &removed)
+                {
+                    if !engine
+                        .is_removal_acknowledged(path, removal.block.as_deref())
+                        .await
+                        && !is_target_changed_async(
+                            engine,
+                            policy,
+                            &removal.target,
+                            removal.name.as_deref(),
+                        )
+                        .await
+                    {
+                        problems.push(removal.diagnostic(path));
+                    }
+                }
+            }
+        }
+
+        for block in parsed.blocks {
+            match block {
+                Ok(block) => {
+                    problems.extend(check_block_async(engine, policy, path, block, false).await)
+                }
+                Err(error) => problems.extend(error),
+            }
+        }
+        for (path, block) in removed {
+            problems.extend(check_block_async(engine, policy, &path, block, true).await);
+        }
+        problems
+    }
+}
+
+/// Check the targets of a block of `path` with an [`AsyncEngine`], as
+/// [`Checker::check_block`] does.
+#[cfg(feature = "tokio")]
+async fn check_block_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    policy: Policy,
+    path: &Path,
+    block: Block,
+    removed: bool,
+) -> Vec<Diagnostic> {
+    let changes = if removed {
+        engine.removed_lines(path, block.lines()).await
+    } else {
+        let mut changes: Option<Vec<String>> = None;
+        for range in policy.trigger_ranges(&block) {
+            if let Some(more) = engine.range_changes(path, range).await {
+                changes.get_or_insert_with(Vec::new).extend(more);
+            }
+        }
+        changes
+    };
+    let Some(changes) = changes else {
+        return Vec::new();
+    };
+    if !removed
+        && policy.compare_content
+        && is_block_moved_async(engine, policy, path, &block).await
+    {
+        return Vec::new();
+    }
+    if let Some(name) = &block.name {
+        if engine.is_block_ignored(path, name).await {
+            return Vec::new();
+        }
+    }
+
+    let (mut block, unnamed_patterns, named_patterns) =
+        ModifiedBlock::new(path, block, changes, policy.normalize_separators);
+    block.removed = removed;
+    let mut targets = Vec::new();
+    let patterns = unnamed_patterns.keys().cloned().collect();
+    for pattern in engine
+        .matches(patterns)
+        .await
+        .into_iter()
+        .flat_map(Result::err)
+    {
+        targets.push(TargetFacts {
+            line: unnamed_patterns[&pattern],
+            name: None,
+            excluded: is_excused_async(engine, policy, &pattern).await,
+            state: TargetState::Unmatched {
+                pattern,
+                last_edit: None,
+            },
+        });
+    }
+    if policy.deleted_targets == DeletedTargetPolicy::Fail {
+        for (pattern, line) in &unnamed_patterns {
+            for dependent in engine
+                .matches(vec![pattern.clone()])
+                .await
+                .into_iter()
+                .flatten()
+            {
+                if engine.is_deleted(&dependent).await {
+                    targets.push(TargetFacts {
+                        line: *line,
+                        name: None,
+                        excluded: false,
+                        state: TargetState::Deleted(dependent),
+                    });
+                }
+            }
+        }
+    }
+
+    for (pattern, (name, line)) in &named_patterns {
+        for result in engine.matches(vec![pattern.clone()]).await {
+            let mut target = TargetFacts {
+                line: *line,
+                name: Some(name.clone()),
+                excluded: false,
+                state: TargetState::Binary,
+            };
+            let dependent = match result {
+                Ok(path) => path,
+                Err(pattern) => {
+                    target.excluded = is_excused_async(engine, policy, &pattern).await;
+                    target.state = TargetState::Unmatched {
+                        last_edit: if policy.blame_targets {
+                            last_edit_async(engine, policy, &pattern, name).await
+                        } else {
+                            None
+                        },
+                        pattern,
+                    };
+                    targets.push(target);
+                    continue;
+                }
+            };
+            target.state = if engine.is_deleted(&dependent).await {
+                TargetState::Deleted(dependent)
+            } else if engine.is_binary(&dependent).await {
+                TargetState::Binary
+            } else {
+                match parse_async(engine, &dependent, policy.max_file_size).await {
+                    Ok(parsed) => {
+                        let (found, comment) = find_named_block(parsed.blocks.into_iter(), name);
+                        match found {
+                            Some(Ok(found)) => {
+                                let modified =
+                                    is_block_modified_async(engine, policy, &dependent, &found)
+                                        .await;
+                                target.excluded =
+                                    !modified && is_excused_async(engine, policy, &dependent).await;
+                                TargetState::Found {
+                                    last_edit: if !modified && policy.blame_targets {
+                                        engine.last_edit(&dependent, found.lines()).await
+                                    } else {
+                                        None
+                                    },
+                                    dependent,
+                                    modified,
+                                }
+                            }
+                            Some(Err(problems)) => TargetState::Invalid(problems),
+                            None => {
+                                let original_removed = removed
+                                    && is_original_block_removed_async(engine, &dependent, name)
+                                        .await;
+                                target.excluded =
+                                    removed && is_excused_async(engine, policy, &dependent).await;
+                                TargetState::Missing {
+                                    dependent,
+                                    comment,
+                                    end: parsed.lines + 1,
+                                    original_removed,
+                                }
+                            }
+                        }
+                    }
+                    Err(error) => TargetState::Unreadable(dependent, error),
+                }
+            };
+            targets.push(target);
+        }
+    }
+    block.problems(policy, targets)
+}
+
+/// Parse `path` with an [`AsyncEngine`], unless it is larger than
+/// `max_file_size` bytes.
+#[cfg(feature = "tokio")]
+async fn parse_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    path: &Path,
+    max_file_size: Option<u64>,
+) -> io::Result<ParsedFile> {
+    let source = engine.read(path).await?;
+    if let Some((size, limit)) = engine.size(path).await.zip(max_file_size) {
+        if size > limit {
+            let has_directives = has_directives(source.as_bytes())?;
+            return Ok(ParsedFile::skipped(path, size, limit, has_directives));
+        }
+    }
+    Ok(ParsedFile::new(path, io::Cursor::new(source.into_bytes())))
+}
+
+/// Check if `target` is excluded and needn't change, as allowed by `policy`.
+#[cfg(feature = "tokio")]
+async fn is_excused_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    policy: Policy,
+    target: &Path,
+) -> bool {
+    !policy.require_excluded_targets && engine.is_excluded(target).await
+}
+
+/// Look up the last edit of the block named `name` in `path` with an
+/// [`AsyncEngine`], as [`last_edit`] does.
+#[cfg(feature = "tokio")]
+async fn last_edit_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    policy: Policy,
+    path: &Path,
+    name: &str,
+) -> Option<LastEdit> {
+    let parsed = parse_async(engine, path, policy.max_file_size).await.ok()?;
+    let found = find_named_block(parsed.blocks.into_iter(), name).0?.ok()?;
+    engine.last_edit(path, found.lines()).await
+}
+
+/// Check if `target`, or its block named `name`, changed with an
+/// [`AsyncEngine`], as [`is_target_changed`] does.
+#[cfg(feature = "tokio")]
+async fn is_target_changed_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    policy: Policy,
+    target: &Path,
+    name: Option<&str>,
+) -> bool {
+    let dependents = engine.matches(vec![target.to_owned()]).await;
+    for dependent in dependents.into_iter().flatten() {
+        let Some(name) = name else {
+            return true;
+        };
+        if engine.is_deleted(&dependent).await || engine.is_binary(&dependent).await {
+            return true;
+        }
+        let Ok(parsed) = parse_async(engine, &dependent, policy.max_file_size).await else {
+            continue;
+        };
+        let changed = match find_named_block(parsed.blocks.into_iter(), name).0 {
+            Some(Ok(found)) => is_block_modified_async(engine, policy, &dependent, &found).await,
+            Some(Err(_)) => false,
+            None => is_original_block_removed_async(engine, &dependent, name).await,
+        };
+        if changed {
+            return true;
+        }
+    }
+    false
+}
+
+/// Check if `block` of `path` was modified with an [`AsyncEngine`], as
+/// [`is_block_modified`] does.
+#[cfg(feature = "tokio")]
+async fn is_block_modified_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    policy: Policy,
+    path: &Path,
+    block: &Block,
+) -> bool {
+    engine
+        .is_range_modified(path, policy.widen(block.lines()))
+        .await
+        && !(policy.compare_content && is_block_moved_async(engine, policy, path, block).await)
+}
+
+/// Check if `block` of `path` only moved with an [`AsyncEngine`], as
+/// [`is_block_moved`] does.
+#[cfg(feature = "tokio")]
+async fn is_block_moved_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    policy: Policy,
+    path: &Path,
+    block: &Block,
+) -> bool {
+    let content = |source: String| -> io::Result<Box<dyn io::BufRead>> {
+        Ok(Box::new(io::Cursor::new(source.into_bytes())))
+    };
+    let (Ok(current), Ok(original)) = (
+        engine
+            .read(path)
+            .await
+            .and_then(|source| FileContent::new(path, content(source))),
+        engine
+            .read_original(path)
+            .await
+            .and_then(|source| FileContent::new(path, content(source))),
+    ) else {
+        return false;
+    };
+    is_moved(&current, &original, policy, block)
+}
+
+/// Check if the block named `name` of the original content of `dependent`
+/// was removed with an [`AsyncEngine`], as [`is_original_block_removed`]
+/// does.
+#[cfg(feature = "tokio")]
+async fn is_original_block_removed_async<E: AsyncEngine + ?Sized>(
+    engine: &E,
+    dependent: &Path,
+    name: &str,
+) -> bool {
+    let Ok(source) = engine.read_original(dependent).await else {
+        return false;
+    };
+    let original = ParsedFile::new(dependent, io::Cursor::new(source.into_bytes()));
+    match find_named_block(original.blocks.into_iter(), name).0 {
+        Some(Ok(block)) => engine
+            .removed_lines(dependent, block.lines())
+            .await
+            .is_some(),
+        _ => false,
+    }
+}
+
+/// The couplings of blocks with problems reported so far, so that a coupling
+/// checked from both of its ends, e.g. in a cycle of blocks requiring each
+/// other, is reported once.
+#[derive(Debug, Default)]
+pub(crate) struct Couplings(HashSet<(Endpoint, Endpoint)>);
+
+/// A file, or a block of it if named.
+type Endpoint = (PathBuf, Option<String>);
+
+impl Couplings {
+    /// Record the coupling of the block of `diagnostic` with its target,
+    /// returning whether it was already reported from the other end.
+    pub(crate) fn is_reported(&mut self, diagnostic: &Diagnostic) -> bool {
+        let Some(target) = &diagnostic.target else {
+            return false;
+        };
+        let source = (diagnostic.path.clone(), diagnostic.block.clone());
+        let target = (target.clone(), diagnostic.target_block.clone());
+        if self.0.contains(&(target.clone(), source.clone())) {
+            return true;
+        }
+        self.0.insert((source, target));
+        false
+    }
+}
+
+/// Targets of a block by path, with the line they are listed on.
+pub(crate) type Targets = BTreeMap<PathBuf, usize>;
+
+/// Named targets of a block by path, with the name of the block to change and
+/// the line they are listed on.
+pub(crate) type NamedTargets = BTreeMap<PathBuf, (String, usize)>;
+
+/// What an engine found out about a target of a modified block, from which
+/// [`ModifiedBlock::problems`] decides the problems of the block without
+/// further I/O, so every way of checking blocks reports the same problems.
+struct TargetFacts {
+    /// The line the target is listed on.
+    line: usize,
+    /// The name of the block of the target to change, if any.
+    name: Option<String>,
+    /// Whether an ignore file excludes the target, if it matters.
+    excluded: bool,
+    state: TargetState,
+}
+
+/// The state of a target of a modified block.
+enum TargetState {
+    /// The pattern of the target matches no changed file. The last edit of
+    /// the block to change is looked up with `blame_targets`.
+    Unmatched {
+        pattern: PathBuf,
+        last_edit: Option<LastEdit>,
+    },
+    /// A changed file matched by the target was deleted.
+    Deleted(PathBuf),
+    /// A changed file matched by the target is binary, so it can't have
+    /// blocks, and changing it at all is the best that can be done.
+    Binary,
+    /// A changed file matched by the target can't be read.
+    Unreadable(PathBuf, io::Error),
+    /// The block to change was found in a changed file matched by the target.
+    Found {
+        dependent: PathBuf,
+        modified: bool,
+        last_edit: Option<LastEdit>,
+    },
+    /// Problems found looking for the block to change.
+    Invalid(Vec<Diagnostic>),
+    /// A changed file matched by the target has no block to change. The
+    /// block may be added at line `end`, in the comment style of the file.
+    Missing {
+        dependent: PathBuf,
+        comment: Option<Comment>,
+        end: usize,
+        /// Whether the block was removed from the original content of the
+        /// file, only looked up for removed blocks.
+        original_removed: bool,
+    },
+}
+
+/// A modified block whose targets are being checked.
+pub(crate) struct ModifiedBlock<'a> {
+    path: &'a Path,
+    name: Option<String>,
+    range: (usize, usize),
+    changes: Vec<String>,
+    comment: Comment,
+    /// Whether the block was removed, so its lines refer to the original
+    /// content of the file.
+    removed: bool,
+    /// Targets pointing outside of the repository, as written.
+    escaping: Vec<Pattern>,
+}
+
+impl<'a> ModifiedBlock<'a> {
+    /// Split the targets of a block of `path`, resolved, by whether they name
+    /// a block.
+    pub(crate) fn new(
+        path: &'a Path,
+        block: Block,
+        changes: Vec<String>,
+        normalize_separators: bool,
+    ) -> (Self, Targets, NamedTargets) {
+        let mut unnamed = BTreeMap::new();
+        let mut named = BTreeMap::new();
+        let mut escaping = Vec::new();
+        for pattern in resolve_patterns(path, block.targets, &block.ignored, normalize_separators) {
+            let pattern = match pattern {
+                Ok(pattern) => pattern,
+                Err(pattern) => {
+                    escaping.push(pattern);
+                    continue;
+                }
+            };
+            // Engines report patterns matching nothing relative to the root.
+            let path = root_relative(&pattern.path);
+            match pattern.name {
+                Some(name) => {
+                    named.insert(path, (name, pattern.line));
+                }
+                None => {
+                    unnamed.insert(path, pattern.line);
+                }
+            }
+        }
+        let block = Self {
+            path,
+            name: block.name,
+            range: block.range,
+            changes,
+            comment: block.comment,
+            removed: false,
+            escaping,
+        };
+        (block, unnamed, named)
+    }
+
+    /// Decide the problems of the block as configured by `policy`, given what
+    /// the engine found out about its `targets`.
+    fn problems(&self, policy: Policy, targets: Vec<TargetFacts>) -> Vec<Diagnostic> {
+        let mut problems = self.escaping_targets().collect::<Vec<_>>();
+        for target in targets {
+            let line = target.line;
+            let with_name = |diagnostic: Diagnostic| match &target.name {
+                Some(name) => diagnostic.with_target_block(name),
+                None => diagnostic,
+            };
+            // Excluded targets needn't change unless required to.
+            let unmodified = |dependent: &Path, last_edit| {
+                (policy.require_excluded_targets || !target.excluded).then(|| {
+                    with_name(self.unmodified_target(line, dependent)).with_last_edit(last_edit)
+                })
+            };
+            match &target.state {
+                TargetState::Unmatched { pattern, last_edit } => {
+                    problems.extend(unmodified(pattern, last_edit.clone()));
+                }
+                TargetState::Deleted(dependent) => {
+                    if policy.deleted_targets == DeletedTargetPolicy::Fail {
+                        problems.push(with_name(self.deleted_target(line, dependent)));
+                    }
+                }
+                TargetState::Binary => {}
+                TargetState::Unreadable(dependent, error) => {
+                    problems.push(self.unreadable_target(line, dependent, error));
+                }
+                TargetState::Found {
+                    dependent,
+                    modified,
+                    last_edit,
+                } => {
+                    if !modified {
+                        problems.extend(unmodified(dependent, last_edit.clone()));
+                    }
+                }
+                TargetState::Invalid(invalid) => problems.extend(invalid.iter().cloned()),
+                // A removed block needs no block to refer to, only the named
+                // block to change, e.g. by being removed too.
+                TargetState::Missing {
+                    dependent,
+                    original_removed,
+                    ..
+                } if self.removed => {
+                    if !original_removed {
+                        problems.extend(unmodified(dependent, None));
+                    }
+                }
+                TargetState::Missing {
+                    dependent,
+                    comment,
+                    end,
+                    ..
+                } => {
+                    let name = target.name.as_deref().unwrap_or_default();
+                    problems.push(self.missing_named_block(
+                        line,
+                        name,
+                        dependent,
+                        comment.clone(),
+                        *end,
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    fn diagnostic(&self, code: Code, line: usize, message: String) -> Diagnostic {
+        Diagnostic::new(code, self.path, Some(line), message)
+            .with_range(self.range)
+            .with_block(self.name.clone())
+            .with_changes(&self.changes)
+    }
+
+    /// Describe where the `then-change` on `line` is, e.g. `in "a.ts" at line 3`.
+    fn origin(&self, line: usize) -> String {
+        let path = self.path;
+        if self.removed {
+            format!("removed from {path:?} at line {line}")
+        } else {
+            format!("in {path:?} at line {line}")
+        }
+    }
+
+    fn unmodified_target(&self, line: usize, target: &Path) -> Diagnostic {
+        let origin = self.origin(line);
+        self.diagnostic(
+            Code::UnmodifiedTarget,
+            line,
+            format!("Expected {target:?} to be modified because of \"then-change\" {origin}."),
+        )
+        .with_target(target)
+    }
+
+    /// Report the targets pointing outside of the repository.
+    pub(crate) fn escaping_targets(&self) -> impl Iterator<Item = Diagnostic> + '_ {
+        self.escaping.iter().map(|pattern| {
+            let (path, line) = (self.path, pattern.line);
+            let target = pattern.path.display();
+            self.diagnostic(
+                Code::EscapingPath,
+                line,
+                format!(
+                    "Path `{target}` for \"then-change\" in {path:?} at line {line} points outside of the repository."
+                ),
+            )
+            .with_target(&pattern.path)
+        })
+    }
+
+    fn deleted_target(&self, line: usize, dependent: &Path) -> Diagnostic {
+        let origin = self.origin(line);
+        self.diagnostic(
+            Code::DeletedTarget,
+            line,
+            format!(
+                "Expected {dependent:?} to be modified because of \"then-change\" {origin}, but it was deleted."
+            ),
+        )
+        .with_target(dependent)
+    }
+
+    pub(crate) fn unreadable_target(
+        &self,
+        line: usize,
+        dependent: &Path,
+        error: &io::Error,
+    ) -> Diagnostic {
+        let path = self.path;
+        self.diagnostic(
+            Code::MissingTarget,
+            line,
+            format!(
+                "Could not open {dependent:?} for \"then-change\" in {path:?} at line {line}: {error:?}"
+            ),
+        )
+        .with_target(dependent)
+    }
+
+    /// Report that `dependent` has no block named `name`, suggesting to add
+    /// one at line `end` in the comment style of the dependent if it has any.
+    pub(crate) fn missing_named_block(
+        &self,
+        line: usize,
+        name: &str,
+        dependent: &Path,
+        comment: Option<Comment>,
+        end: usize,
+    ) -> Diagnostic {
+        let path = self.path;
+        let comment = comment.unwrap_or_else(|| self.comment.clone());
+        let mut back_reference = format!("/{}", path.display());
+        if let Some(block_name) = &self.name {
+            back_reference = format!("{back_reference}:{block_name}");
+        }
+        let if_changed = comment.wrap(&format!("if-changed({name})"));
+        let then_change = comment.wrap(&format!("then-change({back_reference})"));
+        self.diagnostic(
+            Code::MissingNamedBlock,
+            line,
+            format!(
+                "Could not find \"if-changed\" with name \"{name}\" in {dependent:?} for \"then-change\" in {path:?} at line {line}."
+            ),
+        )
+        .with_target(dependent)
+        .with_target_block(name)
+        .with_suggestion(Suggestion {
+            message: format!(
+                "add `{}` to {dependent:?} around the lines to change with {path:?}",
+                if_changed.trim_start()
+            ),
+            path: dependent.to_owned(),
+            line: end,
+            text: format!("{if_changed}\n{then_change}\n"),
+        })
+    }
+}
+
+/// Search the blocks of a dependent for the one named `name`, stopping at the
+/// first error. Also returns the comment style of the blocks passed over.
+pub(crate) fn find_named_block(
+    mut blocks: impl Iterator<Item = Result<Block, Vec<Diagnostic>>>,
+    name: &str,
+) -> (Option<Result<Block, Vec<Diagnostic>>>, Option<Comment>) {
+    let mut comment = None;
+    let found = blocks.find_map(|block| match block {
+        Ok(block) if block.name.as_deref() == Some(name) => Some(Ok(block)),
+        Ok(block) => {
+            comment.get_or_insert(block.comment);
+            None
+        }
+        // Lossy decoding and backslash separators don't hide blocks, unlike
+        // other problems.
+        Err(error)
+            if error.iter().all(|problem| {
+                matches!(problem.code, Code::InvalidUtf8 | Code::BackslashSeparator)
+            }) =>
+        {
+            None
+        }
+        Err(error) => Some(Err(error)),
+    });
+    (found, comment)
+}
+
+impl<'a, E: ?Sized> CheckerBuilder<'a, E> {
+    /// Check `path`, relative to the repository root. May be given several
+    /// times. Without it, all changed files that are not ignored are checked,
+    /// and problems found outside of files, e.g. in commit trailers, are
+    /// included.
+    pub fn path(mut self, path: impl AsRef<Path>) -> Self {
+        self.paths.push(path.as_ref().to_owned());
+        self
+    }
+
+    /// Report problems of the given kinds with the given severities instead
+    /// of [`Severity::Error`].
+    pub fn severity_overrides(
+        mut self,
+        overrides: impl IntoIterator<Item = (Code, Severity)>,
+    ) -> Self {
+        self.severities.extend(overrides);
+        self
+    }
+
+    /// Share the files parsed with other checkers.
+    pub fn cache(mut self, cache: Arc<ParseCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Skip files larger than `max_file_size` bytes instead of parsing them,
+    /// e.g. generated or vendored files. Skipped files containing directives
+    /// are reported with a [`Severity::Note`].
+    pub fn max_file_size(mut self, max_file_size: Option<u64>) -> Self {
+        self.policy.max_file_size = max_file_size;
+        self
+    }
+
+    /// Choose whether deleting a target satisfies a modified block, instead
+    /// of [`DeletedTargetPolicy::Satisfy`].
+    pub fn deleted_targets(mut self, policy: DeletedTargetPolicy) -> Self {
+        self.policy.deleted_targets = policy;
+        self
+    }
+
+    /// Also check the blocks removed from files, e.g. with the files deleted,
+    /// whose targets must change as if the blocks were modified. Requires an
+    /// engine knowing the original content of files.
+    pub fn removed_blocks(mut self, removed_blocks: bool) -> Self {
+        self.policy.removed_blocks = removed_blocks;
+        self
+    }
+
+    /// Report the targets removed from the blocks of files, or with the
+    /// blocks, unless they changed too or the removal was acknowledged, e.g.
+    /// with a `remove-if-changed` trailer. Requires an engine knowing the
+    /// original content of files.
+    pub fn removed_targets(mut self, removed_targets: bool) -> Self {
+        self.policy.removed_targets = removed_targets;
+        self
+    }
+
+    /// Count changes within `lines` lines around blocks, e.g. right above
+    /// their `if-changed`, as changes of the blocks, both to trigger them and
+    /// to satisfy targets naming them. By default, only changes within blocks
+    /// count.
+    pub fn block_context_lines(mut self, lines: usize) -> Self {
+        self.policy.block_context_lines = lines;
+        self
+    }
+
+    /// Choose whether changes of the `if-changed` and `then-change` lines of
+    /// blocks, e.g. adding a target, leave the blocks unmodified, so
+    /// maintaining directives never requires changing targets. Blocks are
+    /// still modified by changes of the lines between them.
+    pub fn ignore_directive_changes(mut self, ignore_directive_changes: bool) -> Self {
+        self.policy.ignore_directive_changes = ignore_directive_changes;
+        self
+    }
+
+    /// Choose whether blocks whose lines have the same content as the same
+    /// block of the original content of their file, by name or else by
+    /// position among the unnamed blocks, are unmodified, both to trigger them and to satisfy targets naming them,
+    /// e.g. blocks moved by lines inserted above them. The original content
+    /// is read from [`Engine::read_original`], so this is only as precise as
+    /// the engine knows it.
+    pub fn compare_content(mut self, compare_content: bool) -> Self {
+        self.policy.compare_content = compare_content;
+        self
+    }
+
+    /// Choose whether `\` in targets separates directories, as on Windows,
+    /// instead of being part of file names. Enabled by default.
+    pub fn normalize_separators(mut self, normalize_separators: bool) -> Self {
+        self.policy.normalize_separators = normalize_separators;
+        self
+    }
+
+    /// Look up the last edit of named target blocks that weren't modified,
+    /// e.g. with `git blame`, so their authors can be asked about the change.
+    /// Disabled by default, as it can be slow for files with long histories.
+    pub fn blame_targets(mut self, blame_targets: bool) -> Self {
+        self.policy.blame_targets = blame_targets;
+        self
+    }
+
+    /// Choose whether targets excluded by `.ifchangedignore` files, see
+    /// [`Engine::is_excluded`], must still change. Enabled by default.
+    pub fn require_excluded_targets(mut self, require_excluded_targets: bool) -> Self {
+        self.policy.require_excluded_targets = require_excluded_targets;
+        self
+    }
+
+    /// Only look for blocks in the files matching `patterns`, e.g. `src/**`,
+    /// in the syntax of `.gitignore`. Other files are skipped without being
+    /// parsed, with a [`Severity::Warning`] if they contain directives.
+    pub fn scan(mut self, patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.scan = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Require the files matching `patterns`, in the syntax of `scan`, to
+    /// contain at least one block, e.g. to keep critical files annotated.
+    /// Changed files without any are reported as [`Code::UnannotatedFile`].
+    pub fn require_blocks(mut self, patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.require_blocks = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Require the files matching the first pattern of each of `rules` to
+    /// change with the files matching the second one, their generator, e.g.
+    /// `("gen/**/*.ts", "proto/**/*.proto")`. Each changed generator is
+    /// checked as if a block spanning it listed the generated files as
+    /// targets, so files that can't carry comments are coupled too.
+    /// Generated files are looked up relative to the directory their
+    /// generator matched from, e.g. `web/package-lock.json` for
+    /// `("package-lock.json", "package.json")` and `web/package.json`.
+    pub fn generated(
+        mut self,
+        rules: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.generated = rules
+            .into_iter()
+            .map(|(generated, generator)| (generated.into(), generator.into()))
+            .collect();
+        self
+    }
+
+    /// Replace the messages of problems according to `messages`, e.g. to
+    /// link to internal documentation.
+    pub fn messages(mut self, messages: MessageConfig) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    pub fn build(self) -> Checker<'a, E> {
+        Checker {
+            engine: self.engine,
+            paths: self.paths,
+            severities: self.severities,
+            messages: self.messages,
+            cache: self.cache,
+            scan: self.scan,
+            require_blocks: self.require_blocks,
+            generated: self.generated,
+            policy: self.policy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{testing::git_test, ChangedFile, GitEngine, MemoryEngine};
+
+    #[test]
+    fn test_checker() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(d.ts)
+                "},
+                "b.ts" => indoc! {"
+                    // if-changed
+                    B,
+                    // then-change(c.ts:c)
+                "},
+                "c.ts" => ""
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let errors = Checker::builder(&engine)
+            .severity_overrides([(Code::MissingNamedBlock, Severity::Warning)])
+            .build()
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.path.to_str().unwrap(), error.code, error.severity))
+                .collect::<Vec<_>>(),
+            [
+                ("a.ts", Code::UnmodifiedTarget, Severity::Error),
+                ("b.ts", Code::MissingNamedBlock, Severity::Warning),
+            ]
+        );
+
+        let mut reported = Vec::new();
+        let summary = Checker::builder(&engine)
+            .severity_overrides([(Code::MissingNamedBlock, Severity::Warning)])
+            .build()
+            .run(&mut reported)
+            .unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                errors: 1,
+                warnings: 1
+            }
+        );
+        assert_eq!(reported.len(), 2);
+
+        // Problems are found lazily, one file at a time.
+        let mut iter = Checker::builder(&engine).build().check_iter();
+        assert_eq!(iter.next().unwrap().path, Path::new("a.ts"));
+        assert_eq!(iter.stats().files, 1);
+        assert_eq!(iter.next().unwrap().path, Path::new("b.ts"));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.stats().files, 3);
+
+        let errors = Checker::builder(&engine)
+            .path("b.ts")
+            .build()
+            .check()
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_parse_cache() {
+        let (tempdir, repo) = git_test! {
+            working: ["a.ts" => "// if-changed(a)\n// then-change()\n"]
+        };
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let cache = ParseCache::default();
+
+        let parsed = cache.parse(&engine, Path::new("a.ts"), None).unwrap();
+        assert_eq!(parsed.blocks.len(), 1);
+        assert_eq!(parsed.lines, 2);
+        assert!(Arc::ptr_eq(
+            &parsed,
+            &cache.parse(&engine, Path::new("a.ts"), None).unwrap()
+        ));
+
+        // A changed file is parsed again.
+        std::fs::write(tempdir.path().join("a.ts"), "A,\n").unwrap();
+        let reparsed = cache.parse(&engine, Path::new("a.ts"), None).unwrap();
+        assert!(reparsed.blocks.is_empty());
+        assert!(cache.parse(&engine, Path::new("b.ts"), None).is_err());
+    }
+
+    #[test]
+    fn test_binary_files() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["b.bin" => "\0\n"]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.bin:b)
+                "},
+                "b.bin" => "\0// if-changed(b)\n",
+                "c.bin" => "\0\u{1}"
+            ]
+        };
+
+        // b.bin, told apart by git, is skipped both as a changed file and as
+        // a target. c.bin is untracked, so it is sniffed instead.
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let mut stats = Stats::default();
+        Checker::builder(&engine)
+            .build()
+            .check_with_stats(&mut stats)
+            .unwrap();
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.binary_files, 3);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let (tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts:b)
+                "}
+            ]
+        };
+        std::fs::write(
+            tempdir.path().join("b.ts"),
+            b"// caf\xe9\n// if-changed(b)\n// then-change(a.ts)\n",
+        )
+        .unwrap();
+
+        // The named block is found despite the note.
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let errors = Checker::builder(&engine).build().check().unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.code, error.severity, error.path.to_str().unwrap()))
+                .collect::<Vec<_>>(),
+            [(Code::InvalidUtf8, Severity::Note, "b.ts")]
+        );
+    }
+
+    #[test]
+    fn test_deleted_targets() {
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("a.ts"),
+                ChangedFile {
+                    content: Some("// if-changed\nA,\n// then-change(b.ts, c.ts:c)\n".into()),
+                    changes: vec![(2, "+A,".into())],
+                    ..Default::default()
+                },
+            ),
+            (PathBuf::from("b.ts"), ChangedFile::default()),
+            (PathBuf::from("c.ts"), ChangedFile::default()),
+        ]);
+        let checker = Checker::builder(&engine).path("a.ts");
+        assert!(checker.build().check().is_ok());
+
+        let checker = Checker::builder(&engine)
+            .path("a.ts")
+            .deleted_targets(DeletedTargetPolicy::Fail);
+        insta::assert_snapshot!(checker
+            .build()
+            .check()
+            .unwrap_err()
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"
+        IC004: Expected "b.ts" to be modified because of "then-change" in "a.ts" at line 3, but it was deleted.
+        IC004: Expected "c.ts" to be modified because of "then-change" in "a.ts" at line 3, but it was deleted.
+        "###);
+    }
+
+    #[test]
+    fn test_removed_blocks() {
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("a.ts"),
+                ChangedFile {
+                    content: Some("B,\n".into()),
+                    changes: vec![
+                        (1, "-// if-changed".into()),
+                        (2, "-A,".into()),
+                        (3, "-// then-change(b.ts)".into()),
+                        (1, "+B,".into()),
+                    ],
+                    original: Some("// if-changed\nA,\n// then-change(b.ts)\n".into()),
+                },
+            ),
+            (
+                PathBuf::from("c.ts"),
+                ChangedFile {
+                    content: None,
+                    changes: vec![
+                        (1, "-// if-changed(c)".into()),
+                        (2, "-C,".into()),
+                        (3, "-// then-change(d.ts:d)".into()),
+                    ],
+                    original: Some("// if-changed(c)\nC,\n// then-change(d.ts:d)\n".into()),
+                },
+            ),
+            (
+                PathBuf::from("d.ts"),
+                ChangedFile {
+                    content: Some("D,\n".into()),
+                    changes: vec![
+                        (1, "-// if-changed(d)".into()),
+                        (3, "-// then-change(c.ts:c)".into()),
+                    ],
+                    original: Some("// if-changed(d)\nD,\n// then-change(c.ts:c)\n".into()),
+                },
+            ),
+        ]);
+        // Deleted files have nothing to check, and d.ts only lost the
+        // directives of its block.
+        assert!(Checker::builder(&engine).build().check().is_ok());
+
+        // c.ts and the block of d.ts were removed together, but b.ts wasn't
+        // changed with the block of a.ts.
+        let mut stats = Stats::default();
+        let errors = Checker::builder(&engine)
+            .removed_blocks(true)
+            .build()
+            .check_with_stats(&mut stats)
+            .unwrap_err();
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"IC002: Expected "b.ts" to be modified because of "then-change" removed from "a.ts" at line 3."###);
+        assert_eq!((stats.files, stats.blocks), (3, 2));
+    }
+
+    #[test]
+    fn test_removed_targets() {
+        let changed = |content: &str| ChangedFile {
+            content: Some(content.into()),
+            changes: vec![(1, "+B,".into())],
+            ..Default::default()
+        };
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("a.ts"),
+                ChangedFile {
+                    content: Some("// if-changed(x)\nA,\n// then-change(b.ts)\n".into()),
+                    changes: vec![
+                        (3, "-// then-change(b.ts, c.ts, d.ts:d)".into()),
+                        (3, "+// then-change(b.ts)".into()),
+                    ],
+                    original: Some(
+                        "// if-changed(x)\nA,\n// then-change(b.ts, c.ts, d.ts:d)\n".into(),
+                    ),
+                },
+            ),
+            (PathBuf::from("b.ts"), changed("B,\n")),
+            (PathBuf::from("c.ts"), changed("C,\n")),
+            (
+                PathBuf::from("e.ts"),
+                ChangedFile {
+                    content: None,
+                    changes: vec![
+                        (1, "-// if-changed".into()),
+                        (2, "-// then-change(f.ts)".into()),
+                    ],
+                    original: Some("// if-changed\n// then-change(f.ts)\n".into()),
+                },
+            ),
+        ]);
+        assert!(Checker::builder(&engine).build().check().is_ok());
+
+        // c.ts changed, but neither the block of d.ts nor f.ts did.
+        let errors = Checker::builder(&engine)
+            .removed_targets(true)
+            .build()
+            .check()
+            .unwrap_err();
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"
+        IC005: Removed `d.ts:d` from "then-change" in "a.ts", originally at line 3, but it was not modified. Modify it too, or acknowledge the removal with a "remove-if-changed: a.ts#x" trailer.
+        IC005: Removed `f.ts` from "then-change" in "e.ts", originally at line 2, but it was not modified. Modify it too, or acknowledge the removal with a "remove-if-changed: e.ts" trailer.
+        "###);
+
+        let engine = engine.with_acknowledged_removals([
+            ("a.ts".into(), Some("x".into())),
+            ("e.ts".into(), Some("e".into())),
+        ]);
+        let errors = Checker::builder(&engine)
+            .removed_targets(true)
+            .build()
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.target.as_deref().unwrap().to_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["f.ts"]
+        );
+    }
+
+    #[test]
+    fn test_cycles() {
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("a.ts"),
+                ChangedFile {
+                    changes: vec![
+                        (1, "-// if-changed(a)".into()),
+                        (2, "-A,".into()),
+                        (3, "-// then-change(b.ts:b)".into()),
+                    ],
+                    original: Some("// if-changed(a)\nA,\n// then-change(b.ts:b)\n".into()),
+                    ..Default::default()
+                },
+            ),
+            (
+                PathBuf::from("b.ts"),
+                ChangedFile {
+                    changes: vec![
+                        (1, "-// if-changed(b)".into()),
+                        (2, "-B,".into()),
+                        (3, "-// then-change(a.ts:a)".into()),
+                    ],
+                    original: Some("// if-changed(b)\nB,\n// then-change(a.ts:a)\n".into()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        // Both blocks were deleted with their files, so each is reported to
+        // require the other. The coupling is reported once.
+        let errors = Checker::builder(&engine)
+            .removed_blocks(true)
+            .deleted_targets(DeletedTargetPolicy::Fail)
+            .build()
+            .check()
+            .unwrap_err();
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"IC004: Expected "b.ts" to be modified because of "then-change" removed from "a.ts" at line 3, but it was deleted."###);
+
+        let mut stats = Stats::default();
+        let errors = Checker::builder(&engine)
+            .removed_blocks(true)
+            .deleted_targets(DeletedTargetPolicy::Fail)
+            .build()
+            .par_check_with_stats(&mut stats)
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_backslash_separators() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => "// if-changed\nA,\n// then-change(gen\\b.ts)\n",
+                "gen/b.ts" => "B,\n"
+            ]
+        };
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let codes = |normalize_separators| {
+            Checker::builder(&engine)
+                .path("a.ts")
+                .normalize_separators(normalize_separators)
+                .build()
+                .check()
+                .unwrap_err()
+                .iter()
+                .map(|error| (error.code, error.severity))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(codes(true), [(Code::BackslashSeparator, Severity::Warning)]);
+        assert_eq!(
+            codes(false),
+            [
+                (Code::UnmodifiedTarget, Severity::Error),
+                (Code::BackslashSeparator, Severity::Warning),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absolute_targets() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["README.md" => "", "docs/b.md" => "// if-changed(b)\n// then-change()\n"]
+            working: [
+                "src/a.ts" => "// if-changed\nA,\n// then-change(/README.md, /docs/b.md:b)\n"
+            ]
+        };
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        insta::assert_snapshot!(Checker::builder(&engine)
+            .path("src/a.ts")
+            .build()
+            .check()
+            .unwrap_err()
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"
+        IC002: Expected "README.md" to be modified because of "then-change" in "src/a.ts" at line 3.
+        IC002: Expected "docs/b.md" to be modified because of "then-change" in "src/a.ts" at line 3.
+        "###);
+    }
+
+    #[test]
+    fn test_max_file_size() {
+        let file = |content: &str| ChangedFile {
+            content: Some(content.into()),
+            changes: vec![(2, "+A,".into())],
+            ..Default::default()
+        };
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("a.ts"),
+                file("// if-changed\nA,\n// then-change(b.ts:b)\n"),
+            ),
+            (
+                PathBuf::from("b.ts"),
+                file(&format!(
+                    "// if-changed(b)\nA,\n// then-change(a.ts)\n{}",
+                    "B,\n".repeat(16)
+                )),
+            ),
+            (PathBuf::from("c.ts"), file(&"A,\n".repeat(64))),
+        ]);
+        // b.ts is noted both as a target of a.ts and as a file to check,
+        // while c.ts has no directives to note.
+        let errors = Checker::builder(&engine)
+            .max_file_size(Some(48))
+            .build()
+            .check()
+            .unwrap_err();
+        insta::assert_debug_snapshot!(errors
+            .iter()
+            .map(|error| (error.severity, error.path.to_str().unwrap()))
+            .collect::<Vec<_>>(), @r###"
+        [
+            (
+                Note,
+                "b.ts",
+            ),
+            (
+                Note,
+                "b.ts",
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_ignored_blocks() {
+        let engine = MemoryEngine::new([(
+            PathBuf::from("a.ts"),
+            ChangedFile {
+                content: Some(
+                    indoc! {"
+                        // if-changed(serializer)
+                        A,
+                        // then-change(b.ts)
+                        // if-changed(parser)
+                        B,
+                        // then-change(c.ts)
+                    "}
+                    .into(),
+                ),
+                changes: vec![(2, "+A,".into()), (5, "+B,".into())],
+                ..Default::default()
+            },
+        )])
+        .with_ignored_blocks([("a.ts".into(), "serializer".into())]);
+        let errors = Checker::builder(&engine).build().check().unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.block.as_deref())
+                .collect::<Vec<_>>(),
+            [Some("parser")]
+        );
+    }
+
+    #[test]
+    fn test_block_context_lines() {
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("a.ts"),
+                ChangedFile {
+                    content: Some(
+                        indoc! {"
+                            import B;
+                            // if-changed
+                            A,
+                            // then-change(b.ts:b)
+                        "}
+                        .into(),
+                    ),
+                    changes: vec![(1, "+import B;".into())],
+                    ..Default::default()
+                },
+            ),
+            (
+                PathBuf::from("b.ts"),
+                ChangedFile {
+                    content: Some(
+                        indoc! {"
+                            // if-changed(b)
+                            B,
+                            // then-change(a.ts)
+
+                            export B;
+                        "}
+                        .into(),
+                    ),
+                    changes: vec![(5, "+export B;".into())],
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert!(Checker::builder(&engine).build().check().is_ok());
+        // The import right above the block of a.ts now counts, but the export
+        // of b.ts is too far from its block.
+        let errors = Checker::builder(&engine)
+            .block_context_lines(1)
+            .build()
+            .check()
+            .unwrap_err();
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"IC002: Expected "b.ts" to be modified because of "then-change" in "a.ts" at line 4."###);
+        assert!(Checker::builder(&engine)
+            .block_context_lines(2)
+            .build()
+            .check()
+            .is_ok());
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn test_function_scope() {
+        let engine = |line: usize| {
+            MemoryEngine::new([
+                (
+                    PathBuf::from("a.rs"),
+                    ChangedFile {
+                        content: Some(
+                            indoc! {"
+                                // if-changed(scope=function)
+                                // then-change(b.rs:b)
+                                fn a() {
+                                    1
+                                }
+                            "}
+                            .into(),
+                        ),
+                        changes: vec![(4, "+    1".into())],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    PathBuf::from("b.rs"),
+                    ChangedFile {
+                        content: Some(
+                            indoc! {"
+                                // if-changed(b, scope=function)
+                                // then-change(a.rs)
+                                fn b() {
+                                    2
+                                }
+
+                                const C: u32 = 3;
+                            "}
+                            .into(),
+                        ),
+                        changes: vec![(line, "+changed".into())],
+                        ..Default::default()
+                    },
+                ),
+            ])
+        };
+        // Changes count within the functions, after the `then-change`.
+        assert!(Checker::builder(&engine(4)).build().check().is_ok());
+        let errors = Checker::builder(&engine(7)).build().check().unwrap_err();
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"IC002: Expected "b.rs" to be modified because of "then-change" in "a.rs" at line 2."###);
+    }
+
+    #[test]
+    fn test_compare_content() {
+        let file = |original: &str, content: &str, changes: &[(usize, &str)]| ChangedFile {
+            content: Some(content.into()),
+            changes: changes
+                .iter()
+                .map(|(line, change)| (*line, change.to_string()))
+                .collect(),
+            original: Some(original.into()),
+        };
+        let check = |a: ChangedFile, b: ChangedFile, compare_content| {
+            let engine =
+                MemoryEngine::new([(PathBuf::from("a.ts"), a), (PathBuf::from("b.ts"), b)]);
+            Checker::builder(&engine)
+                .compare_content(compare_content)
+                .build()
+                .check()
+                .err()
+                .map_or(0, |errors| errors.len())
+        };
+        let a = "// if-changed\nA,\n// then-change(b.ts:b)\n";
+        let b = "// if-changed(b)\nB,\n// then-change(a.ts)\n";
+        let unchanged = || file(b, b, &[]);
+        // A diff aligning the lines of A poorly, e.g. with a line inserted
+        // above the block.
+        let moved = |content: &str| {
+            file(
+                content,
+                &format!("import X;\n{content}"),
+                &[(1, "+import X;"), (2, "-A,"), (3, "+A,")],
+            )
+        };
+        assert_eq!(check(moved(a), unchanged(), false), 1);
+        assert_eq!(check(moved(a), unchanged(), true), 0);
+        // A real change still counts.
+        let changed = file(a, &a.replace("A,", "A2,"), &[(2, "-A,"), (2, "+A2,")]);
+        assert_eq!(check(changed.clone(), unchanged(), true), 1);
+        // So does a moved target, but only without comparing content.
+        assert_eq!(check(changed.clone(), moved(b), false), 0);
+        assert_eq!(check(changed, moved(b), true), 1);
+        // Unnamed blocks are compared by their position, so a block changed
+        // into a copy of another one still counts.
+        let twice = format!("{a}{}", a.replace("A,", "C,"));
+        let copied = file(&twice, &format!("{a}{a}"), &[(5, "-C,"), (5, "+A,")]);
+        assert_eq!(check(copied, unchanged(), true), 1);
+    }
+
+    #[test]
+    fn test_ignore_directive_changes() {
+        let content = indoc! {"
+            // if-changed
+            A,
+            // then-change(
+            //   b.ts,
+            //   c.ts,
+            // )
+        "};
+        let engine = |line: usize| {
+            MemoryEngine::new([(
+                PathBuf::from("a.ts"),
+                ChangedFile {
+                    content: Some(content.into()),
+                    changes: vec![(line, "+changed".into())],
+                    ..Default::default()
+                },
+            )])
+        };
+        let errors = |engine: &MemoryEngine, ignore| {
+            Checker::builder(engine)
+                .ignore_directive_changes(ignore)
+                .build()
+                .check()
+                .err()
+                .map_or(0, |errors| errors.len())
+        };
+        // Blocks end at their `then-change`, so the targets listed after it
+        // never count, and only the line of A counts without the directives.
+        for (line, all, ignored) in [(1, 2, 0), (2, 2, 2), (3, 2, 0), (5, 0, 0)] {
+            assert_eq!(errors(&engine(line), false), all, "line {line}");
+            assert_eq!(errors(&engine(line), true), ignored, "line {line}");
+        }
+    }
+
+    #[test]
+    fn test_require_blocks() {
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("schema/a.rs"),
+                ChangedFile {
+                    content: Some("A,\n".into()),
+                    changes: vec![(1, "+A,".into())],
+                    ..Default::default()
+                },
+            ),
+            (
+                PathBuf::from("schema/b.rs"),
+                ChangedFile {
+                    content: Some("// if-changed\nB,\n// then-change(a.rs)\n".into()),
+                    changes: vec![(2, "+B,".into())],
+                    ..Default::default()
+                },
+            ),
+            (
+                PathBuf::from("c.rs"),
+                ChangedFile {
+                    content: Some("C,\n".into()),
+                    changes: vec![(1, "+C,".into())],
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert!(Checker::builder(&engine).build().check().is_ok());
+        let errors = Checker::builder(&engine)
+            .require_blocks(["schema/*.rs"])
+            .build()
+            .check()
+            .unwrap_err();
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"IC006: Expected "schema/a.rs" to contain an "if-changed" block because it matches `require-blocks`."###);
+    }
+
+    #[test]
+    fn test_generated() {
+        let file = |content: &str| ChangedFile {
+            content: Some(content.into()),
+            changes: vec![(1, "+A".into())],
+            ..Default::default()
+        };
+        let engine = MemoryEngine::new([
+            (PathBuf::from("proto/api/a.proto"), file("A\n")),
+            (PathBuf::from("data.json"), file("{}\n")),
+        ]);
+        let errors = Checker::builder(&engine)
+            .generated([
+                ("gen/*.ts", "proto/**/*.proto"),
+                ("/data.lock", "data.json"),
+                ("docs/*.md", "*.txt"),
+            ])
+            .build()
+            .check()
+            .unwrap_err();
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"
+        IC002: Expected files matching `data.lock` to be modified because they are generated from "data.json".
+        IC002: Expected files matching `gen/*.ts` to be modified because they are generated from "proto/api/a.proto".
+        "###);
+
+        let engine = MemoryEngine::new([
+            (PathBuf::from("proto/api/a.proto"), file("A\n")),
+            (PathBuf::from("gen/a.ts"), file("A\n")),
+        ]);
+        assert!(Checker::builder(&engine)
+            .generated([("gen/*.ts", "proto/**/*.proto")])
+            .build()
+            .check()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_generated_nested() {
+        let file = |content: &str| ChangedFile {
+            content: Some(content.into()),
+            changes: vec![(1, "+A".into())],
+            ..Default::default()
+        };
+        let checker = |engine| {
+            Checker::builder(engine)
+                .generated([("package-lock.json", "package.json")])
+                .build()
+                .check()
+        };
+        // Generated files are looked up next to their generator.
+        let engine = MemoryEngine::new([
+            (PathBuf::from("web/package.json"), file("{}\n")),
+            (PathBuf::from("web/package-lock.json"), file("{}\n")),
+        ]);
+        assert!(checker(&engine).is_ok());
+
+        let engine = MemoryEngine::new([
+            (PathBuf::from("web/package.json"), file("{}\n")),
+            (PathBuf::from("package-lock.json"), file("{}\n")),
+        ]);
+        insta::assert_snapshot!(checker(&engine)
+            .unwrap_err()
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"IC002: Expected files matching `web/package-lock.json` to be modified because they are generated from "web/package.json"."###);
+    }
+
+    #[test]
+    fn test_scan() {
+        let file = |content: &str| ChangedFile {
+            content: Some(content.into()),
+            changes: vec![(2, "+A,".into())],
+            ..Default::default()
+        };
+        let engine = MemoryEngine::new([
+            (
+                PathBuf::from("src/a.ts"),
+                file("// if-changed\nA,\n// then-change(/docs/b.md)\n"),
+            ),
+            (
+                PathBuf::from("src/gen/c.ts"),
+                file("// if-changed\nA,\n// then-change(d.ts)\n"),
+            ),
+            (PathBuf::from("docs/b.md"), file("A,\n")),
+            (
+                PathBuf::from("tools/e.ts"),
+                file("// if-changed\nA,\n// then-change(f.ts)\n"),
+            ),
+        ]);
+        // Targets outside the scanned directories still satisfy blocks, and
+        // only unscanned files with directives are reported.
+        let result = Checker::builder(&engine)
+            .scan(["src/", "!src/gen/"])
+            .build()
+            .check();
+        insta::assert_debug_snapshot!(result
+            .unwrap_err()
+            .iter()
+            .map(|error| (error.code, error.severity, error.path.to_str().unwrap()))
+            .collect::<Vec<_>>(), @r###"
+        [
+            (
+                UnscannedFile,
+                Warning,
+                "src/gen/c.ts",
+            ),
+            (
+                UnscannedFile,
+                Warning,
+                "tools/e.ts",
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn test_par_check() {
+        let file = |content: String| ChangedFile {
+            content: Some(content),
+            changes: vec![(2, "+A,".into())],
+            ..Default::default()
+        };
+        let engine = MemoryEngine::new((0..64).map(|i| {
+            (
+                PathBuf::from(format!("{i}.ts")),
+                file(format!(
+                    "// if-changed\nA,\n// then-change({}.ts:{i})\n",
+                    (i + 1) % 64
+                )),
+            )
+        }));
+        let checker = Checker::builder(&engine).build();
+
+        let mut stats = Stats::default();
+        let errors = checker.par_check_with_stats(&mut stats).unwrap_err();
+        assert_eq!(errors, checker.check().unwrap_err());
+        assert_eq!(errors.len(), 64);
+        assert_eq!(stats.files, 64);
+        assert_eq!(stats.blocks, 64);
+    }
+
+    #[test]
+    fn test_par_check_git() {
+        let (tempdir, repo) = git_test! {
+            working: ["a.ts" => ""]
+        };
+        for i in 0..16 {
+            let content = format!(
+                "// if-changed\nA,\n// then-change({}.ts:{i})\n",
+                (i + 1) % 16
+            );
+            std::fs::write(tempdir.path().join(format!("{i}.ts")), content).unwrap();
+        }
+        let engine = GitEngine::builder(&repo).build().unwrap();
+        let checker = Checker::builder(&engine).build();
+
+        let mut stats = Stats::default();
+        let errors = checker.par_check_with_stats(&mut stats).unwrap_err();
+        assert_eq!(errors, checker.check().unwrap_err());
+        assert_eq!(errors.len(), 16);
+        assert_eq!(stats.files, 17);
+    }
+}
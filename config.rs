@@ -0,0 +1,933 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{owners::matches, Code, Diagnostic, Severity};
+
+/// The name of the configuration file, looked up at the repository root.
+pub const CONFIG_FILE_NAME: &str = ".if-changed.toml";
+
+/// The trailer key recognized when none is configured.
+pub const DEFAULT_IGNORE_TRAILER: &str = "ignore-if-changed";
+
+/// The trailer key acknowledging the removal of targets, e.g.
+/// `remove-if-changed: a.ts#name`.
+pub const REMOVAL_TRAILER: &str = "remove-if-changed";
+
+/// Repository-wide settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// How commit trailers ignoring files are recognized.
+    pub ignore_trailer: TrailerConfig,
+    /// Settings for migrating from other tools.
+    pub migrate: MigrateConfig,
+    /// Templates replacing the messages of problems.
+    pub messages: MessageConfig,
+    /// How the targets of blocks are checked.
+    pub targets: TargetConfig,
+    /// Where the owners of files are listed.
+    pub owners: OwnersConfig,
+    /// Severities of kinds of problems other than [`Severity::Error`].
+    #[serde(deserialize_with = "deserialize_severities")]
+    pub severity: BTreeMap<Code, Severity>,
+    /// Patterns of the files to look for blocks in, e.g. `src/**`, in the
+    /// syntax of `.gitignore`, where `!` excludes files again. By default, all
+    /// files are scanned.
+    pub scan: Vec<String>,
+    /// Patterns of the files that must contain at least one block, e.g.
+    /// `src/schema/*.rs`, in the same syntax as `scan`, so critical files
+    /// stay annotated as they evolve.
+    pub require_blocks: Vec<String>,
+    /// Patterns of generated files, e.g. `gen/**/*.ts`, mapped to patterns of
+    /// the files they are generated from, e.g. `proto/**/*.proto`. Changing a
+    /// generator requires a generated file to change too, as if a block
+    /// spanning the generator listed them as targets.
+    pub generated: BTreeMap<String, String>,
+}
+
+/// The settings of a `.if-changed.toml` file in a subdirectory, overriding
+/// those of parent directories for the files under it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DirectoryConfig {
+    /// Templates replacing the messages of problems.
+    pub messages: MessageConfig,
+    /// Severities of kinds of problems other than [`Severity::Error`].
+    #[serde(deserialize_with = "deserialize_severities")]
+    pub severity: BTreeMap<Code, Severity>,
+}
+
+impl DirectoryConfig {
+    /// Parse a configuration file of a subdirectory.
+    pub fn parse(source: &str) -> io::Result<Self> {
+        toml::from_str(source).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Override the settings of `self` with those of `other`, e.g. of a
+    /// subdirectory.
+    fn merge(&mut self, other: &DirectoryConfig) {
+        if other.messages.template.is_some() {
+            self.messages.template.clone_from(&other.messages.template);
+        }
+        self.messages.codes.extend(other.messages.codes.clone());
+        self.severity.extend(other.severity.clone());
+    }
+}
+
+/// The configuration of a repository together with the `.if-changed.toml`
+/// files of its subdirectories, which override the severities and messages
+/// of problems in the files under them, e.g. in monorepos.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigTree {
+    workdir: PathBuf,
+    root: DirectoryConfig,
+    /// The configuration file of each subdirectory looked up so far, if it
+    /// has one.
+    directories: BTreeMap<PathBuf, Option<DirectoryConfig>>,
+}
+
+impl ConfigTree {
+    /// Start with the configuration of the repository in `workdir`.
+    pub fn new(workdir: impl AsRef<Path>, config: &Config) -> Self {
+        Self {
+            workdir: workdir.as_ref().to_owned(),
+            root: DirectoryConfig {
+                messages: config.messages.clone(),
+                severity: config.severity.clone(),
+            },
+            directories: BTreeMap::new(),
+        }
+    }
+
+    /// Read the configuration files of the directories of `path`, relative
+    /// to the repository root, unless they were read before. Files that
+    /// can't be read are reported as [`Code::Unreadable`].
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Vec<Diagnostic> {
+        let mut problems = Vec::new();
+        for directory in path.as_ref().ancestors().skip(1) {
+            if directory.as_os_str().is_empty() || self.directories.contains_key(directory) {
+                continue;
+            }
+            let file = directory.join(CONFIG_FILE_NAME);
+            let config = match fs::read_to_string(self.workdir.join(&file)) {
+                Ok(source) => DirectoryConfig::parse(&source),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    self.directories.insert(directory.to_owned(), None);
+                    continue;
+                }
+                Err(error) => Err(error),
+            };
+            let config = config
+                .map_err(|error| {
+                    problems.push(Diagnostic::new(
+                        Code::Unreadable,
+                        &file,
+                        None,
+                        format!("Could not load {file:?}: {error}"),
+                    ));
+                })
+                .ok();
+            self.directories.insert(directory.to_owned(), config);
+        }
+        problems
+    }
+
+    /// Get the settings applying to `path`, where those of deeper directories
+    /// take precedence. Only the directories loaded with [`ConfigTree::load`]
+    /// count.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> DirectoryConfig {
+        let mut config = self.root.clone();
+        let directories = path.as_ref().ancestors().skip(1).collect::<Vec<_>>();
+        for directory in directories.into_iter().rev() {
+            if let Some(Some(directory)) = self.directories.get(directory) {
+                config.merge(directory);
+            }
+        }
+        config
+    }
+
+    /// Set the severity and message of `diagnostic` according to the
+    /// settings applying to its file.
+    pub fn apply(&self, diagnostic: &mut Diagnostic) {
+        let config = self.resolve(&diagnostic.path);
+        if let Some(severity) = config.severity.get(&diagnostic.code) {
+            diagnostic.severity = *severity;
+        }
+        config.messages.apply(diagnostic);
+    }
+}
+
+impl Config {
+    /// Read the configuration of the repository in `workdir`.
+    ///
+    /// A missing configuration file is not an error; the defaults are used
+    /// instead.
+    pub fn discover(workdir: impl AsRef<Path>) -> io::Result<Self> {
+        let path = workdir.as_ref().join(CONFIG_FILE_NAME);
+        match fs::read_to_string(&path) {
+            Ok(source) => Self::parse(&source),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Parse a configuration file.
+    pub fn parse(source: &str) -> io::Result<Self> {
+        toml::from_str(source).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Check if blocks are looked for in `path`, relative to the repository
+    /// root, according to [`Config::scan`].
+    pub fn scans(&self, path: impl AsRef<Path>) -> bool {
+        scans(&self.scan, path.as_ref())
+    }
+
+    /// Check if `path`, relative to the repository root, must contain a
+    /// block according to [`Config::require_blocks`].
+    pub fn requires_blocks(&self, path: impl AsRef<Path>) -> bool {
+        requires_blocks(&self.require_blocks, path.as_ref())
+    }
+}
+
+/// Check if `path` is scanned according to the `scan` patterns: all paths
+/// are without patterns, and otherwise the last matching pattern decides,
+/// where patterns starting with `!` exclude paths.
+pub(crate) fn scans(scan: &[String], path: &Path) -> bool {
+    if scan.is_empty() {
+        return true;
+    }
+    let path = path.to_string_lossy().replace('\\', "/");
+    scan.iter()
+        .rev()
+        .find_map(|pattern| match pattern.strip_prefix('!') {
+            Some(pattern) => matches(pattern, &path).then_some(false),
+            None => matches(pattern, &path).then_some(true),
+        })
+        .unwrap_or(false)
+}
+
+/// Check if `path` must contain a block according to the `require-blocks`
+/// patterns, matched as `scan` patterns are. No path must without patterns.
+pub(crate) fn requires_blocks(patterns: &[String], path: &Path) -> bool {
+    !patterns.is_empty() && scans(patterns, path)
+}
+
+/// Report `path` as missing a block if it must contain one according to the
+/// `require-blocks` patterns but `blocks`, its blocks, are empty.
+pub(crate) fn unannotated_file<T>(
+    patterns: &[String],
+    path: &Path,
+    blocks: &[T],
+) -> Option<Diagnostic> {
+    (blocks.is_empty() && requires_blocks(patterns, path)).then(|| {
+        Diagnostic::new(
+            Code::UnannotatedFile,
+            path,
+            None,
+            format!("Expected {path:?} to contain an \"if-changed\" block because it matches `require-blocks`."),
+        )
+    })
+}
+
+#[cfg(any(test, feature = "git"))]
+impl Config {
+    /// Override settings with those of git config, e.g. set with
+    /// `git config --global if-changed.trailer Skip-If-Changed`, so they can
+    /// be customized per clone or per user.
+    ///
+    /// Settings are read from the `if-changed` section: `trailer` (may be
+    /// given several times), `requireReason`, `reasonPattern`, `severity`
+    /// (e.g. `IC003=warning`, may be given several times), `deletedTargets`,
+    /// `normalizeSeparators`, `requireExcluded` and `ownersFile`.
+    pub fn merge_git_config(&mut self, config: &git2::Config) -> io::Result<()> {
+        let keys = git_config_values(config, "if-changed.trailer")?;
+        if !keys.is_empty() {
+            self.ignore_trailer.keys = keys;
+        }
+        if let Some(require_reason) = git_config_bool(config, "if-changed.requireReason")? {
+            self.ignore_trailer.require_reason = require_reason;
+        }
+        if let Some(pattern) = git_config_string(config, "if-changed.reasonPattern")? {
+            self.ignore_trailer.reason_pattern = Some(pattern);
+        }
+        for value in git_config_values(config, "if-changed.severity")? {
+            let (code, severity) = value
+                .split_once('=')
+                .ok_or_else(|| "expected e.g. `IC003=warning`".to_owned())
+                .and_then(|(code, severity)| Ok((code.trim().parse()?, severity.trim().parse()?)))
+                .map_err(|error| invalid_git_config("if-changed.severity", &value, error))?;
+            self.severity.insert(code, severity);
+        }
+        if let Some(value) = git_config_string(config, "if-changed.deletedTargets")? {
+            self.targets.deleted = value
+                .parse()
+                .map_err(|error| invalid_git_config("if-changed.deletedTargets", &value, error))?;
+        }
+        if let Some(normalize) = git_config_bool(config, "if-changed.normalizeSeparators")? {
+            self.targets.normalize_separators = normalize;
+        }
+        if let Some(require) = git_config_bool(config, "if-changed.requireExcluded")? {
+            self.targets.require_excluded = require;
+        }
+        if let Some(file) = git_config_string(config, "if-changed.ownersFile")? {
+            self.owners.file = Some(file.into());
+        }
+        Ok(())
+    }
+}
+
+/// Get the value of `key` in git config, if set.
+#[cfg(any(test, feature = "git"))]
+fn git_config_string(config: &git2::Config, key: &str) -> io::Result<Option<String>> {
+    match config.get_string(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(error) => Err(io::Error::other(error)),
+    }
+}
+
+/// Get the value of `key` in git config as a boolean, e.g. `true` or `no`,
+/// if set.
+#[cfg(any(test, feature = "git"))]
+fn git_config_bool(config: &git2::Config, key: &str) -> io::Result<Option<bool>> {
+    match config.get_bool(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(error) => Err(invalid_git_config(key, "", error.message())),
+    }
+}
+
+/// Get all values of `key` in git config, in order.
+#[cfg(any(test, feature = "git"))]
+fn git_config_values(config: &git2::Config, key: &str) -> io::Result<Vec<String>> {
+    let mut entries = match config.multivar(key, None) {
+        Ok(entries) => entries,
+        Err(error) if error.code() == git2::ErrorCode::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(io::Error::other(error)),
+    };
+    let mut values = Vec::new();
+    while let Some(entry) = entries.next() {
+        let entry = entry.map_err(io::Error::other)?;
+        values.extend(entry.value().map(str::to_owned));
+    }
+    Ok(values)
+}
+
+/// Create the error of an invalid value of `key` in git config.
+#[cfg(any(test, feature = "git"))]
+fn invalid_git_config(key: &str, value: &str, error: impl fmt::Display) -> io::Error {
+    let value = if value.is_empty() {
+        String::new()
+    } else {
+        format!(" {value:?}")
+    };
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid git config `{key}`{value}: {error}"),
+    )
+}
+
+/// How `ignore-if-changed` commit trailers are recognized.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TrailerConfig {
+    /// The accepted trailer keys, compared case-insensitively. An empty list
+    /// is rejected, since it would silently disable ignore trailers.
+    #[serde(deserialize_with = "deserialize_keys")]
+    pub keys: Vec<String>,
+    /// Whether a trailer must give a reason after `--` to be honored.
+    pub require_reason: bool,
+    /// A regular expression the reason must match to be honored, e.g.
+    /// `[A-Z]+-[0-9]+` to require a ticket. Implies `require_reason`.
+    pub reason_pattern: Option<String>,
+    /// Patterns of the emails allowed to ignore files with trailers, e.g.
+    /// `*@example.com`. A commit is allowed if its author, its committer or
+    /// one of its `Signed-off-by` trailers matches. Empty allows everyone.
+    pub allowed_authors: Vec<String>,
+}
+
+impl Default for TrailerConfig {
+    fn default() -> Self {
+        Self {
+            keys: vec![DEFAULT_IGNORE_TRAILER.to_owned()],
+            require_reason: false,
+            reason_pattern: None,
+            allowed_authors: Vec::new(),
+        }
+    }
+}
+
+impl TrailerConfig {
+    /// Check whether a trailer key is accepted.
+    pub fn accepts(&self, key: &[u8]) -> bool {
+        self.keys
+            .iter()
+            .any(|accepted| accepted.as_bytes().eq_ignore_ascii_case(key))
+    }
+}
+
+/// How the targets of blocks are checked.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TargetConfig {
+    /// Whether deleting a target satisfies a block.
+    pub deleted: DeletedTargetPolicy,
+    /// Whether `\` in targets separates directories, as on Windows, instead
+    /// of being part of file names.
+    pub normalize_separators: bool,
+    /// Whether files excluded by `.ifchangedignore` files must still change
+    /// as targets, i.e. are only excluded from being checked themselves.
+    pub require_excluded: bool,
+}
+
+impl Default for TargetConfig {
+    fn default() -> Self {
+        Self {
+            deleted: DeletedTargetPolicy::default(),
+            normalize_separators: true,
+            require_excluded: true,
+        }
+    }
+}
+
+/// Where the owners of files are listed, to route problems to the owners of
+/// their targets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct OwnersConfig {
+    /// The owners file, in the format of `CODEOWNERS`, relative to the
+    /// repository root. By default, `CODEOWNERS` is looked up in `.github/`,
+    /// the root, `docs/` and `.gitlab/`.
+    pub file: Option<PathBuf>,
+}
+
+/// Whether deleting a target of a modified block satisfies it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeletedTargetPolicy {
+    /// Deleting a target counts as changing it.
+    #[default]
+    Satisfy,
+    /// Deleting a target is reported as [`Code::DeletedTarget`].
+    Fail,
+}
+
+impl DeletedTargetPolicy {
+    /// All policies, in order.
+    pub const ALL: [DeletedTargetPolicy; 2] =
+        [DeletedTargetPolicy::Satisfy, DeletedTargetPolicy::Fail];
+
+    /// Get the policy as written in configuration, e.g. `fail`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeletedTargetPolicy::Satisfy => "satisfy",
+            DeletedTargetPolicy::Fail => "fail",
+        }
+    }
+}
+
+impl fmt::Display for DeletedTargetPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DeletedTargetPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DeletedTargetPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.as_str() == s)
+            .ok_or_else(|| format!("{s:?} is not a policy; expected `satisfy` or `fail`"))
+    }
+}
+
+/// Templates replacing the messages of problems, e.g. to link to internal
+/// documentation.
+///
+/// Templates may contain the placeholders `{message}` (the original
+/// message), `{code}`, `{source}` (the file the problem was found in),
+/// `{line}`, `{block}` and `{target}`. Placeholders without a value for a
+/// problem are replaced with nothing; other text is kept as is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MessageConfig {
+    /// The template of messages of problems without one of their own.
+    pub template: Option<String>,
+    /// Templates of messages by kind of problem, e.g. `IC002`.
+    #[serde(deserialize_with = "deserialize_codes")]
+    pub codes: BTreeMap<Code, String>,
+}
+
+impl MessageConfig {
+    /// Replace the message of `diagnostic` according to its template, if any.
+    pub fn apply(&self, diagnostic: &mut Diagnostic) {
+        let Some(template) = self.codes.get(&diagnostic.code).or(self.template.as_ref()) else {
+            return;
+        };
+        let mut message = String::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            message.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest
+                .find('}')
+                .and_then(|end| Some((placeholder(diagnostic, &rest[1..end])?, end)));
+            match value {
+                Some((value, end)) => {
+                    message.push_str(&value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    message.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        message.push_str(rest);
+        diagnostic.message = message;
+    }
+}
+
+/// Get the value of a placeholder of a message template for `diagnostic`, or
+/// `None` if `name` is not a placeholder.
+fn placeholder(diagnostic: &Diagnostic, name: &str) -> Option<String> {
+    let value = match name {
+        "message" => diagnostic.message.clone(),
+        "code" => diagnostic.code.to_string(),
+        "source" => diagnostic.path.display().to_string(),
+        "line" => diagnostic
+            .line
+            .map(|line| line.to_string())
+            .unwrap_or_default(),
+        "block" => diagnostic.block.clone().unwrap_or_default(),
+        "target" => diagnostic
+            .target
+            .as_ref()
+            .map(|target| target.display().to_string())
+            .unwrap_or_default(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Deserialize trailer keys, rejecting an empty list.
+fn deserialize_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let keys = Vec::<String>::deserialize(deserializer)?;
+    if keys.is_empty() {
+        return Err(serde::de::Error::custom(
+            "`keys` must list at least one trailer key",
+        ));
+    }
+    Ok(keys)
+}
+
+/// Deserialize a table keyed by codes, e.g. `IC002`.
+fn deserialize_codes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Code, String>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(code, template)| Ok((code.parse().map_err(serde::de::Error::custom)?, template)))
+        .collect()
+}
+
+/// Deserialize a table of severities keyed by codes, e.g. `IC003 = "warning"`.
+fn deserialize_severities<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Code, Severity>, D::Error> {
+    deserialize_codes(deserializer)?
+        .into_iter()
+        .map(|(code, severity)| Ok((code, severity.parse().map_err(serde::de::Error::custom)?)))
+        .collect()
+}
+
+/// Settings for migrating from other tools.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MigrateConfig {
+    /// Legacy annotation formats in addition to the built-in ones.
+    pub formats: Vec<LegacyFormat>,
+}
+
+/// An annotation format shaped like `if-changed`, with different markers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LegacyFormat {
+    /// The name passed to `migrate --from`.
+    pub name: String,
+    /// The marker starting a block, optionally followed by `(name)`.
+    pub if_changed: String,
+    /// The marker ending a block, followed by `(paths)`.
+    pub then_change: String,
+    /// The prefix of paths relative to the repository root, e.g. `//`.
+    #[serde(default)]
+    pub root_prefix: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(
+            Config::parse(
+                r#"
+                [ignore-trailer]
+                keys = ["Skip-If-Changed", "ignore-if-changed"]
+                require-reason = true
+                reason-pattern = "[A-Z]+-[0-9]+"
+                allowed-authors = ["*@example.com"]
+                "#
+            )
+            .unwrap(),
+            Config {
+                ignore_trailer: TrailerConfig {
+                    keys: vec!["Skip-If-Changed".into(), "ignore-if-changed".into()],
+                    require_reason: true,
+                    reason_pattern: Some("[A-Z]+-[0-9]+".into()),
+                    allowed_authors: vec!["*@example.com".into()],
+                },
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            Config::parse("[ignore-trailer]\nrequire-reason = true")
+                .unwrap()
+                .ignore_trailer
+                .keys,
+            [DEFAULT_IGNORE_TRAILER]
+        );
+        assert_eq!(
+            Config::parse("unknown = 1").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        let error = Config::parse("[ignore-trailer]\nkeys = []").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error
+            .to_string()
+            .contains("`keys` must list at least one trailer key"));
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!(
+            Config::parse("[targets]\ndeleted = \"fail\"")
+                .unwrap()
+                .targets
+                .deleted,
+            DeletedTargetPolicy::Fail
+        );
+        assert!(Config::parse("[targets]\ndeleted = \"warn\"").is_err());
+        assert!(Config::parse("").unwrap().targets.normalize_separators);
+        assert!(
+            !Config::parse("[targets]\nnormalize-separators = false")
+                .unwrap()
+                .targets
+                .normalize_separators
+        );
+        assert!(Config::parse("").unwrap().targets.require_excluded);
+        assert!(
+            !Config::parse("[targets]\nrequire-excluded = false")
+                .unwrap()
+                .targets
+                .require_excluded
+        );
+        assert_eq!("satisfy".parse(), Ok(DeletedTargetPolicy::Satisfy));
+        assert!("warn".parse::<DeletedTargetPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_parse_migrate() {
+        assert_eq!(
+            Config::parse(
+                r#"
+                [[migrate.formats]]
+                name = "sync"
+                if-changed = "SYNC.Begin"
+                then-change = "SYNC.End"
+                "#
+            )
+            .unwrap()
+            .migrate
+            .formats,
+            [LegacyFormat {
+                name: "sync".into(),
+                if_changed: "SYNC.Begin".into(),
+                then_change: "SYNC.End".into(),
+                root_prefix: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_generated() {
+        let config = Config::parse(indoc! {r#"
+            [generated]
+            "gen/**/*.ts" = "proto/**/*.proto"
+            "package-lock.json" = "package.json"
+        "#})
+        .unwrap();
+        assert_eq!(
+            config.generated,
+            BTreeMap::from([
+                ("gen/**/*.ts".into(), "proto/**/*.proto".into()),
+                ("package-lock.json".into(), "package.json".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_requires_blocks() {
+        assert!(!Config::default().requires_blocks("a/b.rs"));
+        let config =
+            Config::parse(r#"require-blocks = ["src/schema/*.rs", "!src/schema/mod.rs"]"#).unwrap();
+        assert!(config.requires_blocks("src/schema/user.rs"));
+        assert!(!config.requires_blocks("src/schema/mod.rs"));
+        assert!(!config.requires_blocks("src/user.rs"));
+    }
+
+    #[test]
+    fn test_scans() {
+        assert!(Config::default().scans("a/b.ts"));
+        let config = Config::parse(r#"scan = ["src/**", "proto/", "!src/vendor/"]"#).unwrap();
+        assert!(config.scans("src/a.ts"));
+        assert!(config.scans("proto/a/b.proto"));
+        assert!(config.scans("src\\a.ts"));
+        assert!(!config.scans("src/vendor/a.ts"));
+        assert!(!config.scans("docs/a.md"));
+        assert!(!config.scans("a/proto.ts"));
+    }
+
+    #[test]
+    fn test_discover() {
+        let tempdir = tempfile::tempdir().unwrap();
+        assert_eq!(Config::discover(tempdir.path()).unwrap(), Config::default());
+
+        fs::write(
+            tempdir.path().join(CONFIG_FILE_NAME),
+            "[ignore-trailer]\nkeys = [\"skip\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            Config::discover(tempdir.path())
+                .unwrap()
+                .ignore_trailer
+                .keys,
+            ["skip"]
+        );
+    }
+
+    #[test]
+    fn test_accepts() {
+        let config = TrailerConfig {
+            keys: vec!["Skip-If-Changed".into(), "ignore-if-changed".into()],
+            ..Default::default()
+        };
+        assert!(config.accepts(b"skip-if-changed"));
+        assert!(config.accepts(b"Ignore-If-Changed"));
+        assert!(!config.accepts(b"ignore"));
+    }
+
+    #[test]
+    fn test_messages() {
+        let config = Config::parse(
+            r#"
+            [messages]
+            template = "{message} See https://wiki.example.com/if-changed#{code}."
+
+            [messages.codes]
+            IC002 = "{source}:{line}: {target} must change with {block}. {unknown}"
+            "#,
+        )
+        .unwrap()
+        .messages;
+
+        let mut diagnostic = Diagnostic::new(
+            Code::UnmodifiedTarget,
+            "a.ts",
+            Some(3),
+            "Expected \"b.ts\" to be modified".into(),
+        )
+        .with_block(Some("colors".into()))
+        .with_target("b.ts");
+        config.apply(&mut diagnostic);
+        insta::assert_snapshot!(diagnostic.message, @"a.ts:3: b.ts must change with colors. {unknown}");
+
+        let mut diagnostic = Diagnostic::new(
+            Code::Unreadable,
+            "c.ts",
+            None,
+            "Could not open c.ts.".into(),
+        );
+        config.apply(&mut diagnostic);
+        insta::assert_snapshot!(diagnostic.message, @"Could not open c.ts. See https://wiki.example.com/if-changed#IC201.");
+
+        assert_eq!(
+            Config::parse("[messages.codes]\nIC999 = \"\"")
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_parse_severity() {
+        assert_eq!(
+            Config::parse("[severity]\nIC002 = \"warning\"\nIC201 = \"note\"")
+                .unwrap()
+                .severity,
+            BTreeMap::from([
+                (Code::UnmodifiedTarget, Severity::Warning),
+                (Code::Unreadable, Severity::Note),
+            ])
+        );
+        insta::assert_snapshot!(Config::parse("[severity]\nIC002 = \"fatal\"").unwrap_err(), @r###"
+        TOML parse error at line 1, column 1
+          |
+        1 | [severity]
+          | ^^^^^^^^^^
+        "fatal" is not a severity; expected `error`, `warning` or `note`
+
+        "###);
+        assert!(DirectoryConfig::parse("[targets]\ndeleted = \"fail\"").is_err());
+    }
+
+    #[test]
+    fn test_config_tree() {
+        let tempdir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tempdir.path().join("web/app")).unwrap();
+        fs::create_dir(tempdir.path().join("api")).unwrap();
+        fs::write(
+            tempdir.path().join("web").join(CONFIG_FILE_NAME),
+            "[severity]\nIC002 = \"warning\"\n\n[messages]\ntemplate = \"{message} Ask #web.\"",
+        )
+        .unwrap();
+        fs::write(
+            tempdir.path().join("web/app").join(CONFIG_FILE_NAME),
+            "[severity]\nIC002 = \"error\"",
+        )
+        .unwrap();
+        fs::write(
+            tempdir.path().join("api").join(CONFIG_FILE_NAME),
+            "[owners]",
+        )
+        .unwrap();
+
+        let config = Config::parse("[severity]\nIC201 = \"note\"").unwrap();
+        let mut configs = ConfigTree::new(tempdir.path(), &config);
+        assert_eq!(configs.load("web/app/a.ts"), []);
+        assert_eq!(configs.load("web/b.ts"), []);
+        insta::assert_debug_snapshot!(configs.load("api/c.rs"), @r###"
+        [
+            Diagnostic {
+                code: Unreadable,
+                severity: Error,
+                path: "api/.if-changed.toml",
+                line: None,
+                range: None,
+                block: None,
+                target: None,
+                target_block: None,
+                changes: [],
+                suggestion: None,
+                last_edit: None,
+                owners: [],
+                message: "Could not load \"api/.if-changed.toml\": TOML parse error at line 1, column 2\n  |\n1 | [owners]\n  |  ^^^^^^\nunknown field `owners`, expected `messages` or `severity`\n",
+            },
+        ]
+        "###);
+
+        let diagnostic = |path: &str| {
+            let mut diagnostic =
+                Diagnostic::new(Code::UnmodifiedTarget, path, Some(1), "Changed.".into());
+            configs.apply(&mut diagnostic);
+            (diagnostic.severity, diagnostic.message)
+        };
+        assert_eq!(diagnostic("a.ts"), (Severity::Error, "Changed.".into()));
+        assert_eq!(
+            diagnostic("web/b.ts"),
+            (Severity::Warning, "Changed. Ask #web.".into())
+        );
+        assert_eq!(
+            diagnostic("web/app/a.ts"),
+            (Severity::Error, "Changed. Ask #web.".into())
+        );
+        assert_eq!(diagnostic("api/c.rs"), (Severity::Error, "Changed.".into()));
+        assert_eq!(
+            configs.resolve("web/app/a.ts").severity,
+            BTreeMap::from([
+                (Code::UnmodifiedTarget, Severity::Error),
+                (Code::Unreadable, Severity::Note),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_merge_git_config() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("config");
+        let git_config = |source: &str| {
+            fs::write(&path, source).unwrap();
+            git2::Config::open(&path).unwrap()
+        };
+
+        let mut config = Config::default();
+        config.merge_git_config(&git_config("")).unwrap();
+        assert_eq!(config, Config::default());
+
+        config
+            .merge_git_config(&git_config(indoc! {"
+                [if-changed]
+                    trailer = Skip-If-Changed
+                    trailer = ignore-if-changed
+                    requireReason = yes
+                    reasonPattern = \"^[A-Z]+-[0-9]+\"
+                    severity = IC003=warning
+                    severity = IC201 = note
+                    deletedTargets = fail
+                    normalizeSeparators = false
+                    ownersFile = OWNERS
+            "}))
+            .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                ignore_trailer: TrailerConfig {
+                    keys: vec!["Skip-If-Changed".into(), "ignore-if-changed".into()],
+                    require_reason: true,
+                    reason_pattern: Some("^[A-Z]+-[0-9]+".into()),
+                    allowed_authors: Vec::new(),
+                },
+                targets: TargetConfig {
+                    deleted: DeletedTargetPolicy::Fail,
+                    normalize_separators: false,
+                    require_excluded: true,
+                },
+                owners: OwnersConfig {
+                    file: Some("OWNERS".into()),
+                },
+                severity: BTreeMap::from([
+                    (Code::MissingNamedBlock, Severity::Warning),
+                    (Code::Unreadable, Severity::Note),
+                ]),
+                ..Default::default()
+            }
+        );
+
+        insta::assert_snapshot!(config.merge_git_config(&git_config("[if-changed]\nseverity = IC003")).unwrap_err(), @r###"invalid git config `if-changed.severity` "IC003": expected e.g. `IC003=warning`"###);
+        insta::assert_snapshot!(config.merge_git_config(&git_config("[if-changed]\ndeletedTargets = warn")).unwrap_err(), @r###"invalid git config `if-changed.deletedTargets` "warn": "warn" is not a policy; expected `satisfy` or `fail`"###);
+        insta::assert_snapshot!(config.merge_git_config(&git_config("[if-changed]\nrequireExcluded = maybe")).unwrap_err(), @"invalid git config `if-changed.requireExcluded`: failed to parse 'maybe' as a boolean value");
+    }
+}
@@ -0,0 +1,206 @@
+mod code;
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub use code::Code;
+
+/// How serious a problem is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// A problem failing the check.
+    #[default]
+    Error,
+    /// A problem worth reporting that doesn't fail the check.
+    Warning,
+    /// Information about the check that isn't a problem, e.g. a file that was
+    /// skipped.
+    Note,
+}
+
+impl Severity {
+    /// All severities, from the most to the least serious.
+    pub const ALL: [Severity; 3] = [Severity::Error, Severity::Warning, Severity::Note];
+
+    /// Get the severity as written in diagnostics, e.g. `error`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Severity::ALL
+            .into_iter()
+            .find(|severity| severity.as_str() == s)
+            .ok_or_else(|| {
+                format!("{s:?} is not a severity; expected `error`, `warning` or `note`")
+            })
+    }
+}
+
+/// A problem found while checking a file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct Diagnostic {
+    /// The kind of problem.
+    pub code: Code,
+    /// How serious the problem is.
+    #[serde(default, skip_serializing_if = "is_error")]
+    pub severity: Severity,
+    /// The file the problem was found in, relative to the repository root.
+    pub path: PathBuf,
+    /// The line the problem refers to, if any.
+    pub line: Option<usize>,
+    /// The lines of the block involved, if any.
+    pub range: Option<(usize, usize)>,
+    /// The name of the block involved, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<String>,
+    /// The file the block depends on, if any.
+    pub target: Option<PathBuf>,
+    /// The name of the block of the target involved, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_block: Option<String>,
+    /// The lines of the block whose change triggered the problem, prefixed
+    /// with `+` or `-`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+    /// A fix that can be applied to resolve the problem, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<Suggestion>,
+    /// The last edit of the target block involved, if looked up, e.g. to
+    /// ask its author about the change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_edit: Option<LastEdit>,
+    /// The owners of the target, e.g. from `CODEOWNERS`, if looked up.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+    /// A human-readable description of the problem.
+    pub message: String,
+}
+
+/// A machine-applicable fix for a problem: text to insert into a file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct Suggestion {
+    /// A human-readable description of the fix, e.g. ``add `// then-change()` ``.
+    pub message: String,
+    /// The file to edit, relative to the repository root.
+    pub path: PathBuf,
+    /// The line to insert the text before. A line past the end of the file
+    /// appends the text to it.
+    pub line: usize,
+    /// The lines to insert, each ending with a newline.
+    pub text: String,
+}
+
+/// The most recent commit changing a range of lines, as found by `git blame`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct LastEdit {
+    /// The id of the commit.
+    pub commit: String,
+    /// The name of the author of the commit.
+    pub author: String,
+    /// The email of the author of the commit.
+    pub email: String,
+    /// The time the commit was authored, in seconds since the Unix epoch.
+    pub time: i64,
+}
+
+impl Diagnostic {
+    /// Create an error of kind `code` found in `path`, e.g. by an engine.
+    pub fn new(code: Code, path: impl AsRef<Path>, line: Option<usize>, message: String) -> Self {
+        Self {
+            code,
+            severity: Severity::Error,
+            path: path.as_ref().to_owned(),
+            line,
+            range: None,
+            block: None,
+            target: None,
+            target_block: None,
+            changes: Vec::new(),
+            suggestion: None,
+            last_edit: None,
+            owners: Vec::new(),
+            message,
+        }
+    }
+
+    /// Get the key reports are sorted by: the file, the line, and the code.
+    /// Problems outside of files come first, then problems without a line.
+    pub fn sort_key(&self) -> (&Path, Option<usize>, Code) {
+        (&self.path, self.line, self.code)
+    }
+
+    pub(crate) fn with_range(mut self, range: (usize, usize)) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub(crate) fn with_block(mut self, name: Option<String>) -> Self {
+        self.block = name;
+        self
+    }
+
+    pub(crate) fn with_changes(mut self, changes: &[String]) -> Self {
+        self.changes = changes.to_vec();
+        self
+    }
+
+    pub(crate) fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestion = Some(suggestion);
+        self
+    }
+
+    pub(crate) fn with_last_edit(mut self, last_edit: Option<LastEdit>) -> Self {
+        self.last_edit = last_edit;
+        self
+    }
+
+    pub(crate) fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub(crate) fn with_target(mut self, target: impl AsRef<Path>) -> Self {
+        self.target = Some(target.as_ref().to_owned());
+        self
+    }
+
+    pub(crate) fn with_target_block(mut self, name: &str) -> Self {
+        self.target_block = Some(name.to_owned());
+        self
+    }
+}
+
+fn is_error(severity: &Severity) -> bool {
+    *severity == Severity::Error
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
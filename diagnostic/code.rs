@@ -0,0 +1,240 @@
+use std::{fmt, str::FromStr};
+
+/// A stable identifier for a kind of diagnostic.
+///
+/// Codes in the `IC0xx` range are failed checks, `IC1xx` are malformed blocks,
+/// `IC2xx` are I/O problems, and `IC3xx` are problems with commit trailers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub enum Code {
+    /// A file named in a `then-change` could not be opened.
+    #[serde(rename = "IC001")]
+    MissingTarget,
+    /// A block was modified, but a target of its `then-change` was not.
+    #[serde(rename = "IC002")]
+    UnmodifiedTarget,
+    /// A `then-change` refers to a named block that does not exist.
+    #[serde(rename = "IC003")]
+    MissingNamedBlock,
+    /// A block was modified, but a target of its `then-change` was deleted.
+    #[serde(rename = "IC004")]
+    DeletedTarget,
+    /// A target was removed from a `then-change` without changing it or
+    /// acknowledging the removal.
+    #[serde(rename = "IC005")]
+    RemovedTarget,
+    /// A file matching `require-blocks` contains no block.
+    #[serde(rename = "IC006")]
+    UnannotatedFile,
+    /// An `if-changed` has no matching `then-change`.
+    #[serde(rename = "IC101")]
+    UnterminatedBlock,
+    /// A `then-change` has no matching `if-changed`.
+    #[serde(rename = "IC102")]
+    UnopenedBlock,
+    /// A `(` after `if-changed` or `then-change` is never closed.
+    #[serde(rename = "IC103")]
+    UnclosedParenthesis,
+    /// A `then-change` is not followed by a list of paths.
+    #[serde(rename = "IC104")]
+    MissingPaths,
+    /// A `then-change` contains an empty path.
+    #[serde(rename = "IC105")]
+    EmptyPath,
+    /// A path in a `then-change` separates directories with `\`.
+    #[serde(rename = "IC106")]
+    BackslashSeparator,
+    /// A checked path or target points outside of the repository.
+    #[serde(rename = "IC107")]
+    EscapingPath,
+    /// The scope of an `if-changed` is unknown, or its function could not be
+    /// found.
+    #[serde(rename = "IC108")]
+    UnresolvedScope,
+    /// A file could not be read.
+    #[serde(rename = "IC201")]
+    Unreadable,
+    /// A file was skipped because it is larger than the maximum file size.
+    #[serde(rename = "IC202")]
+    OversizedFile,
+    /// A file is not valid UTF-8, so it was decoded lossily.
+    #[serde(rename = "IC203")]
+    InvalidUtf8,
+    /// A file with directives was skipped because it is outside the scanned
+    /// directories.
+    #[serde(rename = "IC204")]
+    UnscannedFile,
+    /// An ignore trailer was rejected because it gives no reason.
+    #[serde(rename = "IC301")]
+    MissingIgnoreReason,
+    /// An ignore trailer was rejected because its reason doesn't match the
+    /// configured pattern.
+    #[serde(rename = "IC302")]
+    InvalidIgnoreReason,
+    /// An ignore trailer was rejected because its commit isn't by one of the
+    /// allowed authors.
+    #[serde(rename = "IC303")]
+    UnauthorizedIgnore,
+}
+
+impl Code {
+    /// All codes, in order.
+    pub const ALL: [Code; 21] = [
+        Code::MissingTarget,
+        Code::UnmodifiedTarget,
+        Code::MissingNamedBlock,
+        Code::DeletedTarget,
+        Code::RemovedTarget,
+        Code::UnannotatedFile,
+        Code::UnterminatedBlock,
+        Code::UnopenedBlock,
+        Code::UnclosedParenthesis,
+        Code::MissingPaths,
+        Code::EmptyPath,
+        Code::BackslashSeparator,
+        Code::EscapingPath,
+        Code::UnresolvedScope,
+        Code::Unreadable,
+        Code::OversizedFile,
+        Code::InvalidUtf8,
+        Code::UnscannedFile,
+        Code::MissingIgnoreReason,
+        Code::InvalidIgnoreReason,
+        Code::UnauthorizedIgnore,
+    ];
+
+    /// Get the code as written in diagnostics, e.g. `IC002`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::MissingTarget => "IC001",
+            Code::UnmodifiedTarget => "IC002",
+            Code::MissingNamedBlock => "IC003",
+            Code::DeletedTarget => "IC004",
+            Code::RemovedTarget => "IC005",
+            Code::UnannotatedFile => "IC006",
+            Code::UnterminatedBlock => "IC101",
+            Code::UnopenedBlock => "IC102",
+            Code::UnclosedParenthesis => "IC103",
+            Code::MissingPaths => "IC104",
+            Code::EmptyPath => "IC105",
+            Code::BackslashSeparator => "IC106",
+            Code::EscapingPath => "IC107",
+            Code::UnresolvedScope => "IC108",
+            Code::Unreadable => "IC201",
+            Code::OversizedFile => "IC202",
+            Code::InvalidUtf8 => "IC203",
+            Code::UnscannedFile => "IC204",
+            Code::MissingIgnoreReason => "IC301",
+            Code::InvalidIgnoreReason => "IC302",
+            Code::UnauthorizedIgnore => "IC303",
+        }
+    }
+
+    /// Get the name of the kind of problem, e.g. `unmodified-target`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Code::MissingTarget => "missing-target",
+            Code::UnmodifiedTarget => "unmodified-target",
+            Code::MissingNamedBlock => "missing-named-block",
+            Code::DeletedTarget => "deleted-target",
+            Code::RemovedTarget => "removed-target",
+            Code::UnannotatedFile => "unannotated-file",
+            Code::UnterminatedBlock => "unterminated-block",
+            Code::UnopenedBlock => "unopened-block",
+            Code::UnclosedParenthesis => "unclosed-parenthesis",
+            Code::MissingPaths => "missing-paths",
+            Code::EmptyPath => "empty-path",
+            Code::BackslashSeparator => "backslash-separator",
+            Code::EscapingPath => "escaping-path",
+            Code::UnresolvedScope => "unresolved-scope",
+            Code::Unreadable => "unreadable",
+            Code::OversizedFile => "oversized-file",
+            Code::InvalidUtf8 => "invalid-utf8",
+            Code::UnscannedFile => "unscanned-file",
+            Code::MissingIgnoreReason => "missing-ignore-reason",
+            Code::InvalidIgnoreReason => "invalid-ignore-reason",
+            Code::UnauthorizedIgnore => "unauthorized-ignore",
+        }
+    }
+
+    /// Get a longer description of the problem and how to fix it, in Markdown.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Code::MissingTarget => include_str!("explanations/IC001.md"),
+            Code::UnmodifiedTarget => include_str!("explanations/IC002.md"),
+            Code::MissingNamedBlock => include_str!("explanations/IC003.md"),
+            Code::DeletedTarget => include_str!("explanations/IC004.md"),
+            Code::RemovedTarget => include_str!("explanations/IC005.md"),
+            Code::UnannotatedFile => include_str!("explanations/IC006.md"),
+            Code::UnterminatedBlock => include_str!("explanations/IC101.md"),
+            Code::UnopenedBlock => include_str!("explanations/IC102.md"),
+            Code::UnclosedParenthesis => include_str!("explanations/IC103.md"),
+            Code::MissingPaths => include_str!("explanations/IC104.md"),
+            Code::EmptyPath => include_str!("explanations/IC105.md"),
+            Code::BackslashSeparator => include_str!("explanations/IC106.md"),
+            Code::EscapingPath => include_str!("explanations/IC107.md"),
+            Code::UnresolvedScope => include_str!("explanations/IC108.md"),
+            Code::Unreadable => include_str!("explanations/IC201.md"),
+            Code::OversizedFile => include_str!("explanations/IC202.md"),
+            Code::InvalidUtf8 => include_str!("explanations/IC203.md"),
+            Code::UnscannedFile => include_str!("explanations/IC204.md"),
+            Code::MissingIgnoreReason => include_str!("explanations/IC301.md"),
+            Code::InvalidIgnoreReason => include_str!("explanations/IC302.md"),
+            Code::UnauthorizedIgnore => include_str!("explanations/IC303.md"),
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Code {
+    type Err = String;
+
+    /// Parse a code, ignoring case. The `IC` prefix is optional.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = match s.get(..2) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("IC") => &s[2..],
+            _ => s,
+        };
+        Code::ALL
+            .into_iter()
+            .find(|code| &code.as_str()[2..] == digits)
+            .ok_or_else(|| format!("{s:?} is not a known error code"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for code in Code::ALL {
+            assert_eq!(code.as_str().parse::<Code>(), Ok(code));
+            assert!(!code.explanation().is_empty());
+            assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+            assert_eq!(
+                code.name().replace('-', ""),
+                format!("{code:?}").to_lowercase()
+            );
+        }
+        assert!(Code::ALL
+            .windows(2)
+            .all(|codes| codes[0].as_str() < codes[1].as_str()));
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("ic002".parse::<Code>(), Ok(Code::UnmodifiedTarget));
+        assert_eq!("101".parse::<Code>(), Ok(Code::UnterminatedBlock));
+        assert_eq!(
+            "IC999".parse::<Code>(),
+            Err("\"IC999\" is not a known error code".to_owned())
+        );
+        assert!("".parse::<Code>().is_err());
+    }
+}
@@ -1,13 +1,38 @@
+#[cfg(feature = "tokio")]
+mod asynchronous;
+#[cfg(any(test, feature = "git"))]
 mod git;
+mod memory;
 
 use std::{
-    collections::BTreeMap,
-    path::{Path, PathBuf},
+    fs,
+    io::{self, Read},
+    path::{Component, Path, PathBuf, MAIN_SEPARATOR_STR},
 };
 
-pub use git::GitEngine;
+#[cfg(feature = "tokio")]
+pub use asynchronous::{AsyncEngine, Blocking};
+#[cfg(any(test, feature = "git"))]
+pub use git::{DiffFilter, GitEngine, GitEngineBuilder};
+pub(crate) use memory::matches_pattern;
+pub use memory::{ChangedFile, MemoryEngine};
 
-use super::parser::Parser;
+use super::{
+    parser::Parser, CheckIter, Checker, Code, Diagnostic, LastEdit, Pattern, Severity, Stats,
+};
+
+/// A block covering lines of a file, and the targets it imposes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blame {
+    /// The name of the block, if any.
+    pub name: Option<String>,
+    /// The lines of the `if-changed` and `then-change` of the block.
+    pub range: (usize, usize),
+    /// The patterns of files to change, relative to the repository root, with
+    /// the name of the block to change in them, if any. Targets suppressed
+    /// inline are omitted.
+    pub targets: Vec<(PathBuf, Option<String>)>,
+}
 
 pub trait Engine {
     /// Iterate over changed files that match the given patterns and patterns that don't match any file.
@@ -21,130 +46,335 @@ pub trait Engine {
     /// Resolve a path to an absolute path.
     fn resolve(&self, path: impl AsRef<Path>) -> PathBuf;
 
+    /// Open a file, relative to the repository root. Fails for paths outside
+    /// of the repository.
+    fn read(&self, path: impl AsRef<Path>) -> io::Result<Box<dyn io::BufRead>> {
+        let path = path.as_ref();
+        if is_outside_root(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is outside of the repository"),
+            ));
+        }
+        let file = fs::File::open(self.resolve(path))?;
+        Ok(Box::new(io::BufReader::new(file)))
+    }
+
+    /// Open the content of a changed file before the change, e.g. to check
+    /// the blocks removed from it. By default, it is unknown.
+    fn read_original(&self, path: impl AsRef<Path>) -> io::Result<Box<dyn io::BufRead>> {
+        let path = path.as_ref();
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("the original content of {path:?} is unknown"),
+        ))
+    }
+
+    /// Identify the original content of a changed file, e.g. by the id of its
+    /// blob, so what is parsed from it can be reused while it is unchanged.
+    /// `None` if it can't change while the engine is used.
+    fn original_fingerprint(&self, _path: impl AsRef<Path>) -> Option<String> {
+        None
+    }
+
+    /// Identify the content of a file, e.g. by its modification time and
+    /// size, so what is parsed from it can be reused while it is unchanged.
+    /// `None` if the content can't change while the engine is used.
+    fn fingerprint(&self, path: impl AsRef<Path>) -> Option<String> {
+        let metadata = fs::metadata(self.resolve(path)).ok()?;
+        Some(format!(
+            "{:?}:{}",
+            metadata.modified().ok()?,
+            metadata.len()
+        ))
+    }
+
+    /// Get the size of a file in bytes, e.g. to skip large files, or `None` if
+    /// unknown.
+    fn size(&self, path: impl AsRef<Path>) -> Option<u64> {
+        fs::metadata(self.resolve(path))
+            .ok()
+            .map(|metadata| metadata.len())
+    }
+
+    /// Check if a file is binary, so it is skipped instead of parsed. By
+    /// default, files with a NUL byte near their start are, as by git.
+    fn is_binary(&self, path: impl AsRef<Path>) -> bool {
+        self.read(path).is_ok_and(has_nul_byte)
+    }
+
+    /// Check if a changed file was deleted.
+    fn is_deleted(&self, path: impl AsRef<Path>) -> bool {
+        let _ = path;
+        false
+    }
+
     /// Check if a file has been ignored.
     fn is_ignored(&self, path: impl AsRef<Path>) -> bool;
 
+    /// Check if the block named `name` of a file has been ignored on its
+    /// own, e.g. with an `ignore-if-changed: a.ts#name` trailer. By default,
+    /// no block is.
+    fn is_block_ignored(&self, path: impl AsRef<Path>, name: &str) -> bool {
+        let _ = (path, name);
+        false
+    }
+
+    /// Check if removing targets from the blocks of a file, or from its block
+    /// named `name` only, has been acknowledged, e.g. with a
+    /// `remove-if-changed: a.ts#name` trailer. By default, no removal is.
+    fn is_removal_acknowledged(&self, path: impl AsRef<Path>, name: Option<&str>) -> bool {
+        let _ = (path, name);
+        false
+    }
+
+    /// Check if a file is excluded by an [`.ifchangedignore`](crate::IgnoreFile)
+    /// file. Excluded files are also ignored, and needn't change as targets
+    /// unless the checker requires them to. By default, no file is.
+    fn is_excluded(&self, path: impl AsRef<Path>) -> bool {
+        let _ = path;
+        false
+    }
+
+    /// Get the lines added and removed within a range of lines in a file,
+    /// prefixed with `+` or `-`, or `None` if the range has not been modified.
+    fn range_changes(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<Vec<String>>;
+
+    /// Get the lines removed within a range of lines of the original content
+    /// of a file, prefixed with `-`, or `None` if none were removed.
+    fn removed_lines(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<Vec<String>> {
+        let _ = (path, range);
+        None
+    }
+
     /// Check if a range of lines in a file has been modified.
-    fn is_range_modified(&self, path: impl AsRef<Path>, range: (usize, usize)) -> bool;
+    fn is_range_modified(&self, path: impl AsRef<Path>, range: (usize, usize)) -> bool {
+        self.range_changes(path, range).is_some()
+    }
 
-    /// Check a file for dependent changes.
-    fn check(&self, path: impl AsRef<Path>) -> Result<(), Vec<String>> {
-        let path = path.as_ref();
-        let parser = match Parser::new(path, self.resolve(path)) {
-            Ok(parser) => parser,
-            Err(error) => return Err(vec![format!("Could not open {path:?}: {error}")]),
-        };
+    /// Get the most recent commit changing a range of lines in a file, e.g.
+    /// with `git blame`, or `None` if unknown.
+    fn last_edit(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<LastEdit> {
+        let _ = (path, range);
+        None
+    }
+
+    /// Problems found outside of any checked file, e.g. in commit trailers.
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        Vec::new()
+    }
 
+    /// Get the blocks of a file overlapping a range of lines, with their targets.
+    fn blame(
+        &self,
+        path: impl AsRef<Path>,
+        range: (usize, usize),
+    ) -> Result<Vec<Blame>, Vec<Diagnostic>> {
+        let path = path.as_ref();
+        let reader = self.read(path).map_err(|error| {
+            vec![Diagnostic::new(
+                Code::Unreadable,
+                path,
+                None,
+                format!("Could not open {path:?}: {error}"),
+            )]
+        })?;
+        let parser = Parser::scan(path, reader);
+
+        let mut blames = Vec::new();
         let mut errors = Vec::new();
         for block in parser {
             let block = match block {
                 Ok(block) => block,
                 Err(error) => {
-                    errors.extend(error);
+                    // Notes and warnings, e.g. of lossy decoding, don't
+                    // prevent blaming.
+                    errors.extend(
+                        error
+                            .into_iter()
+                            .filter(|problem| problem.severity == Severity::Error),
+                    );
                     continue;
                 }
             };
-
-            if !self.is_range_modified(path, block.range) {
+            let lines = block.lines();
+            if lines.1 < range.0 || range.1 < lines.0 {
                 continue;
             }
-
-            // Resolve patterns based on the current file.
-            let resolved_patterns = block
-                .patterns
+            let targets = resolve_patterns(path, block.targets, &block.ignored, true)
                 .into_iter()
-                .map(|mut pattern| {
-                    // Empty pattern means current file.
-                    pattern.value = if pattern.value == Path::new("") {
-                        path.to_owned()
-                    } else {
-                        path.parent().unwrap().join(&pattern.value)
-                    };
-                    pattern
-                })
-                .collect::<Vec<_>>();
-
-            let mut named_patterns = BTreeMap::new();
-            let mut unnamed_patterns = BTreeMap::new();
-            for pattern in &resolved_patterns {
-                let Some(name) = &pattern.name else {
-                    unnamed_patterns.insert(&*pattern.value, pattern.line);
-                    continue;
-                };
-                named_patterns.insert(&*pattern.value, (&**name, pattern.line));
-            }
-
-            for pattern in self.matches(unnamed_patterns.keys()).flat_map(Result::err) {
-                let line = unnamed_patterns.get(&*pattern).unwrap();
-                errors.push(format!(
-                    "Expected {pattern:?} to be modified because of \"then-change\" in {path:?} at line {line}."
-                ));
-            }
-
-            for (pattern, (name, line)) in named_patterns {
-                for result in self.matches([pattern]) {
-                    let dependent = match result {
-                        Ok(path) => path,
-                        Err(pattern) => {
-                            errors.push(format!(
-                                "Expected {pattern:?} to be modified because of \"then-change\" in {path:?} at line {line}."
-                            ));
-                            continue;
-                        }
-                    };
-
-                    // Try to open the file in search of the named block.
-                    let mut parser = match Parser::new(&dependent, self.resolve(&dependent)) {
-                        Ok(parser) => parser,
-                        Err(error) => {
-                            errors.push(format!(
-                                "Could not open {dependent:?} for \"then-change\" in {path:?} at line {line}: {error:?}"
-                            ));
-                            continue;
-                        }
-                    };
-
-                    // Search for the named block, accumulating errors along the way.
-                    let Some(block) = parser.find_map(|block| match block {
-                        Ok(block) if block.name.as_deref() == Some(name) => Some(Ok(block)),
-                        Err(error) => Some(Err(error)),
-                        _ => None,
-                    }) else {
-                        errors.push(format!(
-                            "Could not find \"if-changed\" with name \"{name}\" in {dependent:?} for \"then-change\" in {path:?} at line {line}."
-                        ));
-                        continue;
-                    };
-
-                    match block {
-                        Ok(block) => {
-                            if !self.is_range_modified(&dependent, block.range) {
-                                errors.push(format!(
-                                    "Expected {dependent:?} to be modified because of \"then-change\" in {path:?} at line {line}."
-                                ));
-                            }
-                        }
-                        Err(error) => errors.extend(error),
-                    }
-                }
-            }
+                .flatten()
+                .map(|pattern| (root_relative(&pattern.path), pattern.name))
+                .collect();
+            blames.push(Blame {
+                name: block.name,
+                range: lines,
+                targets,
+            });
         }
 
         if errors.is_empty() {
-            Ok(())
+            Ok(blames)
         } else {
             Err(errors)
         }
     }
+
+    /// Check a file for dependent changes.
+    fn check(&self, path: impl AsRef<Path>) -> Result<(), Vec<Diagnostic>> {
+        self.check_with_stats(path, &mut Stats::default())
+    }
+
+    /// Check a file for dependent changes lazily, yielding problems as they
+    /// are found.
+    fn check_iter(&self, path: impl AsRef<Path>) -> CheckIter<'_, Self> {
+        Checker::builder(self).path(path).build().check_iter()
+    }
+
+    /// Check a file for dependent changes, recording statistics into `stats`.
+    fn check_with_stats(
+        &self,
+        path: impl AsRef<Path>,
+        stats: &mut Stats,
+    ) -> Result<(), Vec<Diagnostic>> {
+        Checker::builder(self)
+            .path(path)
+            .build()
+            .check_with_stats(stats)
+    }
+}
+
+/// The number of bytes git searches for a NUL byte to tell binary files.
+const BINARY_SNIFF_LEN: u64 = 8000;
+
+/// Check if the start of `content` has a NUL byte. Unreadable content isn't
+/// considered binary, so the error is reported when it's parsed.
+pub(crate) fn has_nul_byte(content: impl Read) -> bool {
+    let mut start = Vec::new();
+    content
+        .take(BINARY_SNIFF_LEN)
+        .read_to_end(&mut start)
+        .is_ok_and(|_| memchr::memchr(0, &start).is_some())
+}
+
+/// Resolve patterns based on the file containing them, dropping patterns
+/// suppressed with an inline `ignore-if-changed`. If `normalize_separators`,
+/// `\` separates directories as `/` does.
+///
+/// `.` and `..` are resolved lexically. Patterns escaping the repository root
+/// are returned as errors, as written.
+pub(crate) fn resolve_patterns(
+    path: &Path,
+    patterns: Vec<Pattern>,
+    ignored: &[PathBuf],
+    normalize_separators: bool,
+) -> Vec<Result<Pattern, Pattern>> {
+    let resolve = |value: &Path| {
+        let normalized;
+        let value = match value.to_str() {
+            Some(value) if normalize_separators && value.contains('\\') => {
+                normalized = PathBuf::from(value.replace('\\', "/"));
+                &normalized
+            }
+            _ => value,
+        };
+        // Empty pattern means current file.
+        if value == Path::new("") {
+            Some(path.to_owned())
+        } else {
+            // Paths without a parent, e.g. empty ones, are at the root.
+            let directory = path.parent().unwrap_or(Path::new(""));
+            normalize_lexically(&directory.join(value))
+        }
+    };
+    let ignored = ignored
+        .iter()
+        .filter_map(|value| Some(root_relative(&resolve(value)?)))
+        .collect::<Vec<_>>();
+    patterns
+        .into_iter()
+        .map(|pattern| match resolve(&pattern.path) {
+            Some(path) => Ok(Pattern { path, ..pattern }),
+            None => Err(pattern),
+        })
+        .filter(|pattern| {
+            pattern.as_ref().map_or(true, |pattern| {
+                !ignored.contains(&root_relative(&pattern.path))
+            })
+        })
+        .collect()
+}
+
+/// Resolve `.` and `..` in a path relative to the repository root without
+/// touching the file system, or `None` if it escapes the root.
+fn normalize_lexically(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                depth = depth.checked_sub(1)?;
+                normalized.pop();
+            }
+            Component::Normal(name) => {
+                depth += 1;
+                normalized.push(name);
+            }
+            Component::RootDir | Component::Prefix(_) => normalized.push(component),
+        }
+    }
+    Some(normalized)
+}
+
+/// Whether a path relative to the repository root points outside of it, e.g.
+/// because it is absolute or climbs past the root with `..`.
+pub(crate) fn is_outside_root(path: &Path) -> bool {
+    path.has_root() || normalize_lexically(path).is_none()
+}
+
+/// Strip the `/` anchoring a resolved pattern to the repository root.
+pub(crate) fn root_relative(pattern: &Path) -> PathBuf {
+    pattern
+        .strip_prefix(MAIN_SEPARATOR_STR)
+        .unwrap_or(pattern)
+        .to_owned()
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
     use indoc::indoc;
 
-    use crate::{engine::GitEngine, testing::git_test, Engine as _};
+    use super::resolve_patterns;
+    use crate::{engine::GitEngine, testing::git_test, Blame, Code, Engine as _, Pattern, Stats};
+
+    #[test]
+    fn test_resolve_patterns_without_parent() {
+        let pattern = |path: &str| Pattern {
+            name: None,
+            path: path.into(),
+            line: 1,
+        };
+        for path in ["", "/"] {
+            let resolved = resolve_patterns(
+                Path::new(path),
+                vec![pattern("a/b.ts"), pattern("../b.ts")],
+                &[],
+                false,
+            );
+            assert_eq!(
+                resolved
+                    .into_iter()
+                    .map(|result| result
+                        .map(|pattern| pattern.path)
+                        .map_err(|pattern| pattern.path))
+                    .collect::<Vec<_>>(),
+                [Ok(PathBuf::from("a/b.ts")), Err(PathBuf::from("../b.ts"))],
+            );
+        }
+    }
 
     #[test]
     fn test_check() {
@@ -167,7 +397,7 @@ mod tests {
             ]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches([""; 0]).collect::<Vec<_>>(), @r###"[{"Ok": "src/a.js"}, {"Ok": "src/b.js"}]"###);
@@ -194,11 +424,31 @@ mod tests {
             ]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "src/a.js"}]"###);
-        insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")), @r###"{"Err": ["Expected \"src/b.js\" to be modified because of \"then-change\" in \"src/a.js\" at line 3."]}"###);
+        insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")), @r###"
+        {
+          "Err": [
+            {
+              "code": "IC002",
+              "path": "src/a.js",
+              "line": 3,
+              "range": [
+                1,
+                3
+              ],
+              "target": "src/b.js",
+              "changes": [
+                "-foo",
+                "+foobar"
+              ],
+              "message": "Expected \"src/b.js\" to be modified because of \"then-change\" in \"src/a.js\" at line 3."
+            }
+          ]
+        }
+        "###);
     }
 
     #[test]
@@ -222,18 +472,99 @@ mod tests {
             ]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "src/a.js"}]"###);
         insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")), @r###"{"Ok": null}"###);
     }
 
+    #[test]
+    fn test_check_stats() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": [
+                "src/a.js" => indoc!{"
+                    // if-changed
+                    foo
+                    // then-change(b.js)
+
+                    // if-changed
+                    bar
+                    // then-change(b.js)
+                "},
+                "src/b.js" => ""
+            ]
+            working: [
+                "src/a.js" => indoc!{"
+                    // if-changed
+                    foobar
+                    // then-change(b.js)
+
+                    // if-changed
+                    bar
+                    // then-change(b.js)
+                "},
+                "src/b.js" => "bar"
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let mut stats = Stats::default();
+        assert!(engine.check_with_stats("src/a.js", &mut stats).is_ok());
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.blocks, 2);
+        assert_eq!(stats.unmodified_blocks, 1);
+    }
+
+    #[test]
+    fn test_check_parent_relative() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "src/a/a.js" => indoc!{"
+                    // if-changed
+                    foo
+                    // then-change(../b.js, ./../../c.js, /src/../d.js, ../../../e.js)
+                "},
+                "src/b.js" => "",
+                "c.js" => "",
+                "d.js" => ""
+            ]
+        };
+
+        // Targets stay inside the repository, except the last one.
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        insta::assert_snapshot!(engine
+            .check(Path::new("src/a/a.js"))
+            .unwrap_err()
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"IC107: Path `../../../e.js` for "then-change" in "src/a/a.js" at line 3 points outside of the repository."###);
+    }
+
+    #[test]
+    fn test_check_outside_paths() {
+        let (tempdir, repo) = git_test! {
+            working: ["a.js" => "", "src/b.js" => ""]
+        };
+
+        // Paths outside the repository aren't read, even if they exist.
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let outside = tempdir.path().join("a.js");
+        for path in [Path::new("../a.js"), Path::new("b/../../a.js"), &outside] {
+            let errors = engine.check(path).unwrap_err();
+            assert_eq!(errors[0].code, Code::EscapingPath, "{path:?}");
+            assert!(errors[0].message.ends_with("is outside of the repository."));
+        }
+        assert!(engine.read("../a.js").is_err());
+        assert!(engine.read("src/../a.js").is_ok());
+    }
+
     #[test]
     fn test_check_missing_file() {
         let (tempdir, repo) = git_test! {};
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         assert!(engine
@@ -241,6 +572,7 @@ mod tests {
             .unwrap_err()
             .first()
             .unwrap()
+            .message
             .contains("Could not open \"a.js\""));
     }
 
@@ -273,13 +605,73 @@ mod tests {
             ]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches([""; 0]).collect::<Vec<_>>(), @r###"[{"Ok": "src/a.js"}, {"Ok": "src/b.js"}]"###);
         insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")), @r###"{"Ok": null}"###);
     }
 
+    #[test]
+    fn test_check_inline_ignore() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "src/a.js" => indoc!{"
+                    // if-changed
+                    // ignore-if-changed: b.js, /c.js -- Generated.
+                    foo
+                    // then-change(b.js:bar, /c.js, d.js)
+                "}
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")).unwrap_err().iter().map(|error| error.target.as_deref()).collect::<Vec<_>>(), @r###"["src/d.js"]"###);
+    }
+
+    #[test]
+    fn test_blame() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "src/a.js" => indoc!{"
+                    // if-changed
+                    foo
+                    // then-change(b.js:bar, /c.js)
+                    baz
+                    // if-changed(qux)
+                    // ignore-if-changed: d.js -- Generated.
+                    qux
+                    // then-change(d.js, e.js)
+                "}
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        assert_eq!(engine.blame("src/a.js", (4, 4)).unwrap(), []);
+        assert_eq!(
+            engine.blame("src/a.js", (2, 7)).unwrap(),
+            [
+                Blame {
+                    name: None,
+                    range: (1, 3),
+                    targets: vec![
+                        ("src/b.js".into(), Some("bar".into())),
+                        ("c.js".into(), None)
+                    ],
+                },
+                Blame {
+                    name: Some("qux".into()),
+                    range: (5, 8),
+                    targets: vec![("src/e.js".into(), None)],
+                }
+            ]
+        );
+        assert_eq!(
+            engine.blame("src/missing.js", (1, 1)).unwrap_err()[0].code,
+            Code::Unreadable
+        );
+    }
+
     #[test]
     fn test_check_named_fail() {
         let (tempdir, repo) = git_test! {
@@ -310,11 +702,32 @@ mod tests {
             ]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches([""; 0]).collect::<Vec<_>>(), @r###"[{"Ok": "src/a.js"}, {"Ok": "src/b.js"}]"###);
-        insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")), @r###"{"Err": ["Expected \"src/b.js\" to be modified because of \"then-change\" in \"src/a.js\" at line 3."]}"###);
+        insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")), @r###"
+        {
+          "Err": [
+            {
+              "code": "IC002",
+              "path": "src/a.js",
+              "line": 3,
+              "range": [
+                1,
+                3
+              ],
+              "target": "src/b.js",
+              "target_block": "bar",
+              "changes": [
+                "-foo",
+                "+foobar"
+              ],
+              "message": "Expected \"src/b.js\" to be modified because of \"then-change\" in \"src/a.js\" at line 3."
+            }
+          ]
+        }
+        "###);
     }
 
     #[test]
@@ -338,14 +751,35 @@ mod tests {
             ]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches([""; 0]).collect::<Vec<_>>(), @r###"[{"Ok": "src/a.js"}, {"Ok": "src/b.js"}]"###);
         insta::assert_compact_json_snapshot!(engine.check(Path::new("src/a.js")), @r###"
         {
           "Err": [
-            "Could not find \"if-changed\" with name \"bar\" in \"src/b.js\" for \"then-change\" in \"src/a.js\" at line 3."
+            {
+              "code": "IC003",
+              "path": "src/a.js",
+              "line": 3,
+              "range": [
+                1,
+                3
+              ],
+              "target": "src/b.js",
+              "target_block": "bar",
+              "changes": [
+                "-foo",
+                "+foobar"
+              ],
+              "suggestion": {
+                "message": "add `// if-changed(bar)` to \"src/b.js\" around the lines to change with \"src/a.js\"",
+                "path": "src/b.js",
+                "line": 2,
+                "text": "// if-changed(bar)\n// then-change(/src/a.js)\n"
+              },
+              "message": "Could not find \"if-changed\" with name \"bar\" in \"src/b.js\" for \"then-change\" in \"src/a.js\" at line 3."
+            }
           ]
         }
         "###);
@@ -363,10 +797,23 @@ mod tests {
             ]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches([""; 0]).collect::<Vec<_>>(), @r###"[{"Ok": "a.js"}]"###);
-        insta::assert_compact_json_snapshot!(engine.check(Path::new("a.js")), @r###"{"Err": ["Could not find ')' for \"then-change\" at line 3 for \"a.js\"."]}"###);
+        insta::assert_compact_json_snapshot!(engine.check(Path::new("a.js")), @r###"
+        {
+          "Err": [
+            {
+              "code": "IC103",
+              "path": "a.js",
+              "line": 3,
+              "range": null,
+              "target": null,
+              "message": "Could not find ')' for \"then-change\" at line 3 for \"a.js\"."
+            }
+          ]
+        }
+        "###);
     }
 }
@@ -0,0 +1,353 @@
+use std::{
+    io::{self, Read as _},
+    path::{Path, PathBuf},
+};
+
+use super::Engine;
+use crate::{Checker, Diagnostic, LastEdit};
+
+/// An [`Engine`] whose operations may wait on I/O, e.g. engines backed by the
+/// APIs of code hosts.
+///
+/// Use [`Blocking`] to run an [`Engine`] as one.
+#[allow(async_fn_in_trait)]
+pub trait AsyncEngine {
+    /// Get changed files that match the given patterns and patterns that don't match any file.
+    ///
+    /// If patterns is empty, all changed files are returned.
+    async fn matches(&self, patterns: Vec<PathBuf>) -> Vec<Result<PathBuf, PathBuf>>;
+
+    /// Read a file, relative to the repository root.
+    async fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Check if a file has been ignored.
+    async fn is_ignored(&self, path: &Path) -> bool;
+
+    /// Check if the block named `name` of a file has been ignored on its
+    /// own. By default, no block is.
+    async fn is_block_ignored(&self, path: &Path, name: &str) -> bool {
+        let _ = (path, name);
+        false
+    }
+
+    /// Check if a changed file was deleted. By default, none was.
+    async fn is_deleted(&self, path: &Path) -> bool {
+        let _ = path;
+        false
+    }
+
+    /// Check if a file is binary, so it can't have blocks. By default, none
+    /// is.
+    async fn is_binary(&self, path: &Path) -> bool {
+        let _ = path;
+        false
+    }
+
+    /// Read the content of a changed file before the change, e.g. to check
+    /// the blocks removed from it. By default, it is unknown.
+    async fn read_original(&self, path: &Path) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("the original content of {path:?} is unknown"),
+        ))
+    }
+
+    /// Get the size of a file in bytes, e.g. to skip large files, or `None` if
+    /// unknown, as by default.
+    async fn size(&self, path: &Path) -> Option<u64> {
+        let _ = path;
+        None
+    }
+
+    /// Check if removing targets from the blocks of a file, or from its block
+    /// named `name` only, has been acknowledged. By default, no removal is.
+    async fn is_removal_acknowledged(&self, path: &Path, name: Option<&str>) -> bool {
+        let _ = (path, name);
+        false
+    }
+
+    /// Check if a file is excluded by an [`.ifchangedignore`](crate::IgnoreFile)
+    /// file. By default, no file is.
+    async fn is_excluded(&self, path: &Path) -> bool {
+        let _ = path;
+        false
+    }
+
+    /// Get the lines added and removed within a range of lines in a file,
+    /// prefixed with `+` or `-`, or `None` if the range has not been modified.
+    async fn range_changes(&self, path: &Path, range: (usize, usize)) -> Option<Vec<String>>;
+
+    /// Get the lines removed within a range of lines of the original content
+    /// of a file, prefixed with `-`, or `None` if none were removed, as by
+    /// default.
+    async fn removed_lines(&self, path: &Path, range: (usize, usize)) -> Option<Vec<String>> {
+        let _ = (path, range);
+        None
+    }
+
+    /// Check if a range of lines in a file has been modified.
+    async fn is_range_modified(&self, path: &Path, range: (usize, usize)) -> bool {
+        self.range_changes(path, range).await.is_some()
+    }
+
+    /// Get the most recent commit changing a range of lines in a file, or
+    /// `None` if unknown, as by default.
+    async fn last_edit(&self, path: &Path, range: (usize, usize)) -> Option<LastEdit> {
+        let _ = (path, range);
+        None
+    }
+
+    /// Problems found outside of any checked file, e.g. in commit trailers.
+    async fn diagnostics(&self) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+
+    /// Check a file for dependent changes with the default configuration.
+    /// Use [`Checker::check_async`] to configure the check.
+    async fn check(&self, path: &Path) -> Result<(), Vec<Diagnostic>> {
+        Checker::builder(self)
+            .path(path)
+            .build()
+            .check_async()
+            .await
+    }
+
+    /// Check all changed files that are not ignored for dependent changes,
+    /// including problems found outside of them, with the default
+    /// configuration. Use [`Checker::check_async`] to configure the check.
+    async fn check_all(&self) -> Result<(), Vec<Diagnostic>> {
+        Checker::builder(self).build().check_async().await
+    }
+}
+
+/// Runs an [`Engine`] as an [`AsyncEngine`]. Operations of the engine block
+/// the task running them.
+pub struct Blocking<E>(pub E);
+
+impl<E: Engine> AsyncEngine for Blocking<E> {
+    async fn matches(&self, patterns: Vec<PathBuf>) -> Vec<Result<PathBuf, PathBuf>> {
+        self.0.matches(patterns).collect()
+    }
+
+    async fn read(&self, path: &Path) -> io::Result<String> {
+        // Like the parser, invalid UTF-8 is replaced rather than failing.
+        let mut source = Vec::new();
+        self.0.read(path)?.read_to_end(&mut source)?;
+        Ok(String::from_utf8_lossy(&source).into_owned())
+    }
+
+    async fn is_ignored(&self, path: &Path) -> bool {
+        self.0.is_ignored(path)
+    }
+
+    async fn is_block_ignored(&self, path: &Path, name: &str) -> bool {
+        self.0.is_block_ignored(path, name)
+    }
+
+    async fn is_deleted(&self, path: &Path) -> bool {
+        self.0.is_deleted(path)
+    }
+
+    async fn is_binary(&self, path: &Path) -> bool {
+        self.0.is_binary(path)
+    }
+
+    async fn read_original(&self, path: &Path) -> io::Result<String> {
+        let mut source = Vec::new();
+        self.0.read_original(path)?.read_to_end(&mut source)?;
+        Ok(String::from_utf8_lossy(&source).into_owned())
+    }
+
+    async fn size(&self, path: &Path) -> Option<u64> {
+        self.0.size(path)
+    }
+
+    async fn is_removal_acknowledged(&self, path: &Path, name: Option<&str>) -> bool {
+        self.0.is_removal_acknowledged(path, name)
+    }
+
+    async fn is_excluded(&self, path: &Path) -> bool {
+        self.0.is_excluded(path)
+    }
+
+    async fn range_changes(&self, path: &Path, range: (usize, usize)) -> Option<Vec<String>> {
+        self.0.range_changes(path, range)
+    }
+
+    async fn removed_lines(&self, path: &Path, range: (usize, usize)) -> Option<Vec<String>> {
+        self.0.removed_lines(path, range)
+    }
+
+    async fn last_edit(&self, path: &Path, range: (usize, usize)) -> Option<LastEdit> {
+        self.0.last_edit(path, range)
+    }
+
+    async fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.0.diagnostics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{
+        testing::git_test, Checker, CheckerBuilder, Code, DeletedTargetPolicy, GitEngine,
+        MessageConfig, Severity,
+    };
+
+    #[tokio::test]
+    async fn test_blocking() {
+        let (_tempdir, repo) = git_test! {
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(d.ts)
+                "},
+                "b.ts" => indoc! {"
+                    // if-changed
+                    B,
+                    // then-change(c.ts:c)
+                "},
+                "c.ts" => indoc! {"
+                    // if-changed(d)
+                    C,
+                    // then-change(b.ts)
+                "}
+            ]
+        };
+
+        let engine = Blocking(GitEngine::new(&repo, None, None).unwrap());
+        let errors = engine.check_all().await.unwrap_err();
+        assert_eq!(
+            errors,
+            Checker::builder(&engine.0).build().check().unwrap_err()
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.path.to_str().unwrap(), error.code))
+                .collect::<Vec<_>>(),
+            [
+                ("a.ts", Code::UnmodifiedTarget),
+                ("b.ts", Code::MissingNamedBlock),
+            ]
+        );
+        assert!(engine.check(Path::new("c.ts")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_deleted_and_binary_targets() {
+        let (tempdir, repo) = git_test! {
+            "initial": [
+                "d.ts" => indoc! {"
+                    // if-changed(d)
+                    D,
+                    // then-change(a.ts)
+                "},
+                "e.ts" => "E,\n"
+            ]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(d.ts:d, e.bin:e)
+                "},
+                "b.ts" => indoc! {"
+                    // if-changed
+                    B,
+                    // then-change(a.ts:a)
+                "},
+                "e.bin" => "\0// if-changed(f)\n"
+            ]
+        };
+        std::fs::remove_file(tempdir.path().join("d.ts")).unwrap();
+        std::fs::remove_file(tempdir.path().join("e.ts")).unwrap();
+
+        let engine = Blocking(GitEngine::new(&repo, None, None).unwrap());
+        let errors = engine.check_all().await.unwrap_err();
+        assert_eq!(
+            errors,
+            Checker::builder(&engine.0).build().check().unwrap_err()
+        );
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.path.to_str().unwrap(), error.code))
+                .collect::<Vec<_>>(),
+            [("b.ts", Code::MissingNamedBlock)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_async_configured() {
+        let (tempdir, repo) = git_test! {
+            "initial": [
+                "a.ts" => indoc! {"
+                    // if-changed(a)
+                    A,
+                    // then-change(b.ts, c.ts)
+                "},
+                "b.ts" => "B,\n",
+                "c.ts" => "C,\n",
+                "d.ts" => indoc! {"
+                    // if-changed
+                    D,
+                    // then-change(e.ts)
+                "},
+                "e.ts" => "E,\n",
+                "f.ts" => "F,\n",
+                "proto/a.proto" => "A\n"
+            ]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed(a)
+                    B,
+                    // then-change(b.ts, f.ts)
+                "},
+                "proto/a.proto" => "B\n"
+            ]
+        };
+        std::fs::remove_file(tempdir.path().join("d.ts")).unwrap();
+        std::fs::remove_file(tempdir.path().join("f.ts")).unwrap();
+
+        fn configure<E: ?Sized>(builder: CheckerBuilder<'_, E>) -> CheckerBuilder<'_, E> {
+            builder
+                .severity_overrides([(Code::RemovedTarget, Severity::Warning)])
+                .messages(MessageConfig {
+                    template: Some("{message} See the wiki.".into()),
+                    ..Default::default()
+                })
+                .deleted_targets(DeletedTargetPolicy::Fail)
+                .removed_blocks(true)
+                .removed_targets(true)
+                .generated([("gen/*.ts", "proto/*.proto")])
+        }
+        let engine = Blocking(GitEngine::new(&repo, None, None).unwrap());
+        let errors = configure(Checker::builder(&engine))
+            .build()
+            .check_async()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            configure(Checker::builder(&engine.0))
+                .build()
+                .check()
+                .unwrap_err()
+        );
+        insta::assert_snapshot!(errors
+            .iter()
+            .map(|error| format!("{} {:?}: {}", error.code, error.severity, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"
+        IC005 Warning: Removed `c.ts` from "then-change" in "a.ts", originally at line 3, but it was not modified. Modify it too, or acknowledge the removal with a "remove-if-changed: a.ts#a" trailer. See the wiki.
+        IC002 Error: Expected "b.ts" to be modified because of "then-change" in "a.ts" at line 3. See the wiki.
+        IC004 Error: Expected "f.ts" to be modified because of "then-change" in "a.ts" at line 3, but it was deleted. See the wiki.
+        IC002 Error: Expected "e.ts" to be modified because of "then-change" removed from "d.ts" at line 3. See the wiki.
+        IC002 Error: Expected files matching `gen/*.ts` to be modified because they are generated from "proto/a.proto". See the wiki.
+        "###);
+    }
+}
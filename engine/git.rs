@@ -1,235 +1,1218 @@
 use std::{
-    borrow::{BorrowMut, Cow},
+    borrow::Cow,
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Read as _},
     path::{Path, PathBuf, MAIN_SEPARATOR_STR},
-    str::FromStr as _,
+    str::FromStr,
+    sync::Mutex,
 };
 
 use bstr::ByteSlice;
-use genawaiter::{rc::gen, yield_};
 
-use super::Engine;
-
-const IF_CHANGED_IGNORE_TRAILER: &[u8] = b"ignore-if-changed";
+use super::{has_nul_byte, resolve_patterns, root_relative, Engine};
+use crate::{
+    checker::generated_patterns, owners::matches, parser::Parser, Code, Diagnostic, IgnoreFile,
+    LastEdit, TrailerConfig, IGNORE_FILE_NAME, REMOVAL_TRAILER,
+};
 
+/// Compares revisions of a git repository.
+///
+/// The diff is computed once, when the engine is built, and the engine can be
+/// shared between threads, e.g. to check files in parallel.
 pub struct GitEngine<'repo> {
-    ignore_pathspec: Option<git2::Pathspec>,
+    /// Git patterns of the files ignored by trailers and patterns, in order of
+    /// precedence. Pathspecs can't be shared between threads, so patterns are
+    /// compiled when matching.
+    ignore_patterns: Vec<PathBuf>,
+    /// The files and names of the blocks ignored on their own, e.g. by
+    /// `ignore-if-changed: a.ts#name` trailers.
+    ignored_blocks: Vec<(String, String)>,
+    /// The files, and names of blocks, whose removed targets are acknowledged
+    /// by `remove-if-changed` trailers.
+    acknowledged_removals: Vec<(String, Option<String>)>,
+    diagnostics: Vec<Diagnostic>,
+    /// The repository, opened again so the engine owns a handle it can share
+    /// between threads, e.g. to read original content.
+    repository: Mutex<git2::Repository>,
+    /// The canonical path of the working tree, which paths are resolved
+    /// against.
+    root: PathBuf,
+    /// The tree compared against, if any.
+    from_tree: Option<git2::Oid>,
+    /// The commit compared with, or `None` for the working tree.
+    to_commit: Option<git2::Oid>,
+    diff_filter: DiffFilter,
+    /// The diff between the revisions.
+    diff: Mutex<git2::Diff<'repo>>,
+    /// The hunks of each changed file, by path.
+    hunks: HashMap<PathBuf, FileHunks>,
+    /// The `.ifchangedignore` file of each directory looked up so far, if it
+    /// has one.
+    ignore_files: Mutex<HashMap<PathBuf, Option<IgnoreFile>>>,
+}
+
+/// The changes of a file between the revisions.
+struct FileHunks {
+    /// The path of the file before the change, differing if it was renamed.
+    original: PathBuf,
+    /// Whether the file is untracked, i.e. all its lines are new.
+    untracked: bool,
+    /// Whether the file was deleted.
+    deleted: bool,
+    /// Whether `.gitattributes` keeps git from diffing the file like
+    /// reviewers see it, i.e. all its lines count as modified.
+    undiffed: bool,
+    /// Whether git found the file to be binary, if it could tell.
+    binary: Option<bool>,
+    hunks: Vec<Hunk>,
+}
+
+/// A group of changed lines of a file.
+struct Hunk {
+    /// The first line of the hunk in the new content.
+    new_start: usize,
+    /// The number of lines of the hunk in the new content.
+    new_lines: usize,
+    /// The lines added and removed, with their number in the new and old
+    /// content respectively, and their origin, `+` or `-`.
+    lines: Vec<(usize, char, String)>,
+}
+
+/// Builds a [`GitEngine`].
+///
+/// ```no_run
+/// # let repository = git2::Repository::open_from_env().unwrap();
+/// use if_changed::GitEngine;
+///
+/// let engine = GitEngine::builder(&repository)
+///     .from_ref("main")
+///     .include_untracked(false)
+///     .detect_renames(true)
+///     .build()?;
+/// # std::io::Result::Ok(())
+/// ```
+pub struct GitEngineBuilder<'repo> {
     repository: &'repo git2::Repository,
-    from_tree: Option<git2::Tree<'repo>>,
-    to_tree: Option<git2::Tree<'repo>>,
+    from_ref: Option<String>,
+    to_ref: Option<String>,
+    trailers: TrailerConfig,
+    include_untracked: bool,
+    detect_renames: bool,
+    include_mode_changes: bool,
+    diff_filter: DiffFilter,
+    merge_base: bool,
+    context_lines: u32,
+    narrow_to: Vec<String>,
+    narrow_generated: Vec<(String, String)>,
+    ignore: Vec<String>,
 }
 
 impl<'repo> GitEngine<'repo> {
+    /// Create an engine comparing `from_ref`, or HEAD, with `to_ref`, or the
+    /// working tree. Fails if a revision can't be resolved.
     #[allow(clippy::new_ret_no_self)]
     pub fn new(
         repository: &'repo git2::Repository,
         from_ref: Option<&str>,
         to_ref: Option<&str>,
-    ) -> impl Engine + 'repo {
-        let ignore_pathspec = ignore_pathspec(to_ref, repository);
+    ) -> io::Result<impl Engine + 'repo> {
+        Self::with_trailers(repository, from_ref, to_ref, &TrailerConfig::default())
+    }
+
+    /// Create an engine recognizing ignore trailers as described by `trailers`.
+    pub fn with_trailers(
+        repository: &'repo git2::Repository,
+        from_ref: Option<&str>,
+        to_ref: Option<&str>,
+        trailers: &TrailerConfig,
+    ) -> io::Result<impl Engine + 'repo> {
+        let mut builder = Self::builder(repository).trailers(trailers.clone());
+        if let Some(from_ref) = from_ref {
+            builder = builder.from_ref(from_ref);
+        }
+        if let Some(to_ref) = to_ref {
+            builder = builder.to_ref(to_ref);
+        }
+        builder.build()
+    }
+
+    /// Start building an engine comparing revisions of `repository`. By
+    /// default, HEAD is compared with the working tree, including untracked
+    /// files.
+    pub fn builder(repository: &'repo git2::Repository) -> GitEngineBuilder<'repo> {
+        GitEngineBuilder {
+            repository,
+            from_ref: None,
+            to_ref: None,
+            trailers: TrailerConfig::default(),
+            include_untracked: true,
+            detect_renames: false,
+            include_mode_changes: false,
+            diff_filter: DiffFilter::default(),
+            merge_base: false,
+            context_lines: 3,
+            narrow_to: Vec::new(),
+            narrow_generated: Vec::new(),
+            ignore: Vec::new(),
+        }
+    }
+
+    /// Get the files renamed between the revisions, as pairs of their old and
+    /// new paths sorted by old path. Renames are only found with
+    /// [`GitEngineBuilder::detect_renames`].
+    pub fn renames(&self) -> Vec<(PathBuf, PathBuf)> {
+        let mut renames = self
+            .hunks
+            .iter()
+            .filter(|(path, file)| file.original != **path)
+            .map(|(path, file)| (file.original.clone(), path.clone()))
+            .collect::<Vec<_>>();
+        renames.sort();
+        renames
+    }
+
+    /// Get the entry of `path` in the tree compared against, under its path
+    /// before a rename.
+    fn original_entry(
+        &self,
+        repository: &git2::Repository,
+        path: &Path,
+    ) -> Option<git2::TreeEntry<'static>> {
+        let original = self.hunks.get(path).map_or(path, |file| &file.original);
+        let tree = repository.find_tree(self.from_tree?).ok()?;
+        tree.get_path(original).ok()
+    }
+}
+
+impl Engine for GitEngine<'_> {
+    fn matches(
+        &self,
+        patterns: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> impl Iterator<Item = Result<PathBuf, PathBuf>> {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| {
+                let pattern = pattern.as_ref();
+                pattern
+                    .strip_prefix(MAIN_SEPARATOR_STR)
+                    .unwrap_or(pattern)
+                    .to_owned()
+            })
+            .collect();
+        match_diff(&self.diff.lock().unwrap(), self.diff_filter, patterns).into_iter()
+    }
+
+    fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(path.as_ref())
+    }
+
+    fn read_original(&self, path: impl AsRef<Path>) -> io::Result<Box<dyn io::BufRead>> {
+        let path = path.as_ref();
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{path:?} did not exist before the change"),
+            )
+        };
+        let repository = self.repository.lock().unwrap();
+        let blob = self
+            .original_entry(&repository, path)
+            .and_then(|entry| entry.to_object(&repository).ok())
+            .and_then(|object| object.peel_to_blob().ok())
+            .ok_or_else(not_found)?;
+        Ok(Box::new(io::Cursor::new(blob.content().to_owned())))
+    }
+
+    fn original_fingerprint(&self, path: impl AsRef<Path>) -> Option<String> {
+        let repository = self.repository.lock().unwrap();
+        let entry = self.original_entry(&repository, path.as_ref())?;
+        Some(entry.id().to_string())
+    }
+
+    fn is_binary(&self, path: impl AsRef<Path>) -> bool {
+        // Git tells binary files apart while diffing them, honoring e.g. the
+        // `binary` attribute. Other files, e.g. targets or files marked
+        // `-diff`, are sniffed.
+        let path = path.as_ref();
+        match self.hunks.get(path).and_then(|file| file.binary) {
+            Some(binary) => binary,
+            None => self.read(path).is_ok_and(has_nul_byte),
+        }
+    }
+
+    fn is_deleted(&self, path: impl AsRef<Path>) -> bool {
+        self.hunks
+            .get(path.as_ref())
+            .is_some_and(|file| file.deleted)
+    }
+
+    fn is_ignored(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if self.is_excluded(path) {
+            return true;
+        }
+        !self.ignore_patterns.is_empty() && matches_pathspec(&self.ignore_patterns, path)
+    }
+
+    fn is_block_ignored(&self, path: impl AsRef<Path>, name: &str) -> bool {
+        let path = path.as_ref();
+        self.ignored_blocks
+            .iter()
+            .any(|(pattern, ignored)| ignored == name && matches_pathspec([pattern], path))
+    }
+
+    fn is_removal_acknowledged(&self, path: impl AsRef<Path>, name: Option<&str>) -> bool {
+        let path = path.as_ref();
+        self.acknowledged_removals
+            .iter()
+            .any(|(pattern, acknowledged)| {
+                (acknowledged.is_none() || acknowledged.as_deref() == name)
+                    && matches_pathspec([pattern], path)
+            })
+    }
+
+    fn is_excluded(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        let mut ignore_files = self.ignore_files.lock().unwrap();
+        // Files of deeper directories take precedence, as with `.gitignore`.
+        for directory in path.ancestors().skip(1) {
+            let ignore_file = ignore_files.entry(directory.to_owned()).or_insert_with(|| {
+                let mut source = String::new();
+                let mut reader = self.read(directory.join(IGNORE_FILE_NAME)).ok()?;
+                reader.read_to_string(&mut source).ok()?;
+                Some(IgnoreFile::parse(&source))
+            });
+            let Ok(relative) = path.strip_prefix(directory) else {
+                continue;
+            };
+            if let Some(excluded) = ignore_file
+                .as_ref()
+                .and_then(|file| file.excludes(relative))
+            {
+                return excluded;
+            }
+        }
+        false
+    }
+
+    fn last_edit(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<LastEdit> {
+        let path = path.as_ref();
+        let mut options = git2::BlameOptions::new();
+        if let Some(commit) = self.to_commit {
+            options
+                .newest_commit(commit)
+                .min_line(range.0)
+                .max_line(range.1);
+            let repository = self.repository.lock().unwrap();
+            let blame = repository.blame_file(path, Some(&mut options)).ok()?;
+            return last_edit(&blame, range);
+        }
+        // Lines of the working tree may have moved since HEAD, so its content
+        // is blamed on top of HEAD's.
+        let content = fs::read(self.resolve(path)).ok()?;
+        let repository = self.repository.lock().unwrap();
+        let blame = repository.blame_file(path, Some(&mut options)).ok()?;
+        let buffer = blame.blame_buffer(&content).ok()?;
+        last_edit(&buffer, range)
+    }
+
+    fn range_changes(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<Vec<String>> {
+        let path = path.as_ref();
+        let file = self.hunks.get(path)?;
+        // Special case for untracked and undiffed files. They are always
+        // considered modified.
+        if file.untracked || file.undiffed {
+            let source = fs::read_to_string(self.resolve(path)).unwrap_or_default();
+            return Some(
+                source
+                    .lines()
+                    .skip(range.0 - 1)
+                    .take(range.1 - range.0 + 1)
+                    .map(|line| format!("+{line}"))
+                    .collect(),
+            );
+        }
+        let mut changes = Vec::new();
+        for hunk in &file.hunks {
+            if hunk.new_start > range.1 {
+                break;
+            }
+            if hunk.new_start + hunk.new_lines < range.0 {
+                continue;
+            }
+            for (line_no, origin, content) in &hunk.lines {
+                if (range.0..=range.1).contains(line_no) {
+                    changes.push(format!("{origin}{content}"));
+                }
+            }
+        }
+        (!changes.is_empty()).then_some(changes)
+    }
+
+    fn removed_lines(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<Vec<String>> {
+        let file = self.hunks.get(path.as_ref())?;
+        // Removed lines are numbered in the original content.
+        let removed = file
+            .hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .filter(|(line_no, origin, _)| *origin == '-' && (range.0..=range.1).contains(line_no))
+            .map(|(_, origin, content)| format!("{origin}{content}"))
+            .collect::<Vec<_>>();
+        (!removed.is_empty()).then_some(removed)
+    }
+
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.clone()
+    }
+}
+
+impl<'repo> GitEngineBuilder<'repo> {
+    /// Compare against `from_ref` instead of HEAD.
+    pub fn from_ref(mut self, from_ref: impl Into<String>) -> Self {
+        self.from_ref = Some(from_ref.into());
+        self
+    }
+
+    /// Compare with `to_ref` instead of the working tree. Without a
+    /// `from_ref`, `to_ref` is compared with its first parent.
+    pub fn to_ref(mut self, to_ref: impl Into<String>) -> Self {
+        self.to_ref = Some(to_ref.into());
+        self
+    }
+
+    /// Recognize ignore trailers as described by `trailers`.
+    pub fn trailers(mut self, trailers: TrailerConfig) -> Self {
+        self.trailers = trailers;
+        self
+    }
+
+    /// Whether untracked files of the working tree count as changed.
+    pub fn include_untracked(mut self, include_untracked: bool) -> Self {
+        self.include_untracked = include_untracked;
+        self
+    }
+
+    /// Whether to pair deleted and added files into renames, so renamed files
+    /// are matched by their new path only.
+    pub fn detect_renames(mut self, detect_renames: bool) -> Self {
+        self.detect_renames = detect_renames;
+        self
+    }
+
+    /// Whether files whose mode changed, e.g. with `chmod +x`, count as
+    /// changed even if their content didn't. By default, they don't, so they
+    /// neither trigger nor satisfy blocks.
+    pub fn include_mode_changes(mut self, include_mode_changes: bool) -> Self {
+        self.include_mode_changes = include_mode_changes;
+        self
+    }
+
+    /// Only count the files changed in the ways selected by `filter` as
+    /// changed, e.g. to keep added files from triggering or satisfying blocks.
+    /// By default, all files count.
+    pub fn diff_filter(mut self, filter: DiffFilter) -> Self {
+        self.diff_filter = filter;
+        self
+    }
 
+    /// Compare against the merge base of `from_ref` and `to_ref`, or HEAD,
+    /// instead of `from_ref` itself, like `git diff from_ref...to_ref`. Changes
+    /// made on `from_ref` since the branches diverged then don't count.
+    pub fn merge_base(mut self, merge_base: bool) -> Self {
+        self.merge_base = merge_base;
+        self
+    }
+
+    /// The number of unchanged lines around changes grouped into hunks.
+    pub fn context_lines(mut self, context_lines: u32) -> Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    /// Only diff the files matching `patterns`, as given to
+    /// [`Engine::matches`], and the targets of their blocks, e.g. to check a
+    /// few coupled files of a huge repository. Other files are not changed as
+    /// far as the engine is concerned, so renames from them aren't detected.
+    pub fn narrow_to(mut self, patterns: impl IntoIterator<Item = impl AsRef<Path>>) -> Self {
+        self.narrow_to = patterns
+            .into_iter()
+            .map(|pattern| {
+                root_relative(pattern.as_ref())
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        self
+    }
+
+    /// When narrowing, also diff the files generated from the files matching
+    /// the patterns according to `rules`, as given to
+    /// [`CheckerBuilder::generated`](crate::CheckerBuilder::generated), so
+    /// they can be checked too.
+    pub fn narrow_generated(
+        mut self,
+        rules: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.narrow_generated = rules
+            .into_iter()
+            .map(|(generated, generator)| (generated.into(), generator.into()))
+            .collect();
+        self
+    }
+
+    /// Also ignore the files matching `patterns`, e.g. from the description of
+    /// a pull request, as if a commit trailer listed them.
+    pub fn ignore(mut self, patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.ignore.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Build the engine, failing if a revision can't be resolved, an ignore
+    /// trailer or pattern can't be parsed, the revisions can't be diffed, e.g.
+    /// because a file of the working tree can't be read, or the repository is
+    /// bare.
+    pub fn build(self) -> io::Result<GitEngine<'repo>> {
+        let repository = self.repository;
+        let from_ref = self.from_ref.as_deref();
+        let to_ref = self.to_ref.as_deref();
+        tracing::debug!(?from_ref, ?to_ref, "comparing revisions");
+        let Ignored {
+            patterns: ignore_patterns,
+            blocks: ignored_blocks,
+            removals: acknowledged_removals,
+            diagnostics,
+        } = ignore_pathspec(from_ref, to_ref, repository, &self.trailers, &self.ignore)?;
+
+        let tree = |revision: &str| {
+            repository
+                .revparse_single(revision)
+                .and_then(|object| object.peel_to_tree())
+                .map_err(|error| invalid_revision(revision, &error))
+        };
         let (from_tree, to_tree) = match (from_ref, to_ref) {
             (None, None) => (
                 repository
                     .head()
                     .ok()
-                    .map(|head| head.peel_to_tree().unwrap()),
+                    .map(|head| head.peel_to_tree())
+                    .transpose()
+                    .map_err(|error| invalid_revision("HEAD", &error))?,
                 None,
             ),
             (None, Some(to_ref)) => {
                 let to_commit = repository
                     .revparse_single(to_ref)
-                    .expect("to_ref is not a valid revision")
-                    .peel_to_commit()
-                    .expect("to_ref does not point to a commit");
+                    .and_then(|object| object.peel_to_commit())
+                    .map_err(|error| invalid_revision(to_ref, &error))?;
                 (
                     to_commit
                         .parents()
                         .next()
-                        .map(|commit| commit.tree().unwrap()),
-                    Some(to_commit.tree().unwrap()),
+                        .map(|commit| commit.tree())
+                        .transpose()
+                        .map_err(io::Error::other)?,
+                    Some(to_commit.tree().map_err(io::Error::other)?),
                 )
             }
-            (Some(from_ref), to_ref) => (
-                Some(
-                    repository
-                        .revparse_single(from_ref)
-                        .expect("to_ref is not a valid revision")
-                        .peel_to_tree()
-                        .expect("to_ref does not point to a tree"),
-                ),
-                to_ref.map(|to_ref| {
-                    repository
-                        .revparse_single(to_ref)
-                        .expect("to_ref is not a valid revision")
-                        .peel_to_tree()
-                        .expect("to_ref does not point to a tree")
-                }),
+            (Some(from_ref), to_ref) if self.merge_base => (
+                Some(merge_base_tree(repository, from_ref, to_ref)?),
+                to_ref.map(tree).transpose()?,
             ),
+            (Some(from_ref), to_ref) => (Some(tree(from_ref)?), to_ref.map(tree).transpose()?),
         };
 
-        Self {
-            ignore_pathspec,
-            repository,
-            from_tree,
-            to_tree,
+        let to_commit = to_ref
+            .and_then(|to_ref| repository.revparse_single(to_ref).ok())
+            .and_then(|object| object.peel_to_commit().ok())
+            .map(|commit| commit.id());
+
+        let root = repository
+            .workdir()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "bare repositories are not supported",
+                )
+            })?
+            .canonicalize()?;
+
+        // Like in `matches`, later patterns take precedence.
+        let mut pathspecs = self.narrow_to.iter().rev().cloned().collect::<Vec<_>>();
+        let mut diff = self.diff(from_tree.as_ref(), to_tree.as_ref(), &pathspecs)?;
+        if !self.narrow_to.is_empty() {
+            let mut targets = narrowed_targets(
+                repository,
+                &root,
+                &diff,
+                self.diff_filter,
+                &self.narrow_to,
+                &self.narrow_generated,
+            );
+            tracing::debug!(patterns = ?self.narrow_to, ?targets, "narrowing the diff");
+            // Targets come first so negated patterns can't exclude them.
+            targets.append(&mut pathspecs);
+            diff = self.diff(from_tree.as_ref(), to_tree.as_ref(), &targets)?;
         }
+        let hunks = hunks(repository, &diff, self.diff_filter)?;
+
+        Ok(GitEngine {
+            ignore_patterns,
+            ignored_blocks,
+            acknowledged_removals,
+            diagnostics,
+            repository: Mutex::new(reopen(repository)?),
+            root,
+            from_tree: from_tree.map(|tree| tree.id()),
+            to_commit,
+            diff_filter: self.diff_filter,
+            diff: Mutex::new(diff),
+            hunks,
+            ignore_files: Mutex::default(),
+        })
     }
 
-    /// Get the diff of a file, if any.
-    fn diff(&self, mut options: impl BorrowMut<git2::DiffOptions>) -> git2::Diff {
-        match &self.to_tree {
-            Some(to_tree) => self.repository.diff_tree_to_tree(
-                self.from_tree.as_ref(),
-                Some(to_tree),
-                Some(options.borrow_mut()),
-            ),
+    /// Diff `from_tree` with `to_tree`, or the working tree, restricted to the
+    /// files matching `pathspecs` unless empty.
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn diff(
+        &self,
+        from_tree: Option<&git2::Tree<'repo>>,
+        to_tree: Option<&git2::Tree<'repo>>,
+        pathspecs: &[String],
+    ) -> io::Result<git2::Diff<'repo>> {
+        let mut options = git2::DiffOptions::new();
+        options
+            .context_lines(self.context_lines)
+            .ignore_filemode(!self.include_mode_changes);
+        for pathspec in pathspecs {
+            options.pathspec(pathspec);
+        }
+        let mut diff = match to_tree {
+            Some(to_tree) => {
+                self.repository
+                    .diff_tree_to_tree(from_tree, Some(to_tree), Some(&mut options))
+            }
             None => self.repository.diff_tree_to_workdir_with_index(
-                self.from_tree.as_ref(),
-                Some(options.borrow_mut().include_untracked(true)),
+                from_tree,
+                Some(
+                    options
+                        .include_untracked(self.include_untracked)
+                        .recurse_untracked_dirs(self.include_untracked),
+                ),
             ),
         }
-        .unwrap()
+        .map_err(diff_error)?;
+        if self.detect_renames {
+            diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))
+                .map_err(diff_error)?;
+        }
+        Ok(diff)
     }
+}
 
-    /// Get the patch of a file, if any.
-    fn patch(&self, path: &Path) -> Option<git2::Patch> {
-        git2::Patch::from_diff(
-            &self.diff(
-                git2::DiffOptions::new()
-                    .pathspec(path)
-                    .disable_pathspec_match(true),
-            ),
-            0,
-        )
-        .ok()
-        .flatten()
+/// The kinds of changes counting as changes, as selected with the letters of
+/// `git diff --diff-filter`: `A` for added files, `M` for modified, `D` for
+/// deleted, `R` for renamed and `T` for files whose type changed.
+///
+/// Upper-case letters select only those kinds, and lower-case letters exclude
+/// kinds, e.g. `AM` or `a`. Untracked and copied files count as added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffFilter {
+    added: bool,
+    modified: bool,
+    deleted: bool,
+    renamed: bool,
+    type_changed: bool,
+}
+
+impl Default for DiffFilter {
+    fn default() -> Self {
+        Self {
+            added: true,
+            modified: true,
+            deleted: true,
+            renamed: true,
+            type_changed: true,
+        }
     }
 }
 
-impl Engine for GitEngine<'_> {
-    fn matches(
-        &self,
-        patterns: impl IntoIterator<Item = impl AsRef<Path>>,
-    ) -> impl Iterator<Item = Result<PathBuf, PathBuf>> {
-        let mut patterns = patterns
-            .into_iter()
-            .map(|pattern| {
-                let pattern = pattern.as_ref();
-                pattern
-                    .strip_prefix(MAIN_SEPARATOR_STR)
-                    .unwrap_or(pattern)
-                    .to_owned()
-            })
-            .collect::<Vec<_>>();
+impl DiffFilter {
+    /// Check if changes of the kind `status` count.
+    pub fn accepts(self, status: git2::Delta) -> bool {
+        match status {
+            git2::Delta::Added | git2::Delta::Untracked | git2::Delta::Copied => self.added,
+            git2::Delta::Deleted => self.deleted,
+            git2::Delta::Renamed => self.renamed,
+            git2::Delta::Typechange => self.type_changed,
+            _ => self.modified,
+        }
+    }
 
-        // Need to reverse the pathspecs to match in `.gitignore` order.
-        patterns.reverse();
+    /// Get the selected kind of change of `letter`, in either case.
+    fn kind(&mut self, letter: char) -> Option<&mut bool> {
+        match letter.to_ascii_uppercase() {
+            'A' => Some(&mut self.added),
+            'M' => Some(&mut self.modified),
+            'D' => Some(&mut self.deleted),
+            'R' => Some(&mut self.renamed),
+            'T' => Some(&mut self.type_changed),
+            _ => None,
+        }
+    }
+}
 
-        let diff = self.diff(git2::DiffOptions::new());
-        gen!({
-            if patterns.is_empty() {
-                for delta in diff.deltas() {
-                    yield_!(Ok(delta.new_file().path().unwrap().to_owned()))
-                }
-                return;
+impl fmt::Display for DiffFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kinds = [
+            (self.added, 'A'),
+            (self.modified, 'M'),
+            (self.deleted, 'D'),
+            (self.renamed, 'R'),
+            (self.type_changed, 'T'),
+        ];
+        for (selected, letter) in kinds {
+            if selected {
+                write!(f, "{letter}")?;
             }
+        }
+        Ok(())
+    }
+}
 
-            let pathspec = git2::Pathspec::new(patterns).unwrap();
-            let matches = pathspec
-                .match_diff(&diff, git2::PathspecFlags::FIND_FAILURES)
-                .expect("bare repos are not supported");
-            for delta in matches.diff_entries() {
-                yield_!(Ok(delta.new_file().path().unwrap().to_owned()))
-            }
-            for entry in matches.failed_entries() {
-                yield_!(Err(PathBuf::from_str(&entry.to_str_lossy()).unwrap()))
+impl FromStr for DiffFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = if s.chars().any(|letter| letter.is_ascii_uppercase()) {
+            Self {
+                added: false,
+                modified: false,
+                deleted: false,
+                renamed: false,
+                type_changed: false,
             }
-        })
-        .into_iter()
+        } else {
+            Self::default()
+        };
+        for letter in s.chars() {
+            let selected = letter.is_ascii_uppercase();
+            *filter.kind(letter).ok_or_else(|| {
+                format!("{letter:?} is not a kind of change; expected one of `AMDRT`, or lower case to exclude it")
+            })? = selected;
+        }
+        Ok(filter)
     }
+}
 
-    fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
-        self.repository
-            .workdir()
-            .expect("bare repos are not supported")
-            .canonicalize()
-            .unwrap()
-            .join(path.as_ref())
-    }
+/// The files and blocks ignored by trailers and patterns.
+struct Ignored {
+    /// The patterns of the ignored files, in order of precedence.
+    patterns: Vec<PathBuf>,
+    blocks: Vec<(String, String)>,
+    /// The files, and names of blocks, of `remove-if-changed` trailers.
+    removals: Vec<(String, Option<String>)>,
+    /// Trailers rejected because they lack a required reason, or give one not
+    /// matching the required pattern.
+    diagnostics: Vec<Diagnostic>,
+}
 
-    fn is_ignored(&self, path: impl AsRef<Path>) -> bool {
-        let Some(pathspec) = &self.ignore_pathspec else {
-            return false;
-        };
-        pathspec.matches_path(path.as_ref(), git2::PathspecFlags::DEFAULT)
+/// Collect the patterns of the ignore trailers of the commits in
+/// `from_ref..to_ref`, or of `to_ref` alone without `from_ref`.
+///
+/// Patterns ending with `#name`, e.g. `a.ts#name`, ignore the block with that
+/// name only. `remove-if-changed` trailers, acknowledging removed targets,
+/// are collected alike. Trailers rejected because they lack a required reason, or give
+/// one not matching `config.reason_pattern`, are reported as diagnostics.
+/// Fails if the patterns can't be parsed.
+fn ignore_pathspec(
+    from_ref: Option<&str>,
+    to_ref: Option<&str>,
+    repository: &git2::Repository,
+    config: &TrailerConfig,
+    ignore: &[String],
+) -> io::Result<Ignored> {
+    let commit = |revision: &str| {
+        repository
+            .revparse_single(revision)
+            .and_then(|object| object.peel_to_commit())
+            .ok()
+    };
+    let reason_pattern = config
+        .reason_pattern
+        .as_deref()
+        .map(|pattern| {
+            regex::Regex::new(pattern).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid ignore reason pattern {pattern:?}: {error}"),
+                )
+            })
+        })
+        .transpose()?;
+    let require_reason = config.require_reason || reason_pattern.is_some();
+    let mut patterns = Vec::new();
+    let mut blocks = Vec::new();
+    let mut removals = Vec::new();
+    for pattern in ignore {
+        let (pattern, name) = split_block(pattern);
+        git2::Pathspec::new([pattern]).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid ignore pattern {pattern:?}: {}", error.message()),
+            )
+        })?;
+        match name {
+            Some(name) => blocks.push((pattern.to_owned(), name.to_owned())),
+            None => patterns.push(PathBuf::from(pattern)),
+        }
     }
+    let from_commit = from_ref.and_then(commit);
+    let commits = match (to_ref.and_then(commit), &from_commit) {
+        (None, _) => Vec::new(),
+        // Oldest first, so trailers of later commits take precedence.
+        (Some(to_commit), Some(from_commit)) => {
+            let mut walk = repository.revwalk().map_err(io::Error::other)?;
+            walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)
+                .and_then(|()| walk.push(to_commit.id()))
+                .and_then(|()| walk.hide(from_commit.id()))
+                .map_err(io::Error::other)?;
+            walk.map(|id| id.and_then(|id| repository.find_commit(id)))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(io::Error::other)?
+        }
+        (Some(to_commit), None) => vec![to_commit],
+    };
 
-    fn is_range_modified(&self, path: impl AsRef<Path>, range: (usize, usize)) -> bool {
-        let Some(patch) = self.patch(path.as_ref()) else {
-            return false;
+    let mut diagnostics = Vec::new();
+    for commit in &commits {
+        let Ok(trailers) = git2::message_trailers_bytes(commit.message_bytes()) else {
+            continue;
         };
-        // Special case for untracked files. They are always considered modified.
-        if patch.delta().status() == git2::Delta::Untracked {
-            return true;
-        }
-        for (hunk_index, hunk) in (0..patch.num_hunks()).map(|i| (i, patch.hunk(i).unwrap().0)) {
-            if usize::try_from(hunk.new_start()).unwrap() > range.1 {
-                break;
+        let mut commit_patterns = Vec::new();
+        let mut commit_blocks = Vec::new();
+        let mut commit_removals = Vec::new();
+        let is_removal = |name: &[u8]| name.eq_ignore_ascii_case(REMOVAL_TRAILER.as_bytes());
+        for (name, value) in trailers
+            .iter()
+            .filter(|(name, _)| config.accepts(name) || is_removal(name))
+        {
+            let trailer = || format!("{}: {}", name.to_str_lossy(), value.to_str_lossy());
+            if !config.allowed_authors.is_empty() && !is_allowed(commit, &config.allowed_authors) {
+                diagnostics.push(Diagnostic::new(
+                    Code::UnauthorizedIgnore,
+                    "",
+                    None,
+                    format!(
+                        "Ignoring \"{}\" in commit {} because neither its author {} nor a sign-off is allowed to ignore files.",
+                        trailer(),
+                        short_id(commit),
+                        commit.author().email().unwrap_or_default(),
+                    ),
+                ));
+                continue;
+            }
+            match (split_reason(value), &reason_pattern) {
+                (None, _) if require_reason => {
+                    diagnostics.push(Diagnostic::new(
+                        Code::MissingIgnoreReason,
+                        "",
+                        None,
+                        format!(
+                            "Ignoring \"{}\" in commit {} because it gives no reason after \"--\".",
+                            trailer(),
+                            short_id(commit),
+                        ),
+                    ));
+                    continue;
+                }
+                (Some(reason), Some(pattern)) if !pattern.is_match(&reason) => {
+                    diagnostics.push(Diagnostic::new(
+                        Code::InvalidIgnoreReason,
+                        "",
+                        None,
+                        format!(
+                            "Ignoring \"{}\" in commit {} because its reason doesn't match `{pattern}`.",
+                            trailer(),
+                            short_id(commit),
+                        ),
+                    ));
+                    continue;
+                }
+                _ => {}
             }
-            if usize::try_from(hunk.new_start() + hunk.new_lines()).unwrap() < range.0 {
+            if is_removal(name) {
+                for pattern in split_patterns(value) {
+                    let (pattern, name) = split_block(&pattern);
+                    tracing::debug!(%pattern, name, "acknowledging removed targets from a commit trailer");
+                    commit_removals.push((pattern.to_owned(), name.map(str::to_owned)));
+                }
                 continue;
             }
-            for line in (0..patch.num_lines_in_hunk(hunk_index).unwrap())
-                .map(|i| patch.line_in_hunk(hunk_index, i).unwrap())
-            {
-                match line.origin() {
-                    '+' if {
-                        let line_no = usize::try_from(line.new_lineno().unwrap()).unwrap();
-                        line_no >= range.0 && line_no <= range.1
-                    } =>
-                    {
-                        return true;
+            for pattern in split_patterns(value) {
+                match split_block(&pattern) {
+                    (pattern, Some(name)) => {
+                        tracing::debug!(%pattern, name, "ignoring a block from a commit trailer");
+                        commit_blocks.push((pattern.to_owned(), name.to_owned()));
                     }
-                    '-' if {
-                        let line_no = usize::try_from(line.old_lineno().unwrap()).unwrap();
-                        line_no >= range.0 && line_no <= range.1
-                    } =>
-                    {
-                        return true;
-                    }
-                    _ => {
-                        continue;
+                    (pattern, None) => {
+                        tracing::debug!(%pattern, "ignoring files from a commit trailer");
+                        commit_patterns.push(PathBuf::from(pattern));
                     }
                 }
             }
         }
-        false
+        let invalid = |error: git2::Error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid ignore trailer in commit {}: {}",
+                    short_id(commit),
+                    error.message()
+                ),
+            )
+        };
+        for (pattern, name) in commit_blocks {
+            git2::Pathspec::new([&pattern]).map_err(invalid)?;
+            blocks.push((pattern, name));
+        }
+        for (pattern, name) in commit_removals {
+            git2::Pathspec::new([&pattern]).map_err(invalid)?;
+            removals.push((pattern, name));
+        }
+        if commit_patterns.is_empty() {
+            continue;
+        }
+        git2::Pathspec::new(&commit_patterns).map_err(invalid)?;
+        patterns.extend(commit_patterns);
     }
+    // Later patterns take precedence.
+    patterns.reverse();
+    Ok(Ignored {
+        patterns,
+        blocks,
+        removals,
+        diagnostics,
+    })
 }
 
-fn ignore_pathspec(to_ref: Option<&str>, repository: &git2::Repository) -> Option<git2::Pathspec> {
-    let to_ref = to_ref?;
+/// Get the tree of the merge base of `from_ref` and `to_ref`, or HEAD.
+fn merge_base_tree<'repo>(
+    repository: &'repo git2::Repository,
+    from_ref: &str,
+    to_ref: Option<&str>,
+) -> io::Result<git2::Tree<'repo>> {
+    let commit = |revision: &str| {
+        repository
+            .revparse_single(revision)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|error| invalid_revision(revision, &error))
+    };
+    let to_ref = to_ref.unwrap_or("HEAD");
+    let base = repository
+        .merge_base(commit(from_ref)?.id(), commit(to_ref)?.id())
+        .map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "No merge base of {from_ref:?} and {to_ref:?}: {}",
+                    error.message()
+                ),
+            )
+        })?;
+    repository
+        .find_commit(base)
+        .and_then(|commit| commit.tree())
+        .map_err(io::Error::other)
+}
 
-    let commit = repository
-        .revparse_single(to_ref)
-        .ok()?
-        .peel_to_commit()
-        .ok()?;
-    let trailers = git2::message_trailers_bytes(commit.message_bytes()).ok()?;
-    let patterns = trailers
-        .iter()
-        .filter(|(name, _)| name.to_ascii_lowercase() == IF_CHANGED_IGNORE_TRAILER)
-        .flat_map(|(_, value)| split_patterns(value))
-        .map(|pattern| PathBuf::from_str(&pattern).unwrap())
-        .collect::<Vec<_>>();
+/// Describe why `revision` can't be compared, e.g. because it doesn't exist.
+fn invalid_revision(revision: &str, error: &git2::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Invalid revision {revision:?}: {}", error.message()),
+    )
+}
+
+/// Describe why the revisions can't be diffed, e.g. because a file of the
+/// working tree can't be read.
+fn diff_error(error: git2::Error) -> io::Error {
+    io::Error::other(format!("Could not diff the revisions: {}", error.message()))
+}
+
+/// Open `repository` again, so the engine owns a handle it can share between
+/// threads.
+fn reopen(repository: &git2::Repository) -> io::Result<git2::Repository> {
+    let reopened = git2::Repository::open(repository.path()).map_err(io::Error::other)?;
+    if let Some(workdir) = repository.workdir() {
+        if reopened.workdir() != Some(workdir) {
+            reopened
+                .set_workdir(workdir, false)
+                .map_err(io::Error::other)?;
+        }
+    }
+    Ok(reopened)
+}
+
+/// Match the files of `diff` changed in the ways `filter` selects against
+/// `patterns`, as [`Engine::matches`] does.
+fn match_diff(
+    diff: &git2::Diff,
+    filter: DiffFilter,
+    mut patterns: Vec<PathBuf>,
+) -> Vec<Result<PathBuf, PathBuf>> {
+    // Need to reverse the pathspecs to match in `.gitignore` order.
+    patterns.reverse();
+
+    let accepted = |delta: &git2::DiffDelta| filter.accepts(delta.status());
     if patterns.is_empty() {
+        return diff
+            .deltas()
+            .filter(accepted)
+            .filter_map(|delta| delta_path(&delta).map(Ok))
+            .collect();
+    }
+
+    // Patterns git can't parse match nothing.
+    let pathspec = git2::Pathspec::new(&patterns);
+    let Some(matches) = pathspec.as_ref().ok().and_then(|pathspec| {
+        pathspec
+            .match_diff(diff, git2::PathspecFlags::FIND_FAILURES)
+            .ok()
+    }) else {
+        return patterns.into_iter().rev().map(Err).collect();
+    };
+    let found = matches
+        .diff_entries()
+        .filter(accepted)
+        .filter_map(|delta| delta_path(&delta))
+        .collect::<Vec<_>>();
+    let mut failed = matches
+        .failed_entries()
+        .map(|entry| PathBuf::from(entry.to_str_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    if filter != DiffFilter::default() {
+        // Patterns matching filtered out files only match nothing.
+        for pattern in patterns.into_iter().rev() {
+            let matched = git2::Pathspec::new([&pattern]).is_ok_and(|pathspec| {
+                found
+                    .iter()
+                    .any(|path| pathspec.matches_path(path, git2::PathspecFlags::DEFAULT))
+            });
+            if !matched && !failed.contains(&pattern) {
+                failed.push(pattern);
+            }
+        }
+    }
+    found
+        .into_iter()
+        .map(Ok)
+        .chain(failed.into_iter().map(Err))
+        .collect()
+}
+
+/// Get the path of the file changed by `delta`: its new path, or its old path
+/// if it has none.
+fn delta_path(delta: &git2::DiffDelta) -> Option<PathBuf> {
+    delta
+        .new_file()
+        .path()
+        .or_else(|| delta.old_file().path())
+        .map(Path::to_owned)
+}
+
+/// Check if `path` matches the git patterns `patterns`, which were checked
+/// to parse when building the engine.
+fn matches_pathspec<T: git2::IntoCString>(
+    patterns: impl IntoIterator<Item = T>,
+    path: &Path,
+) -> bool {
+    git2::Pathspec::new(patterns)
+        .is_ok_and(|pathspec| pathspec.matches_path(path, git2::PathspecFlags::DEFAULT))
+}
+
+/// Get the targets of the blocks of the files of `diff` matching `patterns`,
+/// in their current and original content, and the files generated from them
+/// according to `generated`, as git patterns.
+///
+/// Targets of the original content are needed to check removed blocks and
+/// targets, e.g. of deleted files.
+fn narrowed_targets(
+    repository: &git2::Repository,
+    root: &Path,
+    diff: &git2::Diff,
+    filter: DiffFilter,
+    patterns: &[String],
+    generated: &[(String, String)],
+) -> Vec<String> {
+    let patterns = patterns.iter().map(PathBuf::from).collect();
+    let originals = diff
+        .deltas()
+        .filter(|delta| !delta.old_file().id().is_zero())
+        .filter_map(|delta| Some((delta_path(&delta)?, delta.old_file().id())))
+        .collect::<HashMap<_, _>>();
+    let mut targets = Vec::new();
+    for path in match_diff(diff, filter, patterns).into_iter().flatten() {
+        targets.extend(
+            generated_patterns(generated, &path)
+                .into_iter()
+                .map(|pattern| pattern.to_string_lossy().into_owned()),
+        );
+        let current = fs::read(root.join(&path)).ok();
+        let original = originals
+            .get(&path)
+            .and_then(|id| repository.find_blob(*id).ok())
+            .map(|blob| blob.content().to_owned());
+        // Diffing to tell binary files apart would defeat narrowing.
+        let blocks = [current, original]
+            .into_iter()
+            .flatten()
+            .filter(|content| !has_nul_byte(content.as_slice()))
+            // Problems parsing the file are reported when checking it.
+            .flat_map(|content| Parser::scan(&path, io::Cursor::new(content)).flatten());
+        for block in blocks {
+            for pattern in resolve_patterns(&path, block.targets, &block.ignored, false)
+                .into_iter()
+                .flatten()
+            {
+                let target = root_relative(&pattern.path).to_string_lossy().into_owned();
+                // Checkers may take `\` as a separator or not, so both
+                // spellings are diffed.
+                if target.contains('\\') {
+                    targets.push(target.replace('\\', "/"));
+                }
+                targets.push(target);
+            }
+        }
+    }
+    targets
+}
+
+/// Whether the `diff` attribute of `path` makes git's hunks differ from
+/// what reviewers see: `-diff` hides the changes, and a driver with a
+/// `textconv` command shows converted content. Files with the `binary`
+/// attribute stay binary.
+fn is_undiffed(repository: &git2::Repository, path: &Path) -> bool {
+    let attribute = |name| {
+        repository
+            .get_attr(path, name, git2::AttrCheckFlags::FILE_THEN_INDEX)
+            .map(git2::AttrValue::from_string)
+    };
+    match attribute("diff") {
+        Ok(git2::AttrValue::False) => !matches!(attribute("binary"), Ok(git2::AttrValue::True)),
+        Ok(git2::AttrValue::String(driver)) => repository
+            .config()
+            .and_then(|config| config.get_string(&format!("diff.{driver}.textconv")))
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/// Index the hunks of each file of `diff` changed in the ways `filter`
+/// selects.
+#[tracing::instrument(level = "trace", skip_all)]
+fn hunks(
+    repository: &git2::Repository,
+    diff: &git2::Diff,
+    filter: DiffFilter,
+) -> io::Result<HashMap<PathBuf, FileHunks>> {
+    let line_number = |number: u32| usize::try_from(number).map_err(io::Error::other);
+    let mut files = HashMap::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        if !filter.accepts(delta.status()) {
+            continue;
+        }
+        let Some(path) = delta_path(&delta) else {
+            continue;
+        };
+        let original = delta.old_file().path().unwrap_or(&path).to_owned();
+        let untracked = delta.status() == git2::Delta::Untracked;
+        let deleted = delta.status() == git2::Delta::Deleted;
+        let undiffed = !untracked && !deleted && is_undiffed(repository, &path);
+        let Ok(Some(patch)) = git2::Patch::from_diff(diff, index) else {
+            files.insert(
+                path,
+                FileHunks {
+                    original,
+                    untracked,
+                    deleted,
+                    undiffed,
+                    binary: binary(untracked || undiffed, delta.flags()),
+                    hunks: Vec::new(),
+                },
+            );
+            continue;
+        };
+        let mut hunks = Vec::new();
+        for hunk_index in 0..patch.num_hunks() {
+            let (hunk, line_count) = patch.hunk(hunk_index).map_err(diff_error)?;
+            let mut lines = Vec::new();
+            for line_index in 0..line_count {
+                let line = patch
+                    .line_in_hunk(hunk_index, line_index)
+                    .map_err(diff_error)?;
+                let line_no = match line.origin() {
+                    '+' => line.new_lineno(),
+                    '-' => line.old_lineno(),
+                    _ => continue,
+                };
+                let Some(line_no) = line_no else {
+                    continue;
+                };
+                let content = line.content().to_str_lossy();
+                lines.push((
+                    line_number(line_no)?,
+                    line.origin(),
+                    content.trim_end_matches(['\n', '\r']).to_owned(),
+                ));
+            }
+            hunks.push(Hunk {
+                new_start: line_number(hunk.new_start())?,
+                new_lines: line_number(hunk.new_lines())?,
+                lines,
+            });
+        }
+        files.insert(
+            path,
+            FileHunks {
+                original,
+                untracked,
+                deleted,
+                undiffed,
+                binary: binary(untracked || undiffed, patch.delta().flags()),
+                hunks,
+            },
+        );
+    }
+    Ok(files)
+}
+
+/// Whether the flags of a diffed file mark it as binary, if git could tell.
+/// The content of untracked files isn't diffed, so git can't.
+fn binary(untracked: bool, flags: git2::DiffFlags) -> Option<bool> {
+    if untracked {
         None
+    } else if flags.is_binary() {
+        Some(true)
+    } else if flags.is_not_binary() {
+        Some(false)
     } else {
-        Some(git2::Pathspec::new(patterns.iter().rev()).expect("Ignore-if-changed is invalid."))
+        None
     }
 }
 
-fn split_patterns(value: &[u8]) -> impl Iterator<Item = Cow<str>> {
+fn short_id(commit: &git2::Commit<'_>) -> String {
+    commit
+        .as_object()
+        .short_id()
+        .ok()
+        .and_then(|id| id.as_str().map(str::to_owned))
+        .unwrap_or_else(|| commit.id().to_string())
+}
+
+fn split_patterns(value: &[u8]) -> impl Iterator<Item = Cow<'_, str>> {
     value
         .split_once_str(b"--")
         .unwrap_or((value, b""))
@@ -238,6 +1221,70 @@ fn split_patterns(value: &[u8]) -> impl Iterator<Item = Cow<str>> {
         .map(|s| s.trim().to_str_lossy())
 }
 
+/// Split the name of a block off an ignore pattern, as in `a.ts#name`.
+fn split_block(pattern: &str) -> (&str, Option<&str>) {
+    match pattern.rsplit_once('#') {
+        Some((pattern, name)) if !pattern.is_empty() && !name.trim().is_empty() => {
+            (pattern, Some(name.trim()))
+        }
+        _ => (pattern, None),
+    }
+}
+
+/// Get the reason given after `--` in a trailer value, if any.
+fn split_reason(value: &[u8]) -> Option<Cow<'_, str>> {
+    let (_, reason) = value.split_once_str(b"--")?;
+    let reason = reason.trim();
+    (!reason.is_empty()).then(|| reason.to_str_lossy())
+}
+
+/// Check if `commit` may ignore files: if the email of its author, its
+/// committer or one of its `Signed-off-by` trailers matches one of `allowed`.
+fn is_allowed(commit: &git2::Commit, allowed: &[String]) -> bool {
+    let mut emails = vec![
+        commit.author().email_bytes().to_str_lossy().into_owned(),
+        commit.committer().email_bytes().to_str_lossy().into_owned(),
+    ];
+    if let Ok(trailers) = git2::message_trailers_bytes(commit.message_bytes()) {
+        emails.extend(
+            trailers
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(b"Signed-off-by"))
+                .filter_map(|(_, value)| {
+                    let (_, email) = value.split_once_str(b"<")?;
+                    let (email, _) = email.split_once_str(b">")?;
+                    Some(email.trim().to_str_lossy().into_owned())
+                }),
+        );
+    }
+    emails.iter().any(|email| {
+        let email = email.to_lowercase();
+        allowed
+            .iter()
+            .any(|pattern| matches(&pattern.to_lowercase(), &email))
+    })
+}
+
+/// Get the latest commit in `blame` touching a line within `range`.
+fn last_edit(blame: &git2::Blame, range: (usize, usize)) -> Option<LastEdit> {
+    let hunk = blame
+        .iter()
+        .filter(|hunk| {
+            let start = hunk.final_start_line();
+            !hunk.final_commit_id().is_zero()
+                && start <= range.1
+                && start + hunk.lines_in_hunk() > range.0
+        })
+        .max_by_key(|hunk| hunk.final_signature().when().seconds())?;
+    let signature = hunk.final_signature();
+    Some(LastEdit {
+        commit: hunk.final_commit_id().to_string(),
+        author: String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+        email: String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+        time: signature.when().seconds(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,13 +1308,24 @@ mod tests {
     );
     extract_pathspec_test!(test_multiple_pathspec_with_empty_comment, b"a/b, b/c --", @r###"["a/b", "b/c"]"###);
 
+    #[test]
+    fn test_split_reason() {
+        assert_eq!(split_reason(b"a"), None);
+        assert_eq!(split_reason(b"a --"), None);
+        assert_eq!(split_reason(b"a --  "), None);
+        assert_eq!(
+            split_reason(b"a -- Hello world!").as_deref(),
+            Some("Hello world!")
+        );
+    }
+
     #[test]
     fn test_git() {
         let (tempdir, repo) = git_test! {
             "initial commit": ["a" => "a", "b" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @"[]");
@@ -281,7 +1339,7 @@ mod tests {
             staged: ["a" => "a", "b" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}]"###);
@@ -295,7 +1353,7 @@ mod tests {
             staged: ["a" => "a", "c/a" => "a", "c/b" => "b", "d/b" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches(&["b"]).collect::<Vec<_>>(), @r###"[{"Err": "b"}]"###);
@@ -316,7 +1374,7 @@ mod tests {
             working: ["c/a" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches([""; 0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "c/a"}]"###);
@@ -329,12 +1387,193 @@ mod tests {
             staged: ["a" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}]"###);
     }
 
+    #[test]
+    fn test_changes_untracked_directory() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a"]
+            working: ["c/a" => "a", "c/d/b" => "b"]
+        };
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
+
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "c/a"}, {"Ok": "c/d/b"}]"###);
+        insta::assert_compact_json_snapshot!(engine.matches(&["c/d/b"]).collect::<Vec<_>>(), @r###"[{"Ok": "c/d/b"}]"###);
+    }
+
+    #[test]
+    fn test_builder_without_untracked() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a"]
+            staged: ["b" => "b"]
+            working: ["a" => "b", "c" => "c"]
+        };
+
+        let engine = GitEngine::builder(&repo)
+            .include_untracked(false)
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}]"###);
+    }
+
+    #[test]
+    fn test_builder_detect_renames() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a\nb\nc\nd\n"]
+        };
+        fs::rename(tempdir.path().join("a"), tempdir.path().join("b")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("a")).unwrap();
+        index.add_path(Path::new("b")).unwrap();
+        index.write().unwrap();
+
+        let engine = GitEngine::builder(&repo).build().unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}]"###);
+        let engine = GitEngine::builder(&repo)
+            .detect_renames(true)
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "b"}]"###);
+
+        // Only the lines changed since the rename are modified.
+        fs::write(tempdir.path().join("b"), "a\nx\nc\nd\n").unwrap();
+        let engine = GitEngine::builder(&repo)
+            .detect_renames(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            engine.range_changes("b", (1, 4)),
+            Some(vec!["-b".into(), "+x".into()])
+        );
+        assert_eq!(engine.range_changes("b", (3, 4)), None);
+        assert_eq!(engine.renames(), [("a".into(), "b".into())]);
+        let engine = GitEngine::builder(&repo).build().unwrap();
+        assert_eq!(engine.renames(), []);
+    }
+
+    #[test]
+    fn test_builder_merge_base() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a"]
+            "second commit": ["a" => "b", "b[1]" => "b"]
+        };
+
+        // HEAD~1 is behind HEAD, so its merge base with HEAD is itself.
+        let engine = GitEngine::builder(&repo)
+            .from_ref("HEAD")
+            .to_ref("HEAD~1")
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["a", "b\\[1]"]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b[1]"}]"###);
+        let engine = GitEngine::builder(&repo)
+            .from_ref("HEAD")
+            .to_ref("HEAD~1")
+            .merge_base(true)
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["a", "b\\[1]"]).collect::<Vec<_>>(), @r###"[{"Err": "b\\[1]"}, {"Err": "a"}]"###);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_builder_include_mode_changes() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "b" => "b"]
+            working: ["b" => "c"]
+        };
+        for path in ["a", "b"] {
+            let path = tempdir.path().join(path);
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let engine = GitEngine::builder(&repo).build().unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["a", "b"]).collect::<Vec<_>>(), @r###"[{"Ok": "b"}, {"Err": "a"}]"###);
+        let engine = GitEngine::builder(&repo)
+            .include_mode_changes(true)
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["a", "b"]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}]"###);
+        assert_eq!(engine.range_changes("a", (1, 1)), None);
+    }
+
+    #[test]
+    fn test_builder_diff_filter() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "b" => "b"]
+            working: ["a" => "b", "c" => "c"]
+        };
+        fs::remove_file(tempdir.path().join("b")).unwrap();
+
+        let engine = GitEngine::builder(&repo).build().unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}, {"Ok": "c"}]"###);
+        let engine = GitEngine::builder(&repo)
+            .diff_filter("a".parse().unwrap())
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}]"###);
+        insta::assert_compact_json_snapshot!(engine.matches(["a", "c", "*"]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}, {"Err": "c"}]"###);
+        assert_eq!(engine.range_changes("c", (1, 1)), None);
+        let engine = GitEngine::builder(&repo)
+            .diff_filter("D".parse().unwrap())
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["a", "b"]).collect::<Vec<_>>(), @r###"[{"Ok": "b"}, {"Err": "a"}]"###);
+        assert!(engine.is_deleted("b"));
+    }
+
+    #[test]
+    fn test_diff_filter() {
+        assert_eq!(DiffFilter::default().to_string(), "AMDRT");
+        let parse = |filter: &str| {
+            filter
+                .parse::<DiffFilter>()
+                .map(|filter| filter.to_string())
+        };
+        assert_eq!(parse("AM").unwrap(), "AM");
+        assert_eq!(parse("ad").unwrap(), "MRT");
+        assert_eq!(parse("AMd").unwrap(), "AM");
+        assert_eq!(parse("").unwrap(), "AMDRT");
+        insta::assert_snapshot!(parse("AX").unwrap_err(), @"'X' is not a kind of change; expected one of `AMDRT`, or lower case to exclude it");
+        let filter = "d".parse::<DiffFilter>().unwrap();
+        assert!(filter.accepts(git2::Delta::Untracked));
+        assert!(!filter.accepts(git2::Delta::Deleted));
+    }
+
+    #[test]
+    fn test_builder_narrow_to() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a.ts" => "", "b.ts" => "", "c.ts" => "", "d/e.ts" => ""]
+            working: [
+                "a.ts" => "// if-changed\nA,\n// then-change(d/*.ts, /b.ts:b)\n",
+                "b.ts" => "B,\n",
+                "c.ts" => "C,\n",
+                "d/e.ts" => "E,\n"
+            ]
+        };
+
+        let engine = GitEngine::builder(&repo)
+            .narrow_to(["/a.ts"])
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a.ts"}, {"Ok": "b.ts"}, {"Ok": "d/e.ts"}]"###);
+        insta::assert_compact_json_snapshot!(engine.matches(["a.ts", "c.ts"]).collect::<Vec<_>>(), @r###"[{"Ok": "a.ts"}, {"Err": "c.ts"}]"###);
+
+        // Negated patterns don't exclude targets.
+        let engine = GitEngine::builder(&repo)
+            .narrow_to(["*.ts", "!b.ts", "!c.ts"])
+            .build()
+            .unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a.ts"}, {"Ok": "b.ts"}, {"Ok": "d/e.ts"}]"###);
+    }
+
     #[test]
     fn test_changes_working_only() {
         let (tempdir, repo) = git_test! {
@@ -342,12 +1581,148 @@ mod tests {
             working: ["a" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, None, None);
+        let engine = GitEngine::new(&repo, None, None).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}]"###);
     }
 
+    #[test]
+    fn test_deleted_files() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "b" => "b"]
+            working: ["a" => "b"]
+        };
+        fs::remove_file(tempdir.path().join("b")).unwrap();
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        insta::assert_compact_json_snapshot!(engine.matches(["";0]).collect::<Vec<_>>(), @r###"[{"Ok": "a"}, {"Ok": "b"}]"###);
+        assert!(!engine.is_deleted("a"));
+        assert!(engine.is_deleted("b"));
+    }
+
+    #[test]
+    fn test_undiffed_files() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": [
+                ".gitattributes" => "*.lock -diff\n*.bin binary\n*.md diff=pandoc\n",
+                "a.lock" => "a\nb\n",
+                "b.bin" => "a\nb\n",
+                "c.md" => "a\nb\n",
+                "d" => "a\nb\n"
+            ]
+            working: [
+                "a.lock" => "a\nc\n",
+                "b.bin" => "a\nc\n",
+                "c.md" => "a\nc\n",
+                "d" => "a\nc\n"
+            ]
+        };
+        repo.config()
+            .unwrap()
+            .set_str("diff.pandoc.textconv", "pandoc -t plain")
+            .unwrap();
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        assert!(!engine.is_binary("a.lock"));
+        assert!(engine.is_binary("b.bin"));
+        assert_eq!(
+            engine.range_changes("a.lock", (1, 1)),
+            Some(vec!["+a".into()])
+        );
+        assert_eq!(
+            engine.range_changes("c.md", (1, 1)),
+            Some(vec!["+a".into()])
+        );
+        assert_eq!(engine.range_changes("d", (1, 1)), None);
+    }
+
+    #[test]
+    fn test_invalid_revisions() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a"]
+        };
+
+        let error = |builder: GitEngineBuilder| builder.build().err().unwrap().to_string();
+        insta::assert_snapshot!(error(GitEngine::builder(&repo).from_ref("mian")), @r###"Invalid revision "mian": revspec 'mian' not found"###);
+        insta::assert_snapshot!(error(GitEngine::builder(&repo).to_ref("HEAD~1")), @r###"Invalid revision "HEAD~1": parent 0 does not exist"###);
+        insta::assert_snapshot!(
+            error(GitEngine::builder(&repo).from_ref("HEAD").to_ref("mian")),
+            @r###"Invalid revision "mian": revspec 'mian' not found"###
+        );
+
+        let bare = git2::Repository::init_bare(tempdir.path().join("bare")).unwrap();
+        insta::assert_snapshot!(error(GitEngine::builder(&bare)), @"bare repositories are not supported");
+    }
+
+    #[test]
+    fn test_original_content() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a\nb\nc\n", "b" => "b\n"]
+            working: ["a" => "a\nc\n", "c" => "c\n"]
+        };
+        fs::remove_file(tempdir.path().join("b")).unwrap();
+
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let mut original = String::new();
+        engine
+            .read_original("a")
+            .unwrap()
+            .read_to_string(&mut original)
+            .unwrap();
+        assert_eq!(original, "a\nb\nc\n");
+        assert!(engine.read_original("c").is_err());
+        assert_eq!(engine.removed_lines("a", (1, 3)), Some(vec!["-b".into()]));
+        assert_eq!(engine.removed_lines("a", (3, 3)), None);
+        assert_eq!(engine.removed_lines("b", (1, 1)), Some(vec!["-b".into()]));
+    }
+
+    #[test]
+    fn test_last_edit() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a\nb\nc\n"]
+        };
+        let initial = repo.head().unwrap().peel_to_commit().unwrap();
+        fs::write(tempdir.path().join("a"), "a\nB\nc\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::new(
+            "Other User",
+            "other@example.com",
+            &git2::Time::new(86400, 0),
+        )
+        .unwrap();
+        let head = repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "second commit",
+                &tree,
+                &[&initial],
+            )
+            .unwrap()
+            .to_string();
+        let initial = initial.id().to_string();
+        fs::write(tempdir.path().join("a"), "z\na\nB\nc\n").unwrap();
+
+        // Lines of the working tree moved since HEAD.
+        let engine = GitEngine::new(&repo, None, None).unwrap();
+        let last_edit = engine.last_edit("a", (2, 4)).unwrap();
+        assert_eq!(last_edit.commit, head);
+        assert_eq!(last_edit.author, "Other User");
+        assert_eq!(last_edit.email, "other@example.com");
+        assert_eq!(last_edit.time, 86400);
+        assert_eq!(engine.last_edit("a", (2, 2)).unwrap().commit, initial);
+        // The line added in the working tree wasn't committed by anyone.
+        assert_eq!(engine.last_edit("a", (1, 1)), None);
+
+        let engine = GitEngine::new(&repo, Some("HEAD~"), Some("HEAD~")).unwrap();
+        assert_eq!(engine.last_edit("a", (1, 3)).unwrap().commit, initial);
+        assert_eq!(engine.last_edit("missing", (1, 1)), None);
+    }
+
     #[test]
     fn test_without_if_changed_ignore_trailer() {
         let (tempdir, repo) = git_test! {
@@ -355,7 +1730,7 @@ mod tests {
             "second commit": ["a" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, Some("HEAD~1"), Some("HEAD"));
+        let engine = GitEngine::new(&repo, Some("HEAD~1"), Some("HEAD")).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         assert!(!engine.is_ignored(Path::new("a")));
@@ -369,10 +1744,240 @@ mod tests {
             "second commit\n\nignore-if-changed: c/a": ["a" => "b"]
         };
 
-        let engine = GitEngine::new(&repo, Some("HEAD~1"), Some("HEAD"));
+        let engine = GitEngine::new(&repo, Some("HEAD~1"), Some("HEAD")).unwrap();
         assert_eq!(engine.resolve(""), tempdir.path().canonicalize().unwrap());
 
         assert!(!engine.is_ignored(Path::new("a")));
         assert!(engine.is_ignored(Path::new("c/a")));
     }
+
+    #[test]
+    fn test_with_ignore_trailers_in_range() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit\n\nignore-if-changed: d/b": ["a" => "a", "c/a" => "a", "c/b" => "b", "d/b" => "b"]
+            "second commit\n\nignore-if-changed: c/a": ["a" => "b"]
+            "third commit\n\nignore-if-changed: c/b": ["a" => "c"]
+            "fourth commit": ["a" => "d"]
+        };
+
+        let engine = GitEngine::new(&repo, Some("HEAD~3"), Some("HEAD")).unwrap();
+        assert!(engine.is_ignored(Path::new("c/a")));
+        assert!(engine.is_ignored(Path::new("c/b")));
+        assert!(!engine.is_ignored(Path::new("d/b")));
+
+        let engine = GitEngine::new(&repo, Some("HEAD~2"), Some("HEAD")).unwrap();
+        assert!(!engine.is_ignored(Path::new("c/a")));
+        assert!(engine.is_ignored(Path::new("c/b")));
+
+        // Without `from_ref`, only the commit itself is checked.
+        let engine = GitEngine::new(&repo, None, Some("HEAD~1")).unwrap();
+        assert!(!engine.is_ignored(Path::new("c/a")));
+        assert!(engine.is_ignored(Path::new("c/b")));
+    }
+
+    #[test]
+    fn test_with_block_ignore_trailers() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a.ts" => "a", "b.ts" => "b"]
+            "second commit\n\nignore-if-changed: a.ts#serializer, *.js#parser, b.ts# -- Renamed.": ["a.ts" => "b"]
+        };
+
+        let engine = GitEngine::new(&repo, Some("HEAD~1"), Some("HEAD")).unwrap();
+        assert!(engine.is_block_ignored(Path::new("a.ts"), "serializer"));
+        assert!(!engine.is_block_ignored(Path::new("a.ts"), "parser"));
+        assert!(!engine.is_ignored(Path::new("a.ts")));
+        assert!(engine.is_block_ignored(Path::new("src/c.js"), "parser"));
+        // Without a name, `#` is part of the pattern.
+        assert!(!engine.is_ignored(Path::new("b.ts")));
+        assert!(!engine.is_block_ignored(Path::new("b.ts"), ""));
+    }
+
+    #[test]
+    fn test_with_removal_trailers() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a.ts" => "a", "b.ts" => "b"]
+            "second commit\n\nRemove-If-Changed: a.ts#serializer, src/*.ts -- Decoupled.": ["a.ts" => "b"]
+        };
+
+        let engine = GitEngine::new(&repo, Some("HEAD~1"), Some("HEAD")).unwrap();
+        assert!(engine.is_removal_acknowledged(Path::new("a.ts"), Some("serializer")));
+        assert!(!engine.is_removal_acknowledged(Path::new("a.ts"), Some("parser")));
+        assert!(!engine.is_removal_acknowledged(Path::new("a.ts"), None));
+        assert!(engine.is_removal_acknowledged(Path::new("src/c.ts"), None));
+        assert!(!engine.is_removal_acknowledged(Path::new("b.ts"), None));
+        // Removals acknowledge nothing else.
+        assert!(!engine.is_ignored(Path::new("a.ts")));
+        assert!(!engine.is_block_ignored(Path::new("a.ts"), "serializer"));
+    }
+
+    #[test]
+    fn test_split_block() {
+        assert_eq!(split_block("a.ts#name"), ("a.ts", Some("name")));
+        assert_eq!(split_block("a.ts# name "), ("a.ts", Some("name")));
+        assert_eq!(split_block("a#b.ts#name"), ("a#b.ts", Some("name")));
+        assert_eq!(split_block("a.ts#"), ("a.ts#", None));
+        assert_eq!(split_block("#a.ts"), ("#a.ts", None));
+        assert_eq!(split_block("a.ts"), ("a.ts", None));
+    }
+
+    #[test]
+    fn test_with_custom_ignore_trailers() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "c/a" => "a", "c/b" => "b", "d/b" => "b"]
+            "second commit\n\nSkip-If-Changed: c/a\nignore-if-changed: c/b": ["a" => "b"]
+        };
+
+        let trailers = TrailerConfig {
+            keys: vec!["skip-if-changed".into()],
+            ..Default::default()
+        };
+        let engine =
+            GitEngine::with_trailers(&repo, Some("HEAD~1"), Some("HEAD"), &trailers).unwrap();
+        assert!(engine.is_ignored(Path::new("c/a")));
+        assert!(!engine.is_ignored(Path::new("c/b")));
+
+        let trailers = TrailerConfig {
+            keys: vec!["skip-if-changed".into(), "ignore-if-changed".into()],
+            ..Default::default()
+        };
+        let engine =
+            GitEngine::with_trailers(&repo, Some("HEAD~1"), Some("HEAD"), &trailers).unwrap();
+        assert!(engine.is_ignored(Path::new("c/a")));
+        assert!(engine.is_ignored(Path::new("c/b")));
+        assert_eq!(engine.diagnostics(), vec![]);
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": [
+                ".ifchangedignore" => "generated/\n*.pb.go\n",
+                "api/.ifchangedignore" => "!keep.pb.go\n",
+                "api/a.pb.go" => "",
+                "api/keep.pb.go" => "",
+                "src/generated/a.ts" => "",
+                "src/a.ts" => ""
+            ]
+        };
+
+        let engine = GitEngine::new(&repo, None, Some("HEAD")).unwrap();
+        assert!(engine.is_excluded("api/a.pb.go"));
+        assert!(!engine.is_excluded("api/keep.pb.go"));
+        assert!(engine.is_excluded("src/generated/a.ts"));
+        assert!(engine.is_ignored("src/generated/a.ts"));
+        assert!(!engine.is_excluded("src/a.ts"));
+    }
+
+    #[test]
+    fn test_with_ignore_patterns() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "c/a" => "a", "c/b" => "b", "d/b" => "b"]
+            "second commit\n\nignore-if-changed: c/a": ["a" => "b"]
+            working: ["a" => "c"]
+        };
+
+        let engine = GitEngine::builder(&repo)
+            .from_ref("HEAD~1")
+            .to_ref("HEAD")
+            .ignore(["d/*"])
+            .build()
+            .unwrap();
+        assert!(engine.is_ignored(Path::new("c/a")));
+        assert!(!engine.is_ignored(Path::new("c/b")));
+        assert!(engine.is_ignored(Path::new("d/b")));
+
+        // Patterns apply to the working tree too, which has no trailers.
+        let engine = GitEngine::builder(&repo).ignore(["c/b"]).build().unwrap();
+        assert!(!engine.is_ignored(Path::new("c/a")));
+        assert!(engine.is_ignored(Path::new("c/b")));
+    }
+
+    #[test]
+    fn test_with_required_ignore_reason() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "c/a" => "a", "c/b" => "b", "d/b" => "b"]
+            "second commit\n\nignore-if-changed: c/a -- generated\nignore-if-changed: c/b --": ["a" => "b"]
+        };
+
+        let trailers = TrailerConfig {
+            require_reason: true,
+            ..Default::default()
+        };
+        let engine =
+            GitEngine::with_trailers(&repo, Some("HEAD~1"), Some("HEAD"), &trailers).unwrap();
+        assert!(engine.is_ignored(Path::new("c/a")));
+        assert!(!engine.is_ignored(Path::new("c/b")));
+
+        let diagnostics = engine.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0]
+            .message
+            .starts_with("Ignoring \"ignore-if-changed: c/b --\" in commit "));
+    }
+
+    #[test]
+    fn test_with_allowed_authors() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "c/a" => "a", "c/b" => "b"]
+            "second commit\n\nignore-if-changed: c/a": ["a" => "b"]
+            "third commit\n\nignore-if-changed: c/b\nSigned-off-by: Lead <Lead@Corp.com>": ["a" => "c"]
+        };
+
+        let trailers = TrailerConfig {
+            allowed_authors: vec!["lead@corp.com".into()],
+            ..Default::default()
+        };
+        let engine =
+            GitEngine::with_trailers(&repo, Some("HEAD~2"), Some("HEAD"), &trailers).unwrap();
+        assert!(!engine.is_ignored(Path::new("c/a")));
+        assert!(engine.is_ignored(Path::new("c/b")));
+        let diagnostics = engine.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, Code::UnauthorizedIgnore);
+        assert!(diagnostics[0]
+            .message
+            .ends_with("because neither its author test@example.com nor a sign-off is allowed to ignore files."));
+
+        let trailers = TrailerConfig {
+            allowed_authors: vec!["*@example.com".into()],
+            ..Default::default()
+        };
+        let engine =
+            GitEngine::with_trailers(&repo, Some("HEAD~2"), Some("HEAD"), &trailers).unwrap();
+        assert!(engine.is_ignored(Path::new("c/a")));
+        assert!(engine.is_ignored(Path::new("c/b")));
+        assert_eq!(engine.diagnostics(), vec![]);
+    }
+
+    #[test]
+    fn test_with_ignore_reason_pattern() {
+        let (_tempdir, repo) = git_test! {
+            "initial commit": ["a" => "a", "c/a" => "a", "c/b" => "b", "d/b" => "b"]
+            "second commit\n\nignore-if-changed: c/a -- PROJ-12: generated\nignore-if-changed: c/b -- generated\nignore-if-changed: d/b": ["a" => "b"]
+        };
+
+        let trailers = TrailerConfig {
+            reason_pattern: Some("[A-Z]+-[0-9]+".into()),
+            ..Default::default()
+        };
+        let engine =
+            GitEngine::with_trailers(&repo, Some("HEAD~1"), Some("HEAD"), &trailers).unwrap();
+        assert!(engine.is_ignored(Path::new("c/a")));
+        assert!(!engine.is_ignored(Path::new("c/b")));
+        assert!(!engine.is_ignored(Path::new("d/b")));
+        assert_eq!(
+            engine
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect::<Vec<_>>(),
+            [Code::InvalidIgnoreReason, Code::MissingIgnoreReason]
+        );
+
+        let trailers = TrailerConfig {
+            reason_pattern: Some("(".into()),
+            ..Default::default()
+        };
+        assert!(GitEngine::with_trailers(&repo, Some("HEAD~1"), Some("HEAD"), &trailers).is_err());
+    }
 }
@@ -0,0 +1,320 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf, MAIN_SEPARATOR_STR},
+};
+
+use super::Engine;
+use crate::Diagnostic;
+
+/// A file changed between revisions, as given to a [`MemoryEngine`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangedFile {
+    /// The content of the file after the change, or `None` if it was deleted.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub content: Option<String>,
+    /// The lines added and removed, prefixed with `+` or `-`, with their line
+    /// number. As in unified diffs, added lines are numbered in the new
+    /// content and removed lines in the old one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub changes: Vec<(usize, String)>,
+    /// The content of the file before the change, if known, e.g. to check
+    /// the blocks removed from it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub original: Option<String>,
+}
+
+/// An engine over changes computed beforehand, e.g. by a code review UI or a
+/// CI step, for when no repository is at hand.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEngine {
+    files: BTreeMap<PathBuf, ChangedFile>,
+    ignored: Vec<PathBuf>,
+    ignored_blocks: Vec<(PathBuf, String)>,
+    acknowledged_removals: Vec<(PathBuf, Option<String>)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl MemoryEngine {
+    /// Create an engine over the given changed files, relative to the
+    /// repository root.
+    pub fn new(files: impl IntoIterator<Item = (PathBuf, ChangedFile)>) -> Self {
+        Self {
+            files: files.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Ignore files matching `patterns`, e.g. from `Ignore-if-changed`
+    /// trailers.
+    pub fn with_ignored(mut self, patterns: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.ignored.extend(patterns);
+        self
+    }
+
+    /// Ignore the blocks with the given names of the files matching the
+    /// given patterns, e.g. from `ignore-if-changed: a.ts#name` trailers.
+    pub fn with_ignored_blocks(
+        mut self,
+        blocks: impl IntoIterator<Item = (PathBuf, String)>,
+    ) -> Self {
+        self.ignored_blocks.extend(blocks);
+        self
+    }
+
+    /// Acknowledge removing targets from the blocks of the files matching the
+    /// given patterns, or from their blocks with the given names only, e.g.
+    /// from `remove-if-changed: a.ts#name` trailers.
+    pub fn with_acknowledged_removals(
+        mut self,
+        removals: impl IntoIterator<Item = (PathBuf, Option<String>)>,
+    ) -> Self {
+        self.acknowledged_removals.extend(removals);
+        self
+    }
+
+    /// Report `diagnostics` as found outside of any checked file.
+    pub fn with_diagnostics(mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) -> Self {
+        self.diagnostics.extend(diagnostics);
+        self
+    }
+}
+
+impl Engine for MemoryEngine {
+    fn matches(
+        &self,
+        patterns: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> impl Iterator<Item = Result<PathBuf, PathBuf>> {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| {
+                let pattern = pattern.as_ref();
+                pattern
+                    .strip_prefix(MAIN_SEPARATOR_STR)
+                    .unwrap_or(pattern)
+                    .to_owned()
+            })
+            .collect::<Vec<_>>();
+
+        let mut matches = Vec::new();
+        let mut failures = Vec::new();
+        if patterns.is_empty() {
+            matches.extend(self.files.keys().cloned().map(Ok));
+        } else {
+            let mut matched = vec![false; patterns.len()];
+            for path in self.files.keys() {
+                let mut is_match = false;
+                for (pattern, matched) in patterns.iter().zip(&mut matched) {
+                    if matches_pattern(pattern, path) {
+                        *matched = true;
+                        is_match = true;
+                    }
+                }
+                if is_match {
+                    matches.push(Ok(path.clone()));
+                }
+            }
+            failures.extend(
+                patterns
+                    .into_iter()
+                    .zip(matched)
+                    .filter(|(_, matched)| !matched)
+                    .map(|(pattern, _)| Err(pattern)),
+            );
+        }
+        matches.into_iter().chain(failures)
+    }
+
+    fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        path.as_ref().to_owned()
+    }
+
+    fn read(&self, path: impl AsRef<Path>) -> io::Result<Box<dyn io::BufRead>> {
+        let path = path.as_ref();
+        let content = self
+            .files
+            .get(path)
+            .and_then(|file| file.content.clone())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("{path:?} is not changed"))
+            })?;
+        Ok(Box::new(io::Cursor::new(content)))
+    }
+
+    fn read_original(&self, path: impl AsRef<Path>) -> io::Result<Box<dyn io::BufRead>> {
+        let path = path.as_ref();
+        let original = self
+            .files
+            .get(path)
+            .and_then(|file| file.original.clone())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("the original content of {path:?} is unknown"),
+                )
+            })?;
+        Ok(Box::new(io::Cursor::new(original)))
+    }
+
+    fn fingerprint(&self, _path: impl AsRef<Path>) -> Option<String> {
+        None
+    }
+
+    fn size(&self, path: impl AsRef<Path>) -> Option<u64> {
+        let content = self.files.get(path.as_ref())?.content.as_ref()?;
+        Some(content.len() as u64)
+    }
+
+    fn is_deleted(&self, path: impl AsRef<Path>) -> bool {
+        self.files
+            .get(path.as_ref())
+            .is_some_and(|file| file.content.is_none())
+    }
+
+    fn is_ignored(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.ignored
+            .iter()
+            .any(|pattern| matches_pattern(pattern, path))
+    }
+
+    fn is_block_ignored(&self, path: impl AsRef<Path>, name: &str) -> bool {
+        let path = path.as_ref();
+        self.ignored_blocks
+            .iter()
+            .any(|(pattern, ignored)| ignored == name && matches_pattern(pattern, path))
+    }
+
+    fn is_removal_acknowledged(&self, path: impl AsRef<Path>, name: Option<&str>) -> bool {
+        let path = path.as_ref();
+        self.acknowledged_removals
+            .iter()
+            .any(|(pattern, acknowledged)| {
+                (acknowledged.is_none() || acknowledged.as_deref() == name)
+                    && matches_pattern(pattern, path)
+            })
+    }
+
+    fn range_changes(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<Vec<String>> {
+        let file = self.files.get(path.as_ref())?;
+        let changes = file
+            .changes
+            .iter()
+            .filter(|(line, _)| (range.0..=range.1).contains(line))
+            .map(|(_, change)| change.clone())
+            .collect::<Vec<_>>();
+        (!changes.is_empty()).then_some(changes)
+    }
+
+    fn removed_lines(&self, path: impl AsRef<Path>, range: (usize, usize)) -> Option<Vec<String>> {
+        let file = self.files.get(path.as_ref())?;
+        let removed = file
+            .changes
+            .iter()
+            .filter(|(line, change)| change.starts_with('-') && (range.0..=range.1).contains(line))
+            .map(|(_, change)| change.clone())
+            .collect::<Vec<_>>();
+        (!removed.is_empty()).then_some(removed)
+    }
+
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.clone()
+    }
+}
+
+/// Check if `path` matches `pattern` as a git pathspec would, i.e. if it is
+/// the path, a directory containing it, or a glob matching it. Unlike shell
+/// globs, `*` also matches `/`.
+pub(crate) fn matches_pattern(pattern: &Path, path: &Path) -> bool {
+    if path.starts_with(pattern) {
+        return true;
+    }
+    let pattern = pattern.to_string_lossy().replace('\\', "/");
+    let path = path.to_string_lossy().replace('\\', "/");
+    glob(pattern.as_bytes(), path.as_bytes())
+}
+
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && glob(rest, &text[1..]),
+        Some((byte, rest)) => text.first() == Some(byte) && glob(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{Checker, Code};
+
+    #[test]
+    fn test_memory_engine() {
+        let engine = MemoryEngine::new([
+            (
+                "src/a.ts".into(),
+                ChangedFile {
+                    content: Some(
+                        indoc! {"
+                            // if-changed
+                            A,
+                            // then-change(b.ts:b)
+                        "}
+                        .into(),
+                    ),
+                    changes: vec![(2, "+A,".into())],
+                    ..Default::default()
+                },
+            ),
+            (
+                "src/b.ts".into(),
+                ChangedFile {
+                    content: Some(
+                        indoc! {"
+                            // if-changed(b)
+                            B,
+                            // then-change(a.ts)
+                        "}
+                        .into(),
+                    ),
+                    changes: vec![(1, "-// if-changed(b)".into())],
+                    ..Default::default()
+                },
+            ),
+            ("src/c.ts".into(), ChangedFile::default()),
+        ])
+        .with_ignored(["*/c.ts".into()]);
+
+        assert_eq!(
+            engine.matches(["src", "*.ts", "d.ts"]).collect::<Vec<_>>(),
+            [
+                Ok("src/a.ts".into()),
+                Ok("src/b.ts".into()),
+                Ok("src/c.ts".into()),
+                Err("d.ts".into()),
+            ]
+        );
+        assert!(engine.is_ignored("src/c.ts"));
+        assert_eq!(
+            engine.range_changes("src/a.ts", (1, 3)),
+            Some(vec!["+A,".into()])
+        );
+        assert_eq!(engine.range_changes("src/a.ts", (3, 3)), None);
+        assert!(Checker::builder(&engine).build().check().is_ok());
+
+        let engine = MemoryEngine {
+            files: [(
+                "src/b.ts".into(),
+                engine.files[Path::new("src/b.ts")].clone(),
+            )]
+            .into(),
+            ..engine
+        };
+        let errors = Checker::builder(&engine).build().check().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, Code::UnmodifiedTarget);
+    }
+}
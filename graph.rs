@@ -0,0 +1,325 @@
+//! The coupling of files and blocks through their targets.
+//!
+//! ```
+//! use if_changed::{graph, ChangedFile, MemoryEngine};
+//!
+//! let engine = MemoryEngine::new([(
+//!     "a.ts".into(),
+//!     ChangedFile {
+//!         content: Some("// if-changed\nA,\n// then-change(b.ts)\n".into()),
+//!         changes: vec![],
+//!         ..Default::default()
+//!     },
+//! )]);
+//! let graph = graph::build(&engine, ["a.ts"]);
+//! assert_eq!(graph.edges().len(), 1);
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    engine::{resolve_patterns, root_relative},
+    parser::Parser,
+    Code, Diagnostic, Engine,
+};
+
+/// A file, or a named block of a file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Node {
+    /// The file, or pattern of files for targets, relative to the repository
+    /// root.
+    pub path: PathBuf,
+    /// The name of the block, if any.
+    pub name: Option<String>,
+}
+
+/// A target of a block: changing `from` requires changing `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    /// The block, or the file of an unnamed block.
+    pub from: Node,
+    /// The target.
+    pub to: Node,
+    /// The line the target is listed on, starting at 1.
+    pub line: usize,
+}
+
+/// The files and blocks of a set of files, and the targets coupling them.
+#[derive(Debug, Clone, Default)]
+pub struct CouplingGraph {
+    nodes: BTreeSet<Node>,
+    blocks: BTreeSet<Node>,
+    /// The files read that contain no blocks, not even malformed ones.
+    blockless: BTreeSet<PathBuf>,
+    edges: Vec<Edge>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Build the graph of the blocks of `paths`, relative to the repository root
+/// of `engine`. Problems reading or parsing files are kept in
+/// [`CouplingGraph::diagnostics`].
+pub fn build(
+    engine: &(impl Engine + ?Sized),
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+) -> CouplingGraph {
+    let mut graph = CouplingGraph::default();
+    for path in paths {
+        let path = path.as_ref();
+        let reader = match engine.read(path) {
+            Ok(reader) => reader,
+            Err(error) => {
+                graph.diagnostics.push(Diagnostic::new(
+                    Code::Unreadable,
+                    path,
+                    None,
+                    format!("Could not open {path:?}: {error}"),
+                ));
+                continue;
+            }
+        };
+        let mut blockless = true;
+        for block in Parser::scan(path, reader) {
+            blockless = false;
+            let block = match block {
+                Ok(block) => block,
+                Err(error) => {
+                    graph.diagnostics.extend(error);
+                    continue;
+                }
+            };
+            let from = Node {
+                path: path.to_owned(),
+                name: block.name,
+            };
+            graph.nodes.insert(from.clone());
+            graph.blocks.insert(from.clone());
+            for pattern in resolve_patterns(path, block.targets, &block.ignored, true)
+                .into_iter()
+                .flatten()
+            {
+                let to = Node {
+                    path: root_relative(&pattern.path),
+                    name: pattern.name,
+                };
+                graph.nodes.insert(to.clone());
+                graph.edges.push(Edge {
+                    from: from.clone(),
+                    to,
+                    line: pattern.line,
+                });
+            }
+        }
+        if blockless {
+            graph.blockless.insert(path.to_owned());
+        }
+    }
+    graph
+}
+
+impl CouplingGraph {
+    /// Iterate over the blocks and targets of the graph, in order.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter()
+    }
+
+    /// Get the targets of the graph, in the order they were found.
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Problems found while reading or parsing files.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Iterate over the targets to change when `node` changes.
+    pub fn dependents_of<'a>(&'a self, node: &'a Node) -> impl Iterator<Item = &'a Node> {
+        self.edges
+            .iter()
+            .filter(move |edge| &edge.from == node)
+            .map(|edge| &edge.to)
+    }
+
+    /// Get the groups of nodes that require changing each other, directly or
+    /// transitively, e.g. blocks targeting each other.
+    pub fn cycles(&self) -> Vec<Vec<&Node>> {
+        let mut successors = BTreeMap::<&Node, Vec<&Node>>::new();
+        for edge in &self.edges {
+            successors.entry(&edge.from).or_default().push(&edge.to);
+        }
+        let mut tarjan = Tarjan {
+            successors: &successors,
+            index: BTreeMap::new(),
+            low: BTreeMap::new(),
+            stack: Vec::new(),
+            components: Vec::new(),
+        };
+        for node in &self.nodes {
+            if !tarjan.index.contains_key(node) {
+                tarjan.visit(node);
+            }
+        }
+        let mut cycles = tarjan
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1
+                    || successors
+                        .get(component[0])
+                        .is_some_and(|next| next.contains(&component[0]))
+            })
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+            .collect::<Vec<_>>();
+        cycles.sort();
+        cycles
+    }
+
+    /// Iterate over the named blocks that no target refers to.
+    pub fn orphans(&self) -> impl Iterator<Item = &Node> {
+        let referenced = self
+            .edges
+            .iter()
+            .map(|edge| &edge.to)
+            .collect::<BTreeSet<_>>();
+        self.blocks
+            .iter()
+            .filter(move |node| node.name.is_some() && !referenced.contains(node))
+    }
+
+    /// Iterate over the files that targets refer to but that contain no
+    /// blocks, in order. Only the files the graph was built from are known.
+    pub fn blockless_targets(&self) -> impl Iterator<Item = &Path> {
+        self.edges
+            .iter()
+            .map(|edge| edge.to.path.as_path())
+            .filter(|path| self.blockless.contains(*path))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+}
+
+/// Tarjan's algorithm for strongly connected components.
+struct Tarjan<'s, 'a> {
+    successors: &'s BTreeMap<&'a Node, Vec<&'a Node>>,
+    index: BTreeMap<&'a Node, usize>,
+    low: BTreeMap<&'a Node, usize>,
+    stack: Vec<&'a Node>,
+    components: Vec<Vec<&'a Node>>,
+}
+
+impl<'a> Tarjan<'_, 'a> {
+    fn visit(&mut self, node: &'a Node) {
+        let index = self.index.len();
+        self.index.insert(node, index);
+        self.low.insert(node, index);
+        self.stack.push(node);
+
+        for &next in self.successors.get(node).into_iter().flatten() {
+            if !self.index.contains_key(next) {
+                self.visit(next);
+                let low = self.low[node].min(self.low[next]);
+                self.low.insert(node, low);
+            } else if self.stack.contains(&next) {
+                let low = self.low[node].min(self.index[next]);
+                self.low.insert(node, low);
+            }
+        }
+
+        if self.low[node] == index {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{ChangedFile, MemoryEngine};
+
+    fn node(path: &str, name: Option<&str>) -> Node {
+        Node {
+            path: path.into(),
+            name: name.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_graph() {
+        let file = |content: &str| ChangedFile {
+            content: Some(content.into()),
+            changes: vec![],
+            ..Default::default()
+        };
+        let engine = MemoryEngine::new([
+            (
+                "src/a.ts".into(),
+                file(indoc! {"
+                    // if-changed(a)
+                    A,
+                    // then-change(b.ts:b, /c.ts)
+                "}),
+            ),
+            (
+                "src/b.ts".into(),
+                file(indoc! {"
+                    // if-changed(b)
+                    B,
+                    // then-change(a.ts:a)
+                    // if-changed(unused)
+                    // then-change(missing.ts)
+                "}),
+            ),
+            ("c.ts".into(), file("// if-changed\n")),
+            ("src/missing.ts".into(), file("M,\n")),
+        ]);
+        let graph = build(
+            &engine,
+            ["src/a.ts", "src/b.ts", "c.ts", "d.ts", "src/missing.ts"],
+        );
+
+        assert_eq!(
+            graph
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| (diagnostic.path.to_str().unwrap(), diagnostic.code))
+                .collect::<Vec<_>>(),
+            [
+                ("c.ts", Code::UnterminatedBlock),
+                ("d.ts", Code::Unreadable)
+            ]
+        );
+        assert_eq!(
+            graph
+                .dependents_of(&node("src/a.ts", Some("a")))
+                .collect::<Vec<_>>(),
+            [&node("src/b.ts", Some("b")), &node("c.ts", None)]
+        );
+        assert_eq!(
+            graph.cycles(),
+            [[&node("src/a.ts", Some("a")), &node("src/b.ts", Some("b"))]]
+        );
+        assert_eq!(
+            graph.orphans().collect::<Vec<_>>(),
+            [&node("src/b.ts", Some("unused"))]
+        );
+        assert_eq!(
+            graph.blockless_targets().collect::<Vec<_>>(),
+            [Path::new("src/missing.ts")]
+        );
+    }
+}
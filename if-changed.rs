@@ -1,24 +1,103 @@
+mod checker;
+mod config;
+mod diagnostic;
 mod engine;
+pub mod graph;
+mod ignore_file;
+#[cfg(feature = "index")]
+mod index;
+mod manifest;
+mod owners;
 mod parser;
+mod reporter;
+#[cfg(any(test, feature = "git"))]
+mod repository;
+mod stats;
+#[cfg(feature = "tree-sitter")]
+mod syntax;
 
+#[cfg(any(test, feature = "git"))]
 pub mod testing;
 
 use std::path::PathBuf;
 
-pub use engine::{Engine, GitEngine};
+pub use checker::{CheckIter, Checker, CheckerBuilder, ParseCache};
+pub use config::{
+    Config, ConfigTree, DeletedTargetPolicy, DirectoryConfig, LegacyFormat, MessageConfig,
+    MigrateConfig, OwnersConfig, TargetConfig, TrailerConfig, CONFIG_FILE_NAME,
+    DEFAULT_IGNORE_TRAILER, REMOVAL_TRAILER,
+};
+pub use diagnostic::{Code, Diagnostic, LastEdit, Severity, Suggestion};
+#[cfg(feature = "tokio")]
+pub use engine::{AsyncEngine, Blocking};
+pub use engine::{Blame, ChangedFile, Engine, MemoryEngine};
+#[cfg(any(test, feature = "git"))]
+pub use engine::{DiffFilter, GitEngine, GitEngineBuilder};
+pub use ignore_file::{IgnoreFile, IGNORE_FILE_NAME};
+#[cfg(feature = "index")]
+pub use index::{BlockIndex, INDEX_FILE_NAME};
+pub use manifest::Manifest;
+pub use owners::Owners;
+pub use parser::{parse_file, parse_str};
+pub use reporter::{Reporter, Summary};
+#[cfg(any(test, feature = "git"))]
+pub use repository::{check_repository, CheckOptions, FileReport, Report};
+pub use stats::Stats;
 
-#[derive(Debug, Clone)]
-#[cfg_attr(test, derive(serde::Serialize))]
-struct Pattern {
+/// The version of the serialized form of [`Block`], [`Pattern`] and
+/// [`Diagnostic`]. It is bumped when fields are removed or change meaning, but
+/// not when fields are added.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A target listed in a `then-change`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(any(test, feature = "serde"), derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct Pattern {
+    /// The name of the block to change in the target, if any, as in
+    /// `then-change(a.ts:name)`.
     pub name: Option<String>,
-    pub value: PathBuf,
+    /// The path or pattern of the target, as written. It is relative to the
+    /// file containing it unless it starts with `/`, and empty for the file
+    /// itself.
+    pub path: PathBuf,
+    /// The line the target is listed on, starting at 1.
     pub line: usize,
 }
 
-#[derive(Debug, Clone)]
-#[cfg_attr(test, derive(serde::Serialize))]
-struct IfChangedBlock {
+/// An `if-changed` block and the targets of its `then-change`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(any(test, feature = "serde"), derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct Block {
+    /// The name of the block, if any, as in `if-changed(name)`.
     pub name: Option<String>,
+    /// The lines of the `if-changed` and the `then-change`, starting at 1.
     pub range: (usize, usize),
-    pub patterns: Vec<Pattern>,
+    /// The lines of the function the block covers instead, as in
+    /// `if-changed(scope=function)`, starting at 1.
+    #[cfg_attr(
+        any(test, feature = "serde"),
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub scope: Option<(usize, usize)>,
+    /// The targets of the `then-change`.
+    pub targets: Vec<Pattern>,
+    /// Paths of targets suppressed with an inline `ignore-if-changed`.
+    #[cfg_attr(
+        any(test, feature = "serde"),
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub ignored: Vec<PathBuf>,
+    /// The style of the comment containing the `if-changed`.
+    #[cfg_attr(any(test, feature = "serde"), serde(skip))]
+    pub(crate) comment: parser::Comment,
+}
+
+impl Block {
+    /// Get the lines whose changes count as changes of the block: its scope,
+    /// if any, or else its range.
+    pub fn lines(&self) -> (usize, usize) {
+        self.scope.unwrap_or(self.range)
+    }
 }
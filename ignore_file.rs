@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use crate::owners::matches;
+
+/// The name of the files excluding files from checks, looked up in every
+/// directory like `.gitignore`.
+pub const IGNORE_FILE_NAME: &str = ".ifchangedignore";
+
+/// The patterns of an `.ifchangedignore` file, in the syntax of `.gitignore`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IgnoreFile {
+    rules: Vec<Rule>,
+}
+
+/// A line of an `.ifchangedignore` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    pattern: String,
+    /// Whether the line starts with `!`, re-including the files it matches.
+    negated: bool,
+}
+
+impl IgnoreFile {
+    /// Parse an `.ifchangedignore` file.
+    ///
+    /// Each line is a pattern of paths relative to the directory of the file,
+    /// e.g. `generated/` or `*.pb.go`. Lines starting with `!` re-include the
+    /// files excluded by earlier lines, and lines starting with `#` are
+    /// comments.
+    pub fn parse(source: &str) -> Self {
+        let rules = source
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.strip_prefix('!') {
+                Some(pattern) => Rule {
+                    pattern: pattern.to_owned(),
+                    negated: true,
+                },
+                None => Rule {
+                    pattern: line.to_owned(),
+                    negated: false,
+                },
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Check if `path`, relative to the directory of the file, is excluded.
+    /// The last matching line decides, and `None` means no line matches, so
+    /// the files of parent directories decide.
+    pub fn excludes(&self, path: impl AsRef<Path>) -> Option<bool> {
+        let path = path.as_ref().to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| matches(&rule.pattern, &path))
+            .map(|rule| !rule.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_excludes() {
+        let file = IgnoreFile::parse(indoc! {r"
+            # Generated code.
+            generated/
+            *.pb.go
+            !keep.pb.go
+            /docs/*.md
+            \#notes.txt
+        "});
+        assert_eq!(file.excludes("src/generated/a.ts"), Some(true));
+        assert_eq!(file.excludes("generated"), None);
+        assert_eq!(file.excludes("api/a.pb.go"), Some(true));
+        assert_eq!(file.excludes("api/keep.pb.go"), Some(false));
+        assert_eq!(file.excludes("docs/a.md"), Some(true));
+        assert_eq!(file.excludes("src/docs/a.md"), None);
+        assert_eq!(file.excludes("#notes.txt"), Some(true));
+        assert_eq!(file.excludes("src/a.ts"), None);
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    checker::{find_named_block, ModifiedBlock},
+    config::{requires_blocks, unannotated_file},
+    parser::Parser,
+    Block, Diagnostic, SCHEMA_VERSION,
+};
+
+/// The name of the file the index is stored in, in the git directory.
+pub const INDEX_FILE_NAME: &str = "if-changed-cache";
+
+/// The blocks of the files of a repository, keyed by the id of their content
+/// and stored in the git directory between runs, so only files changed since
+/// the last run are parsed.
+///
+/// ```no_run
+/// # let repository = git2::Repository::open_from_env().unwrap();
+/// use if_changed::BlockIndex;
+///
+/// let mut index = BlockIndex::open(&repository);
+/// let problems = index.verify(["a.ts", "b.ts"]);
+/// index.save()?;
+/// # std::io::Result::Ok(())
+/// ```
+pub struct BlockIndex<'repo> {
+    repository: &'repo git2::Repository,
+    files: HashMap<PathBuf, IndexEntry>,
+    /// Whether entries were added or replaced since the index was loaded.
+    modified: bool,
+}
+
+/// The serialized form of the index.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IndexFile {
+    version: u32,
+    /// Whether blocks were parsed only in the comments found by tree-sitter,
+    /// which finds other blocks in some files.
+    #[serde(default)]
+    tree_sitter: bool,
+    files: Vec<IndexEntry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct IndexEntry {
+    path: PathBuf,
+    /// The id of the blob of the content the blocks were parsed from.
+    oid: String,
+    blocks: Vec<Result<Block, Vec<Diagnostic>>>,
+    /// The number of lines of the file.
+    lines: usize,
+}
+
+impl<'repo> BlockIndex<'repo> {
+    /// Create an empty index of the working tree of `repository`.
+    pub fn new(repository: &'repo git2::Repository) -> Self {
+        Self {
+            repository,
+            files: HashMap::new(),
+            modified: false,
+        }
+    }
+
+    /// Load the index stored for `repository`. An index that is missing,
+    /// unreadable, of another version, or built with another setting of the
+    /// `tree-sitter` feature is started over.
+    pub fn open(repository: &'repo git2::Repository) -> Self {
+        let mut index = Self::new(repository);
+        let stored = fs::read(index.file())
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<IndexFile>(&bytes).ok())
+            .filter(|stored| {
+                stored.version == SCHEMA_VERSION
+                    && stored.tree_sitter == cfg!(feature = "tree-sitter")
+            });
+        if let Some(stored) = stored {
+            index.files = stored
+                .files
+                .into_iter()
+                .map(|entry| (entry.path.clone(), entry))
+                .collect();
+        }
+        index
+    }
+
+    /// Store the index for later runs, without the files that are no longer
+    /// tracked. Does nothing if no file was parsed since it was loaded.
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.modified {
+            return Ok(());
+        }
+        let tracked = self.repository.index().map_err(io::Error::other)?;
+        self.files
+            .retain(|path, _| tracked.get_path(path, 0).is_some());
+        let mut files = self.files.values().collect::<Vec<_>>();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let json = serde_json::json!({
+            "version": SCHEMA_VERSION,
+            "tree_sitter": cfg!(feature = "tree-sitter"),
+            "files": files,
+        });
+
+        // Write a temporary file first so concurrent runs never read half an
+        // index.
+        let file = self.file();
+        let temporary = file.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(&json)?)?;
+        fs::rename(temporary, file)?;
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Get the blocks of `path`, relative to the working tree, parsing it only
+    /// if its content changed since it was indexed.
+    pub fn blocks(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<&[Result<Block, Vec<Diagnostic>>]> {
+        Ok(&self.entry(path.as_ref())?.blocks)
+    }
+
+    /// Get the entry of `path`, parsing it unless it is fresh.
+    fn entry(&mut self, path: &Path) -> io::Result<&IndexEntry> {
+        let workdir = self
+            .repository
+            .workdir()
+            .ok_or_else(|| io::Error::other("bare repos are not supported"))?;
+        let oid = self.oid(workdir, path)?.to_string();
+        let fresh = self.files.get(path).is_some_and(|entry| entry.oid == oid);
+        if !fresh {
+            let mut parser = Parser::new(path, workdir.join(path))?;
+            let blocks = parser.by_ref().collect();
+            self.files.insert(
+                path.to_owned(),
+                IndexEntry {
+                    path: path.to_owned(),
+                    oid,
+                    blocks,
+                    lines: parser.lines_read(),
+                },
+            );
+            self.modified = true;
+        }
+        Ok(&self.files[path])
+    }
+
+    /// Check the blocks of `paths` without comparing revisions: that they are
+    /// well-formed, that their targets are tracked, and that the blocks named
+    /// by targets exist.
+    pub fn verify(&mut self, paths: impl IntoIterator<Item = impl AsRef<Path>>) -> Vec<Diagnostic> {
+        let mut problems = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let blocks = match self.blocks(path) {
+                Ok(blocks) => blocks.to_vec(),
+                Err(error) => {
+                    problems.push(Diagnostic::new(
+                        crate::Code::Unreadable,
+                        path,
+                        None,
+                        format!("Could not open {path:?}: {error}"),
+                    ));
+                    continue;
+                }
+            };
+            for block in blocks {
+                let block = match block {
+                    Ok(block) => block,
+                    Err(error) => {
+                        problems.extend(error);
+                        continue;
+                    }
+                };
+                let (block, unnamed, named) = ModifiedBlock::new(path, block, Vec::new(), true);
+                for (pattern, line) in &unnamed {
+                    if self.tracked(pattern).is_empty() {
+                        problems.push(block.unreadable_target(*line, pattern, &not_tracked()));
+                    }
+                }
+                for (pattern, (name, line)) in &named {
+                    let dependents = self.tracked(pattern);
+                    if dependents.is_empty() {
+                        problems.push(block.unreadable_target(*line, pattern, &not_tracked()));
+                    }
+                    for dependent in dependents {
+                        let entry = match self.entry(&dependent) {
+                            Ok(entry) => entry,
+                            Err(error) => {
+                                problems.push(block.unreadable_target(*line, &dependent, &error));
+                                continue;
+                            }
+                        };
+                        let (found, comment) = find_named_block(entry.blocks.iter().cloned(), name);
+                        match found {
+                            Some(Ok(_)) => {}
+                            Some(Err(error)) => problems.extend(error),
+                            None => problems.push(block.missing_named_block(
+                                *line,
+                                name,
+                                &dependent,
+                                comment,
+                                entry.lines + 1,
+                            )),
+                        }
+                    }
+                }
+            }
+        }
+        problems
+    }
+
+    /// Check that the files of `paths` matching `patterns`, in the syntax of
+    /// [`Config::scan`](crate::Config::scan), contain at least one block.
+    /// Files that can't be read were reported by [`BlockIndex::verify`].
+    pub fn verify_required(
+        &mut self,
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+        patterns: &[String],
+    ) -> Vec<Diagnostic> {
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let path = path.as_ref();
+                if !requires_blocks(patterns, path) {
+                    return None;
+                }
+                unannotated_file(patterns, path, self.blocks(path).ok()?)
+            })
+            .collect()
+    }
+
+    /// The path of the stored index.
+    fn file(&self) -> PathBuf {
+        self.repository.path().join(INDEX_FILE_NAME)
+    }
+
+    /// Get the id of the content of `path`, from the git index if the file is
+    /// unchanged since it was staged, or by hashing it.
+    fn oid(&self, workdir: &Path, path: &Path) -> io::Result<git2::Oid> {
+        let file = workdir.join(path);
+        let metadata = fs::metadata(&file)?;
+        let staged = self
+            .repository
+            .index()
+            .ok()
+            .and_then(|index| index.get_path(path, 0));
+        if let (Some(entry), Ok(modified)) = (staged, metadata.modified()) {
+            let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+            if u64::from(entry.file_size) == metadata.len()
+                && i64::from(entry.mtime.seconds()) == modified.as_secs() as i64
+                && entry.mtime.nanoseconds() == modified.subsec_nanos()
+            {
+                return Ok(entry.id);
+            }
+        }
+        git2::Oid::hash_file(git2::ObjectType::Blob, file).map_err(io::Error::other)
+    }
+
+    /// Get the tracked files matching `pattern`, relative to the repository
+    /// root. Files deleted from the working tree but not from the git index
+    /// don't count, so targets left stale by deletions are caught before they
+    /// are committed.
+    fn tracked(&self, pattern: &Path) -> Vec<PathBuf> {
+        let pattern = crate::engine::root_relative(pattern);
+        let (Some(workdir), Ok(pathspec), Ok(index)) = (
+            self.repository.workdir(),
+            git2::Pathspec::new([&pattern]),
+            self.repository.index(),
+        ) else {
+            return Vec::new();
+        };
+        let Ok(matches) = pathspec.match_index(&index, git2::PathspecFlags::DEFAULT) else {
+            return Vec::new();
+        };
+        matches
+            .entries()
+            .filter_map(|entry| std::str::from_utf8(entry).ok())
+            .map(PathBuf::from)
+            .filter(|path| workdir.join(path).exists())
+            .collect()
+    }
+}
+
+/// The error of targets matching no tracked file.
+fn not_tracked() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no tracked file matches")
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{testing::git_test, Code};
+
+    #[test]
+    fn test_block_index() {
+        let (tempdir, repo) = git_test! {
+            "initial commit": [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts, c.ts:c, d.ts)
+                "},
+                "b.ts" => indoc! {"
+                    // if-changed(b)
+                    B,
+                    // then-change(a.ts)
+                "},
+                "c.ts" => "C,\n"
+            ]
+        };
+
+        let mut index = BlockIndex::open(&repo);
+        let problems = index.verify(["a.ts", "b.ts"]);
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| (
+                    problem.code,
+                    problem.target.as_deref().unwrap().to_str().unwrap()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (Code::MissingTarget, "d.ts"),
+                (Code::MissingNamedBlock, "c.ts")
+            ]
+        );
+        index.save().unwrap();
+
+        // Unchanged files are taken from the stored index.
+        let mut index = BlockIndex::open(&repo);
+        assert_eq!(index.files.len(), 3);
+        assert!(!index.files[Path::new("a.ts")].blocks.is_empty());
+        index.blocks("a.ts").unwrap();
+        assert!(!index.modified);
+
+        // Changed files are parsed again.
+        fs::write(
+            tempdir.path().join("c.ts"),
+            "// if-changed(c)\nC,\n// then-change(a.ts)\n",
+        )
+        .unwrap();
+        let problems = index.verify(["a.ts"]);
+        assert_eq!(problems.len(), 1);
+        assert!(index.modified);
+
+        // Deleted targets are missing even before the deletion is staged.
+        fs::remove_file(tempdir.path().join("b.ts")).unwrap();
+        let problems = index.verify(["a.ts"]);
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| (problem.code, problem.target.as_deref().unwrap()))
+                .collect::<Vec<_>>(),
+            [
+                (Code::MissingTarget, Path::new("b.ts")),
+                (Code::MissingTarget, Path::new("d.ts"))
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checker::{find_named_block, ModifiedBlock},
+    engine::matches_pattern,
+    parser::Parser,
+    Block, Code, Diagnostic,
+};
+
+/// The files a build rule checks and the files their targets may refer to,
+/// e.g. as written by a Bazel or Buck2 rule for a validation action.
+///
+/// Checking a manifest doesn't compare revisions or read anything but the
+/// listed files, so it is hermetic.
+///
+/// ```no_run
+/// let manifest = if_changed::Manifest {
+///     srcs: vec!["a.ts".into()],
+///     deps: vec!["b.ts".into()],
+/// };
+/// assert!(manifest.validate(".").is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The files to check, relative to the root.
+    pub srcs: Vec<PathBuf>,
+    /// The files targets may refer to besides `srcs`, e.g. the sources of
+    /// the dependencies of the rule, relative to the root.
+    #[serde(default)]
+    pub deps: Vec<PathBuf>,
+}
+
+/// The parsed blocks of a file and its number of lines.
+type Parsed = (Vec<Result<Block, Vec<Diagnostic>>>, usize);
+
+impl Manifest {
+    /// Check the blocks of `srcs` under `root`: that they are well-formed,
+    /// that their targets are among `srcs` and `deps`, and that the blocks
+    /// named by targets exist.
+    pub fn validate(&self, root: impl AsRef<Path>) -> Vec<Diagnostic> {
+        let root = root.as_ref();
+        let mut parsed = HashMap::<PathBuf, io::Result<Parsed>>::new();
+        let mut parse = |path: &Path| -> Result<Parsed, io::Error> {
+            let entry = parsed.entry(path.to_owned()).or_insert_with(|| {
+                let mut parser = Parser::new(path, root.join(path))?;
+                let blocks = parser.by_ref().collect();
+                Ok((blocks, parser.lines_read()))
+            });
+            match entry {
+                Ok(parsed) => Ok(parsed.clone()),
+                Err(error) => Err(io::Error::new(error.kind(), error.to_string())),
+            }
+        };
+        let allowed = |pattern: &Path| {
+            self.srcs
+                .iter()
+                .chain(&self.deps)
+                .filter(|path| matches_pattern(pattern, path))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let mut problems = Vec::new();
+        for path in &self.srcs {
+            let blocks = match parse(path) {
+                Ok((blocks, _)) => blocks,
+                Err(error) => {
+                    problems.push(Diagnostic::new(
+                        Code::Unreadable,
+                        path,
+                        None,
+                        format!("Could not open {path:?}: {error}"),
+                    ));
+                    continue;
+                }
+            };
+            for block in blocks {
+                let block = match block {
+                    Ok(block) => block,
+                    Err(error) => {
+                        problems.extend(error);
+                        continue;
+                    }
+                };
+                let (block, unnamed, named) = ModifiedBlock::new(path, block, Vec::new(), true);
+                problems.extend(block.escaping_targets());
+                for (pattern, line) in &unnamed {
+                    if allowed(pattern).is_empty() {
+                        problems.push(block.unreadable_target(*line, pattern, &not_listed()));
+                    }
+                }
+                for (pattern, (name, line)) in &named {
+                    let dependents = allowed(pattern);
+                    if dependents.is_empty() {
+                        problems.push(block.unreadable_target(*line, pattern, &not_listed()));
+                    }
+                    for dependent in dependents {
+                        let (blocks, lines) = match parse(&dependent) {
+                            Ok(parsed) => parsed,
+                            Err(error) => {
+                                problems.push(block.unreadable_target(*line, &dependent, &error));
+                                continue;
+                            }
+                        };
+                        let (found, comment) = find_named_block(blocks.into_iter(), name);
+                        match found {
+                            Some(Ok(_)) => {}
+                            Some(Err(error)) => problems.extend(error),
+                            None => problems.push(block.missing_named_block(
+                                *line,
+                                name,
+                                &dependent,
+                                comment,
+                                lines + 1,
+                            )),
+                        }
+                    }
+                }
+            }
+        }
+        problems.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+        problems
+    }
+}
+
+/// The error of targets matching no file of the manifest.
+fn not_listed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "no file of the manifest matches, so it may not be a dependency of the rule",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let tempdir = tempfile::tempdir().unwrap();
+        for (path, content) in [
+            (
+                "a.ts",
+                "// if-changed\nA,\n// then-change(b.ts, c.ts:c, /d.ts)\n",
+            ),
+            ("b.ts", "// if-changed(b)\nB,\n// then-change(a.ts)\n"),
+            ("c.ts", "C,\n"),
+            ("d.ts", "D,\n"),
+        ] {
+            fs::write(tempdir.path().join(path), content).unwrap();
+        }
+        let manifest = Manifest {
+            srcs: vec!["a.ts".into(), "b.ts".into()],
+            deps: vec!["c.ts".into()],
+        };
+        insta::assert_snapshot!(manifest
+            .validate(tempdir.path())
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join("\n"), @r###"
+        IC001: Could not open "d.ts" for "then-change" in "a.ts" at line 3: Custom { kind: NotFound, error: "no file of the manifest matches, so it may not be a dependency of the rule" }
+        IC003: Could not find "if-changed" with name "c" in "c.ts" for "then-change" in "a.ts" at line 3.
+        "###);
+
+        let manifest = Manifest {
+            srcs: vec!["b.ts".into()],
+            deps: vec!["a.ts".into()],
+        };
+        assert!(manifest.validate(tempdir.path()).is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use std::{fs, io, path::Path};
+
+use crate::{config::OwnersConfig, Diagnostic};
+
+/// Where `CODEOWNERS` files are looked up when none is configured, in order,
+/// as GitHub and GitLab do.
+const CODEOWNERS_PATHS: [&str; 4] = [
+    ".github/CODEOWNERS",
+    "CODEOWNERS",
+    "docs/CODEOWNERS",
+    ".gitlab/CODEOWNERS",
+];
+
+/// The owners of files, as listed in a `CODEOWNERS` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Owners {
+    rules: Vec<Rule>,
+}
+
+/// A line of a `CODEOWNERS` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    pattern: String,
+    owners: Vec<String>,
+}
+
+impl Owners {
+    /// Read the owners file of the repository in `workdir`, as configured by
+    /// `config` or else at one of the usual paths of `CODEOWNERS`.
+    ///
+    /// A missing `CODEOWNERS` file is not an error, but a missing configured
+    /// file is.
+    pub fn discover(workdir: impl AsRef<Path>, config: &OwnersConfig) -> io::Result<Option<Self>> {
+        let workdir = workdir.as_ref();
+        if let Some(file) = &config.file {
+            return fs::read_to_string(workdir.join(file)).map(|source| Some(Self::parse(&source)));
+        }
+        for path in CODEOWNERS_PATHS {
+            match fs::read_to_string(workdir.join(path)) {
+                Ok(source) => return Ok(Some(Self::parse(&source))),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Parse a `CODEOWNERS` file.
+    ///
+    /// Each line lists a pattern of paths followed by their owners, e.g.
+    /// `/src/api/ @org/backend`. Comments, and the section headers of GitLab,
+    /// are skipped.
+    pub fn parse(source: &str) -> Self {
+        let rules = source
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(['#', '[', '^']))
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let pattern = words.next()?.to_owned();
+                let owners = words
+                    .take_while(|word| !word.starts_with('#'))
+                    .map(str::to_owned)
+                    .collect();
+                Some(Rule { pattern, owners })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Get the owners of `path`, relative to the repository root. The last
+    /// matching line decides, so a line without owners leaves paths unowned.
+    pub fn of(&self, path: impl AsRef<Path>) -> &[String] {
+        let path = path.as_ref().to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| matches(&rule.pattern, &path))
+            .map_or(&[], |rule| &rule.owners)
+    }
+
+    /// Set the owners of a diagnostic to those of its target, if any.
+    pub fn annotate(&self, diagnostic: &mut Diagnostic) {
+        if let Some(target) = &diagnostic.target {
+            diagnostic.owners = self.of(target).to_vec();
+        }
+    }
+}
+
+/// Check if `path` matches a pattern of a `CODEOWNERS` file, following the
+/// rules of `.gitignore`: patterns without a `/` but at their end match at
+/// any depth, and a matching directory matches all files beneath it.
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    match_directory(pattern, path).is_some()
+}
+
+/// Get the directory of `path` that `pattern` matches from as [`matches`]
+/// does, e.g. `web/` for `package.json` and `web/package.json`, or `None`
+/// if it doesn't match.
+pub(crate) fn match_directory(pattern: &str, path: &str) -> Option<String> {
+    let directory = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    let anchored = pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+    let components = path.split('/').collect::<Vec<_>>();
+    let starts = if anchored { 0..1 } else { 0..components.len() };
+    let start = starts.into_iter().find(|&start| {
+        (start + 1..=components.len()).any(|end| {
+            (end < components.len() || !directory)
+                && glob(
+                    pattern.as_bytes(),
+                    components[start..end].join("/").as_bytes(),
+                )
+        })
+    })?;
+    Some(
+        components[..start]
+            .iter()
+            .map(|component| format!("{component}/"))
+            .collect(),
+    )
+}
+
+/// Match a glob where `*` and `?` don't match `/` but `**` does, and `**/`
+/// also matches no directory at all.
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    if let Some(rest) = pattern.strip_prefix(b"**/") {
+        return (0..=text.len())
+            .filter(|&skip| skip == 0 || text[skip - 1] == b'/')
+            .any(|skip| glob(rest, &text[skip..]));
+    }
+    if let Some(rest) = pattern.strip_prefix(b"**") {
+        return (0..=text.len()).any(|skip| glob(rest, &text[skip..]));
+    }
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len())
+            .take_while(|&skip| skip == 0 || text[skip - 1] != b'/')
+            .any(|skip| glob(rest, &text[skip..])),
+        Some((b'?', rest)) => {
+            text.first().is_some_and(|byte| *byte != b'/') && glob(rest, &text[1..])
+        }
+        Some((b'\\', [byte, rest @ ..])) => text.first() == Some(byte) && glob(rest, &text[1..]),
+        Some((byte, rest)) => text.first() == Some(byte) && glob(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::Code;
+
+    #[test]
+    fn test_of() {
+        let owners = Owners::parse(indoc! {"
+            # Default owners.
+            *                   @org/everyone
+            *.ts                @org/frontend # TypeScript
+            /src/api/           @org/backend alice@example.com
+            docs/               @org/writers
+            /src/**/generated   @org/tools
+            /src/api/vendor
+            [Section]
+            ^[Optional section]
+            /src/a?.ts          @org/a
+        "});
+        let of = |path| owners.of(path).to_vec();
+        assert_eq!(of("README.md"), ["@org/everyone"]);
+        assert_eq!(of("web/b.ts"), ["@org/frontend"]);
+        assert_eq!(of("src/api/c.rs"), ["@org/backend", "alice@example.com"]);
+        assert_eq!(of("src/api/d.ts"), ["@org/backend", "alice@example.com"]);
+        assert_eq!(of("api/e.rs"), ["@org/everyone"]);
+        assert_eq!(of("docs/f.md"), ["@org/writers"]);
+        assert_eq!(of("src/docs/g.md"), ["@org/writers"]);
+        assert_eq!(of("src/generated/h.rs"), ["@org/tools"]);
+        assert_eq!(of("src/x/y/generated/i.rs"), ["@org/tools"]);
+        assert_eq!(of("src/api/vendor/j.rs"), [] as [&str; 0]);
+        assert_eq!(of("src/ab.ts"), ["@org/a"]);
+        assert_eq!(of("src/x/ab.ts"), ["@org/frontend"]);
+        assert_eq!(of("src\\ab.ts"), ["@org/a"]);
+    }
+
+    #[test]
+    fn test_discover() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let config = OwnersConfig::default();
+        assert_eq!(Owners::discover(tempdir.path(), &config).unwrap(), None);
+
+        fs::create_dir(tempdir.path().join("docs")).unwrap();
+        fs::write(tempdir.path().join("docs/CODEOWNERS"), "* @docs").unwrap();
+        let owners = Owners::discover(tempdir.path(), &config).unwrap().unwrap();
+        assert_eq!(owners.of("a"), ["@docs"]);
+
+        fs::write(tempdir.path().join("CODEOWNERS"), "* @root").unwrap();
+        let owners = Owners::discover(tempdir.path(), &config).unwrap().unwrap();
+        assert_eq!(owners.of("a"), ["@root"]);
+
+        let config = OwnersConfig {
+            file: Some("OWNERS".into()),
+        };
+        assert!(Owners::discover(tempdir.path(), &config).is_err());
+        fs::write(tempdir.path().join("OWNERS"), "* @owners").unwrap();
+        let owners = Owners::discover(tempdir.path(), &config).unwrap().unwrap();
+        assert_eq!(owners.of("a"), ["@owners"]);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let owners = Owners::parse("b.ts @org/b");
+        let mut diagnostic = Diagnostic::new(Code::UnmodifiedTarget, "a.ts", Some(3), "".into());
+        owners.annotate(&mut diagnostic);
+        assert!(diagnostic.owners.is_empty());
+        diagnostic.target = Some("b.ts".into());
+        owners.annotate(&mut diagnostic);
+        assert_eq!(diagnostic.owners, ["@org/b"]);
+    }
+}
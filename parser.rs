@@ -1,19 +1,58 @@
 use std::{
+    borrow::Cow,
     fs,
-    io::{self, BufRead},
+    io::{self, BufRead, Read as _},
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
-    str::FromStr,
+    str::{self, FromStr},
 };
 
-use super::IfChangedBlock;
-use crate::Pattern;
+use super::Block;
+use crate::{Code, Diagnostic, Pattern, Severity, Suggestion};
+
+/// Starts an inline suppression of targets inside a block.
+const IGNORE_TOKEN: &str = "ignore-if-changed";
+
+/// Contained in every directive: `if-changed`, `then-change` and
+/// `ignore-if-changed`.
+const DIRECTIVE_NEEDLE: &[u8] = b"-change";
+
+/// Starts some files saved on Windows.
+const BYTE_ORDER_MARK: char = '\u{feff}';
 
 const COMMENT_START_TOKENS: [char; 12] =
     ['/', '#', '-', '\'', ';', 'R', 'E', 'M', '!', '*', '<', '!'];
 
+/// Closing delimiters of block comments kept after a generated directive.
+const COMMENT_END_TOKENS: [&str; 5] = ["-->", "*/", "*)", "#>", "-}"];
+
+/// The delimiters around a directive in a comment, used to write new
+/// directives in the same style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Comment {
+    /// The text before the directive, including indentation, e.g. `    // `.
+    pub prefix: String,
+    /// The text after the directive closing a block comment, e.g. ` -->`.
+    pub suffix: String,
+}
+
+impl Comment {
+    /// Write a directive, e.g. `then-change(a.ts)`, in this comment style.
+    pub(crate) fn wrap(&self, directive: &str) -> String {
+        format!("{}{directive}{}", self.prefix, self.suffix)
+    }
+}
+
+impl Default for Comment {
+    fn default() -> Self {
+        Self {
+            prefix: "// ".to_owned(),
+            suffix: String::new(),
+        }
+    }
+}
+
 struct StringRef {
-    #[allow(dead_code)]
     owner: String,
     reference: *const str,
 }
@@ -35,13 +74,18 @@ impl StringRef {
         self.reference = f(&*self)?;
         Some(self)
     }
+
+    /// Get the part of the whole line before the current reference.
+    fn consumed(&self) -> &str {
+        &self.owner[..self.owner.len() - self.len()]
+    }
 }
 
 impl Deref for StringRef {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
-        return unsafe { self.reference.as_ref().unwrap_unchecked() };
+        unsafe { self.reference.as_ref().unwrap_unchecked() }
     }
 }
 
@@ -51,7 +95,11 @@ struct NumberedLine {
 }
 
 impl NumberedLine {
-    fn new(number: usize, line: String) -> NumberedLine {
+    fn new(number: usize, mut line: String) -> NumberedLine {
+        // A byte order mark would hide the comment token of the first line.
+        if number == 1 && line.starts_with(BYTE_ORDER_MARK) {
+            line.drain(..BYTE_ORDER_MARK.len_utf8());
+        }
         NumberedLine {
             number,
             value: StringRef::new(line),
@@ -73,13 +121,117 @@ impl DerefMut for NumberedLine {
     }
 }
 
+/// Parse the blocks of a file.
+///
+/// Blocks are yielded in the order their `then-change` appears, so nested
+/// blocks come before the blocks containing them. Problems are reported with
+/// `path` as given.
+pub fn parse_file(
+    path: impl AsRef<Path>,
+) -> io::Result<impl Iterator<Item = Result<Block, Vec<Diagnostic>>>> {
+    Parser::new(&path, &path)
+}
+
+/// Parse the blocks of `source`, reporting problems as found in `path`.
+///
+/// See [`parse_file`].
+pub fn parse_str(
+    path: impl AsRef<Path>,
+    source: &str,
+) -> impl Iterator<Item = Result<Block, Vec<Diagnostic>>> {
+    Parser::scan(path, io::Cursor::new(source.to_owned()))
+}
+
+/// Check whether `reader` may contain directives, without parsing it, e.g. to
+/// tell whether a file too large to parse has blocks.
+pub(crate) fn has_directives(mut reader: impl BufRead) -> io::Result<bool> {
+    // The end of the content read so far, in case the needle spans chunks.
+    let overlap = DIRECTIVE_NEEDLE.len() - 1;
+    let mut tail = Vec::with_capacity(2 * overlap);
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(false);
+        }
+        if memchr::memmem::find(chunk, DIRECTIVE_NEEDLE).is_some() {
+            return Ok(true);
+        }
+        tail.extend_from_slice(&chunk[..chunk.len().min(overlap)]);
+        if memchr::memmem::find(&tail, DIRECTIVE_NEEDLE).is_some() {
+            return Ok(true);
+        }
+        if chunk.len() >= overlap {
+            tail.clear();
+            tail.extend_from_slice(&chunk[chunk.len() - overlap..]);
+        } else {
+            tail.drain(..tail.len().saturating_sub(overlap));
+        }
+        let consumed = chunk.len();
+        reader.consume(consumed);
+    }
+}
+
+/// Where the lines of a file are read from.
+enum Source {
+    /// A reader, read line by line, and the bytes of the last line.
+    Reader(Box<dyn BufRead>, Vec<u8>),
+    /// The whole content of a file, e.g. memory-mapped, and the position of
+    /// the next line in it.
+    Bytes(Box<dyn AsRef<[u8]>>, usize),
+}
+
+impl Source {
+    /// Read the next line into `buffer`, without its line ending, replacing
+    /// invalid UTF-8. Returns whether bytes were replaced, or `None` at the
+    /// end.
+    fn read_line(&mut self, buffer: &mut String) -> io::Result<Option<bool>> {
+        let line = match self {
+            Source::Reader(reader, bytes) => {
+                bytes.clear();
+                if reader.read_until(b'\n', bytes)? == 0 {
+                    return Ok(None);
+                }
+                &bytes[..]
+            }
+            Source::Bytes(bytes, position) => {
+                let rest = &(**bytes).as_ref()[*position..];
+                if rest.is_empty() {
+                    return Ok(None);
+                }
+                let end = memchr::memchr(b'\n', rest).map_or(rest.len(), |end| end + 1);
+                *position += end;
+                &rest[..end]
+            }
+        };
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let decoded = String::from_utf8_lossy(line);
+        buffer.push_str(&decoded);
+        Ok(Some(matches!(decoded, Cow::Owned(_))))
+    }
+}
+
+/// The targets of a `then-change`, and the line and column it starts at.
+type ThenChange = (Vec<Pattern>, usize, usize);
+
 pub(super) struct Parser {
     path: PathBuf,
 
-    lines: io::Lines<io::BufReader<std::fs::File>>,
+    source: Source,
     line: NumberedLine,
+    /// Whether reading failed in a way that cannot be recovered from.
+    broken: bool,
+    /// Whether a line was decoded lossily, which is noted once per file.
+    lossy: bool,
+    /// Problems not preventing blocks from being parsed, e.g. the note of the
+    /// first line decoded lossily, until they are yielded.
+    notes: Vec<Diagnostic>,
+    /// The syntax tree of the file, if its language is known, to resolve the
+    /// scopes of blocks.
+    #[cfg(feature = "tree-sitter")]
+    syntax: Option<crate::syntax::Syntax>,
 
-    blocks: Vec<IfChangedBlock>,
+    blocks: Vec<Block>,
 }
 
 impl Parser {
@@ -87,31 +239,130 @@ impl Parser {
         relpath: impl AsRef<Path>,
         path: impl AsRef<Path>,
     ) -> Result<Parser, io::Error> {
-        Ok(Parser {
-            path: relpath.as_ref().to_owned(),
-            lines: io::BufReader::new(match fs::File::open(&path) {
-                Ok(file) => file,
-                Err(error) => return Err(error),
-            })
-            .lines(),
+        let file = fs::File::open(&path)?;
+        // SAFETY: The file may be modified while mapped, changing the bytes
+        // read. Like other tools scanning source trees, we accept wrong
+        // results in that case. Mapping fails for some files, e.g. empty files
+        // or directories, which are read instead.
+        #[cfg(any(unix, windows))]
+        if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+            return Ok(Self::from_bytes(relpath, Box::new(map)));
+        }
+        Ok(Self::scan(relpath, io::BufReader::new(file)))
+    }
+
+    /// Read a whole file before parsing it, skipping the line-by-line parsing
+    /// of files without any directive. Errors reading the file are reported
+    /// by the parser.
+    pub(super) fn scan(path: impl AsRef<Path>, mut reader: impl BufRead + 'static) -> Parser {
+        let mut bytes = Vec::new();
+        if reader.read_to_end(&mut bytes).is_err() {
+            return Self::from_reader(path, io::Cursor::new(bytes).chain(reader));
+        }
+        Self::from_bytes(path, Box::new(bytes))
+    }
+
+    /// Parse the whole content of a file, iterating over its lines in place.
+    fn from_bytes(path: impl AsRef<Path>, bytes: Box<dyn AsRef<[u8]>>) -> Parser {
+        let content = (*bytes).as_ref();
+        if memchr::memmem::find(content, DIRECTIVE_NEEDLE).is_some() {
+            #[cfg(feature = "tree-sitter")]
+            if let Some(syntax) = crate::syntax::Syntax::parse(path.as_ref(), content) {
+                let masked = syntax.mask(content);
+                let mut parser = Self::from_source(path, Source::Bytes(Box::new(masked), 0));
+                parser.syntax = Some(syntax);
+                return parser;
+            }
+            return Self::from_source(path, Source::Bytes(bytes, 0));
+        }
+        let mut lines = memchr::memchr_iter(b'\n', content).count();
+        if content.last().is_some_and(|byte| *byte != b'\n') {
+            lines += 1;
+        }
+        let mut parser = Self::from_source(path, Source::Bytes(Box::new([]), 0));
+        parser.line = NumberedLine::new(lines, String::default());
+        parser
+    }
+
+    pub(super) fn from_reader(path: impl AsRef<Path>, reader: impl BufRead + 'static) -> Parser {
+        Self::from_source(path, Source::Reader(Box::new(reader), Vec::new()))
+    }
+
+    fn from_source(path: impl AsRef<Path>, source: Source) -> Parser {
+        Parser {
+            path: path.as_ref().to_owned(),
+            source,
             line: NumberedLine::new(0, String::default()),
+            broken: false,
+            lossy: false,
+            notes: Vec::new(),
+            #[cfg(feature = "tree-sitter")]
+            syntax: None,
             blocks: Vec::new(),
-        })
+        }
     }
 
-    fn next_line(&mut self) -> Result<bool, Vec<String>> {
-        match self.lines.next() {
-            Some(result) => match result {
-                Ok(line) => {
-                    self.line = NumberedLine::new(self.line.number + 1, line);
-                    Ok(true)
+    /// Get the number of lines read so far, i.e. of the whole file once
+    /// parsing is done.
+    pub(super) fn lines_read(&self) -> usize {
+        self.line.number
+    }
+
+    fn next_line(&mut self) -> Result<bool, Vec<Diagnostic>> {
+        if self.broken {
+            return Ok(false);
+        }
+        // Reuse the allocation of the previous line.
+        let mut buffer = std::mem::take(&mut self.line.value.owner);
+        buffer.clear();
+        let result = self.source.read_line(&mut buffer);
+        let number = self.line.number + usize::from(matches!(result, Ok(Some(_))));
+        self.line = NumberedLine::new(number, buffer);
+        match result {
+            Ok(Some(lossy)) => {
+                if lossy && !self.lossy {
+                    self.lossy = true;
+                    self.notes.push(
+                        Diagnostic::new(
+                            Code::InvalidUtf8,
+                            &self.path,
+                            Some(number),
+                            format!(
+                                "Line {number} of {:?} is not valid UTF-8. Invalid bytes were replaced to find directives.",
+                                self.path
+                            ),
+                        )
+                        .with_severity(Severity::Note),
+                    );
                 }
-                Err(value) => Err(vec![format!("Failed to read {}: {:?}", value, self.path)]),
-            },
-            None => Ok(false),
+                Ok(true)
+            }
+            Ok(None) => Ok(false),
+            Err(value) => {
+                // Errors, e.g. reading a directory, would repeat forever.
+                self.broken = true;
+                Err(vec![Diagnostic::new(
+                    Code::Unreadable,
+                    &self.path,
+                    None,
+                    format!("Failed to read {}: {:?}", value, self.path),
+                )])
+            }
         }
     }
 
+    /// Get the lines of the function scoped by the `then-change` at `line`
+    /// and `column`.
+    #[cfg(feature = "tree-sitter")]
+    fn function_scope(&self, line: usize, column: usize) -> Option<(usize, usize)> {
+        self.syntax.as_ref()?.function_scope(line, column)
+    }
+
+    #[cfg(not(feature = "tree-sitter"))]
+    fn function_scope(&self, _line: usize, _column: usize) -> Option<(usize, usize)> {
+        None
+    }
+
     fn skip_comments(&mut self) {
         self.skip_whitespaces();
         self.line
@@ -136,53 +387,126 @@ impl Parser {
             .is_some()
     }
 
-    fn parse_if_changed(&mut self) -> Result<Option<Option<String>>, Vec<String>> {
+    /// Parse an `if-changed` into the block it opens, without its end.
+    fn parse_if_changed(&mut self) -> Result<Option<Block>, Vec<Diagnostic>> {
         self.skip_comments();
+        self.skip_whitespaces();
+        let prefix = self.line.consumed().to_owned();
         Ok(if self.skip_whitespaces_and_eat("if-changed") {
-            Some(self.parse_if_changed_name()?)
+            let (name, scoped) = self.parse_if_changed_options()?;
+            let rest = self.line.trim_start();
+            let suffix = COMMENT_END_TOKENS
+                .into_iter()
+                .find(|token| rest.starts_with(token))
+                .map_or_else(String::new, |token| format!(" {token}"));
+            Some(Block {
+                name,
+                range: (self.line.number, 0),
+                // Scopes are resolved at the `then-change`, once the comment
+                // of the block is complete.
+                scope: scoped.then_some((0, 0)),
+                targets: Vec::new(),
+                ignored: Vec::new(),
+                comment: Comment { prefix, suffix },
+            })
         } else {
             None
         })
     }
 
-    fn parse_if_changed_name(&mut self) -> Result<Option<String>, Vec<String>> {
+    /// Parse the name of an `if-changed`, and whether its block is scoped to
+    /// a function, as in `if-changed(name, scope=function)`.
+    fn parse_if_changed_options(&mut self) -> Result<(Option<String>, bool), Vec<Diagnostic>> {
         if !self.skip_whitespaces_and_eat("(") {
-            return Ok(None);
+            return Ok((None, false));
         }
         let end = match self.line.find(')') {
             Some(end) => end,
             None => {
-                return Err(vec![format!(
-                    "Could not find ')' for \"if-changed\" at line {} for {:?}.",
-                    self.line.number, self.path
+                return Err(vec![Diagnostic::new(
+                    Code::UnclosedParenthesis,
+                    &self.path,
+                    Some(self.line.number),
+                    format!(
+                        "Could not find ')' for \"if-changed\" at line {} for {:?}.",
+                        self.line.number, self.path
+                    ),
                 )])
             }
         };
-        let id = self.line[..end].trim().to_string();
+        let mut name = Vec::new();
+        let mut scoped = false;
+        for part in self.line[..end].split(',') {
+            let scope = part
+                .trim()
+                .strip_prefix("scope")
+                .and_then(|rest| rest.trim_start().strip_prefix('='))
+                .map(str::trim);
+            match scope {
+                Some("function") => scoped = true,
+                Some(scope) => {
+                    return Err(vec![Diagnostic::new(
+                        Code::UnresolvedScope,
+                        &self.path,
+                        Some(self.line.number),
+                        format!(
+                            "Unknown scope `{scope}` for \"if-changed\" at line {} for {:?}. The only scope is `function`.",
+                            self.line.number, self.path
+                        ),
+                    )])
+                }
+                None => name.push(part),
+            }
+        }
+        let name = name.join(",").trim().to_string();
         self.line.map(|line| &line[end + 1..]);
-        Ok(Some(id))
+        Ok(((!scoped || !name.is_empty()).then_some(name), scoped))
     }
 
-    fn parse_then_change(&mut self) -> Result<Option<(Vec<Pattern>, usize)>, Vec<String>> {
+    /// Parse an inline `ignore-if-changed: <paths> -- <reason>` suppression.
+    fn parse_ignore(&mut self) -> Option<Vec<PathBuf>> {
+        if !self.skip_whitespaces_and_eat(IGNORE_TOKEN) || !self.skip_whitespaces_and_eat(":") {
+            return None;
+        }
+        let value: &str = &self.line;
+        let value = value.split_once("--").map_or(value, |(value, _)| value);
+        let paths = value
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        // The reason may contain anything, including "then-change".
+        self.line.map(|line| &line[line.len()..]);
+        Some(paths)
+    }
+
+    fn parse_then_change(&mut self) -> Result<Option<ThenChange>, Vec<Diagnostic>> {
         Ok(if self.find_and_eat("then-change") {
+            let column = self.line.consumed().len() - "then-change".len();
             // Note we grab the line number before parsing the paths. This is
             // important as changes in file references shouldn't require
             // changing existing file references. This only matters if the
             // file references are multiline.
             let line = self.line.number;
             let specs = self.parse_then_change_paths()?;
-            Some((specs, line))
+            Some((specs, line, column))
         } else {
             None
         })
     }
 
-    fn parse_then_change_paths(&mut self) -> Result<Vec<Pattern>, Vec<String>> {
+    fn parse_then_change_paths(&mut self) -> Result<Vec<Pattern>, Vec<Diagnostic>> {
         let then_change_line = self.line.number;
         if !self.skip_whitespaces_and_eat("(") {
-            return Err(vec![format!(
-                "Could not find '(' for \"then-change\" at line {then_change_line} for {:?}.",
-                self.path
+            return Err(vec![Diagnostic::new(
+                Code::MissingPaths,
+                &self.path,
+                Some(then_change_line),
+                format!(
+                    "Could not find '(' for \"then-change\" at line {then_change_line} for {:?}.",
+                    self.path
+                ),
             )]);
         }
 
@@ -198,9 +522,14 @@ impl Parser {
                 self.line.is_empty()
             } {
                 if !self.next_line()? {
-                    return Err(vec![format!(
-                        "Could not find ')' for \"then-change\" at line {then_change_line} for {:?}.",
-                        self.path
+                    return Err(vec![Diagnostic::new(
+                        Code::UnclosedParenthesis,
+                        &self.path,
+                        Some(then_change_line),
+                        format!(
+                            "Could not find ')' for \"then-change\" at line {then_change_line} for {:?}.",
+                            self.path
+                        ),
                     )]);
                 }
                 self.skip_comments();
@@ -210,7 +539,15 @@ impl Parser {
             if pattern_line == 0 {
                 pattern_line = self.line.number;
             }
-            match self.line.find('\\') {
+            // A `\` ending a line continues the path on the next one, while a
+            // `\` followed by more of the path separates directories.
+            let continuation = self.line.match_indices('\\').find_map(|(index, _)| {
+                let rest = &self.line[index + 1..];
+                (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(index)
+            });
+            let delimiter = self.line.find([',', ')']);
+            match continuation.filter(|index| delimiter.is_none_or(|delimiter| index < &delimiter))
+            {
                 Some(index) => {
                     pattern_buffer.push_str(self.line[..index].trim());
                     self.line.map(|line| &line[index + 1..]);
@@ -245,18 +582,37 @@ impl Parser {
                         if right_paren_found {
                             break;
                         }
-                        return Err(vec![format!(
-                            "Unexpected empty path at line {pattern_line} for \"then-change\" at line {then_change_line} for {:?}.",
-                            self.path
+                        return Err(vec![Diagnostic::new(
+                            Code::EmptyPath,
+                            &self.path,
+                            Some(pattern_line),
+                            format!(
+                                "Unexpected empty path at line {pattern_line} for \"then-change\" at line {then_change_line} for {:?}.",
+                                self.path
+                            ),
                         )]);
                     }
                     (pattern_buffer.clone(), None)
                 }
             };
 
+            if pattern.contains('\\') {
+                self.notes.push(
+                    Diagnostic::new(
+                        Code::BackslashSeparator,
+                        &self.path,
+                        Some(pattern_line),
+                        format!(
+                            "Path `{pattern}` at line {pattern_line} for {:?} separates directories with `\\`, which only works on Windows. Use `/` instead.",
+                            self.path
+                        ),
+                    )
+                    .with_severity(Severity::Warning),
+                );
+            }
             related_paths.push(Pattern {
                 name,
-                value: PathBuf::from_str(&pattern).unwrap(),
+                path: PathBuf::from_str(&pattern).unwrap(),
                 line: pattern_line,
             });
             if right_paren_found {
@@ -271,32 +627,41 @@ impl Parser {
 }
 
 impl Iterator for Parser {
-    type Item = Result<IfChangedBlock, Vec<String>>;
+    type Item = Result<Block, Vec<Diagnostic>>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if !self.notes.is_empty() {
+            return Some(Err(std::mem::take(&mut self.notes)));
+        }
         while match self.next_line() {
             Ok(value) => value,
             Err(error) => return Some(Err(error)),
         } {
-            if let Some(name) = match self.parse_if_changed() {
-                Ok(name) => name,
+            match self.parse_if_changed() {
+                Ok(Some(block)) => self.blocks.push(block),
+                Ok(None) => {}
                 Err(error) => return Some(Err(error)),
-            } {
-                self.blocks.push(IfChangedBlock {
-                    name,
-                    range: (self.line.number, 0),
-                    patterns: Vec::new(),
-                });
             }
 
-            if let Some((paths, end)) = match self.parse_then_change() {
+            if let Some(ignored) = self.parse_ignore() {
+                if let Some(block) = self.blocks.last_mut() {
+                    block.ignored.extend(ignored);
+                }
+            }
+
+            if let Some((paths, end, column)) = match self.parse_then_change() {
                 Ok(info) => info,
                 Err(error) => {
                     let mut errors = Vec::new();
                     if self.blocks.pop().is_none() {
-                        errors.push(format!(
-                            "Missing \"if-changed\" for \"then-change\" at line {} for {:?}.",
-                            self.line.number, self.path
+                        errors.push(Diagnostic::new(
+                            Code::UnopenedBlock,
+                            &self.path,
+                            Some(self.line.number),
+                            format!(
+                                "Missing \"if-changed\" for \"then-change\" at line {} for {:?}.",
+                                self.line.number, self.path
+                            ),
                         ));
                     }
                     errors.extend(error);
@@ -306,19 +671,42 @@ impl Iterator for Parser {
                 let mut block = match self.blocks.pop() {
                     Some(block) => block,
                     None => {
-                        return Some(Err(vec![format!(
-                            "Missing \"if-changed\" for \"then-change\" at line {} for {:?}.",
-                            end, self.path
+                        return Some(Err(vec![Diagnostic::new(
+                            Code::UnopenedBlock,
+                            &self.path,
+                            Some(end),
+                            format!(
+                                "Missing \"if-changed\" for \"then-change\" at line {} for {:?}.",
+                                end, self.path
+                            ),
                         )]))
                     }
                 };
 
                 block.range.1 = end;
-                block.patterns = paths;
+                block.targets = paths;
+                if block.scope.is_some() {
+                    block.scope = self.function_scope(end, column);
+                    if block.scope.is_none() {
+                        return Some(Err(vec![Diagnostic::new(
+                            Code::UnresolvedScope,
+                            &self.path,
+                            Some(block.range.0),
+                            format!(
+                                "Could not find the function of \"if-changed\" at line {} for {:?}. Functions are only found in the languages known with the `tree-sitter` feature.",
+                                block.range.0, self.path
+                            ),
+                        )]));
+                    }
+                }
+                tracing::trace!(name = block.name, range = ?block.range, "parsed block");
 
                 return Some(Ok(block));
             }
         }
+        if !self.notes.is_empty() {
+            return Some(Err(std::mem::take(&mut self.notes)));
+        }
         if self.blocks.is_empty() {
             return None;
         }
@@ -327,10 +715,25 @@ impl Iterator for Parser {
             .into_iter()
             .filter(|block| block.range.1 == 0)
             .map(|block| {
-                format!(
-                    "Missing \"then-changed\" for \"if-changed\" at line {} for {:?}.",
-                    block.range.0, self.path
+                let directive = block.comment.wrap("then-change()");
+                Diagnostic::new(
+                    Code::UnterminatedBlock,
+                    &self.path,
+                    Some(block.range.0),
+                    format!(
+                        "Missing \"then-changed\" for \"if-changed\" at line {} for {:?}.",
+                        block.range.0, self.path
+                    ),
                 )
+                .with_suggestion(Suggestion {
+                    message: format!(
+                        "add `{}` after the end of the block, listing the files to change with it",
+                        directive.trim_start()
+                    ),
+                    path: self.path.clone(),
+                    line: self.line.number + 1,
+                    text: format!("{directive}\n"),
+                })
             })
             .collect()))
     }
@@ -338,11 +741,12 @@ impl Iterator for Parser {
 
 #[cfg(test)]
 mod tests {
-    use std::io::Write;
+    use std::io::{self, Write};
 
     use tempfile::NamedTempFile;
 
     use super::Parser;
+    use crate::Code;
 
     macro_rules! parser_test {
         ($name:ident, $value:expr, @$exp:literal) => {
@@ -359,6 +763,221 @@ mod tests {
 
     parser_test!(it_parses_empty_files, "", @r###"{"Ok": []}"###);
 
+    #[cfg(unix)]
+    #[test]
+    fn it_stops_on_unreadable_files() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let results = Parser::new("dir", tempdir.path())
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn it_finds_directives_across_chunks() {
+        let source = "a\n// then-change(b.ts)\n";
+        for capacity in 1..source.len() {
+            let reader = io::BufReader::with_capacity(capacity, source.as_bytes());
+            assert!(super::has_directives(reader).unwrap(), "{capacity}");
+        }
+        assert!(!super::has_directives("a\nchange\n".as_bytes()).unwrap());
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn it_parses_only_comments_of_known_languages() {
+        let source = "let a = \"\n// if-changed\n\";\n// then-change(b.rs)\n";
+        let errors = |path| {
+            crate::parse_str(path, source)
+                .filter(Result::is_err)
+                .count()
+        };
+        // The `if-changed` in the string only counts without a grammar.
+        assert_eq!(errors("a.rs"), 1);
+        assert_eq!(errors("a.txt"), 0);
+    }
+
+    #[test]
+    fn it_skips_files_without_directives() {
+        let mut parser = Parser::scan("a.ts", io::Cursor::new("a\nb\nc"));
+        assert!(parser.next().is_none());
+        assert_eq!(parser.lines_read(), 3);
+
+        let mut parser = Parser::scan("a.ts", io::Cursor::new("a\n// then-change(b.ts)\n"));
+        assert_eq!(
+            parser.next().unwrap().unwrap_err()[0].code,
+            Code::UnopenedBlock
+        );
+
+        // Invalid UTF-8 isn't noted without directives.
+        let mut parser = Parser::scan("a.ts", io::Cursor::new(b"a\n\xff\n"));
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn it_decodes_invalid_utf8_lossily() {
+        let source = b"// caf\xe9\n// if-changed(\xe9)\nb\n// then-change(c.ts)\n\xff\n";
+        let results = |parser: Parser| {
+            parser
+                .map(|result| {
+                    result
+                        .map(|block| (block.name, block.range))
+                        .map_err(|error| (error[0].severity, error[0].line))
+                })
+                .collect::<Vec<_>>()
+        };
+        let read = results(Parser::from_reader("a.ts", io::Cursor::new(source)));
+        assert_eq!(results(Parser::scan("a.ts", io::Cursor::new(source))), read);
+        insta::assert_debug_snapshot!(read, @r###"
+            [
+                Ok(
+                    (
+                        Some(
+                            "�",
+                        ),
+                        (
+                            2,
+                            4,
+                        ),
+                    ),
+                ),
+                Err(
+                    (
+                        Note,
+                        Some(
+                            1,
+                        ),
+                    ),
+                ),
+            ]
+            "###);
+    }
+
+    #[test]
+    fn it_reads_mapped_files() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "a\r\n// if-changed\r\nb\r\n// then-change(c.ts)").unwrap();
+        let mut parser = Parser::new(file.path(), file.path()).unwrap();
+        let block = parser.next().unwrap().unwrap();
+        assert_eq!(block.range, (2, 4));
+        assert_eq!(block.targets[0].path.to_str(), Some("c.ts"));
+        assert!(parser.next().is_none());
+        assert_eq!(parser.lines_read(), 4);
+    }
+
+    #[test]
+    fn it_warns_about_backslash_separators() {
+        let results = super::parse_str(
+            "a.ts",
+            "// if-changed\n// then-change(gen\\types.ts, very/ \\\n//   far.ts)\n",
+        )
+        .map(|result| {
+            result
+                .map(|block| block.targets)
+                .map_err(|error| (error[0].code, error[0].severity, error[0].line))
+        })
+        .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(results, @r###"
+        [
+            Ok(
+                [
+                    Pattern {
+                        name: None,
+                        path: "gen\\types.ts",
+                        line: 2,
+                    },
+                    Pattern {
+                        name: None,
+                        path: "very/far.ts",
+                        line: 2,
+                    },
+                ],
+            ),
+            Err(
+                (
+                    BackslashSeparator,
+                    Warning,
+                    Some(
+                        2,
+                    ),
+                ),
+            ),
+        ]
+        "###);
+    }
+
+    #[test]
+    fn it_parses_strings() {
+        let results = super::parse_str(
+            "a.ts",
+            "// if-changed(a)\n// then-change(b.ts:b)\n// then-change\n",
+        )
+        .collect::<Vec<_>>();
+        insta::assert_compact_json_snapshot!(results, @r###"
+        [
+          {
+            "Ok": {
+              "name": "a",
+              "range": [
+                1,
+                2
+              ],
+              "targets": [
+                {
+                  "name": "b",
+                  "path": "b.ts",
+                  "line": 2
+                }
+              ]
+            }
+          },
+          {
+            "Err": [
+              {
+                "code": "IC102",
+                "path": "a.ts",
+                "line": 3,
+                "range": null,
+                "target": null,
+                "message": "Missing \"if-changed\" for \"then-change\" at line 3 for \"a.ts\"."
+              },
+              {
+                "code": "IC104",
+                "path": "a.ts",
+                "line": 3,
+                "range": null,
+                "target": null,
+                "message": "Could not find '(' for \"then-change\" at line 3 for \"a.ts\"."
+              }
+            ]
+          }
+        ]
+        "###);
+    }
+
+    #[test]
+    fn it_suggests_then_change_in_the_same_comment_style() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "<ul>\n  <!-- if-changed(list) -->\n  <li></li>\n</ul>\n"
+        )
+        .unwrap();
+        let errors = Parser::new("a.html", file.path())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_err();
+        insta::assert_json_snapshot!(errors[0].suggestion, @r###"
+        {
+          "message": "add `<!-- then-change() -->` after the end of the block, listing the files to change with it",
+          "path": "a.html",
+          "line": 5,
+          "text": "  <!-- then-change() -->\n"
+        }
+        "###);
+    }
+
     parser_test!(
         it_parses,
         "
@@ -378,10 +997,10 @@ mod tests {
             2,
             4
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 4
             }
           ]
@@ -392,10 +1011,10 @@ mod tests {
             6,
             8
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 8
             }
           ]
@@ -424,10 +1043,10 @@ mod tests {
             2,
             4
           ],
-          "patterns": [
+          "targets": [
             {
               "name": "b",
-              "value": "",
+              "path": "",
               "line": 4
             }
           ]
@@ -438,10 +1057,10 @@ mod tests {
             6,
             8
           ],
-          "patterns": [
+          "targets": [
             {
               "name": "a",
-              "value": "",
+              "path": "",
               "line": 8
             }
           ]
@@ -451,9 +1070,81 @@ mod tests {
     "###
     );
 
+    parser_test!(
+        it_parses_crlf_line_endings,
+        "// if-changed(a)\r\nA\r\n// then-change(\r\n//   b.ts\r\n// )\r\n// if-changed then-change(c.ts)\r", @r###"
+    {
+      "Ok": [
+        {
+          "name": "a",
+          "range": [
+            1,
+            3
+          ],
+          "targets": [
+            {
+              "name": null,
+              "path": "b.ts",
+              "line": 4
+            }
+          ]
+        },
+        {
+          "name": null,
+          "range": [
+            6,
+            6
+          ],
+          "targets": [
+            {
+              "name": null,
+              "path": "c.ts",
+              "line": 6
+            }
+          ]
+        }
+      ]
+    }
+    "###
+    );
+
+    parser_test!(
+        it_parses_files_starting_with_a_byte_order_mark,
+        "\u{feff}// if-changed\nA\n// then-change(b.ts)", @r###"{"Ok": [{"name": null, "range": [1, 3], "targets": [{"name": null, "path": "b.ts", "line": 3}]}]}"###
+    );
+
+    #[test]
+    fn it_reports_unresolved_scopes() {
+        let error = |source| {
+            let error = super::parse_str("a.txt", source)
+                .next()
+                .unwrap()
+                .unwrap_err();
+            (error[0].code, error[0].line)
+        };
+        assert_eq!(
+            error("// if-changed(a, scope=file)\n// then-change(b.ts)\n"),
+            (Code::UnresolvedScope, Some(1))
+        );
+        // Functions are only found in the languages known with a grammar.
+        assert_eq!(
+            error("// if-changed(scope=function)\n// then-change(b.ts)\nfunction a() {}\n"),
+            (Code::UnresolvedScope, Some(1))
+        );
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn it_parses_function_scopes() {
+        let source = "// if-changed(a, scope=function)\n// then-change(b.rs)\nfn a() {\n}\n";
+        let block = crate::parse_str("a.rs", source).next().unwrap().unwrap();
+        assert_eq!(block.name.as_deref(), Some("a"));
+        assert_eq!((block.range, block.scope), ((1, 2), Some((3, 4))));
+    }
+
     parser_test!(
         it_parses_inline_blocks,
-        "// if-changed this is a test then-change(foo.rs)", @r###"{"Ok": [{"name": null, "range": [1, 1], "patterns": [{"name": null, "value": "foo.rs", "line": 1}]}]}"###
+        "// if-changed this is a test then-change(foo.rs)", @r###"{"Ok": [{"name": null, "range": [1, 1], "targets": [{"name": null, "path": "foo.rs", "line": 1}]}]}"###
     );
 
     parser_test!(
@@ -475,15 +1166,15 @@ mod tests {
             2,
             4
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 4
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 4
             }
           ]
@@ -494,20 +1185,20 @@ mod tests {
             6,
             8
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 8
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 8
             },
             {
               "name": null,
-              "value": "baz.rs",
+              "path": "baz.rs",
               "line": 8
             }
           ]
@@ -559,15 +1250,15 @@ mod tests {
             2,
             4
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 5
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 6
             }
           ]
@@ -578,15 +1269,15 @@ mod tests {
             9,
             11
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 11
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 12
             }
           ]
@@ -597,15 +1288,15 @@ mod tests {
             15,
             17
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 17
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 18
             }
           ]
@@ -616,15 +1307,15 @@ mod tests {
             20,
             22
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 22
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 23
             }
           ]
@@ -635,15 +1326,15 @@ mod tests {
             26,
             28
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 29
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 30
             }
           ]
@@ -673,15 +1364,15 @@ mod tests {
             2,
             5
           ],
-          "patterns": [
+          "targets": [
             {
               "name": null,
-              "value": "foo.rs",
+              "path": "foo.rs",
               "line": 6
             },
             {
               "name": null,
-              "value": "bar.rs",
+              "path": "bar.rs",
               "line": 7
             }
           ]
@@ -690,4 +1381,48 @@ mod tests {
     }
     "###
     );
+
+    parser_test!(
+        it_parses_inline_ignores,
+        "
+            // if-changed
+            // ignore-if-changed: foo.rs, /bar.rs -- then-change is not needed
+            /* ignore-if-changed: baz.rs -- */
+            // then-change(foo.rs, bar.rs, baz.rs)
+        ", @r###"
+    {
+      "Ok": [
+        {
+          "name": null,
+          "range": [
+            2,
+            5
+          ],
+          "targets": [
+            {
+              "name": null,
+              "path": "foo.rs",
+              "line": 5
+            },
+            {
+              "name": null,
+              "path": "bar.rs",
+              "line": 5
+            },
+            {
+              "name": null,
+              "path": "baz.rs",
+              "line": 5
+            }
+          ],
+          "ignored": [
+            "foo.rs",
+            "/bar.rs",
+            "baz.rs"
+          ]
+        }
+      ]
+    }
+    "###
+    );
 }
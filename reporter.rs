@@ -0,0 +1,76 @@
+use std::{fmt, io};
+
+use crate::{Diagnostic, Severity};
+
+/// Counts of the problems reported in a run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// Number of problems failing the check.
+    pub errors: usize,
+    /// Number of problems that don't fail the check.
+    pub warnings: usize,
+}
+
+impl Summary {
+    /// Count a reported problem.
+    pub fn record(&mut self, diagnostic: &Diagnostic) {
+        match diagnostic.severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Note => {}
+        }
+    }
+
+    /// Whether the check passed, i.e. no errors were reported.
+    pub fn is_success(&self) -> bool {
+        self.errors == 0
+    }
+}
+
+/// Describes the counts, e.g. `2 errors and 1 warning` or `no problems`.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count, noun| match count {
+            1 => format!("1 {noun}"),
+            _ => format!("{count} {noun}s"),
+        };
+        match (self.errors, self.warnings) {
+            (0, 0) => f.write_str("no problems"),
+            (errors, 0) => f.write_str(&plural(errors, "error")),
+            (0, warnings) => f.write_str(&plural(warnings, "warning")),
+            (errors, warnings) => write!(
+                f,
+                "{} and {}",
+                plural(errors, "error"),
+                plural(warnings, "warning")
+            ),
+        }
+    }
+}
+
+/// A sink for the problems found in a run, e.g. printing them or filing them
+/// in a bug tracker.
+pub trait Reporter {
+    /// Report a problem, with paths relative to the repository root.
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()>;
+
+    /// Finish reporting, e.g. writing a footer, and summarize the problems
+    /// reported.
+    fn finish(&mut self) -> io::Result<Summary>;
+}
+
+/// Collects reported problems, e.g. for inspecting them after a run.
+impl Reporter for Vec<Diagnostic> {
+    fn report(&mut self, diagnostic: &Diagnostic) -> io::Result<()> {
+        self.push(diagnostic.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<Summary> {
+        let mut summary = Summary::default();
+        for diagnostic in self.iter() {
+            summary.record(diagnostic);
+        }
+        Ok(summary)
+    }
+}
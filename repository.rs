@@ -0,0 +1,375 @@
+use std::{collections::BTreeMap, io, path::PathBuf, sync::Arc, time::Instant};
+
+use crate::{
+    checker::{Couplings, ParseCache},
+    Checker, Code, Config, ConfigTree, DeletedTargetPolicy, Diagnostic, DiffFilter, Engine as _,
+    GitEngine, Severity, Stats, Summary, TrailerConfig,
+};
+
+/// What [`check_repository`] checks and how.
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    /// A directory inside the repository to check.
+    pub repository: PathBuf,
+    /// The revision to compare against. By default, HEAD is used.
+    pub from_ref: Option<String>,
+    /// The revision to compare with. By default, the working tree is used.
+    pub to_ref: Option<String>,
+    /// Git patterns of the files to check, relative to the repository root.
+    /// By default, all changed files are checked.
+    pub patterns: Vec<String>,
+    /// How ignore trailers are recognized. By default, as configured in the
+    /// repository's `.if-changed.toml`.
+    pub trailers: Option<TrailerConfig>,
+    /// Whether untracked files of the working tree count as changed.
+    pub include_untracked: bool,
+    /// Whether to pair deleted and added files into renames.
+    pub detect_renames: bool,
+    /// Whether files whose mode changed count as changed even if their
+    /// content didn't.
+    pub include_mode_changes: bool,
+    /// Which kinds of changed files count as changed. By default, all do.
+    pub diff_filter: DiffFilter,
+    /// Whether to compare against the merge base of `from_ref` and `to_ref`,
+    /// like `git diff from_ref...to_ref`.
+    pub merge_base: bool,
+    /// The number of unchanged lines around changes grouped into hunks. By
+    /// default, 3 lines are used.
+    pub diff_context_lines: Option<u32>,
+    /// Severities of kinds of problems other than [`Severity::Error`].
+    pub severities: BTreeMap<Code, Severity>,
+    /// Whether deleting a target satisfies a modified block. By default, as
+    /// configured in the repository's `.if-changed.toml`.
+    pub deleted_targets: Option<DeletedTargetPolicy>,
+    /// Skip files larger than this many bytes instead of parsing them. By
+    /// default, files of any size are parsed.
+    pub max_file_size: Option<u64>,
+    /// Whether to also check the blocks removed from files, e.g. with the
+    /// files deleted.
+    pub removed_blocks: bool,
+    /// Whether to report the targets removed from blocks, unless they
+    /// changed too or the removal was acknowledged.
+    pub removed_targets: bool,
+    /// The number of lines around blocks whose changes count as changes of
+    /// the blocks. By default, only changes within blocks count.
+    pub block_context_lines: usize,
+    /// Whether changes of the `if-changed` and `then-change` lines of blocks
+    /// leave them unmodified.
+    pub ignore_directive_changes: bool,
+    /// Whether blocks with the same content as a block of the original
+    /// content of their file, e.g. moved ones, are unmodified.
+    pub compare_content: bool,
+    /// Whether to report who last edited the targets left unmodified, with
+    /// `git blame`.
+    pub blame_targets: bool,
+    /// Whether to check files on rayon's thread pool. Requires the `rayon`
+    /// feature.
+    pub parallel: bool,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            repository: PathBuf::from("."),
+            from_ref: None,
+            to_ref: None,
+            patterns: Vec::new(),
+            trailers: None,
+            include_untracked: true,
+            detect_renames: false,
+            include_mode_changes: false,
+            diff_filter: DiffFilter::default(),
+            merge_base: false,
+            diff_context_lines: None,
+            severities: BTreeMap::new(),
+            deleted_targets: None,
+            max_file_size: None,
+            removed_blocks: false,
+            removed_targets: false,
+            block_context_lines: 0,
+            ignore_directive_changes: false,
+            compare_content: false,
+            blame_targets: false,
+            parallel: false,
+        }
+    }
+}
+
+/// The results of [`check_repository`].
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// The files checked, in the order they were matched, with their problems.
+    pub files: Vec<FileReport>,
+    /// Problems found outside of any checked file, e.g. in commit trailers.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Statistics of the run.
+    pub stats: Stats,
+}
+
+/// The problems found in a checked file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileReport {
+    /// The file, relative to the repository root.
+    pub path: PathBuf,
+    /// The problems found in the file, sorted by line and code.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// Iterate over all problems, starting with the ones found outside of
+    /// files.
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .chain(self.files.iter().flat_map(|file| &file.diagnostics))
+    }
+
+    /// Count the errors and warnings of the run.
+    pub fn summary(&self) -> Summary {
+        let mut summary = Summary::default();
+        for diagnostic in self.iter() {
+            summary.record(diagnostic);
+        }
+        summary
+    }
+}
+
+/// Check a repository for dependent changes, as the `if-changed` command does.
+///
+/// Opens the repository containing `options.repository`, reads its
+/// configuration, e.g. message templates, and checks each changed file matching `options.patterns`
+/// that is not ignored.
+///
+/// ```no_run
+/// let report = if_changed::check_repository(&if_changed::CheckOptions {
+///     from_ref: Some("main".into()),
+///     ..Default::default()
+/// })?;
+/// assert!(report.summary().is_success());
+/// # std::io::Result::Ok(())
+/// ```
+pub fn check_repository(options: &CheckOptions) -> io::Result<Report> {
+    let repository = git2::Repository::discover(&options.repository).map_err(io::Error::other)?;
+    let mut config = Config::discover(repository.workdir().unwrap_or(repository.path()))?;
+    config.merge_git_config(&repository.config().map_err(io::Error::other)?)?;
+    let trailers = options
+        .trailers
+        .clone()
+        .unwrap_or_else(|| config.ignore_trailer.clone());
+    let engine = build_engine(&repository, options, &trailers, &config.generated)?;
+    let mut configs = ConfigTree::new(repository.workdir().unwrap_or(repository.path()), &config);
+
+    let mut report = Report::default();
+    for mut diagnostic in engine.diagnostics() {
+        configure(&configs, options, &mut diagnostic);
+        report.diagnostics.push(diagnostic);
+    }
+
+    let start = Instant::now();
+    let mut paths = Vec::new();
+    for path in engine.matches(&options.patterns).flat_map(Result::ok) {
+        if engine.is_ignored(&path) {
+            report.stats.ignored_files += 1;
+        } else if engine.is_binary(&path) {
+            report.stats.binary_files += 1;
+        } else {
+            paths.push(path);
+        }
+    }
+    report.stats.match_time += start.elapsed();
+    for path in &paths {
+        report.diagnostics.extend(configs.load(path));
+    }
+
+    let cache = Arc::<ParseCache>::default();
+    let check = |path| check_file(&engine, options, &config, &configs, &cache, path);
+    #[cfg(any(test, feature = "rayon"))]
+    let files = if options.parallel {
+        use rayon::prelude::*;
+
+        // The engine and its diff are shared by all threads.
+        paths.into_par_iter().map(check).collect::<Vec<_>>()
+    } else {
+        paths.into_iter().map(check).collect()
+    };
+    #[cfg(not(any(test, feature = "rayon")))]
+    let files = paths.into_iter().map(check).collect::<Vec<_>>();
+    for (file, stats) in files {
+        report.files.push(file);
+        report.stats += &stats;
+    }
+    drop_reported_couplings(&mut report);
+    sort(&mut report);
+    Ok(report)
+}
+
+/// Create the engine comparing the revisions given in `options`.
+fn build_engine<'repo>(
+    repository: &'repo git2::Repository,
+    options: &CheckOptions,
+    trailers: &TrailerConfig,
+    generated: &BTreeMap<String, String>,
+) -> io::Result<GitEngine<'repo>> {
+    let mut builder = GitEngine::builder(repository)
+        .trailers(trailers.clone())
+        .include_untracked(options.include_untracked)
+        .detect_renames(options.detect_renames)
+        .include_mode_changes(options.include_mode_changes)
+        .diff_filter(options.diff_filter)
+        .merge_base(options.merge_base)
+        .narrow_to(&options.patterns)
+        .narrow_generated(generated);
+    if let Some(from_ref) = &options.from_ref {
+        builder = builder.from_ref(from_ref);
+    }
+    if let Some(to_ref) = &options.to_ref {
+        builder = builder.to_ref(to_ref);
+    }
+    if let Some(context_lines) = options.diff_context_lines {
+        builder = builder.context_lines(context_lines);
+    }
+    builder.build()
+}
+
+/// Check `path` on its own, with the statistics of the check.
+fn check_file(
+    engine: &GitEngine,
+    options: &CheckOptions,
+    config: &Config,
+    configs: &ConfigTree,
+    cache: &Arc<ParseCache>,
+    path: PathBuf,
+) -> (FileReport, Stats) {
+    let checker = Checker::builder(engine)
+        .path(&path)
+        .cache(cache.clone())
+        .max_file_size(options.max_file_size)
+        .deleted_targets(options.deleted_targets.unwrap_or(config.targets.deleted))
+        .removed_blocks(options.removed_blocks)
+        .removed_targets(options.removed_targets)
+        .block_context_lines(options.block_context_lines)
+        .ignore_directive_changes(options.ignore_directive_changes)
+        .compare_content(options.compare_content)
+        .blame_targets(options.blame_targets)
+        .normalize_separators(config.targets.normalize_separators)
+        .require_excluded_targets(config.targets.require_excluded)
+        .scan(&config.scan)
+        .require_blocks(&config.require_blocks)
+        .generated(&config.generated)
+        .build();
+    let mut iter = checker.check_iter();
+    let diagnostics = iter
+        .by_ref()
+        .map(|mut diagnostic| {
+            configure(configs, options, &mut diagnostic);
+            diagnostic
+        })
+        .collect();
+    (FileReport { path, diagnostics }, iter.stats().clone())
+}
+
+/// Set the severity and message of `diagnostic` as configured for its file,
+/// unless `options` override its severity.
+fn configure(configs: &ConfigTree, options: &CheckOptions, diagnostic: &mut Diagnostic) {
+    configs.apply(diagnostic);
+    if let Some(severity) = options.severities.get(&diagnostic.code) {
+        diagnostic.severity = *severity;
+    }
+}
+
+/// Drop the problems of couplings already reported from their other end, as
+/// each file is checked on its own.
+fn drop_reported_couplings(report: &mut Report) {
+    let mut couplings = Couplings::default();
+    for file in &mut report.files {
+        file.diagnostics
+            .retain(|diagnostic| !couplings.is_reported(diagnostic));
+    }
+}
+
+/// Sort the problems of each file by line and code, as the `if-changed`
+/// command reports them.
+fn sort(report: &mut Report) {
+    for file in &mut report.files {
+        file.diagnostics
+            .sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::testing::git_test;
+
+    #[test]
+    fn test_check_repository() {
+        let (tempdir, _repo) = git_test! {
+            "Initial commit": [
+                ".if-changed.toml" => indoc! {r#"
+                    [ignore-trailer]
+                    keys = ["skip"]
+
+                    [messages]
+                    template = "{message} ({code})"
+                "#}
+            ]
+            working: [
+                "a.ts" => indoc! {"
+                    // if-changed
+                    A,
+                    // then-change(b.ts)
+                "},
+                "b.ts" => indoc! {"
+                    // if-changed(b)
+                    B,
+                    // then-change(c.ts:c)
+                "},
+                "c.ts" => "C,\n"
+            ]
+        };
+
+        let options = CheckOptions {
+            repository: tempdir.path().to_owned(),
+            severities: [(Code::MissingNamedBlock, Severity::Warning)].into(),
+            ..Default::default()
+        };
+        let report = check_repository(&options).unwrap();
+        assert_eq!(
+            report
+                .files
+                .iter()
+                .map(|file| (
+                    file.path.to_str().unwrap(),
+                    file.diagnostics
+                        .iter()
+                        .map(|diagnostic| (diagnostic.code, diagnostic.severity))
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("a.ts", vec![]),
+                ("b.ts", vec![(Code::MissingNamedBlock, Severity::Warning)]),
+                ("c.ts", vec![]),
+            ]
+        );
+        assert_eq!(
+            report.summary(),
+            Summary {
+                errors: 0,
+                warnings: 1
+            }
+        );
+        assert!(report.files[1].diagnostics[0].message.ends_with(" (IC003)"));
+        assert_eq!(report.stats.files, 3);
+
+        let parallel = check_repository(&CheckOptions {
+            parallel: true,
+            patterns: vec!["a.ts".into(), "b.ts".into()],
+            ..options
+        })
+        .unwrap();
+        assert_eq!(parallel.files, report.files[..2]);
+    }
+}
@@ -0,0 +1,39 @@
+use std::{ops::AddAssign, time::Duration};
+
+/// Counters and timings collected while checking files.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of files checked.
+    pub files: usize,
+    /// Number of changed files skipped because they were ignored.
+    pub ignored_files: usize,
+    /// Number of files, changed or targeted, skipped because they were
+    /// binary.
+    pub binary_files: usize,
+    /// Number of blocks found in checked files.
+    pub blocks: usize,
+    /// Number of blocks skipped because they were not modified.
+    pub unmodified_blocks: usize,
+    /// Time spent finding changed files.
+    pub match_time: Duration,
+    /// Time spent parsing checked files.
+    pub parse_time: Duration,
+    /// Time spent determining whether blocks were modified.
+    pub diff_time: Duration,
+    /// Time spent resolving and checking the targets of modified blocks.
+    pub target_time: Duration,
+}
+
+impl AddAssign<&Stats> for Stats {
+    fn add_assign(&mut self, other: &Stats) {
+        self.files += other.files;
+        self.ignored_files += other.ignored_files;
+        self.binary_files += other.binary_files;
+        self.blocks += other.blocks;
+        self.unmodified_blocks += other.unmodified_blocks;
+        self.match_time += other.match_time;
+        self.parse_time += other.parse_time;
+        self.diff_time += other.diff_time;
+        self.target_time += other.target_time;
+    }
+}
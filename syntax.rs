@@ -0,0 +1,323 @@
+use std::path::Path;
+
+use tree_sitter::{Language, Node, Parser, Point, Tree, TreeCursor};
+
+/// Kinds of nodes defining functions and methods, in any of the grammars.
+const FUNCTION_KINDS: [&str; 6] = [
+    "function_declaration",
+    "function_definition",
+    "function_item",
+    "generator_function_declaration",
+    "method_declaration",
+    "method_definition",
+];
+
+/// Kinds of nodes wrapping a definition, e.g. `export function f() {}`.
+const WRAPPER_KINDS: [&str; 2] = ["decorated_definition", "export_statement"];
+
+/// Kinds of nodes between a comment and the function they annotate, e.g.
+/// `#[test]`.
+const ATTRIBUTE_KINDS: [&str; 2] = ["attribute_item", "decorator"];
+
+/// Kinds of nodes of bodies that comments at their start precede, e.g. the
+/// body of a class in Python.
+const BODY_KINDS: [&str; 1] = ["block"];
+
+/// Get the grammar of the language of `path`, by its extension.
+fn language(path: &Path) -> Option<Language> {
+    let language = match path.extension()?.to_str()? {
+        "c" | "h" => tree_sitter_c::LANGUAGE,
+        "go" => tree_sitter_go::LANGUAGE,
+        "js" | "jsx" | "mjs" | "cjs" => tree_sitter_javascript::LANGUAGE,
+        "py" | "pyi" => tree_sitter_python::LANGUAGE,
+        "rs" => tree_sitter_rust::LANGUAGE,
+        "ts" | "mts" | "cts" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        "tsx" => tree_sitter_typescript::LANGUAGE_TSX,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+/// The syntax tree of a file, as parsed by the grammar of its language.
+pub(crate) struct Syntax {
+    tree: Tree,
+}
+
+impl Syntax {
+    /// Parse `content` with the grammar of the language of `path`. Returns
+    /// `None` if the language of `path` is unknown.
+    pub(crate) fn parse(path: &Path, content: &[u8]) -> Option<Syntax> {
+        let mut parser = Parser::new();
+        parser.set_language(&language(path)?).ok()?;
+        let tree = parser.parse(content, None)?;
+        Some(Syntax { tree })
+    }
+
+    /// Blank out everything but the comments of `content`, the content this
+    /// was parsed from, so directives are only parsed in comments, e.g. not
+    /// in string literals.
+    ///
+    /// Line endings are kept, so lines keep their numbers.
+    pub(crate) fn mask(&self, content: &[u8]) -> Vec<u8> {
+        let mut masked = content
+            .iter()
+            .map(|byte| match byte {
+                b'\n' | b'\r' => *byte,
+                _ => b' ',
+            })
+            .collect::<Vec<_>>();
+        let mut cursor = self.tree.walk();
+        let mut keep = |start: usize, end: usize| {
+            masked[start..end].copy_from_slice(&content[start..end]);
+        };
+        visit_comments(&mut cursor, &mut keep);
+        masked
+    }
+
+    /// Get the lines of the function scoped by the comment at `line` and
+    /// `column`, starting at 1 and 0: the function right after the comment,
+    /// with its attributes, or else the innermost function containing it.
+    ///
+    /// Code between the comment and a function, e.g. `const A = 1;`, makes it
+    /// scope the function containing it instead.
+    pub(crate) fn function_scope(&self, line: usize, column: usize) -> Option<(usize, usize)> {
+        let point = Point::new(line - 1, column);
+        let mut comment = self
+            .tree
+            .root_node()
+            .named_descendant_for_point_range(point, point)?;
+        while !is_comment(comment) {
+            comment = comment.parent()?;
+        }
+        // Doc comments of Rust are made of comments.
+        while let Some(parent) = comment.parent().filter(|parent| is_comment(*parent)) {
+            comment = parent;
+        }
+
+        let mut attributes = None;
+        let mut sibling = comment.next_named_sibling();
+        while let Some(node) = sibling {
+            if is_function(node) {
+                return Some(lines(attributes.unwrap_or(node), node));
+            }
+            if ATTRIBUTE_KINDS.contains(&node.kind()) {
+                attributes.get_or_insert(node);
+            } else if BODY_KINDS.contains(&node.kind()) {
+                sibling = node.named_child(0);
+                continue;
+            } else if !is_comment(node) {
+                break;
+            }
+            sibling = node.next_named_sibling();
+        }
+
+        let mut ancestor = comment.parent();
+        while let Some(node) = ancestor {
+            if is_function(node) {
+                return Some(lines(node, node));
+            }
+            ancestor = node.parent();
+        }
+        None
+    }
+}
+
+fn is_comment(node: Node) -> bool {
+    node.is_named() && node.kind().contains("comment")
+}
+
+fn is_function(node: Node) -> bool {
+    FUNCTION_KINDS.contains(&node.kind())
+        || WRAPPER_KINDS.contains(&node.kind())
+            && node
+                .named_children(&mut node.walk())
+                .any(|child| FUNCTION_KINDS.contains(&child.kind()))
+}
+
+/// Get the lines from the start of `first` to the end of `last`, starting at
+/// 1.
+fn lines(first: Node, last: Node) -> (usize, usize) {
+    let end = last.end_position();
+    // Nodes ending with a line ending end at the start of the next line.
+    let end = if end.column == 0 && end.row > first.start_position().row {
+        end.row
+    } else {
+        end.row + 1
+    };
+    (first.start_position().row + 1, end)
+}
+
+/// Call `f` with the byte range of every comment below the node of `cursor`,
+/// not descending into comments, e.g. into the doc comments of Rust.
+fn visit_comments(cursor: &mut TreeCursor, f: &mut impl FnMut(usize, usize)) {
+    loop {
+        let node = cursor.node();
+        if is_comment(node) {
+            f(node.start_byte(), node.end_byte());
+        } else if cursor.goto_first_child() {
+            visit_comments(cursor, f);
+            cursor.goto_parent();
+        }
+        if !cursor.goto_next_sibling() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn masked(path: &str, content: &str) -> Option<String> {
+        let syntax = Syntax::parse(Path::new(path), content.as_bytes())?;
+        let masked = syntax.mask(content.as_bytes());
+        // Trailing whitespaces are trimmed to keep snapshots readable.
+        Some(
+            String::from_utf8(masked)
+                .unwrap()
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+
+    #[test]
+    fn test_mask() {
+        let content = indoc! {r#"
+            // if-changed
+            let a = "then-change(b.rs)";
+            /* then-change(
+                c.rs,
+            ) */
+            let b = 1; // d.rs
+        "#};
+        insta::assert_snapshot!(masked("a.rs", content).unwrap(), @r"
+        // if-changed
+
+        /* then-change(
+            c.rs,
+        ) */
+                   // d.rs
+        ");
+    }
+
+    #[test]
+    fn test_mask_python() {
+        let content = indoc! {r#"
+            # if-changed
+            """
+            then-change(b.py)
+            """
+            # then-change(c.py)
+        "#};
+        insta::assert_snapshot!(masked("a.py", content).unwrap(), @r"
+        # if-changed
+
+
+
+        # then-change(c.py)
+        ");
+    }
+
+    fn function_scope(path: &str, content: &str, line: usize) -> Option<(usize, usize)> {
+        let syntax = Syntax::parse(Path::new(path), content.as_bytes())?;
+        let column = content.lines().nth(line - 1)?.find("then-change")?;
+        syntax.function_scope(line, column)
+    }
+
+    #[test]
+    fn test_function_scope() {
+        let content = indoc! {"
+            // if-changed(scope=function)
+            // then-change(b.rs)
+            #[inline]
+            fn a() {
+                // if-changed(scope=function)
+                // then-change(c.rs)
+                let a = 1;
+            }
+
+            // then-change(d.rs)
+            const A: u32 = 1;
+        "};
+        assert_eq!(function_scope("a.rs", content, 2), Some((3, 8)));
+        assert_eq!(function_scope("a.rs", content, 6), Some((4, 8)));
+        assert_eq!(function_scope("a.rs", content, 10), None);
+    }
+
+    #[test]
+    fn test_function_scope_items() {
+        let content = indoc! {"
+            fn a() {
+                // if-changed(scope=function)
+                // then-change(b.rs)
+                impl A {
+                    fn b() {}
+                }
+
+                // if-changed(scope=function)
+                // then-change(c.rs)
+                mod m {
+                    fn c() {}
+                }
+            }
+
+            // then-change(d.rs)
+            impl B {
+                fn d() {}
+            }
+        "};
+        assert_eq!(function_scope("a.rs", content, 3), Some((1, 13)));
+        assert_eq!(function_scope("a.rs", content, 9), Some((1, 13)));
+        assert_eq!(function_scope("a.rs", content, 15), None);
+    }
+
+    #[test]
+    fn test_function_scope_python() {
+        let content = indoc! {"
+            class A:
+                # if-changed(scope=function)
+                # then-change(b.py)
+                @property
+                def a(self):
+                    return 1
+
+                def b(self):
+                    # if-changed(scope=function)
+                    # then-change(c.py)
+                    return 2
+        "};
+        assert_eq!(function_scope("a.py", content, 3), Some((4, 6)));
+        assert_eq!(function_scope("a.py", content, 10), Some((8, 11)));
+    }
+
+    #[test]
+    fn test_function_scope_typescript() {
+        let content = indoc! {"
+            // if-changed(scope=function)
+            // then-change(b.ts)
+            export function a() {
+              return 1;
+            }
+
+            class B {
+              // if-changed(scope=function)
+              // then-change(c.ts)
+              b() {
+                return 2;
+              }
+            }
+        "};
+        assert_eq!(function_scope("a.ts", content, 2), Some((3, 5)));
+        assert_eq!(function_scope("a.ts", content, 9), Some((10, 12)));
+    }
+
+    #[test]
+    fn test_mask_unknown_language() {
+        assert_eq!(masked("a.txt", "# if-changed\n"), None);
+        assert_eq!(masked("Makefile", "# if-changed\n"), None);
+    }
+}